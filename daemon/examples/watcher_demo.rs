@@ -10,7 +10,7 @@ fn main() {
     let config = Config::default();
     println!("Configuration loaded:");
     println!("  Include paths: {:?}", config.indexing.include_paths);
-    println!("  Exclude patterns: {:?}", config.indexing.exclude_patterns);
+    println!("  Exclude patterns: {:?}", config.indexing.effective_exclude_patterns());
     println!();
     
     // Create filesystem watcher
@@ -1,12 +1,16 @@
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 use notify::{Watcher, RecursiveMode, Event, EventKind};
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::display::PathDisplayMode;
+use crate::paths;
+use crate::database::{NameNormalization, PathStorage, SearchScope};
 
 /// Main configuration structure
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Config {
     #[serde(default)]
     pub indexing: IndexingConfig,
@@ -14,15 +18,158 @@ pub struct Config {
     pub performance: PerformanceConfig,
     #[serde(default)]
     pub ui: UiConfig,
+    #[serde(default)]
+    pub ranking: RankingConfig,
 }
 
 /// Indexing configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IndexingConfig {
     #[serde(default = "default_include_paths")]
-    pub include_paths: Vec<String>,
+    pub include_paths: Vec<IncludePath>,
+    /// Newline-delimited file of additional include paths (`#` starts a comment line);
+    /// a missing file adds none
+    #[serde(default)]
+    pub include_paths_file: Option<String>,
+    /// Paths read from `include_paths_file` when the config was loaded
+    #[serde(skip)]
+    pub listed_include_paths: Vec<String>,
+    /// Daemon data and config directories, skipped regardless of the other settings
+    #[serde(skip, default = "paths::internal_dirs")]
+    pub internal_dirs: Vec<PathBuf>,
+    /// Glob patterns matched against entry names, or, when they contain a `/`,
+    /// against the path relative to the include root (e.g. `build/*`)
     #[serde(default = "default_exclude_patterns")]
     pub exclude_patterns: Vec<String>,
+    /// Names of curated pattern sets from `EXCLUDE_BUNDLES` excluded on top of `exclude_patterns`
+    #[serde(default)]
+    pub exclude_bundles: Vec<String>,
+    /// Patterns of the enabled `exclude_bundles`, expanded when the config was loaded
+    #[serde(skip)]
+    pub bundled_exclude_patterns: Vec<String>,
+    /// Index dot-prefixed (hidden) files and directories
+    #[serde(default)]
+    pub index_hidden_files: bool,
+    /// Descend into symlinked directories during scans; each directory is still walked once
+    #[serde(default)]
+    pub follow_symlinks: bool,
+    /// Only follow symlinked directories whose target lies inside an include path
+    #[serde(default = "default_true")]
+    pub follow_symlinks_within_roots: bool,
+    /// Reconcile the index with a background rescan at this interval
+    #[serde(default)]
+    pub reindex_interval_hours: Option<u64>,
+    /// Stop the startup scan after this many seconds and finish it in the background
+    #[serde(default)]
+    pub initial_scan_timeout_secs: Option<u64>,
+    /// Only index files under the include paths modified within this many days
+    #[serde(default)]
+    pub max_age_days: Option<u64>,
+    /// Delete indexed files older than `max_age_days` when a rescan or full reindex skips them
+    #[serde(default)]
+    pub prune_aged_out: bool,
+    /// Abort a full reindex whose scan finds fewer than this fraction of the indexed entries
+    #[serde(default = "default_reindex_min_fraction")]
+    pub reindex_min_fraction: f64,
+    /// File extensions indexed even when an exclude pattern matches the file
+    #[serde(default)]
+    pub always_include_extensions: Vec<String>,
+    /// Scan and watch standard application directories
+    #[serde(default = "default_true")]
+    pub index_applications: bool,
+    /// Watch system-wide application directories (disable on multi-user hosts)
+    #[serde(default = "default_true")]
+    pub watch_system_applications: bool,
+    /// Skip the separate scan of application directories an include path already covers
+    #[serde(default = "default_true")]
+    pub skip_covered_applications: bool,
+    /// Skip entries whose full path is longer than this many bytes
+    #[serde(default = "default_max_path_length")]
+    pub max_path_length: usize,
+    /// What happens to entries whose path is longer than `max_path_length`
+    #[serde(default)]
+    pub long_path_policy: LongPathPolicy,
+    /// Read extended attributes during scans
+    #[serde(default)]
+    pub index_xattrs: bool,
+    /// Attribute name prefixes to store when `index_xattrs` is enabled
+    #[serde(default = "default_xattr_allowlist")]
+    pub xattr_allowlist: Vec<String>,
+    /// Store the text of small files for full-text search (expensive: every file is read)
+    #[serde(default)]
+    pub index_content: bool,
+    /// Largest file, in bytes, whose text is stored when `index_content` is enabled
+    #[serde(default = "default_max_file_size")]
+    pub max_file_size: u64,
+    /// Record the git repository (nearest directory containing `.git`) of each entry
+    #[serde(default)]
+    pub detect_git_repos: bool,
+    /// Record the owner and group (uid and gid) of each entry; turning it on
+    /// fills them in for the entries already indexed
+    #[serde(default)]
+    pub index_ownership: bool,
+    /// Store each directory path once and only filenames per entry
+    #[serde(default)]
+    pub intern_directories: bool,
+    /// Unicode form filenames and queries are normalized to before matching
+    #[serde(default)]
+    pub unicode_normalization: NameNormalization,
+    /// Scan and watch filesystems mounted under include paths after startup,
+    /// and hide the entries of those unmounted until they are back
+    #[serde(default)]
+    pub watch_mounts: bool,
+    /// Rescan watched roots when the kernel drops filesystem events
+    #[serde(default = "default_true")]
+    pub rescan_on_overflow: bool,
+}
+
+/// Entry of `include_paths`: a plain path, or a table such as
+/// `{ path = "~/Downloads", recursive = false }`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum IncludePath {
+    Path(String),
+    Detailed {
+        path: String,
+        /// Scan and watch subdirectories too; `false` covers only the top level
+        #[serde(default = "default_true")]
+        recursive: bool,
+    },
+}
+
+impl IncludePath {
+    /// The path as written in the config, before tilde expansion
+    pub fn path(&self) -> &str {
+        match self {
+            IncludePath::Path(path) | IncludePath::Detailed { path, .. } => path,
+        }
+    }
+
+    /// Whether subdirectories below the path are scanned and watched
+    pub fn recursive(&self) -> bool {
+        match self {
+            IncludePath::Path(_) => true,
+            IncludePath::Detailed { recursive, .. } => *recursive,
+        }
+    }
+}
+
+impl From<String> for IncludePath {
+    fn from(path: String) -> Self {
+        IncludePath::Path(path)
+    }
+}
+
+impl From<&str> for IncludePath {
+    fn from(path: &str) -> Self {
+        IncludePath::Path(path.to_string())
+    }
+}
+
+impl PartialEq<&str> for IncludePath {
+    fn eq(&self, other: &&str) -> bool {
+        self.path() == *other
+    }
 }
 
 /// Performance configuration
@@ -36,6 +183,78 @@ pub struct PerformanceConfig {
     pub batch_size: usize,
     #[serde(default = "default_flush_interval_ms")]
     pub flush_interval_ms: u64,
+    /// Maximum number of directories watched natively, each directory below a
+    /// recursive watch counting as one; paths beyond it are polled
+    #[serde(default)]
+    pub max_watches: Option<usize>,
+    /// Paths registered with the watcher at a time during startup (0 registers all at once)
+    #[serde(default = "default_watch_batch_size")]
+    pub watch_batch_size: usize,
+    /// Pause between batches of watch registrations
+    #[serde(default = "default_watch_batch_delay_ms")]
+    pub watch_batch_delay_ms: u64,
+    /// Collapse queued operations on the same path into one before each flush
+    #[serde(default = "default_true")]
+    pub coalesce_operations: bool,
+    /// Log queued operations to disk so changes seen before a crash are indexed on restart
+    #[serde(default)]
+    pub operation_journal: bool,
+    /// Pause indexing while the database's filesystem has less free space than this (0 disables)
+    #[serde(default = "default_min_free_disk_mb")]
+    pub min_free_disk_mb: u64,
+    /// Number of include roots walked concurrently during a scan (1 scans them in turn)
+    #[serde(default = "default_scan_parallelism")]
+    pub scan_parallelism: usize,
+    /// What happens to filesystem events that arrive while indexing is paused
+    #[serde(default)]
+    pub pause_policy: PausePolicy,
+    /// Debounce window for events on directories, which tend to change in bursts
+    #[serde(default = "default_debounce_directory_ms")]
+    pub debounce_directory_ms: u64,
+    /// Most results returned for a query over the control socket
+    #[serde(default = "default_max_ipc_results")]
+    pub max_ipc_results: usize,
+    /// Scan errors logged one by one per scan; the rest are only counted and summarized
+    #[serde(default = "default_max_logged_scan_errors")]
+    pub max_logged_scan_errors: usize,
+    /// Index entries re-checked against the disk per second in the background (0 disables)
+    #[serde(default = "default_verify_rate_per_sec")]
+    pub verify_rate_per_sec: usize,
+    /// Watch errors a path may report before it is marked degraded and its errors ignored
+    #[serde(default = "default_watch_error_threshold")]
+    pub watch_error_threshold: u32,
+    /// Seconds before a degraded path is watched again, doubling on each failure (0 never retries)
+    #[serde(default = "default_watch_error_retry_secs")]
+    pub watch_error_retry_secs: u64,
+    /// How often launches reported over the control socket are written to the index
+    #[serde(default = "default_launch_flush_interval_ms")]
+    pub launch_flush_interval_ms: u64,
+    /// What the running daemon does when its config file is deleted
+    #[serde(default)]
+    pub on_config_removed: OnConfigRemoved,
+}
+
+/// Handling of filesystem events while indexing is paused
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PausePolicy {
+    /// Keep events pending and index them after resuming
+    #[default]
+    Buffer,
+    /// Discard events; the changes are missing until the next `reindex`
+    /// (periodic when `reindex_interval_hours` is set)
+    Drop,
+}
+
+/// Handling of entries whose path is longer than `indexing.max_path_length`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LongPathPolicy {
+    /// Leave them out of scans, watch events and every write to the index
+    #[default]
+    Skip,
+    /// Index them like any other path, whatever their length
+    Store,
 }
 
 /// UI configuration
@@ -45,22 +264,101 @@ pub struct UiConfig {
     pub keyboard_shortcut: String,
     #[serde(default = "default_max_results")]
     pub max_results: usize,
+    /// How result paths are rendered ("lossy" or "lossless")
+    #[serde(default)]
+    pub path_display: PathDisplayMode,
+    /// Share of the recent activity feed given to launched rather than modified files (0 to 1)
+    #[serde(default = "default_recent_launch_weight")]
+    pub recent_launch_weight: f64,
+    /// Show paths under the home directory as `~/...` in command output
+    #[serde(default = "default_true")]
+    pub shorten_home_paths: bool,
+    /// Match searches against the filename, the whole path, or both ("filename", "path" or "both")
+    #[serde(default)]
+    pub search_scope: SearchScope,
+    /// Shortest query the daemon answers while the user is typing, in characters
+    #[serde(default = "default_min_query_length")]
+    pub min_query_length: usize,
+}
+
+/// Search ranking configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RankingConfig {
+    /// Factor applied to the launch count of directories when ranking by relevance
+    #[serde(default = "default_directory_weight")]
+    pub directory_weight: f64,
+}
+
+/// Exclude pattern sets users can enable by name in `indexing.exclude_bundles`
+pub const EXCLUDE_BUNDLES: &[(&str, &[&str])] = &[
+    // Dependencies, virtual environments, caches and build output of common toolchains
+    ("dev", &[
+        "node_modules",
+        ".venv",
+        "venv",
+        "__pycache__",
+        "*.pyc",
+        ".tox",
+        ".mypy_cache",
+        ".pytest_cache",
+        ".gradle",
+        ".next",
+        "target",
+        "build",
+        "dist",
+    ]),
+    // Thumbnail and preview caches written by file managers and photo tools
+    ("media-cache", &[
+        ".thumbnails",
+        "Thumbs.db",
+        ".DS_Store",
+        "@eaDir",
+        "*.lrdata",
+        ".dtrash",
+    ]),
+    // Version control metadata
+    ("vcs", &[".git", ".hg", ".svn", ".bzr"]),
+];
+
+/// Get the patterns of the named exclude bundles, skipping unknown names and duplicates
+pub fn expand_exclude_bundles(names: &[String]) -> Vec<String> {
+    let mut patterns: Vec<String> = Vec::new();
+    for name in names {
+        let bundle = EXCLUDE_BUNDLES.iter().find(|(bundle, _)| bundle == name);
+        for pattern in bundle.map_or(&[][..], |(_, patterns)| patterns) {
+            if !patterns.iter().any(|known| known == pattern) {
+                patterns.push(pattern.to_string());
+            }
+        }
+    }
+    patterns
 }
 
 // Default value functions for serde
-fn default_include_paths() -> Vec<String> {
-    vec!["~".to_string()]
+fn default_include_paths() -> Vec<IncludePath> {
+    vec!["~".into()]
 }
 
 fn default_exclude_patterns() -> Vec<String> {
     vec![
-        ".*".to_string(),
         "node_modules".to_string(),
         ".git".to_string(),
         "target".to_string(),
     ]
 }
 
+fn default_max_path_length() -> usize {
+    4096
+}
+
+fn default_xattr_allowlist() -> Vec<String> {
+    vec!["user.".to_string()]
+}
+
+fn default_max_file_size() -> u64 {
+    1024 * 1024
+}
+
 fn default_max_cpu_percent() -> u8 {
     10
 }
@@ -77,6 +375,66 @@ fn default_flush_interval_ms() -> u64 {
     1000
 }
 
+fn default_launch_flush_interval_ms() -> u64 {
+    5000
+}
+
+fn default_watch_batch_size() -> usize {
+    256
+}
+
+fn default_watch_batch_delay_ms() -> u64 {
+    50
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_min_free_disk_mb() -> u64 {
+    100
+}
+
+fn default_reindex_min_fraction() -> f64 {
+    0.1
+}
+
+fn default_scan_parallelism() -> usize {
+    1
+}
+
+fn default_debounce_directory_ms() -> u64 {
+    1000
+}
+
+fn default_max_ipc_results() -> usize {
+    500
+}
+
+fn default_max_logged_scan_errors() -> usize {
+    100
+}
+
+fn default_verify_rate_per_sec() -> usize {
+    20
+}
+
+fn default_watch_error_threshold() -> u32 {
+    5
+}
+
+fn default_watch_error_retry_secs() -> u64 {
+    300
+}
+
+fn default_recent_launch_weight() -> f64 {
+    0.5
+}
+
+fn default_directory_weight() -> f64 {
+    1.0
+}
+
 fn default_keyboard_shortcut() -> String {
     "Super+Space".to_string()
 }
@@ -85,30 +443,203 @@ fn default_max_results() -> usize {
     50
 }
 
-impl Default for Config {
-    fn default() -> Self {
-        Config {
-            indexing: IndexingConfig::default(),
-            performance: PerformanceConfig::default(),
-            ui: UiConfig::default(),
-        }
-    }
+fn default_min_query_length() -> usize {
+    1
 }
 
 impl Default for IndexingConfig {
     fn default() -> Self {
         IndexingConfig {
-            include_paths: vec!["~".to_string()],
+            include_paths: vec!["~".into()],
+            include_paths_file: None,
+            listed_include_paths: Vec::new(),
+            internal_dirs: paths::internal_dirs(),
             exclude_patterns: vec![
-                ".*".to_string(),
                 "node_modules".to_string(),
                 ".git".to_string(),
                 "target".to_string(),
             ],
+            exclude_bundles: Vec::new(),
+            bundled_exclude_patterns: Vec::new(),
+            index_hidden_files: false,
+            follow_symlinks: false,
+            follow_symlinks_within_roots: true,
+            reindex_interval_hours: None,
+            initial_scan_timeout_secs: None,
+            max_age_days: None,
+            prune_aged_out: false,
+            reindex_min_fraction: 0.1,
+            always_include_extensions: Vec::new(),
+            index_applications: true,
+            watch_system_applications: true,
+            skip_covered_applications: true,
+            max_path_length: 4096,
+            long_path_policy: LongPathPolicy::Skip,
+            index_xattrs: false,
+            xattr_allowlist: vec!["user.".to_string()],
+            index_content: false,
+            max_file_size: 1024 * 1024,
+            detect_git_repos: false,
+            index_ownership: false,
+            intern_directories: false,
+            unicode_normalization: NameNormalization::Nfc,
+            watch_mounts: false,
+            rescan_on_overflow: true,
         }
     }
 }
 
+impl IndexingConfig {
+    /// Path storage the index should be converted to at startup
+    pub fn path_storage(&self) -> PathStorage {
+        if self.intern_directories {
+            PathStorage::Interned
+        } else {
+            PathStorage::Full
+        }
+    }
+
+    /// Check whether a file's extension is listed in `always_include_extensions`
+    ///
+    /// Extensions match case-insensitively, with or without a leading dot.
+    pub fn always_includes(&self, path: &Path) -> bool {
+        let ext = match path.extension() {
+            Some(ext) => ext.to_string_lossy(),
+            None => return false,
+        };
+        self.always_include_extensions
+            .iter()
+            .any(|wanted| wanted.trim_start_matches('.').eq_ignore_ascii_case(&ext))
+    }
+
+    /// Check whether `path` lies in one of the daemon's own directories
+    pub fn is_internal_path(&self, path: &Path) -> bool {
+        self.internal_dirs.iter().any(|dir| path.starts_with(dir))
+    }
+
+    /// Get the include paths, from the config and `include_paths_file`, with `~` expanded
+    pub fn include_roots(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = Vec::new();
+        let inline = self.include_paths.iter().map(IncludePath::path);
+        for path in inline.chain(self.listed_include_paths.iter().map(String::as_str)) {
+            let path = expand_tilde(path);
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+        paths
+    }
+
+    /// Get `exclude_patterns` together with the patterns of the enabled `exclude_bundles`
+    pub fn effective_exclude_patterns(&self) -> Vec<String> {
+        let mut patterns = self.exclude_patterns.clone();
+        for pattern in &self.bundled_exclude_patterns {
+            if !patterns.contains(pattern) {
+                patterns.push(pattern.clone());
+            }
+        }
+        patterns
+    }
+
+    /// Check `path` and the directories above it against the exclude patterns containing a `/`
+    ///
+    /// Those patterns match the path relative to the innermost include root
+    /// containing it, so `docs/_generated` excludes that directory and
+    /// everything below it. Patterns without a `/` are not checked here.
+    pub fn excludes_relative_path(&self, path: &Path) -> bool {
+        let patterns: Vec<glob::Pattern> = self
+            .effective_exclude_patterns()
+            .iter()
+            .filter(|pattern| is_path_pattern(pattern))
+            .filter_map(|pattern| glob::Pattern::new(pattern).ok())
+            .collect();
+        if patterns.is_empty() {
+            return false;
+        }
+
+        let relative = match relative_to_root(path, &self.include_roots()) {
+            Some(relative) => relative,
+            None => return false,
+        };
+        relative
+            .ancestors()
+            .filter(|ancestor| !ancestor.as_os_str().is_empty())
+            .any(|ancestor| patterns.iter().any(|pattern| pattern.matches_path(ancestor)))
+    }
+
+    /// Get the modification time files need to be indexed by a scan, from `max_age_days`
+    pub fn max_age_cutoff(&self) -> Option<SystemTime> {
+        let days = self.max_age_days?;
+        SystemTime::now().checked_sub(Duration::from_secs(days.saturating_mul(24 * 60 * 60)))
+    }
+
+    /// Get the modification time below which rescans keep indexed files they skipped
+    ///
+    /// Files that aged past `max_age_days` since they were indexed stay in the
+    /// index unless `prune_aged_out` is on; deleted ones are still removed by
+    /// verification.
+    pub fn aged_out_cutoff(&self) -> Option<SystemTime> {
+        self.max_age_cutoff().filter(|_| !self.prune_aged_out)
+    }
+
+    /// Get the longest path indexed, or `None` when `long_path_policy` stores every path
+    pub fn path_length_limit(&self) -> Option<usize> {
+        (self.long_path_policy == LongPathPolicy::Skip).then_some(self.max_path_length)
+    }
+
+    /// Get the size limit for content indexing, or `None` when `index_content` is off
+    pub fn content_size_limit(&self) -> Option<u64> {
+        self.index_content.then_some(self.max_file_size)
+    }
+
+    /// Check whether walking the include paths reaches `path`
+    ///
+    /// True when `path` is an include root, or lies under a recursive one
+    /// without any directory on the way being hidden (unless hidden files are
+    /// indexed) or matching an exclude pattern.
+    pub fn include_walk_covers(&self, path: &Path) -> bool {
+        if self.is_internal_path(path) {
+            return false;
+        }
+
+        let patterns = self.effective_exclude_patterns();
+        let name_patterns: Vec<glob::Pattern> = patterns
+            .iter()
+            .filter(|pattern| !is_path_pattern(pattern))
+            .filter_map(|pattern| glob::Pattern::new(pattern).ok())
+            .collect();
+        let excluded_name = |name: &str| {
+            (!self.index_hidden_files && name.starts_with('.'))
+                || name_patterns.iter().any(|pattern| pattern.matches(name))
+        };
+
+        self.include_roots().iter().any(|root| {
+            let relative = match path.strip_prefix(root) {
+                Ok(relative) => relative,
+                Err(_) => return false,
+            };
+            if relative.as_os_str().is_empty() {
+                return true;
+            }
+            self.is_recursive_root(root)
+                && !relative.components().any(|name| excluded_name(&name.as_os_str().to_string_lossy()))
+                && !self.excludes_relative_path(path)
+        })
+    }
+
+    /// Check whether an expanded include root is scanned and watched recursively
+    ///
+    /// Roots from `include_paths_file`, and paths that are not include roots at
+    /// all (e.g. application directories), are always recursive. When a path is
+    /// listed more than once, its first entry decides.
+    pub fn is_recursive_root(&self, root: &Path) -> bool {
+        self.include_paths
+            .iter()
+            .find(|include| expand_tilde(include.path()) == root)
+            .is_none_or(IncludePath::recursive)
+    }
+}
+
 impl Default for PerformanceConfig {
     fn default() -> Self {
         PerformanceConfig {
@@ -116,6 +647,22 @@ impl Default for PerformanceConfig {
             max_memory_mb: 100,
             batch_size: 100,
             flush_interval_ms: 1000,
+            max_watches: None,
+            watch_batch_size: 256,
+            watch_batch_delay_ms: 50,
+            coalesce_operations: true,
+            operation_journal: false,
+            min_free_disk_mb: 100,
+            scan_parallelism: 1,
+            pause_policy: PausePolicy::Buffer,
+            debounce_directory_ms: 1000,
+            max_ipc_results: 500,
+            max_logged_scan_errors: 100,
+            verify_rate_per_sec: 20,
+            watch_error_threshold: 5,
+            watch_error_retry_secs: 300,
+            launch_flush_interval_ms: 5000,
+            on_config_removed: OnConfigRemoved::Keep,
         }
     }
 }
@@ -125,6 +672,19 @@ impl Default for UiConfig {
         UiConfig {
             keyboard_shortcut: "Super+Space".to_string(),
             max_results: 50,
+            path_display: PathDisplayMode::Lossy,
+            recent_launch_weight: 0.5,
+            shorten_home_paths: true,
+            search_scope: SearchScope::Filename,
+            min_query_length: 1,
+        }
+    }
+}
+
+impl Default for RankingConfig {
+    fn default() -> Self {
+        RankingConfig {
+            directory_weight: 1.0,
         }
     }
 }
@@ -141,34 +701,110 @@ impl Config {
         let contents = fs::read_to_string(path)
             .map_err(|e| ConfigError::IoError(e.to_string()))?;
         
-        let config: Config = toml::from_str(&contents)
+        let mut config: Config = toml::from_str(&contents)
             .map_err(|e| ConfigError::ParseError(e.to_string()))?;
         
+        if let Some(list_file) = &config.indexing.include_paths_file {
+            // Relative list files live next to the config file
+            let list_path = path.parent().unwrap_or(Path::new("")).join(expand_tilde(list_file));
+            // A missing list adds no roots, like a missing config file; other failures are errors
+            let list = match fs::read_to_string(&list_path) {
+                Ok(list) => list,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    eprintln!("Warning: Include paths file {} not found; adding no roots from it", list_path.display());
+                    String::new()
+                }
+                Err(e) => {
+                    return Err(ConfigError::IoError(format!("Failed to read {}: {}", list_path.display(), e)));
+                }
+            };
+            config.indexing.listed_include_paths = parse_include_paths_list(&list);
+        }
+        config.indexing.bundled_exclude_patterns = expand_exclude_bundles(&config.indexing.exclude_bundles);
+        
         config.validate()?;
         
         Ok(config)
     }
 
     /// Save configuration to a TOML file
+    ///
+    /// The contents are written to a temporary file in the same directory and
+    /// renamed over the target, so readers see either the old or the new file,
+    /// never a partially written one.
     pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), ConfigError> {
+        let path = path.as_ref();
         let contents = toml::to_string_pretty(self)
             .map_err(|e| ConfigError::SerializeError(e.to_string()))?;
         
-        fs::write(path, contents)
-            .map_err(|e| ConfigError::IoError(e.to_string()))?;
+        let file_name = path.file_name()
+            .ok_or_else(|| ConfigError::IoError(format!("Invalid config path: {}", path.display())))?;
+        let mut temp_name = std::ffi::OsString::from(".");
+        temp_name.push(file_name);
+        temp_name.push(format!(".{}.tmp", std::process::id()));
+        let temp_path = path.with_file_name(temp_name);
+        
+        let result = Self::write_synced(&temp_path, contents.as_bytes())
+            .and_then(|_| fs::rename(&temp_path, path));
+        if let Err(e) = result {
+            let _ = fs::remove_file(&temp_path);
+            return Err(ConfigError::IoError(e.to_string()));
+        }
         
         Ok(())
     }
 
+    /// Write a file and flush it to disk before returning
+    fn write_synced(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+        use std::io::Write;
+        
+        let mut file = fs::File::create(path)?;
+        file.write_all(contents)?;
+        file.sync_all()
+    }
+
     /// Validate configuration values
     pub fn validate(&self) -> Result<(), ConfigError> {
         // Validate include_paths is not empty
-        if self.indexing.include_paths.is_empty() {
+        if self.indexing.include_paths.is_empty() && self.indexing.include_paths_file.is_none() {
             return Err(ConfigError::ValidationError(
                 "include_paths cannot be empty".to_string()
             ));
         }
 
+        // Validate exclude_bundles names
+        if let Some(unknown) = self.indexing.exclude_bundles
+            .iter()
+            .find(|name| !EXCLUDE_BUNDLES.iter().any(|(bundle, _)| bundle == name))
+        {
+            let known: Vec<&str> = EXCLUDE_BUNDLES.iter().map(|(bundle, _)| *bundle).collect();
+            return Err(ConfigError::ValidationError(format!(
+                "unknown exclude bundle \"{}\" (available: {})",
+                unknown,
+                known.join(", ")
+            )));
+        }
+
+        // Validate reindex_interval_hours is reasonable
+        if self.indexing.reindex_interval_hours == Some(0) {
+            return Err(ConfigError::ValidationError(
+                "reindex_interval_hours must be greater than 0".to_string()
+            ));
+        }
+
+        if self.indexing.max_age_days == Some(0) {
+            return Err(ConfigError::ValidationError(
+                "max_age_days must be greater than 0".to_string()
+            ));
+        }
+
+        // Validate max_path_length is reasonable
+        if self.indexing.max_path_length == 0 {
+            return Err(ConfigError::ValidationError(
+                "max_path_length must be greater than 0".to_string()
+            ));
+        }
+
         // Validate max_cpu_percent is reasonable
         if self.performance.max_cpu_percent == 0 || self.performance.max_cpu_percent > 100 {
             return Err(ConfigError::ValidationError(
@@ -197,6 +833,54 @@ impl Config {
             ));
         }
 
+        if self.performance.launch_flush_interval_ms == 0 {
+            return Err(ConfigError::ValidationError(
+                "launch_flush_interval_ms must be greater than 0".to_string()
+            ));
+        }
+
+        // Validate watch_error_threshold is reasonable
+        if self.performance.watch_error_threshold == 0 {
+            return Err(ConfigError::ValidationError(
+                "watch_error_threshold must be greater than 0".to_string()
+            ));
+        }
+
+        // Validate max_watches is reasonable
+        if self.performance.max_watches == Some(0) {
+            return Err(ConfigError::ValidationError(
+                "max_watches must be greater than 0".to_string()
+            ));
+        }
+
+        // Validate initial_scan_timeout_secs is reasonable
+        if self.indexing.initial_scan_timeout_secs == Some(0) {
+            return Err(ConfigError::ValidationError(
+                "initial_scan_timeout_secs must be greater than 0".to_string()
+            ));
+        }
+
+        // Validate reindex_min_fraction is a fraction
+        if !(0.0..=1.0).contains(&self.indexing.reindex_min_fraction) {
+            return Err(ConfigError::ValidationError(
+                "reindex_min_fraction must be between 0 and 1".to_string()
+            ));
+        }
+
+        // Validate scan_parallelism is reasonable
+        if self.performance.scan_parallelism == 0 {
+            return Err(ConfigError::ValidationError(
+                "scan_parallelism must be greater than 0".to_string()
+            ));
+        }
+
+        // Validate max_ipc_results is reasonable
+        if self.performance.max_ipc_results == 0 {
+            return Err(ConfigError::ValidationError(
+                "max_ipc_results must be greater than 0".to_string()
+            ));
+        }
+
         // Validate max_results is reasonable
         if self.ui.max_results == 0 {
             return Err(ConfigError::ValidationError(
@@ -211,6 +895,20 @@ impl Config {
             ));
         }
 
+        // Validate directory_weight is a usable factor
+        if !(self.ranking.directory_weight.is_finite() && self.ranking.directory_weight >= 0.0) {
+            return Err(ConfigError::ValidationError(
+                "directory_weight must be a number of at least 0".to_string()
+            ));
+        }
+
+        // Validate recent_launch_weight is a fraction
+        if !(0.0..=1.0).contains(&self.ui.recent_launch_weight) {
+            return Err(ConfigError::ValidationError(
+                "recent_launch_weight must be between 0 and 1".to_string()
+            ));
+        }
+
         Ok(())
     }
 
@@ -219,20 +917,65 @@ impl Config {
         Duration::from_millis(self.performance.flush_interval_ms)
     }
 
+    /// Get the launch flush interval as Duration
+    pub fn launch_flush_interval(&self) -> Duration {
+        Duration::from_millis(self.performance.launch_flush_interval_ms)
+    }
+
+    /// Get the initial scan timeout as Duration, if set
+    pub fn initial_scan_timeout(&self) -> Option<Duration> {
+        self.indexing.initial_scan_timeout_secs.map(Duration::from_secs)
+    }
+
+    /// Get the delay before retrying a degraded watch as Duration, if enabled
+    pub fn watch_error_retry(&self) -> Option<Duration> {
+        (self.performance.watch_error_retry_secs > 0)
+            .then(|| Duration::from_secs(self.performance.watch_error_retry_secs))
+    }
+
+    /// Get the periodic reindex interval as Duration, if enabled
+    pub fn reindex_interval(&self) -> Option<Duration> {
+        self.indexing.reindex_interval_hours
+            .map(|hours| Duration::from_secs(hours * 60 * 60))
+    }
+
     /// Expand tilde in paths to home directory
+    ///
+    /// Inline `include_paths` come first, followed by any new paths from
+    /// `include_paths_file`.
     pub fn expand_paths(&self) -> Vec<PathBuf> {
-        self.indexing.include_paths
-            .iter()
-            .map(|p| expand_tilde(p))
-            .collect()
+        self.indexing.include_roots()
     }
 }
 
+/// Check whether an exclude pattern matches relative paths rather than names
+pub fn is_path_pattern(pattern: &str) -> bool {
+    pattern.contains('/')
+}
+
+/// Get `path` relative to the innermost of `roots` containing it
+pub fn relative_to_root<'a>(path: &'a Path, roots: &[PathBuf]) -> Option<&'a Path> {
+    roots
+        .iter()
+        .filter_map(|root| path.strip_prefix(root).ok())
+        .min_by_key(|relative| relative.components().count())
+}
+
+/// Parse an include paths list file: one path per line, blank lines and `#` comments ignored
+pub fn parse_include_paths_list(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
 /// Expand tilde (~) to home directory
 fn expand_tilde(path: &str) -> PathBuf {
-    if path.starts_with("~/") {
+    if let Some(rest) = path.strip_prefix("~/") {
         if let Ok(home) = std::env::var("HOME") {
-            return PathBuf::from(home).join(&path[2..]);
+            return PathBuf::from(home).join(rest);
         }
     } else if path == "~" {
         if let Ok(home) = std::env::var("HOME") {
@@ -264,68 +1007,121 @@ impl std::fmt::Display for ConfigError {
 
 impl std::error::Error for ConfigError {}
 
+/// Quiet period after the last config file event before reloading
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// What `ConfigWatcher` does when the config file is deleted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnConfigRemoved {
+    /// Keep running with the configuration loaded last
+    #[default]
+    Keep,
+    /// Reload the built-in defaults, as a start without a config file would
+    Defaults,
+}
+
 /// Configuration file watcher
 pub struct ConfigWatcher {
     current_config: Arc<Mutex<Config>>,
-    _watcher: notify::RecommendedWatcher,
+    watcher: Option<notify::RecommendedWatcher>,
     reload_receiver: std::sync::mpsc::Receiver<()>,
+    reload_attempts: Arc<AtomicUsize>,
 }
 
 impl ConfigWatcher {
-    /// Create a new configuration watcher
+    /// Create a new configuration watcher that keeps the current config if the file is deleted
     pub fn new(config_path: PathBuf) -> Result<Self, ConfigError> {
+        Self::with_removal_policy(config_path, OnConfigRemoved::default())
+    }
+
+    /// Create a new configuration watcher, choosing what a deleted config file means
+    ///
+    /// The file counts as deleted when it is still missing once the reload
+    /// debounce has passed, so editors that save by deleting and recreating
+    /// the file reload the new contents either way.
+    pub fn with_removal_policy(config_path: PathBuf, on_removed: OnConfigRemoved) -> Result<Self, ConfigError> {
         let config = Config::load_from_file(&config_path)?;
         let current_config = Arc::new(Mutex::new(config));
+        let reload_attempts = Arc::new(AtomicUsize::new(0));
         
         let (reload_sender, reload_receiver) = std::sync::mpsc::channel();
+        let (change_sender, change_receiver) = std::sync::mpsc::channel();
         let config_path_clone = config_path.clone();
-        let current_config_clone = current_config.clone();
         
-        // Create debounced sender
-        let debounced_sender = Arc::new(Mutex::new(DebouncedSender::new(
-            reload_sender,
-            Duration::from_millis(500),
-        )));
-        
-        // Set up file watcher
+        // Set up file watcher; it only signals changes, the reload happens debounced below
         let mut watcher = notify::recommended_watcher(move |res: Result<Event, notify::Error>| {
             match res {
                 Ok(event) => {
-                    // Only reload on modify or create events
-                    if matches!(event.kind, EventKind::Modify(_) | EventKind::Create(_)) {
-                        if event.paths.iter().any(|p| p == &config_path_clone) {
-                            // Attempt to reload config
-                            match Config::load_from_file(&config_path_clone) {
-                                Ok(new_config) => {
-                                    if let Ok(mut config) = current_config_clone.lock() {
-                                        *config = new_config;
-                                        // Send reload notification (debounced)
-                                        if let Ok(sender) = debounced_sender.lock() {
-                                            sender.send();
-                                        }
-                                    }
-                                }
-                                Err(e) => {
-                                    eprintln!("Failed to reload config: {}", e);
-                                }
-                            }
-                        }
+                    // Only reload on modify, create or remove events
+                    if matches!(event.kind, EventKind::Modify(_) | EventKind::Create(_) | EventKind::Remove(_))
+                        && event.paths.iter().any(|p| p == &config_path_clone)
+                    {
+                        let _ = change_sender.send(());
                     }
                 }
                 Err(e) => eprintln!("Watch error: {:?}", e),
             }
         }).map_err(|e| ConfigError::IoError(format!("Failed to create watcher: {}", e)))?;
         
-        // Watch the config file's parent directory (watching files directly can be unreliable)
-        if let Some(parent) = config_path.parent() {
-            watcher.watch(parent, RecursiveMode::NonRecursive)
-                .map_err(|e| ConfigError::IoError(format!("Failed to watch config directory: {}", e)))?;
-        }
+        // Watch the config file's parent directory (watching files directly can be unreliable).
+        // On a fresh install the directory may not exist yet, so create it first; if it still
+        // can't be watched, run without hot reload rather than failing to start.
+        let watcher = match config_path.parent() {
+            Some(parent) => {
+                let result = std::fs::create_dir_all(parent)
+                    .map_err(|e| e.to_string())
+                    .and_then(|_| {
+                        watcher.watch(parent, RecursiveMode::NonRecursive).map_err(|e| e.to_string())
+                    });
+                match result {
+                    Ok(()) => Some(watcher),
+                    Err(e) => {
+                        eprintln!(
+                            "Warning: Cannot watch config directory {}: {} (hot reload disabled)",
+                            parent.display(),
+                            e
+                        );
+                        None
+                    }
+                }
+            }
+            None => Some(watcher),
+        };
+        
+        // Reload at most once per burst of events; the thread exits when the watcher is dropped
+        let current_config_clone = current_config.clone();
+        let reload_attempts_clone = reload_attempts.clone();
+        std::thread::spawn(move || {
+            while change_receiver.recv().is_ok() {
+                // Wait until no further events arrive within the debounce window
+                while change_receiver.recv_timeout(RELOAD_DEBOUNCE).is_ok() {}
+                
+                reload_attempts_clone.fetch_add(1, Ordering::Relaxed);
+                // A missing file loads as the defaults
+                if !config_path.exists() && on_removed == OnConfigRemoved::Keep {
+                    eprintln!("Config file {} was removed; keeping the current configuration", config_path.display());
+                    continue;
+                }
+                match Config::load_from_file(&config_path) {
+                    Ok(new_config) => {
+                        if let Ok(mut config) = current_config_clone.lock() {
+                            *config = new_config;
+                        }
+                        let _ = reload_sender.send(());
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to reload config: {}", e);
+                    }
+                }
+            }
+        });
         
         Ok(ConfigWatcher {
             current_config,
-            _watcher: watcher,
+            watcher,
             reload_receiver,
+            reload_attempts,
         })
     }
     
@@ -334,6 +1130,16 @@ impl ConfigWatcher {
         self.current_config.lock().unwrap().clone()
     }
     
+    /// Check whether configuration changes are being watched for hot reload
+    pub fn is_watching(&self) -> bool {
+        self.watcher.is_some()
+    }
+    
+    /// Get the number of reload attempts made so far
+    pub fn reload_attempts(&self) -> usize {
+        self.reload_attempts.load(Ordering::Relaxed)
+    }
+    
     /// Wait for the next configuration reload event (blocking)
     pub fn wait_for_reload_blocking(&mut self) -> Option<Config> {
         self.reload_receiver.recv().ok()?;
@@ -347,39 +1153,6 @@ impl ConfigWatcher {
     }
 }
 
-/// Helper to debounce reload notifications
-struct DebouncedSender {
-    sender: std::sync::mpsc::Sender<()>,
-    debounce_duration: Duration,
-    last_send: Arc<Mutex<Option<std::time::Instant>>>,
-}
-
-impl DebouncedSender {
-    fn new(sender: std::sync::mpsc::Sender<()>, debounce_duration: Duration) -> Self {
-        DebouncedSender {
-            sender,
-            debounce_duration,
-            last_send: Arc::new(Mutex::new(None)),
-        }
-    }
-    
-    fn send(&self) {
-        let now = std::time::Instant::now();
-        let mut last_send = self.last_send.lock().unwrap();
-        
-        // Check if enough time has passed since last send
-        let should_send = match *last_send {
-            Some(last) => now.duration_since(last) >= self.debounce_duration,
-            None => true,
-        };
-        
-        if should_send {
-            *last_send = Some(now);
-            let _ = self.sender.send(());
-        }
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -396,6 +1169,35 @@ mod tests {
         assert_eq!(config.performance.flush_interval_ms, 1000);
         assert_eq!(config.ui.keyboard_shortcut, "Super+Space");
         assert_eq!(config.ui.max_results, 50);
+        assert!(!config.indexing.index_hidden_files);
+    }
+
+    #[test]
+    fn test_index_hidden_files_from_toml() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        let toml_content = r#"
+[indexing]
+include_paths = ["~"]
+index_hidden_files = true
+"#;
+        temp_file.write_all(toml_content.as_bytes()).unwrap();
+
+        let config = Config::load_from_file(temp_file.path()).unwrap();
+        assert!(config.indexing.index_hidden_files);
+    }
+
+    #[test]
+    fn test_path_display_from_toml() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        let toml_content = r#"
+[ui]
+path_display = "lossless"
+"#;
+        temp_file.write_all(toml_content.as_bytes()).unwrap();
+
+        let config = Config::load_from_file(temp_file.path()).unwrap();
+        assert_eq!(config.ui.path_display, PathDisplayMode::Lossless);
+        assert_eq!(Config::default().ui.path_display, PathDisplayMode::Lossy);
     }
 
     #[test]
@@ -419,6 +1221,7 @@ max_cpu_percent = 20
 max_memory_mb = 200
 batch_size = 50
 flush_interval_ms = 500
+on_config_removed = "defaults"
 
 [ui]
 keyboard_shortcut = "Ctrl+Alt+F"
@@ -430,9 +1233,30 @@ max_results = 100
         assert_eq!(config.indexing.include_paths, vec!["/home/user/Documents", "/home/user/Projects"]);
         assert_eq!(config.indexing.exclude_patterns, vec!["*.tmp", "*.log"]);
         assert_eq!(config.performance.max_cpu_percent, 20);
+        assert_eq!(config.performance.on_config_removed, OnConfigRemoved::Defaults);
         assert_eq!(config.ui.keyboard_shortcut, "Ctrl+Alt+F");
     }
 
+    #[test]
+    fn test_non_recursive_include_path_from_toml() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        let toml_content = r#"
+[indexing]
+include_paths = ["/home/user", { path = "/home/user/Downloads", recursive = false }]
+"#;
+        temp_file.write_all(toml_content.as_bytes()).unwrap();
+
+        let config = Config::load_from_file(temp_file.path()).unwrap();
+        assert_eq!(config.indexing.include_paths, vec!["/home/user", "/home/user/Downloads"]);
+        assert_eq!(config.expand_paths(), vec![
+            PathBuf::from("/home/user"),
+            PathBuf::from("/home/user/Downloads"),
+        ]);
+        assert!(config.indexing.is_recursive_root(Path::new("/home/user")));
+        assert!(!config.indexing.is_recursive_root(Path::new("/home/user/Downloads")));
+        assert!(config.indexing.is_recursive_root(Path::new("/usr/share/applications")));
+    }
+
     #[test]
     fn test_load_invalid_toml() {
         let mut temp_file = NamedTempFile::new().unwrap();
@@ -484,6 +1308,26 @@ max_results = 100
         config = Config::default();
         config.ui.max_results = 0;
         assert!(config.validate().is_err());
+        
+        config = Config::default();
+        config.performance.max_watches = Some(0);
+        assert!(config.validate().is_err());
+        
+        config = Config::default();
+        config.indexing.reindex_interval_hours = Some(0);
+        assert!(config.validate().is_err());
+        
+        config = Config::default();
+        config.indexing.reindex_min_fraction = 1.5;
+        assert!(config.validate().is_err());
+        
+        config = Config::default();
+        config.indexing.initial_scan_timeout_secs = Some(0);
+        assert!(config.validate().is_err());
+        
+        config = Config::default();
+        config.ui.recent_launch_weight = -0.1;
+        assert!(config.validate().is_err());
     }
 
     #[test]
@@ -519,7 +1363,7 @@ max_results = 100
     #[test]
     fn test_expand_paths() {
         let mut config = Config::default();
-        config.indexing.include_paths = vec!["~".to_string(), "~/Documents".to_string()];
+        config.indexing.include_paths = vec!["~".into(), "~/Documents".into()];
         
         let expanded = config.expand_paths();
         let home = std::env::var("HOME").unwrap();
@@ -528,12 +1372,104 @@ max_results = 100
         assert_eq!(expanded[1], PathBuf::from(&home).join("Documents"));
     }
 
+    #[test]
+    fn test_parse_include_paths_list() {
+        let contents = "# Work projects\n~/work\n\n   \n  /data/photos  \n# ~/old\n~/work\n";
+        assert_eq!(
+            parse_include_paths_list(contents),
+            vec!["~/work", "/data/photos", "~/work"]
+        );
+    }
+
+    #[test]
+    fn test_include_paths_file_merged_on_load() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            temp_dir.path().join("include_paths.txt"),
+            "# Extra roots\n/data/photos\n\n~/Documents\n/srv/shared\n",
+        ).unwrap();
+        let config_path = temp_dir.path().join("config.toml");
+        std::fs::write(&config_path, r#"
+            [indexing]
+            include_paths = ["~/Documents"]
+            include_paths_file = "include_paths.txt"
+        "#).unwrap();
+        
+        let config = Config::load_from_file(&config_path).unwrap();
+        let home = PathBuf::from(std::env::var("HOME").unwrap());
+        assert_eq!(config.expand_paths(), vec![
+            home.join("Documents"),
+            PathBuf::from("/data/photos"),
+            PathBuf::from("/srv/shared"),
+        ]);
+        
+        // The list is read on every load, so edits show up on reload
+        std::fs::write(temp_dir.path().join("include_paths.txt"), "/mnt/backup\n").unwrap();
+        let config = Config::load_from_file(&config_path).unwrap();
+        assert_eq!(config.expand_paths(), vec![home.join("Documents"), PathBuf::from("/mnt/backup")]);
+    }
+
+    #[test]
+    fn test_missing_include_paths_file_adds_no_roots() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config_path = temp_dir.path().join("config.toml");
+        std::fs::write(&config_path, r#"
+            [indexing]
+            include_paths = ["/data/photos"]
+            include_paths_file = "include_paths.txt"
+        "#).unwrap();
+
+        let config = Config::load_from_file(&config_path).unwrap();
+        assert_eq!(config.expand_paths(), vec![PathBuf::from("/data/photos")]);
+
+        // A list that exists but cannot be read still fails the load
+        std::fs::create_dir(temp_dir.path().join("include_paths.txt")).unwrap();
+        assert!(matches!(Config::load_from_file(&config_path), Err(ConfigError::IoError(_))));
+    }
+
+    #[test]
+    fn test_exclude_bundles_expanded_on_load() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config_path = temp_dir.path().join("config.toml");
+        std::fs::write(&config_path, r#"
+            [indexing]
+            exclude_patterns = ["*.iso", "node_modules"]
+            exclude_bundles = ["dev"]
+        "#).unwrap();
+        
+        let config = Config::load_from_file(&config_path).unwrap();
+        let patterns = config.indexing.effective_exclude_patterns();
+        assert_eq!(&patterns[..2], ["*.iso", "node_modules"]);
+        assert!(patterns.contains(&"__pycache__".to_string()));
+        assert!(patterns.contains(&".venv".to_string()));
+        assert_eq!(patterns.iter().filter(|p| *p == "node_modules").count(), 1);
+        assert!(!patterns.contains(&".thumbnails".to_string()));
+        
+        // Saving keeps the bundle name rather than its patterns
+        config.save_to_file(&config_path).unwrap();
+        let saved = std::fs::read_to_string(&config_path).unwrap();
+        assert!(saved.contains("exclude_bundles = [\"dev\"]"));
+        assert!(!saved.contains("__pycache__"));
+        
+        std::fs::write(&config_path, "[indexing]\nexclude_bundles = [\"games\"]\n").unwrap();
+        assert!(matches!(Config::load_from_file(&config_path), Err(ConfigError::ValidationError(_))));
+    }
+
     #[test]
     fn test_flush_interval() {
         let config = Config::default();
         assert_eq!(config.flush_interval(), Duration::from_millis(1000));
     }
 
+    #[test]
+    fn test_reindex_interval() {
+        let mut config = Config::default();
+        assert_eq!(config.reindex_interval(), None);
+        
+        config.indexing.reindex_interval_hours = Some(6);
+        assert_eq!(config.reindex_interval(), Some(Duration::from_secs(6 * 60 * 60)));
+    }
+
     #[test]
     fn test_partial_config() {
         let mut temp_file = NamedTempFile::new().unwrap();
@@ -564,6 +1500,100 @@ include_paths = ["/home/user/Documents"]
         assert_eq!(loaded_config.indexing.include_paths, vec!["~"]);
     }
 
+    #[test]
+    fn test_application_options_from_toml() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        let toml_content = r#"
+[indexing]
+include_paths = ["~"]
+watch_system_applications = false
+"#;
+        temp_file.write_all(toml_content.as_bytes()).unwrap();
+
+        let config = Config::load_from_file(temp_file.path()).unwrap();
+        assert!(config.indexing.index_applications);
+        assert!(!config.indexing.watch_system_applications);
+    }
+
+    #[test]
+    fn test_always_includes_extension() {
+        let mut config = IndexingConfig::default();
+        assert!(!config.always_includes(Path::new("/home/user/report.pdf")));
+        
+        config.always_include_extensions = vec![".pdf".to_string(), "log".to_string()];
+        assert!(config.always_includes(Path::new("/home/user/report.pdf")));
+        assert!(config.always_includes(Path::new("/home/user/REPORT.PDF")));
+        assert!(config.always_includes(Path::new("/var/log/app.log")));
+        assert!(!config.always_includes(Path::new("/home/user/notes.txt")));
+        assert!(!config.always_includes(Path::new("/home/user/pdf")));
+    }
+
+    #[test]
+    fn test_save_to_file_is_atomic() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config_path = temp_dir.path().join("config.toml");
+        
+        let mut config = Config::default();
+        config.ui.max_results = 10;
+        config.save_to_file(&config_path).unwrap();
+        config.ui.max_results = 20;
+        config.save_to_file(&config_path).unwrap();
+        
+        // Only the final, complete file remains
+        let files: Vec<_> = fs::read_dir(temp_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(files, vec![std::ffi::OsString::from("config.toml")]);
+        assert_eq!(Config::load_from_file(&config_path).unwrap().ui.max_results, 20);
+    }
+
+    #[test]
+    fn test_save_to_file_failure_cleans_up() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        // A directory in the way makes the final rename fail
+        let config_path = temp_dir.path().join("config.toml");
+        fs::create_dir(&config_path).unwrap();
+        fs::write(config_path.join("keep"), "").unwrap();
+        
+        assert!(Config::default().save_to_file(&config_path).is_err());
+        
+        let files: Vec<_> = fs::read_dir(temp_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(files, vec![std::ffi::OsString::from("config.toml")]);
+    }
+
+    #[test]
+    fn test_config_watcher_missing_parent_dir() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config_dir = temp_dir.path().join("novasearch");
+        let config_path = config_dir.join("config.toml");
+        assert!(!config_dir.exists());
+        
+        let mut watcher = ConfigWatcher::new(config_path.clone()).unwrap();
+        assert!(config_dir.is_dir());
+        assert!(watcher.is_watching());
+        assert_eq!(watcher.get_config().ui.max_results, 50);
+        
+        // A config written after startup is still picked up
+        let mut config = Config::default();
+        config.ui.max_results = 25;
+        config.save_to_file(&config_path).unwrap();
+        
+        let start = std::time::Instant::now();
+        let mut reloaded = None;
+        while start.elapsed() < Duration::from_secs(5) {
+            if let Some(config) = watcher.try_recv_reload() {
+                reloaded = Some(config);
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        assert_eq!(reloaded.unwrap().ui.max_results, 25);
+    }
+
     #[test]
     fn test_config_watcher_reload() {
         // Create a temporary config file
@@ -621,6 +1651,67 @@ max_results = 50
         }
     }
 
+    #[test]
+    fn test_config_watcher_debounces_rapid_writes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config_path = temp_dir.path().join("config.toml");
+        
+        Config::default().save_to_file(&config_path).unwrap();
+        let mut watcher = ConfigWatcher::new(config_path.clone()).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        
+        // Several writes well within the debounce window
+        for cpu in 11..16 {
+            let mut config = Config::default();
+            config.performance.max_cpu_percent = cpu;
+            config.save_to_file(&config_path).unwrap();
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        
+        std::thread::sleep(RELOAD_DEBOUNCE * 3);
+        
+        // File watching may be unavailable on some systems; if it fired, it fired once
+        assert!(watcher.reload_attempts() <= 1);
+        if watcher.reload_attempts() == 1 {
+            let config = watcher.try_recv_reload().unwrap();
+            assert_eq!(config.performance.max_cpu_percent, 15);
+            assert!(watcher.try_recv_reload().is_none());
+        }
+    }
+
+    #[test]
+    fn test_config_watcher_removed_file() {
+        for on_removed in [OnConfigRemoved::Keep, OnConfigRemoved::Defaults] {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let config_path = temp_dir.path().join("config.toml");
+            let mut config = Config::default();
+            config.ui.max_results = 25;
+            config.save_to_file(&config_path).unwrap();
+
+            let mut watcher = ConfigWatcher::with_removal_policy(config_path.clone(), on_removed).unwrap();
+            std::thread::sleep(Duration::from_millis(100));
+            std::fs::remove_file(&config_path).unwrap();
+
+            // Wait for the debounced reload to have run (if file watching works here)
+            let start = std::time::Instant::now();
+            while watcher.reload_attempts() == 0 && start.elapsed() < Duration::from_secs(5) {
+                std::thread::sleep(Duration::from_millis(50));
+            }
+            std::thread::sleep(Duration::from_millis(100));
+            assert_eq!(watcher.reload_attempts(), 1, "removing the config file was not noticed");
+
+            match on_removed {
+                OnConfigRemoved::Keep => {
+                    assert!(watcher.try_recv_reload().is_none());
+                    assert_eq!(watcher.get_config().ui.max_results, 25);
+                }
+                OnConfigRemoved::Defaults => {
+                    assert_eq!(watcher.try_recv_reload().unwrap().ui.max_results, 50);
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_config_watcher_invalid_reload() {
         let temp_dir = tempfile::tempdir().unwrap();
@@ -629,7 +1720,7 @@ max_results = 50
         let initial_config = Config::default();
         initial_config.save_to_file(&config_path).unwrap();
         
-        let mut watcher = ConfigWatcher::new(config_path.clone()).unwrap();
+        let watcher = ConfigWatcher::new(config_path.clone()).unwrap();
         let initial = watcher.get_config();
         
         std::thread::sleep(Duration::from_millis(100));
@@ -1,22 +1,621 @@
-use rusqlite::{Connection, Result as SqliteResult, params, OptionalExtension};
+use rusqlite::{Connection, Result as SqliteResult, params, params_from_iter, OptionalExtension};
+use rusqlite::functions::FunctionFlags;
+use rusqlite::types::Value;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH, Duration};
-use crate::models::{FileEntry, FileType, IndexOperation};
+use unicode_normalization::UnicodeNormalization;
+use crate::config::Config;
+use crate::models::{self, FileEntry, FileEntryWithStats, FileType, IndexOperation, MatchKind, SearchResult};
 
 /// Database schema version
-const SCHEMA_VERSION: i32 = 2;
+pub const SCHEMA_VERSION: i32 = 16;
+
+/// Condition that hides the entries of filesystems unmounted for now (see `set_mount_offline`)
+///
+/// Paths under a mount sort between "mount/" and "mount0" ('0' follows '/').
+/// Files must be aliased as `f`. A macro so the query constants can `concat!` it.
+macro_rules! online_condition {
+    () => {
+        "NOT EXISTS (SELECT 1 FROM offline_mounts m
+                        WHERE f.path >= m.path || '/' AND f.path < m.path || '0')"
+    };
+}
+
+/// `online_condition!` for queries built at run time
+const ONLINE_CONDITION: &str = online_condition!();
+
+/// Filename search ranked by match quality, then usage
+///
+/// Takes the LIKE-escaped query, the raw query, the escaped query again and the
+/// limit, with the query normalized like the stored names. `query_files` adds
+/// its exclusions after `QUERY_FILES_MATCH`, taking their parameters in between.
+const QUERY_FILES_SQL: &str = concat!(
+    "SELECT f.id, f.filename, f.path, f.size, f.modified_time, f.file_type, f.indexed_time,
+            f.modified_nsec, f.is_executable,
+            COALESCE(u.launch_count, 0) as launch_count,
+            COALESCE(u.last_launched, 0) as last_launched
+     FROM files f
+     LEFT JOIN usage_stats u ON f.id = u.file_id
+     WHERE f.normalized_name LIKE '%' || ? || '%' ESCAPE '\\'
+       AND ", online_condition!(), "
+     ORDER BY 
+        CASE 
+            WHEN f.normalized_name = ? THEN 0
+            WHEN f.normalized_name LIKE ? || '%' ESCAPE '\\' THEN 1
+            ELSE 2
+        END,
+        COALESCE(u.launch_count, 0) DESC,
+        f.filename COLLATE NOCASE
+     LIMIT ?");
+
+/// The name condition of `QUERY_FILES_SQL`
+const QUERY_FILES_MATCH: &str = "WHERE f.normalized_name LIKE '%' || ? || '%' ESCAPE '\\'";
+
+/// Filename search through the `files_fts` index, ranked like `QUERY_FILES_SQL`
+///
+/// Takes the MATCH expression, the raw query, the LIKE-escaped query and the
+/// limit, with the query normalized like the stored names.
+const QUERY_FILES_FTS_SQL: &str = concat!(
+    "SELECT f.id, f.filename, f.path, f.size, f.modified_time, f.file_type, f.indexed_time,
+            f.modified_nsec, f.is_executable
+     FROM files_fts
+     JOIN files f ON f.id = files_fts.rowid
+     LEFT JOIN usage_stats u ON f.id = u.file_id
+     WHERE files_fts MATCH ?
+       AND ", online_condition!(), "
+     ORDER BY
+        CASE
+            WHEN f.normalized_name = ? THEN 0
+            WHEN f.normalized_name LIKE ? || '%' ESCAPE '\\' THEN 1
+            ELSE 2
+        END,
+        COALESCE(u.launch_count, 0) DESC,
+        f.filename COLLATE NOCASE
+     LIMIT ?");
+
+/// Directory search for folder jumping, ranked by usage, then match quality
+///
+/// Takes the same parameters as `QUERY_FILES_SQL`. The `file_type` condition
+/// matches the partial index created by `create_directory_index`, so only
+/// directory rows are scanned rather than every indexed file.
+const QUERY_DIRECTORIES_SQL: &str = concat!(
+    "SELECT f.id, f.filename, f.path, f.size, f.modified_time, f.file_type, f.indexed_time,
+            f.modified_nsec, f.is_executable
+     FROM files f
+     LEFT JOIN usage_stats u ON f.id = u.file_id
+     WHERE f.file_type = 'directory' AND f.normalized_name LIKE '%' || ? || '%' ESCAPE '\\'
+       AND ", online_condition!(), "
+     ORDER BY
+        COALESCE(u.launch_count, 0) DESC,
+        CASE
+            WHEN f.normalized_name = ? THEN 0
+            WHEN f.normalized_name LIKE ? || '%' ESCAPE '\\' THEN 1
+            ELSE 2
+        END,
+        f.filename COLLATE NOCASE
+     LIMIT ?");
+
+/// Filters applied to a filename search at query time
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    /// Maximum number of results
+    pub limit: usize,
+    /// Only return these file types (empty matches all)
+    pub file_types: Vec<FileType>,
+    /// Only return filenames with one of these extensions, without the dot (empty matches all)
+    pub extensions: Vec<String>,
+    /// Hide results located under any of these directories
+    pub exclude_prefixes: Vec<PathBuf>,
+    /// Order of the returned results
+    pub order_by: SearchOrder,
+    /// What an empty query returns when no type or extension filter is set
+    pub empty_query: EmptyQuery,
+    /// Which part of each entry's path the query is matched against
+    pub scope: SearchScope,
+    /// Factor applied to the launch count of directories for `SearchOrder::Relevance`
+    pub directory_weight: f64,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            limit: 50,
+            file_types: Vec::new(),
+            extensions: Vec::new(),
+            exclude_prefixes: Vec::new(),
+            order_by: SearchOrder::Relevance,
+            empty_query: EmptyQuery::Nothing,
+            scope: SearchScope::Filename,
+            directory_weight: 1.0,
+        }
+    }
+}
+
+/// Part of an entry the search text is matched against
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchScope {
+    /// The filename only
+    #[default]
+    Filename,
+    /// Anywhere in the full path, ranked by usage alone
+    Path,
+    /// Anywhere in the full path, with filename matches ranked above directory-only ones
+    Both,
+}
+
+/// Results for a search whose query is empty or only whitespace
+///
+/// Such a query matches every filename, so rather than ranking the whole
+/// table the caller picks one of these.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EmptyQuery {
+    /// No results
+    #[default]
+    Nothing,
+    /// The most frequently launched files, as from `get_most_used_files`
+    Frequent,
+}
+
+/// Result ordering for a filename search
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SearchOrder {
+    /// Exact matches, then prefix matches, then substrings (then directory-only
+    /// matches for `SearchScope::Both`); most launched first within each, with
+    /// directory launches scaled by `SearchOptions::directory_weight`
+    #[default]
+    Relevance,
+    /// Most recently modified first
+    ModifiedDesc,
+    /// Largest first
+    SizeDesc,
+    /// Filename, case-insensitive
+    NameAsc,
+    /// Full path
+    PathAsc,
+}
+
+impl SearchOrder {
+    /// ORDER BY terms for `build_search_sql`, which aliases files as `f`
+    fn order_by_sql(self) -> &'static str {
+        match self {
+            SearchOrder::Relevance => {
+                "match_tier, weighted_launches DESC, f.filename COLLATE NOCASE"
+            }
+            SearchOrder::ModifiedDesc => {
+                "f.modified_time DESC, f.modified_nsec DESC, f.filename COLLATE NOCASE"
+            }
+            SearchOrder::SizeDesc => "f.size DESC, f.filename COLLATE NOCASE",
+            SearchOrder::NameAsc => "f.filename COLLATE NOCASE, f.path",
+            SearchOrder::PathAsc => "f.path",
+        }
+    }
+}
+
+impl std::str::FromStr for SearchOrder {
+    type Err = String;
+
+    /// Parse the names accepted by `search --sort`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "relevance" => Ok(SearchOrder::Relevance),
+            "modified" => Ok(SearchOrder::ModifiedDesc),
+            "size" => Ok(SearchOrder::SizeDesc),
+            "name" => Ok(SearchOrder::NameAsc),
+            "path" => Ok(SearchOrder::PathAsc),
+            _ => Err(format!(
+                "unknown sort order '{}' (expected relevance, modified, size, name or path)",
+                s
+            )),
+        }
+    }
+}
+
+/// Predicate applied to search results after they are fetched; `false` drops the entry
+pub type ResultFilter = Box<dyn Fn(&FileEntry) -> bool>;
+
+/// Built-in result filter that drops entries no longer present on disk
+pub fn exists_on_disk(entry: &FileEntry) -> bool {
+    entry.path.symlink_metadata().is_ok()
+}
+
+/// Outcome of reconciling the index against a fresh scan
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ReconcileStats {
+    pub updated: usize,
+    pub deleted: usize,
+}
+
+/// Rows changed by one `execute_batch` call, by kind of change
+///
+/// Adds and updates are counted by what actually happened to the row, so an
+/// add for a path already indexed counts as updated. Operations that matched
+/// no row (e.g. deleting a path that was never indexed) are not counted.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BatchResult {
+    pub added: usize,
+    pub updated: usize,
+    pub deleted: usize,
+    pub moved: usize,
+}
+
+impl BatchResult {
+    /// Get the number of rows changed in any way
+    pub fn total(&self) -> usize {
+        self.added + self.updated + self.deleted + self.moved
+    }
+}
+
+/// Outcome of replacing the whole index with a fresh scan
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplaceOutcome {
+    /// The index now holds exactly the scanned entries
+    Replaced { previous: usize, indexed: usize },
+    /// The scan found too few entries to be trusted; the old index was kept
+    Refused { previous: usize, scanned: usize },
+}
+
+/// Number of compiled statements kept by the connection's statement cache
+const STATEMENT_CACHE_CAPACITY: usize = 32;
+
+/// How long a statement waits for another connection's lock before failing as busy
+const BUSY_TIMEOUT: Duration = Duration::from_millis(5000);
+
+/// Deletions `prune_missing` commits per transaction
+const PRUNE_BATCH_SIZE: usize = 1000;
+
+/// Entries `prune_missing_with_progress` checks between progress reports
+pub const PRUNE_PROGRESS_INTERVAL: usize = 5000;
+
+/// Bound parameters allowed in one statement by every SQLite build we may link
+/// against (the default was raised from 999 to 32766 in 3.32)
+const MAX_BOUND_PARAMETERS: usize = 999;
+
+/// Bound parameters per row of the `files` upsert
+const UPSERT_COLUMNS: usize = 12;
+
+/// How indexed paths are stored
+///
+/// Reads always go through `files`, which has the same columns either way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PathStorage {
+    /// `files` is a table holding each entry's full path
+    #[default]
+    Full,
+    /// `files` is a view that joins `file_rows`, holding a directory id and the
+    /// filename, with `directories`, where each directory path is stored once
+    Interned,
+}
+
+impl PathStorage {
+    /// Table that rows are written to
+    fn table(self) -> &'static str {
+        match self {
+            PathStorage::Full => "files",
+            PathStorage::Interned => "file_rows",
+        }
+    }
+
+    /// Column of `table()` that stores the path
+    fn path_column(self) -> &'static str {
+        match self {
+            PathStorage::Full => "path",
+            PathStorage::Interned => "dir_id",
+        }
+    }
+
+    /// Placeholder that turns `path_value` into the value of `path_column()`
+    fn path_placeholder(self) -> &'static str {
+        match self {
+            PathStorage::Full => "?",
+            PathStorage::Interned => "(SELECT id FROM directories WHERE path = ?)",
+        }
+    }
+
+    /// Columns that identify a row in `table()`
+    fn conflict_target(self) -> &'static str {
+        match self {
+            PathStorage::Full => "path",
+            PathStorage::Interned => "dir_id, filename",
+        }
+    }
+
+    /// Value bound to `path_placeholder()` for `path`
+    fn path_value(self, path: &Path) -> Value {
+        match self {
+            PathStorage::Full => Value::Text(path.to_string_lossy().to_string()),
+            PathStorage::Interned => Value::Text(split_path(path).0),
+        }
+    }
+
+    /// Value of the `filename` column of `table()` for an entry at `path`
+    ///
+    /// Interned rows are keyed by the last component as `split_path` returns
+    /// it, which differs from the entry's name for `/` and paths ending in `..`.
+    fn stored_filename(self, filename: &str, path: &Path) -> String {
+        match self {
+            PathStorage::Full => filename.to_string(),
+            PathStorage::Interned => split_path(path).1,
+        }
+    }
+
+    /// Condition on `table()` selecting the row of one path; bind `path_params`
+    fn path_condition(self) -> &'static str {
+        match self {
+            PathStorage::Full => "path = ?",
+            PathStorage::Interned => "dir_id = (SELECT id FROM directories WHERE path = ?) AND filename = ?",
+        }
+    }
+
+    /// Condition on `table()` selecting the rows below the directory `path`; bind `descendant_params`
+    ///
+    /// Paths under a directory sort between "dir/" and "dir0" ('0' follows '/').
+    fn descendant_condition(self) -> &'static str {
+        match self {
+            PathStorage::Full => "path >= ? AND path < ?",
+            PathStorage::Interned => {
+                "dir_id IN (SELECT id FROM directories WHERE path = ? OR (path >= ? AND path < ?))"
+            }
+        }
+    }
+
+    /// Parameters of `descendant_condition()` for `path`
+    fn descendant_params(self, path: &Path) -> Vec<Value> {
+        let path = path.to_string_lossy();
+        let path = path.trim_end_matches('/');
+        let range = [Value::Text(format!("{}/", path)), Value::Text(format!("{}0", path))];
+        match self {
+            PathStorage::Full => range.into(),
+            PathStorage::Interned => std::iter::once(Value::Text(path.to_string())).chain(range).collect(),
+        }
+    }
+
+    /// Parameters of `path_condition()` for `path`
+    fn path_params(self, path: &Path) -> Vec<Value> {
+        match self {
+            PathStorage::Full => vec![Value::Text(path.to_string_lossy().to_string())],
+            PathStorage::Interned => {
+                let (dir, filename) = split_path(path);
+                vec![Value::Text(dir), Value::Text(filename)]
+            }
+        }
+    }
+
+    /// Make sure the directories of `paths` have ids before rows refer to them
+    fn intern_directories<'a>(
+        self,
+        connection: &Connection,
+        paths: impl IntoIterator<Item = &'a Path>,
+    ) -> SqliteResult<()> {
+        if self == PathStorage::Interned {
+            let mut stmt = connection.prepare_cached("INSERT OR IGNORE INTO directories (path) VALUES (?)")?;
+            for path in paths {
+                stmt.execute(params![split_path(path).0])?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Unicode normalization form of the names searches match against
+///
+/// The same character can be spelled as one code point or as a base letter
+/// plus combining marks; macOS filesystems store the latter (NFD), most Linux
+/// tools produce the former (NFC). Each entry keeps its filename as found on
+/// disk and also a copy normalized to this form, which queries are normalized
+/// to as well, so both spellings find each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NameNormalization {
+    /// Composed characters
+    #[default]
+    Nfc,
+    /// Decomposed characters
+    Nfd,
+}
+
+impl NameNormalization {
+    /// Normalize `name` to this form
+    pub fn normalize(self, name: &str) -> String {
+        match self {
+            NameNormalization::Nfc => name.nfc().collect(),
+            NameNormalization::Nfd => name.nfd().collect(),
+        }
+    }
+
+    /// Name stored in the metadata table
+    fn as_str(self) -> &'static str {
+        match self {
+            NameNormalization::Nfc => "nfc",
+            NameNormalization::Nfd => "nfd",
+        }
+    }
+}
+
+/// The `files` view over `file_rows` used with `PathStorage::Interned`
+///
+/// Paths are rebuilt the way `split_path` took them apart. Rows without a
+/// last component (`/`, or a path ending in `..`) are named after the whole
+/// path, as `models::entry_name` does.
+const FILES_VIEW_SQL: &str =
+    "CREATE VIEW files AS
+        SELECT f.id,
+               CASE WHEN f.filename IN ('', '..') THEN d.path || '/' || f.filename ELSE f.filename END AS filename,
+               d.path || '/' || f.filename AS path, f.size, f.modified_time,
+               f.file_type, f.indexed_time, f.modified_nsec, f.is_executable, f.repo_root,
+               f.normalized_name, f.uid, f.gid
+        FROM file_rows f
+        JOIN directories d ON d.id = f.dir_id;";
+
+/// Build the MATCH expression for `query_files_fts`, or `None` if `query` has no words
+///
+/// Every whitespace-separated word becomes a quoted prefix phrase on the
+/// `filename` column, with quotes doubled, so FTS5 syntax in user input
+/// (`"`, `*`, `:`, `AND`, `NEAR` and so on) is matched as text. Words without
+/// a letter or digit would tokenize to nothing and are left out.
+fn fts_name_expression(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .map(|word| format!("filename : \"{}\"*", word.replace('"', "\"\"")))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" AND "))
+}
+
+/// Split an absolute path into its directory (empty for `/`) and last component
+///
+/// Matches the SQL used when converting to `PathStorage::Interned`, so joining
+/// the parts with `/` gives back the path.
+fn split_path(path: &Path) -> (String, String) {
+    let path = path.to_string_lossy();
+    match path.rfind('/') {
+        Some(index) => (path[..index].to_string(), path[index + 1..].to_string()),
+        None => (String::new(), path.into_owned()),
+    }
+}
+
+/// SQL for the directory part of the path in `column`, as `split_path` computes it
+///
+/// Trimming every character other than `/` off the end leaves the directory
+/// with its trailing slash, which is then cut off.
+fn sql_path_dir(column: &str) -> String {
+    let with_slash = format!("rtrim({0}, replace({0}, '/', ''))", column);
+    format!("substr({0}, 1, length({0}) - 1)", with_slash)
+}
+
+/// Read-only view of the index pinned by `Database::read_snapshot`
+///
+/// Dereferences to the `Database`, so the usual query methods are available.
+pub struct SnapshotReader<'a> {
+    db: &'a Database,
+    _transaction: rusqlite::Transaction<'a>,
+}
+
+impl std::ops::Deref for SnapshotReader<'_> {
+    type Target = Database;
+
+    fn deref(&self) -> &Database {
+        self.db
+    }
+}
 
 /// Database connection wrapper
 pub struct Database {
     connection: Connection,
+    storage: PathStorage,
+    normalization: NameNormalization,
+    /// Queries with fewer characters than this match nothing (see `set_min_query_length`)
+    min_query_length: usize,
+    /// Entries with longer paths are not written (see `set_max_path_length`)
+    max_path_length: Option<usize>,
 }
 
 impl Database {
     /// Open or create the database at the specified path
+    ///
+    /// The database is switched to write-ahead logging, so readers (including
+    /// `read_snapshot`) and the daemon's writer do not block each other.
     pub fn open<P: AsRef<Path>>(path: P) -> SqliteResult<Self> {
         let connection = Connection::open(path)?;
-        let db = Database { connection };
+        // Readers such as `status` and the panel keep working while the daemon writes
+        connection.query_row("PRAGMA journal_mode = WAL", [], |_| Ok(()))?;
+        connection.execute_batch("PRAGMA synchronous = NORMAL")?;
+        connection.busy_timeout(BUSY_TIMEOUT)?;
+        Self::from_connection(connection)
+    }
+
+    /// Open or create the database at `path` after checking that it can be written
+    ///
+    /// SQLite only notices a read-only location on the first write, and then
+    /// reports it as a generic I/O error. The directory is created if needed and
+    /// probed with a throwaway file first, so a read-only location fails with
+    /// `DatabaseError::NotWritable` instead.
+    pub fn open_writable<P: AsRef<Path>>(path: P) -> Result<Self, DatabaseError> {
+        let path = path.as_ref();
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let not_writable = |e: std::io::Error| {
+            DatabaseError::NotWritable(format!(
+                "{}: {} (use --data-dir to keep the index in another directory)",
+                dir.display(),
+                e
+            ))
+        };
+
+        std::fs::create_dir_all(dir).map_err(not_writable)?;
+        let probe = dir.join(format!(".write-test-{}", std::process::id()));
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&probe)
+            .map_err(not_writable)?;
+        let _ = std::fs::remove_file(&probe);
+
+        Self::open(path).map_err(|e| DatabaseError::SqliteError(e.to_string()))
+    }
+
+    /// Replace the database at `path`, which this connection has open, with
+    /// the one built at `replacement`, and reopen it
+    ///
+    /// The connection is closed during the swap so its write-ahead log cannot
+    /// be applied to the new file. If the swap fails the old database is
+    /// reopened and the error returned.
+    pub fn swap_in(&mut self, path: &Path, replacement: &Path) -> Result<(), DatabaseError> {
+        let sqlite_error = |e: rusqlite::Error| DatabaseError::SqliteError(e.to_string());
+        let (min_query_length, max_path_length) = (self.min_query_length, self.max_path_length);
+        *self = Self::open_in_memory().map_err(sqlite_error)?;
+        let installed = install_database_file(replacement, path);
+        *self = Self::open(path).map_err(sqlite_error)?;
+        self.min_query_length = min_query_length;
+        self.max_path_length = max_path_length;
+        installed
+    }
+
+    /// Open a database that lives only in memory and is lost when dropped
+    pub fn open_in_memory() -> SqliteResult<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    /// Wrap a fresh connection, creating or migrating the schema
+    fn from_connection(connection: Connection) -> SqliteResult<Self> {
+        connection.set_prepared_statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
+        // Paths have no normalized copy, so path searches normalize them as they go
+        connection.create_scalar_function(
+            "normalize_text",
+            2,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+            |ctx| {
+                let text: String = ctx.get(0)?;
+                let form = match ctx.get::<String>(1)?.as_str() {
+                    "nfd" => NameNormalization::Nfd,
+                    _ => NameNormalization::Nfc,
+                };
+                Ok(if text.is_ascii() { text } else { form.normalize(&text) })
+            },
+        )?;
+        let mut db = Database {
+            connection,
+            storage: PathStorage::Full,
+            normalization: NameNormalization::default(),
+            min_query_length: 1,
+            max_path_length: None,
+        };
+
+        // Detected before initializing so migrations know which table holds the rows
+        let files_is_view: bool = db.connection.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'view' AND name = 'files'",
+            [],
+            |row| row.get::<_, i32>(0),
+        )? > 0;
+        if files_is_view {
+            db.storage = PathStorage::Interned;
+        }
+        db.set_foreign_keys()?;
         db.initialize()?;
+        if db.get_metadata("name_normalization")?.as_deref() == Some(NameNormalization::Nfd.as_str()) {
+            db.normalization = NameNormalization::Nfd;
+        }
         Ok(db)
     }
 
@@ -39,19 +638,7 @@ impl Database {
 
     /// Create the database schema from scratch
     fn create_schema(&self) -> SqliteResult<()> {
-        // Create files table
-        self.connection.execute(
-            "CREATE TABLE IF NOT EXISTS files (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                filename TEXT NOT NULL,
-                path TEXT NOT NULL UNIQUE,
-                size INTEGER NOT NULL,
-                modified_time INTEGER NOT NULL,
-                file_type TEXT NOT NULL,
-                indexed_time INTEGER NOT NULL
-            )",
-            [],
-        )?;
+        self.create_files_table()?;
 
         // Create usage statistics table
         self.connection.execute(
@@ -65,678 +652,4486 @@ impl Database {
             [],
         )?;
 
-        // Create indexes for efficient searching
         self.connection.execute(
-            "CREATE INDEX IF NOT EXISTS idx_filename ON files(filename COLLATE NOCASE)",
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_usage_file_id ON usage_stats(file_id)",
             [],
         )?;
 
         self.connection.execute(
-            "CREATE INDEX IF NOT EXISTS idx_path ON files(path COLLATE NOCASE)",
+            "CREATE INDEX IF NOT EXISTS idx_usage_launch_count ON usage_stats(launch_count DESC)",
             [],
         )?;
 
+        // Create metadata table
         self.connection.execute(
-            "CREATE INDEX IF NOT EXISTS idx_modified_time ON files(modified_time)",
+            "CREATE TABLE IF NOT EXISTS metadata (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            )",
             [],
         )?;
 
+        self.create_xattrs_table()?;
+        self.create_content_table("files")?;
+        self.create_directories_table()?;
+        self.create_name_index(self.storage)?;
+        self.create_offline_mounts_table()?;
+
+        Ok(())
+    }
+
+    /// Create the table of unmounted filesystems whose entries are hidden (version 16)
+    fn create_offline_mounts_table(&self) -> SqliteResult<()> {
         self.connection.execute(
-            "CREATE INDEX IF NOT EXISTS idx_usage_file_id ON usage_stats(file_id)",
+            "CREATE TABLE IF NOT EXISTS offline_mounts (
+                path TEXT PRIMARY KEY
+            )",
             [],
         )?;
+        Ok(())
+    }
 
+    /// Create the `files` table holding full paths, with its indexes
+    fn create_files_table(&self) -> SqliteResult<()> {
         self.connection.execute(
-            "CREATE INDEX IF NOT EXISTS idx_usage_launch_count ON usage_stats(launch_count DESC)",
+            "CREATE TABLE IF NOT EXISTS files (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                filename TEXT NOT NULL,
+                path TEXT NOT NULL UNIQUE,
+                size INTEGER NOT NULL,
+                modified_time INTEGER NOT NULL,
+                file_type TEXT NOT NULL,
+                indexed_time INTEGER NOT NULL,
+                modified_nsec INTEGER NOT NULL DEFAULT 0,
+                is_executable INTEGER NOT NULL DEFAULT 0,
+                repo_root TEXT,
+                normalized_name TEXT NOT NULL DEFAULT '',
+                uid INTEGER,
+                gid INTEGER
+            )",
             [],
         )?;
 
-        // Create metadata table
+        // Create indexes for efficient searching
         self.connection.execute(
-            "CREATE TABLE IF NOT EXISTS metadata (
-                key TEXT PRIMARY KEY,
-                value TEXT NOT NULL
-            )",
+            "CREATE INDEX IF NOT EXISTS idx_filename ON files(filename COLLATE NOCASE)",
             [],
         )?;
 
-        Ok(())
-    }
+        self.connection.execute(
+            "CREATE INDEX IF NOT EXISTS idx_path ON files(path COLLATE NOCASE)",
+            [],
+        )?;
 
-    /// Get the current schema version
-    fn get_schema_version(&self) -> SqliteResult<i32> {
-        // Check if metadata table exists
-        let table_exists: bool = self.connection.query_row(
-            "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='metadata'",
+        self.connection.execute(
+            "CREATE INDEX IF NOT EXISTS idx_modified_time ON files(modified_time)",
             [],
-            |row| row.get::<_, i32>(0),
-        )? > 0;
+        )?;
 
-        if !table_exists {
-            return Ok(0);
-        }
+        self.create_size_index()?;
 
-        // Try to get schema version
-        match self.connection.query_row(
-            "SELECT value FROM metadata WHERE key = 'schema_version'",
+        self.connection.execute(
+            "CREATE INDEX IF NOT EXISTS idx_repo_root ON files(repo_root)",
             [],
-            |row| row.get::<_, String>(0),
-        ) {
-            Ok(version_str) => Ok(version_str.parse().unwrap_or(0)),
-            Err(_) => Ok(0),
-        }
+        )?;
+
+        self.create_directory_index()?;
+        self.create_owner_index(PathStorage::Full)?;
+
+        Ok(())
     }
 
-    /// Set the schema version
-    fn set_schema_version(&self, version: i32) -> SqliteResult<()> {
+    /// Create the partial index of directory names used by `query_directories`
+    /// (version 10, on the normalized names since version 12)
+    fn create_directory_index(&self) -> SqliteResult<()> {
+        let (name, table) = match self.storage {
+            PathStorage::Full => ("idx_directory_filename", "files"),
+            PathStorage::Interned => ("idx_file_rows_directory_filename", "file_rows"),
+        };
         self.connection.execute(
-            "INSERT OR REPLACE INTO metadata (key, value) VALUES ('schema_version', ?)",
-            [version.to_string()],
+            &format!(
+                "CREATE INDEX IF NOT EXISTS {} ON {}(normalized_name COLLATE NOCASE) WHERE file_type = 'directory'",
+                name, table
+            ),
+            [],
         )?;
         Ok(())
     }
 
-    /// Migrate schema from one version to another
-    fn migrate_schema(&self, from_version: i32, to_version: i32) -> SqliteResult<()> {
-        for version in from_version..to_version {
-            match version {
-                1 => self.migrate_v1_to_v2()?,
-                _ => {
-                    // Unknown migration path
-                    return Err(rusqlite::Error::InvalidQuery);
-                }
-            }
-        }
-        self.set_schema_version(to_version)?;
+    /// Create the table of interned directory paths (version 9)
+    fn create_directories_table(&self) -> SqliteResult<()> {
+        self.connection.execute(
+            "CREATE TABLE IF NOT EXISTS directories (
+                id INTEGER PRIMARY KEY,
+                path TEXT NOT NULL UNIQUE
+            )",
+            [],
+        )?;
         Ok(())
     }
 
-    /// Migrate from version 1 to version 2 (add usage tracking)
-    fn migrate_v1_to_v2(&self) -> SqliteResult<()> {
-        // Create usage statistics table
+    /// Create the extended attributes table and its cleanup trigger
+    fn create_xattrs_table(&self) -> SqliteResult<()> {
         self.connection.execute(
-            "CREATE TABLE IF NOT EXISTS usage_stats (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
+            "CREATE TABLE IF NOT EXISTS xattrs (
                 file_id INTEGER NOT NULL,
-                launch_count INTEGER NOT NULL DEFAULT 0,
-                last_launched INTEGER,
+                name TEXT NOT NULL,
+                value TEXT NOT NULL,
+                PRIMARY KEY (file_id, name),
                 FOREIGN KEY (file_id) REFERENCES files (id) ON DELETE CASCADE
             )",
             [],
         )?;
 
-        // Create indexes for usage stats
         self.connection.execute(
-            "CREATE INDEX IF NOT EXISTS idx_usage_file_id ON usage_stats(file_id)",
+            "CREATE INDEX IF NOT EXISTS idx_xattrs_name_value ON xattrs(name, value)",
             [],
         )?;
 
+        // Foreign keys are not enforced on this connection, so clean up explicitly
         self.connection.execute(
-            "CREATE INDEX IF NOT EXISTS idx_usage_launch_count ON usage_stats(launch_count DESC)",
+            "CREATE TRIGGER IF NOT EXISTS trg_files_delete_xattrs AFTER DELETE ON files
+             BEGIN
+                DELETE FROM xattrs WHERE file_id = OLD.id;
+             END",
             [],
         )?;
 
         Ok(())
     }
 
-    /// Get the underlying connection (for testing and operations)
-    pub fn connection(&self) -> &Connection {
-        &self.connection
-    }
+    /// Create the full-text table of file contents, cleaned up on deletes from `table` (version 11)
+    fn create_content_table(&self, table: &str) -> SqliteResult<()> {
+        // Each row shares the id of its file
+        self.connection.execute(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS file_content USING fts5(content)",
+            [],
+        )?;
 
-    /// Insert a new file entry into the database
-    pub fn insert_file(&self, entry: &FileEntry) -> SqliteResult<i64> {
-        let modified_time = system_time_to_timestamp(entry.modified_time);
-        let indexed_time = system_time_to_timestamp(entry.indexed_time);
-        
         self.connection.execute(
-            "INSERT INTO files (filename, path, size, modified_time, file_type, indexed_time)
-             VALUES (?, ?, ?, ?, ?, ?)",
-            params![
-                entry.filename,
-                entry.path.to_string_lossy().to_string(),
-                entry.size as i64,
-                modified_time,
-                entry.file_type.as_str(),
-                indexed_time,
-            ],
+            &format!(
+                "CREATE TRIGGER IF NOT EXISTS trg_{table}_delete_content AFTER DELETE ON {table}
+                 BEGIN
+                    DELETE FROM file_content WHERE rowid = OLD.id;
+                 END"
+            ),
+            [],
         )?;
-        
-        Ok(self.connection.last_insert_rowid())
+
+        Ok(())
     }
 
-    /// Update an existing file entry
-    pub fn update_file(&self, entry: &FileEntry) -> SqliteResult<()> {
-        let modified_time = system_time_to_timestamp(entry.modified_time);
-        let indexed_time = system_time_to_timestamp(entry.indexed_time);
+    /// Create the full-text index of names and paths kept in sync by triggers (version 13)
+    ///
+    /// `files_fts` holds the normalized name and the path of every row under
+    /// its id. It is filled from the index when first created; later calls
+    /// only add the triggers on `storage`'s table, e.g. after converting paths.
+    fn create_name_index(&self, storage: PathStorage) -> SqliteResult<()> {
+        let exists: bool = self.connection.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE name = 'files_fts'",
+            [],
+            |row| row.get::<_, i32>(0),
+        )? > 0;
+        if !exists {
+            self.connection.execute_batch(
+                "CREATE VIRTUAL TABLE files_fts USING fts5(filename, path, prefix = '2 3');
+                 INSERT INTO files_fts (rowid, filename, path) SELECT id, normalized_name, path FROM files;",
+            )?;
+        }
+
+        let (table, path, path_columns) = match storage {
+            PathStorage::Full => ("files", "NEW.path".to_string(), "path"),
+            PathStorage::Interned => (
+                "file_rows",
+                "(SELECT path FROM directories WHERE id = NEW.dir_id) || '/' || NEW.filename".to_string(),
+                "dir_id, filename",
+            ),
+        };
+        let changed = path_columns
+            .split(", ")
+            .map(|column| format!("OLD.{column} IS NOT NEW.{column}"))
+            .collect::<Vec<_>>()
+            .join(" OR ");
+        self.connection.execute_batch(&format!(
+            "CREATE TRIGGER IF NOT EXISTS trg_{table}_insert_fts AFTER INSERT ON {table}
+             BEGIN
+                INSERT INTO files_fts (rowid, filename, path) VALUES (NEW.id, NEW.normalized_name, {path});
+             END;
+             CREATE TRIGGER IF NOT EXISTS trg_{table}_delete_fts AFTER DELETE ON {table}
+             BEGIN
+                DELETE FROM files_fts WHERE rowid = OLD.id;
+             END;
+             CREATE TRIGGER IF NOT EXISTS trg_{table}_update_fts AFTER UPDATE OF normalized_name, {path_columns} ON {table}
+             WHEN OLD.normalized_name IS NOT NEW.normalized_name OR {changed}
+             BEGIN
+                DELETE FROM files_fts WHERE rowid = OLD.id;
+                INSERT INTO files_fts (rowid, filename, path) VALUES (NEW.id, NEW.normalized_name, {path});
+             END;"
+        ))
+    }
+
+    /// Get how paths are stored in this database
+    pub fn path_storage(&self) -> PathStorage {
+        self.storage
+    }
+
+    /// Convert the index to `storage`, rewriting every row in one transaction
+    ///
+    /// Row ids are kept, so usage statistics and extended attributes stay
+    /// attached to their files. Does nothing if the index already uses `storage`.
+    ///
+    /// Foreign keys are switched to match `storage` (see `set_foreign_keys`).
+    pub fn set_path_storage(&mut self, storage: PathStorage) -> SqliteResult<()> {
+        if storage == self.storage {
+            return Ok(());
+        }
+
+        // Dropping `files` with foreign keys on would cascade into usage_stats
+        self.connection.execute_batch("PRAGMA foreign_keys = OFF")?;
+        let tx = self.connection.unchecked_transaction()?;
+        match storage {
+            PathStorage::Interned => {
+                tx.execute_batch(&format!(
+                    "CREATE TABLE file_rows (
+                        id INTEGER PRIMARY KEY AUTOINCREMENT,
+                        dir_id INTEGER NOT NULL,
+                        filename TEXT NOT NULL,
+                        size INTEGER NOT NULL,
+                        modified_time INTEGER NOT NULL,
+                        file_type TEXT NOT NULL,
+                        indexed_time INTEGER NOT NULL,
+                        modified_nsec INTEGER NOT NULL DEFAULT 0,
+                        is_executable INTEGER NOT NULL DEFAULT 0,
+                        repo_root TEXT,
+                        normalized_name TEXT NOT NULL DEFAULT '',
+                        uid INTEGER,
+                        gid INTEGER,
+                        UNIQUE (dir_id, filename)
+                    );
+                    INSERT OR IGNORE INTO directories (path) SELECT {dir} FROM files f;
+                    INSERT INTO file_rows (id, dir_id, filename, size, modified_time, file_type, indexed_time,
+                                           modified_nsec, is_executable, repo_root, normalized_name, uid, gid)
+                        SELECT f.id, d.id, substr(f.path, length(d.path) + 2), f.size, f.modified_time,
+                               f.file_type, f.indexed_time, f.modified_nsec, f.is_executable, f.repo_root,
+                               f.normalized_name, f.uid, f.gid
+                        FROM files f
+                        JOIN directories d ON d.path = {dir};
+                    DROP TABLE files;
+                    {view}
+                    CREATE INDEX idx_file_rows_filename ON file_rows(filename COLLATE NOCASE);
+                    CREATE INDEX idx_file_rows_modified_time ON file_rows(modified_time);
+                    CREATE INDEX idx_file_rows_size ON file_rows(size);
+                    CREATE INDEX idx_file_rows_repo_root ON file_rows(repo_root);
+                    CREATE INDEX idx_file_rows_directory_filename ON file_rows(normalized_name COLLATE NOCASE)
+                        WHERE file_type = 'directory';
+                    CREATE TRIGGER trg_file_rows_delete AFTER DELETE ON file_rows
+                    BEGIN
+                        DELETE FROM xattrs WHERE file_id = OLD.id;
+                        DELETE FROM usage_stats WHERE file_id = OLD.id;
+                    END;",
+                    dir = sql_path_dir("f.path"),
+                    view = FILES_VIEW_SQL,
+                ))?;
+                self.create_content_table("file_rows")?;
+                self.create_owner_index(PathStorage::Interned)?;
+                self.create_name_index(PathStorage::Interned)?;
+            }
+            PathStorage::Full => {
+                // Kept under another name to copy the rows out with their paths rebuilt
+                tx.execute_batch(&format!(
+                    "DROP VIEW files; {}",
+                    FILES_VIEW_SQL.replacen("VIEW files", "VIEW interned_files", 1)
+                ))?;
+                self.create_files_table()?;
+                self.create_xattrs_table()?;
+                self.create_content_table("files")?;
+                tx.execute_batch(
+                    "INSERT INTO files (id, filename, path, size, modified_time, file_type, indexed_time,
+                                        modified_nsec, is_executable, repo_root, normalized_name, uid, gid)
+                         SELECT id, filename, path, size, modified_time, file_type, indexed_time,
+                                modified_nsec, is_executable, repo_root, normalized_name, uid, gid
+                         FROM interned_files;
+                     DROP VIEW interned_files;
+                     DROP TABLE file_rows;
+                     DELETE FROM directories;",
+                )?;
+                // After copying the rows, which are already in the name index
+                self.create_name_index(PathStorage::Full)?;
+            }
+        }
+        tx.commit()?;
+
+        self.storage = storage;
+        self.set_foreign_keys()
+    }
+
+    /// Enforce foreign keys for full paths only, whatever the SQLite build defaults to
+    ///
+    /// With full paths, deleting a file cascades into its usage statistics
+    /// and attributes. A view cannot be the parent of a foreign key, so with
+    /// interned paths they stay off and triggers on `file_rows` do that instead.
+    fn set_foreign_keys(&self) -> SqliteResult<()> {
+        self.connection.execute_batch(match self.storage {
+            PathStorage::Full => "PRAGMA foreign_keys = ON",
+            PathStorage::Interned => "PRAGMA foreign_keys = OFF",
+        })
+    }
+
+    /// Get the shortest query `query_files` runs
+    pub fn min_query_length(&self) -> usize {
+        self.min_query_length
+    }
+
+    /// Make `query_files` return nothing for queries shorter than `length` characters
+    ///
+    /// Meant for search-as-you-type callers (see `ui.min_query_length`): a
+    /// one-letter query matches most of the index and is replaced by the next
+    /// keystroke anyway. Leading and trailing whitespace does not count.
+    pub fn set_min_query_length(&mut self, length: usize) {
+        self.min_query_length = length;
+    }
+
+    /// Get the longest path written to the index, if there is a limit
+    pub fn max_path_length(&self) -> Option<usize> {
+        self.max_path_length
+    }
+
+    /// Skip entries whose path is longer than `length` bytes in batch writes
+    ///
+    /// Set from `indexing.max_path_length` under `LongPathPolicy::Skip`, so
+    /// paths the scanner would skip stay out of the index whichever way they
+    /// arrive (journal replays, imports, rescans). Adds and updates of such
+    /// paths are dropped and a move to one deletes the source entry. `None`,
+    /// the default, stores every path; SQLite keeps long keys in overflow
+    /// pages, so they cost space but do not break the unique path index.
+    pub fn set_max_path_length(&mut self, length: Option<usize>) {
+        self.max_path_length = length;
+    }
+
+    /// Check a path against `max_path_length`
+    fn within_path_limit(&self, path: &Path) -> bool {
+        self.max_path_length.is_none_or(|limit| path.as_os_str().len() <= limit)
+    }
+
+    /// Get the form names are normalized to for matching
+    pub fn name_normalization(&self) -> NameNormalization {
+        self.normalization
+    }
+
+    /// Switch the normalized names to `form`, recomputing them in one transaction
+    ///
+    /// Does nothing if the index already uses `form`.
+    pub fn set_name_normalization(&mut self, form: NameNormalization) -> SqliteResult<()> {
+        if form == self.normalization {
+            return Ok(());
+        }
+        self.normalize_names(form)?;
+        self.normalization = form;
+        Ok(())
+    }
+
+    /// Bring the index in line with the `indexing` and `ui` settings of `config`
+    ///
+    /// Converts the path storage and name normalization if they changed, which
+    /// rewrites every entry, records owners per `set_index_ownership`, and sets
+    /// the query and path length limits. Returns the number of entries whose
+    /// owner was filled in.
+    pub fn apply_config(&mut self, config: &Config) -> SqliteResult<usize> {
+        if self.storage != config.indexing.path_storage() {
+            self.set_path_storage(config.indexing.path_storage())?;
+        }
+        self.set_name_normalization(config.indexing.unicode_normalization)?;
+        let filled = self.set_index_ownership(config.indexing.index_ownership)?;
+        self.set_min_query_length(config.ui.min_query_length);
+        self.set_max_path_length(config.indexing.path_length_limit());
+        Ok(filled)
+    }
+
+    /// Record whether new entries carry their owner and group, filling them in
+    /// for the existing entries when it is turned on
+    ///
+    /// Does nothing if the index already has them. Entries indexed while it
+    /// was off are read from disk; those that cannot be read keep none.
+    /// Returns the number of entries filled in.
+    pub fn set_index_ownership(&self, enabled: bool) -> SqliteResult<usize> {
+        let recorded = self.get_metadata("index_ownership")?.as_deref() == Some("1");
+        if !enabled {
+            if recorded {
+                self.delete_metadata("index_ownership")?;
+            }
+            return Ok(0);
+        }
+        if recorded {
+            return Ok(0);
+        }
+
+        let tx = self.connection.unchecked_transaction()?;
+        let mut filled = 0;
+        {
+            let mut select = tx.prepare("SELECT id, path FROM files WHERE uid IS NULL")?;
+            let rows = select
+                .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
+                .collect::<SqliteResult<Vec<_>>>()?;
+            let mut update = tx.prepare(&format!("UPDATE {} SET uid = ?, gid = ? WHERE id = ?", self.storage.table()))?;
+            for (id, path) in rows {
+                if let Ok(metadata) = std::fs::symlink_metadata(&path) {
+                    let (uid, gid) = models::ownership(&metadata);
+                    filled += update.execute(params![uid, gid, id])?;
+                }
+            }
+        }
+        tx.execute("INSERT OR REPLACE INTO metadata (key, value) VALUES ('index_ownership', '1')", [])?;
+        tx.commit()?;
+        Ok(filled)
+    }
+
+    /// Fill in the normalized name of every row in `form` and record the form
+    fn normalize_names(&self, form: NameNormalization) -> SqliteResult<()> {
+        let table = self.storage.table();
+        let tx = self.connection.unchecked_transaction()?;
+        {
+            // Through the view, which names interned rows like `models::entry_name`
+            let mut select = tx.prepare("SELECT id, filename FROM files")?;
+            let rows = select
+                .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
+                .collect::<SqliteResult<Vec<_>>>()?;
+            let mut update = tx.prepare(&format!("UPDATE {} SET normalized_name = ? WHERE id = ?", table))?;
+            for (id, filename) in rows {
+                update.execute(params![form.normalize(&filename), id])?;
+            }
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO metadata (key, value) VALUES ('name_normalization', ?)",
+            [form.as_str()],
+        )?;
+        tx.commit()
+    }
+
+    /// Get the schema version recorded in the database
+    pub fn schema_version(&self) -> SqliteResult<i32> {
+        self.get_schema_version()
+    }
+
+    /// Get the current schema version
+    fn get_schema_version(&self) -> SqliteResult<i32> {
+        // Check if metadata table exists
+        let table_exists: bool = self.connection.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='metadata'",
+            [],
+            |row| row.get::<_, i32>(0),
+        )? > 0;
+
+        if !table_exists {
+            return Ok(0);
+        }
+
+        // Try to get schema version
+        match self.connection.query_row(
+            "SELECT value FROM metadata WHERE key = 'schema_version'",
+            [],
+            |row| row.get::<_, String>(0),
+        ) {
+            Ok(version_str) => Ok(version_str.parse().unwrap_or(0)),
+            Err(_) => Ok(0),
+        }
+    }
+
+    /// Set the schema version
+    fn set_schema_version(&self, version: i32) -> SqliteResult<()> {
+        self.connection.execute(
+            "INSERT OR REPLACE INTO metadata (key, value) VALUES ('schema_version', ?)",
+            [version.to_string()],
+        )?;
+        Ok(())
+    }
+
+    /// Migrate schema from one version to another
+    fn migrate_schema(&self, from_version: i32, to_version: i32) -> SqliteResult<()> {
+        for version in from_version..to_version {
+            match version {
+                1 => self.migrate_v1_to_v2()?,
+                2 => self.migrate_v2_to_v3()?,
+                3 => self.migrate_v3_to_v4()?,
+                4 => self.create_xattrs_table()?,
+                5 => self.migrate_v5_to_v6()?,
+                6 => self.create_size_index()?,
+                7 => self.migrate_v7_to_v8()?,
+                8 => self.create_directories_table()?,
+                // The directory index is created on the normalized names of version 12
+                9 => {}
+                10 => self.create_content_table(self.storage.table())?,
+                11 => self.migrate_v11_to_v12()?,
+                12 => self.create_name_index(self.storage)?,
+                13 => self.migrate_v13_to_v14()?,
+                14 => self.migrate_v14_to_v15()?,
+                15 => self.create_offline_mounts_table()?,
+                _ => {
+                    // Unknown migration path
+                    return Err(rusqlite::Error::InvalidQuery);
+                }
+            }
+        }
+        self.set_schema_version(to_version)?;
+        Ok(())
+    }
+
+    /// Migrate from version 1 to version 2 (add usage tracking)
+    fn migrate_v1_to_v2(&self) -> SqliteResult<()> {
+        // Create usage statistics table
+        self.connection.execute(
+            "CREATE TABLE IF NOT EXISTS usage_stats (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                file_id INTEGER NOT NULL,
+                launch_count INTEGER NOT NULL DEFAULT 0,
+                last_launched INTEGER,
+                FOREIGN KEY (file_id) REFERENCES files (id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        // Create indexes for usage stats
+        self.connection.execute(
+            "CREATE INDEX IF NOT EXISTS idx_usage_file_id ON usage_stats(file_id)",
+            [],
+        )?;
+
+        self.connection.execute(
+            "CREATE INDEX IF NOT EXISTS idx_usage_launch_count ON usage_stats(launch_count DESC)",
+            [],
+        )?;
+
+        Ok(())
+    }
+
+    /// Migrate from version 2 to version 3 (one usage row per file)
+    fn migrate_v2_to_v3(&self) -> SqliteResult<()> {
+        // Keep only the newest usage row for each file before adding the constraint
+        self.connection.execute(
+            "DELETE FROM usage_stats
+             WHERE id NOT IN (SELECT MAX(id) FROM usage_stats GROUP BY file_id)",
+            [],
+        )?;
+
+        self.connection.execute("DROP INDEX IF EXISTS idx_usage_file_id", [])?;
+
+        self.connection.execute(
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_usage_file_id ON usage_stats(file_id)",
+            [],
+        )?;
+
+        Ok(())
+    }
+
+    /// Migrate from version 3 to version 4 (sub-second modification times)
+    fn migrate_v3_to_v4(&self) -> SqliteResult<()> {
+        // Existing rows keep whole seconds until they are next re-indexed
+        if !self.column_exists("files", "modified_nsec")? {
+            self.connection.execute(
+                "ALTER TABLE files ADD COLUMN modified_nsec INTEGER NOT NULL DEFAULT 0",
+                [],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Migrate from version 5 to version 6 (executable flag)
+    fn migrate_v5_to_v6(&self) -> SqliteResult<()> {
+        // Existing rows are refreshed by the next reindex or change event
+        if !self.column_exists("files", "is_executable")? {
+            self.connection.execute(
+                "ALTER TABLE files ADD COLUMN is_executable INTEGER NOT NULL DEFAULT 0",
+                [],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Create the index used to list the largest files (version 7)
+    fn create_size_index(&self) -> SqliteResult<()> {
+        self.connection.execute(
+            "CREATE INDEX IF NOT EXISTS idx_size ON files(size)",
+            [],
+        )?;
+        Ok(())
+    }
+
+    /// Migrate from version 7 to version 8 (add the git repository of each entry)
+    fn migrate_v7_to_v8(&self) -> SqliteResult<()> {
+        // Existing rows are filled in by the next reindex or change event
+        if !self.column_exists("files", "repo_root")? {
+            self.connection.execute("ALTER TABLE files ADD COLUMN repo_root TEXT", [])?;
+        }
+        self.connection.execute(
+            "CREATE INDEX IF NOT EXISTS idx_repo_root ON files(repo_root)",
+            [],
+        )?;
+
+        Ok(())
+    }
+
+    /// Migrate from version 11 to version 12 (add names normalized for matching)
+    fn migrate_v11_to_v12(&self) -> SqliteResult<()> {
+        let table = self.storage.table();
+        if !self.column_exists(table, "normalized_name")? {
+            self.connection.execute(
+                &format!("ALTER TABLE {} ADD COLUMN normalized_name TEXT NOT NULL DEFAULT ''", table),
+                [],
+            )?;
+        }
+        // The view as of this version; later migrations recreate it with their columns
+        if self.storage == PathStorage::Interned {
+            self.connection.execute_batch(
+                "DROP VIEW files;
+                 CREATE VIEW files AS
+                    SELECT f.id, f.filename, d.path || '/' || f.filename AS path, f.size, f.modified_time,
+                           f.file_type, f.indexed_time, f.modified_nsec, f.is_executable, f.repo_root,
+                           f.normalized_name
+                    FROM file_rows f
+                    JOIN directories d ON d.id = f.dir_id;",
+            )?;
+        }
+
+        // The directory index moves to the normalized names
+        let index = match self.storage {
+            PathStorage::Full => "idx_directory_filename",
+            PathStorage::Interned => "idx_file_rows_directory_filename",
+        };
+        self.connection.execute(&format!("DROP INDEX IF EXISTS {}", index), [])?;
+        self.create_directory_index()?;
+
+        self.normalize_names(NameNormalization::default())
+    }
+
+    /// Migrate from version 13 to version 14 (add the owner and group of each entry)
+    fn migrate_v13_to_v14(&self) -> SqliteResult<()> {
+        // Existing rows are filled in by `set_index_ownership` once `indexing.index_ownership` is on
+        self.add_owner_columns()?;
+        if self.storage == PathStorage::Interned {
+            self.connection.execute_batch(
+                "DROP VIEW files;
+                 CREATE VIEW files AS
+                    SELECT f.id, f.filename, d.path || '/' || f.filename AS path, f.size, f.modified_time,
+                           f.file_type, f.indexed_time, f.modified_nsec, f.is_executable, f.repo_root,
+                           f.normalized_name, f.uid, f.gid
+                    FROM file_rows f
+                    JOIN directories d ON d.id = f.dir_id;",
+            )?;
+        }
+        self.create_owner_index(self.storage)
+    }
+
+    /// Migrate from version 14 to version 15 (key interned rows by their last path component)
+    ///
+    /// `/` and paths ending in `..` were stored under their whole path, which
+    /// the view turned into paths no lookup could find again.
+    fn migrate_v14_to_v15(&self) -> SqliteResult<()> {
+        if self.storage != PathStorage::Interned {
+            return Ok(());
+        }
+        let last_component = "substr(filename, length(rtrim(filename, replace(filename, '/', ''))) + 1)";
+        self.connection.execute_batch(&format!(
+            "UPDATE OR IGNORE file_rows SET filename = {last_component} WHERE filename LIKE '%/%';
+             DELETE FROM file_rows WHERE filename LIKE '%/%';
+             DROP VIEW files;
+             CREATE VIEW files AS
+                SELECT f.id,
+                       CASE WHEN f.filename IN ('', '..') THEN d.path || '/' || f.filename ELSE f.filename END AS filename,
+                       d.path || '/' || f.filename AS path, f.size, f.modified_time,
+                       f.file_type, f.indexed_time, f.modified_nsec, f.is_executable, f.repo_root,
+                       f.normalized_name, f.uid, f.gid
+                FROM file_rows f
+                JOIN directories d ON d.id = f.dir_id;"
+        ))
+    }
+
+    /// Add the `uid` and `gid` columns of version 14 unless they exist
+    fn add_owner_columns(&self) -> SqliteResult<()> {
+        let table = self.storage.table();
+        for column in ["uid", "gid"] {
+            if !self.column_exists(table, column)? {
+                self.connection.execute(&format!("ALTER TABLE {} ADD COLUMN {} INTEGER", table, column), [])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Create the index of owners used by `query_by_owner` (version 14)
+    ///
+    /// Partial, so it stays empty while `indexing.index_ownership` is off.
+    fn create_owner_index(&self, storage: PathStorage) -> SqliteResult<()> {
+        let (name, table) = match storage {
+            PathStorage::Full => ("idx_uid", "files"),
+            PathStorage::Interned => ("idx_file_rows_uid", "file_rows"),
+        };
+        self.connection.execute(
+            &format!("CREATE INDEX IF NOT EXISTS {} ON {}(uid) WHERE uid IS NOT NULL", name, table),
+            [],
+        )?;
+        Ok(())
+    }
+
+    /// Check whether a table has a column (keeps column-adding migrations re-runnable)
+    fn column_exists(&self, table: &str, column: &str) -> SqliteResult<bool> {
+        let count: i64 = self.connection.query_row(
+            "SELECT COUNT(*) FROM pragma_table_info(?1) WHERE name = ?2",
+            params![table, column],
+            |row| row.get(0),
+        )?;
+        Ok(count > 0)
+    }
+
+    /// Get a value from the metadata table
+    pub fn get_metadata(&self, key: &str) -> SqliteResult<Option<String>> {
+        self.connection.query_row(
+            "SELECT value FROM metadata WHERE key = ?",
+            params![key],
+            |row| row.get(0),
+        ).optional()
+    }
+
+    /// Store a value in the metadata table, replacing any previous value
+    pub fn set_metadata(&self, key: &str, value: &str) -> SqliteResult<()> {
+        self.connection.execute(
+            "INSERT OR REPLACE INTO metadata (key, value) VALUES (?, ?)",
+            params![key, value],
+        )?;
+        Ok(())
+    }
+
+    /// Remove a value from the metadata table
+    pub fn delete_metadata(&self, key: &str) -> SqliteResult<()> {
+        self.connection.execute("DELETE FROM metadata WHERE key = ?", params![key])?;
+        Ok(())
+    }
+
+    /// Get the underlying connection (for testing and operations)
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    /// Pin the current contents of the index for a series of reads
+    ///
+    /// Every query made through the returned reader (or through this
+    /// `Database` while it is alive) sees the index as it was when this was
+    /// called, even while another connection, such as the daemon's, keeps
+    /// writing. It is meant for reads only: writing through this connection
+    /// while it is open fails. The snapshot is released when the reader is dropped.
+    pub fn read_snapshot(&self) -> SqliteResult<SnapshotReader<'_>> {
+        let transaction = self.connection.unchecked_transaction()?;
+        // A deferred transaction takes its snapshot at the first read
+        transaction.query_row("SELECT COUNT(*) FROM sqlite_master", [], |_| Ok(()))?;
+        Ok(SnapshotReader { db: self, _transaction: transaction })
+    }
+
+    /// Insert a new file entry into the database
+    pub fn insert_file(&self, entry: &FileEntry) -> SqliteResult<i64> {
+        let (modified_time, modified_nsec) = system_time_to_timestamp_nanos(entry.modified_time);
+        let indexed_time = system_time_to_timestamp(entry.indexed_time);
+        
+        self.storage.intern_directories(&self.connection, [entry.path.as_path()])?;
+        self.connection.prepare_cached(&format!(
+            "INSERT INTO {} (filename, {}, size, modified_time, modified_nsec, file_type, indexed_time,
+                             is_executable, repo_root, normalized_name, uid, gid)
+             VALUES (?, {}, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            self.storage.table(),
+            self.storage.path_column(),
+            self.storage.path_placeholder(),
+        ))?.execute(params![
+            self.storage.stored_filename(&entry.filename, &entry.path),
+            self.storage.path_value(&entry.path),
+            entry.size as i64,
+            modified_time,
+            modified_nsec,
+            entry.file_type.as_str(),
+            indexed_time,
+            entry.is_executable,
+            entry.repo_root.as_ref().map(|root| root.to_string_lossy().to_string()),
+            self.normalization.normalize(&entry.filename),
+            entry.uid,
+            entry.gid,
+        ])?;
+        let file_id = self.connection.last_insert_rowid();
+        
+        if let Some(xattrs) = &entry.xattrs {
+            replace_xattrs(&self.connection, self.storage, &entry.path, xattrs)?;
+        }
+        if let Some(content) = &entry.content {
+            replace_content(&self.connection, self.storage, &entry.path, content)?;
+        }
+        
+        Ok(file_id)
+    }
+
+    /// Update an existing file entry
+    pub fn update_file(&self, entry: &FileEntry) -> SqliteResult<()> {
+        if self.within_path_limit(&entry.path) {
+            upsert_files(&self.connection, self.storage, self.normalization, &[entry])?;
+        }
+        Ok(())
+    }
+
+    /// Delete a file entry by path
+    pub fn delete_file<P: AsRef<Path>>(&self, path: P) -> SqliteResult<()> {
+        delete_path(&self.connection, self.storage, path.as_ref())?;
+        Ok(())
+    }
+
+    /// Move a file entry (update its path)
+    pub fn move_file<P: AsRef<Path>>(&self, from: P, to: P) -> SqliteResult<()> {
+        self.execute_batch(&[IndexOperation::Move {
+            from: from.as_ref().to_path_buf(),
+            to: to.as_ref().to_path_buf(),
+        }])?;
+        Ok(())
+    }
+
+    /// Query files by filename pattern with usage-based ranking
+    ///
+    /// Always matches filenames; `search` with `SearchOptions::scope` also
+    /// matches directory names in the path. Queries shorter than
+    /// `min_query_length` return nothing without touching the index.
+    ///
+    /// Words starting with `-` exclude names containing the rest of the word
+    /// (`report -draft`); `\-` keeps a leading dash literal. See
+    /// `split_exclusions` for how the remaining words are matched.
+    pub fn query_files(&self, query: &str, limit: usize) -> SqliteResult<Vec<FileEntry>> {
+        let (sql, values) = match self.query_files_sql(query, limit) {
+            Some(statement) => statement,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut stmt = self.connection.prepare_cached(&sql)?;
+        let entries = stmt.query_map(params_from_iter(values), row_to_file_entry)?;
+
+        entries.collect()
+    }
+
+    /// Build `QUERY_FILES_SQL` with the exclusions of `query` and its parameters
+    ///
+    /// Returns `None` for queries that match nothing: those shorter than
+    /// `min_query_length` and those made only of exclusions.
+    fn query_files_sql(&self, query: &str, limit: usize) -> Option<(String, Vec<Value>)> {
+        let query = query.trim();
+        if query.is_empty() || query.chars().count() < self.min_query_length {
+            return None;
+        }
+
+        let query = self.normalization.normalize(query);
+        let (query, exclusions) = split_exclusions(&query);
+        if query.is_empty() {
+            return None;
+        }
+
+        let pattern = escape_like(&query);
+        let condition = "\n       AND f.normalized_name NOT LIKE '%' || ? || '%' ESCAPE '\\'";
+        let sql = QUERY_FILES_SQL.replacen(
+            QUERY_FILES_MATCH,
+            &format!("{}{}", QUERY_FILES_MATCH, condition.repeat(exclusions.len())),
+            1,
+        );
+        let mut values = vec![Value::Text(pattern.clone())];
+        values.extend(exclusions.iter().map(|exclusion| Value::Text(escape_like(exclusion))));
+        values.extend([Value::Text(query), Value::Text(pattern), Value::Integer(limit as i64)]);
+
+        Some((sql, values))
+    }
+
+    /// Query files by the words of their names through the full-text index
+    ///
+    /// Unlike `query_files`, which finds the query anywhere in a name and has
+    /// to read every row for it, each word of the query matches the start of a
+    /// word in the name (`rep` finds `my_report.pdf` but `port` does not), and
+    /// the index is used. Results are ranked like `query_files`. Queries
+    /// without any letters or digits, and databases without the index, go
+    /// through `query_files` instead.
+    pub fn query_files_fts(&self, query: &str, limit: usize) -> SqliteResult<Vec<FileEntry>> {
+        let query = query.trim();
+        if query.is_empty() || query.chars().count() < self.min_query_length {
+            return Ok(Vec::new());
+        }
+
+        let query = self.normalization.normalize(query);
+        let expression = match fts_name_expression(&query) {
+            Some(expression) if self.has_name_index()? => expression,
+            _ => return self.query_files(&query, limit),
+        };
+
+        let mut stmt = self.connection.prepare_cached(QUERY_FILES_FTS_SQL)?;
+        let entries = stmt.query_map(
+            params![expression, query, escape_like(&query), limit as i64],
+            row_to_file_entry,
+        )?;
+
+        entries.collect()
+    }
+
+    /// Check whether the `files_fts` name index exists (it needs SQLite's FTS5)
+    fn has_name_index(&self) -> SqliteResult<bool> {
+        let count: i32 = self.connection.prepare_cached(
+            "SELECT COUNT(*) FROM sqlite_master WHERE name = 'files_fts'"
+        )?.query_row([], |row| row.get(0))?;
+        Ok(count > 0)
+    }
+
+    /// Count the files `query_files` would return for `query` without a limit
+    pub fn count_matches(&self, query: &str) -> SqliteResult<usize> {
+        let query = query.trim();
+        if query.is_empty() || query.chars().count() < self.min_query_length {
+            return Ok(0);
+        }
+
+        let query = self.normalization.normalize(query);
+        let (query, exclusions) = split_exclusions(&query);
+        if query.is_empty() {
+            return Ok(0);
+        }
+
+        let condition = "\n   AND f.normalized_name NOT LIKE '%' || ? || '%' ESCAPE '\\'";
+        let sql = format!(
+            "SELECT COUNT(*) FROM files f WHERE f.normalized_name LIKE '%' || ? || '%' ESCAPE '\\'{}\n   AND {}",
+            condition.repeat(exclusions.len()),
+            ONLINE_CONDITION
+        );
+        let mut values = vec![Value::Text(escape_like(&query))];
+        values.extend(exclusions.iter().map(|exclusion| Value::Text(escape_like(exclusion))));
+
+        let count: i64 = self.connection.prepare_cached(&sql)?.query_row(params_from_iter(values), |row| row.get(0))?;
+
+        Ok(count as usize)
+    }
+
+    /// Query files like `query_files`, also returning each file's usage statistics
+    pub fn query_with_stats(&self, query: &str, limit: usize) -> SqliteResult<Vec<FileEntryWithStats>> {
+        let (sql, values) = match self.query_files_sql(query, limit) {
+            Some(statement) => statement,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut stmt = self.connection.prepare_cached(&sql)?;
+        let entries = stmt.query_map(
+            params_from_iter(values),
+            |row| {
+                let last_launched: i64 = row.get(10)?;
+                Ok(FileEntryWithStats {
+                    entry: row_to_file_entry(row)?,
+                    launch_count: row.get(9)?,
+                    last_launched: (last_launched > 0).then(|| timestamp_to_system_time(last_launched)),
+                })
+            },
+        )?;
+
+        entries.collect()
+    }
+
+    /// Query directories by name for jumping to a folder
+    ///
+    /// Most-opened directories come first, then the same match ranking as
+    /// `query_files`. Unlike `query_files_types` with `FileType::Directory`,
+    /// this reads only the partial directory index, which matters on indexes
+    /// where directories are a small share of the rows.
+    pub fn query_directories(&self, query: &str, limit: usize) -> SqliteResult<Vec<FileEntry>> {
+        let query = query.trim();
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let query = self.normalization.normalize(query);
+        let mut stmt = self.connection.prepare_cached(QUERY_DIRECTORIES_SQL)?;
+        let pattern = escape_like(&query);
+
+        let entries = stmt.query_map(
+            params![pattern, query, pattern, limit as i64],
+            row_to_file_entry,
+        )?;
+
+        entries.collect()
+    }
+
+    /// Query files by filename pattern, restricted to the given file types
+    ///
+    /// An empty `types` slice matches all file types.
+    pub fn query_files_types(&self, query: &str, types: &[FileType], limit: usize) -> SqliteResult<Vec<FileEntry>> {
+        if types.is_empty() {
+            return self.query_files(query, limit);
+        }
+
+        self.search(query, &SearchOptions {
+            limit,
+            file_types: types.to_vec(),
+            ..SearchOptions::default()
+        })
+    }
+
+    /// Query files by filename with additional filters from `options`
+    ///
+    /// Results are ranked the same way as `query_files`. The query is trimmed;
+    /// when nothing is left and no type or extension filter is set, the result
+    /// follows `options.empty_query` (the frequent feed ignores the other options
+    /// apart from the limit).
+    pub fn search(&self, query: &str, options: &SearchOptions) -> SqliteResult<Vec<FileEntry>> {
+        let query = query.trim();
+        if is_unfiltered_empty_query(query, options) {
+            return match options.empty_query {
+                EmptyQuery::Nothing => Ok(Vec::new()),
+                EmptyQuery::Frequent => self.get_most_used_files(options.limit),
+            };
+        }
+
+        let (sql, values) = build_search_sql(query, options, self.normalization);
+
+        let mut stmt = self.connection.prepare_cached(&sql)?;
+        let entries = stmt.query_map(params_from_iter(values), row_to_file_entry)?;
+
+        entries.collect()
+    }
+
+    /// Like `search`, but drop results rejected by any of `filters`
+    ///
+    /// Extra candidates are fetched as needed so that up to `options.limit`
+    /// results are still returned when some are filtered out.
+    pub fn search_filtered(
+        &self,
+        query: &str,
+        options: &SearchOptions,
+        filters: &[ResultFilter],
+    ) -> SqliteResult<Vec<FileEntry>> {
+        if filters.is_empty() {
+            return self.search(query, options);
+        }
+
+        let mut fetch_limit = options.limit.saturating_mul(2).max(1);
+        loop {
+            let candidates = self.search(query, &SearchOptions {
+                limit: fetch_limit,
+                ..options.clone()
+            })?;
+            let exhausted = candidates.len() < fetch_limit;
+
+            let results: Vec<FileEntry> = candidates
+                .into_iter()
+                .filter(|entry| filters.iter().all(|filter| filter(entry)))
+                .take(options.limit)
+                .collect();
+            if results.len() == options.limit || exhausted {
+                return Ok(results);
+            }
+            fetch_limit = fetch_limit.saturating_mul(2);
+        }
+    }
+
+    /// Like `search`, but report why each result matched and its relevance score
+    ///
+    /// An empty query without type or extension filters returns nothing, since
+    /// the frequent feed has no match kind to report.
+    pub fn search_ranked(&self, query: &str, options: &SearchOptions) -> SqliteResult<Vec<SearchResult>> {
+        let query = query.trim();
+        if is_unfiltered_empty_query(query, options) {
+            return Ok(Vec::new());
+        }
+
+        let (sql, values) = build_search_sql(query, options, self.normalization);
+
+        let mut stmt = self.connection.prepare_cached(&sql)?;
+        let results = stmt.query_map(params_from_iter(values), |row| {
+            let match_kind = MatchKind::from_tier(row.get(9)?);
+            let launch_count: i64 = row.get(10)?;
+            Ok(SearchResult {
+                entry: row_to_file_entry(row)?,
+                score: match_kind.score(launch_count),
+                match_kind,
+            })
+        })?;
+
+        results.collect()
+    }
+
+    /// Execute a batch of operations with retry logic
+    ///
+    /// Returns how many rows each kind of change touched.
+    pub fn execute_batch(&self, operations: &[IndexOperation]) -> SqliteResult<BatchResult> {
+        self.execute_with_retry(|| self.try_execute_batch(operations))
+    }
+
+    /// Try to execute a batch of operations (helper for retry logic)
+    fn try_execute_batch(&self, operations: &[IndexOperation]) -> SqliteResult<BatchResult> {
+        // Use unchecked_transaction to work with immutable self
+        let tx = self.connection.unchecked_transaction()?;
+        let mut result = BatchResult::default();
+            
+            // Consecutive adds and updates are written with multi-row inserts
+            let mut upserts: Vec<&FileEntry> = Vec::new();
+            for operation in operations {
+                if !matches!(operation, IndexOperation::Add(_) | IndexOperation::Update(_)) {
+                    let (added, updated) = upsert_files(&tx, self.storage, self.normalization, &upserts)?;
+                    result.added += added;
+                    result.updated += updated;
+                    upserts.clear();
+                }
+                
+                match operation {
+                    IndexOperation::Add(entry) | IndexOperation::Update(entry) => {
+                        if self.within_path_limit(&entry.path) {
+                            upserts.push(entry);
+                        }
+                    }
+                    IndexOperation::Delete(path) => {
+                        result.deleted += delete_path(&tx, self.storage, path)?;
+                    }
+                    IndexOperation::UpdatePermissions { path, is_executable } => {
+                        let mut values = vec![Value::Integer(*is_executable as i64)];
+                        values.extend(self.storage.path_params(path));
+                        result.updated += tx.prepare_cached(&format!(
+                            "UPDATE {} SET is_executable = ? WHERE {}",
+                            self.storage.table(),
+                            self.storage.path_condition(),
+                        ))?.execute(params_from_iter(values))?;
+                    }
+                    IndexOperation::Move { from, to } if !self.within_path_limit(to) => {
+                        result.deleted += delete_path(&tx, self.storage, from)?;
+                        result.deleted += delete_descendants(&tx, self.storage, from)?;
+                    }
+                    IndexOperation::Move { from, to } => {
+                        let filename = models::entry_name(to);
+                        
+                        // A row already indexed under the new path (e.g. a case-only
+                        // rename seen as a create first) would violate the unique path
+                        if from != to {
+                            delete_path(&tx, self.storage, to)?;
+                        }
+                        self.storage.intern_directories(&tx, [to.as_path()])?;
+                        let mut values = vec![
+                            self.storage.path_value(to),
+                            Value::Text(self.normalization.normalize(&filename)),
+                            Value::Text(self.storage.stored_filename(&filename, to)),
+                        ];
+                        values.extend(self.storage.path_params(from));
+                        result.moved += tx.prepare_cached(&format!(
+                            "UPDATE {} SET {} = {}, normalized_name = ?, filename = ? WHERE {}",
+                            self.storage.table(),
+                            self.storage.path_column(),
+                            self.storage.path_placeholder(),
+                            self.storage.path_condition(),
+                        ))?.execute(params_from_iter(values))?;
+                        // A renamed directory takes its contents along
+                        result.moved += move_descendants(&tx, self.storage, from, to)?;
+                    }
+                }
+            }
+            let (added, updated) = upsert_files(&tx, self.storage, self.normalization, &upserts)?;
+            result.added += added;
+            result.updated += updated;
+            
+            tx.commit()?;
+            Ok(result)
+    }
+
+    /// Execute an operation with exponential backoff retry logic
+    fn execute_with_retry<F, T>(&self, mut operation: F) -> SqliteResult<T>
+    where
+        F: FnMut() -> SqliteResult<T>,
+    {
+        let max_retries = 5;
+        let mut delay_ms = 100;
+        
+        for attempt in 0..max_retries {
+            match operation() {
+                Ok(result) => return Ok(result),
+                Err(rusqlite::Error::SqliteFailure(err, _)) 
+                    if err.code == rusqlite::ErrorCode::DatabaseBusy 
+                    || err.code == rusqlite::ErrorCode::DatabaseLocked => 
+                {
+                    if attempt < max_retries - 1 {
+                        std::thread::sleep(Duration::from_millis(delay_ms));
+                        delay_ms = (delay_ms * 2).min(1600); // Cap at 1600ms
+                    } else {
+                        return Err(rusqlite::Error::SqliteFailure(err, None));
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        
+        unreachable!()
+    }
+
+    /// Reconcile the index with a fresh scan of `roots`
+    ///
+    /// New or changed entries (by size or modification time) are upserted, and
+    /// indexed paths under one of `roots` that the scan no longer found are
+    /// deleted. Entries outside the scanned roots are left untouched.
+    pub fn reconcile(&self, entries: &[FileEntry], roots: &[PathBuf]) -> SqliteResult<ReconcileStats> {
+        self.reconcile_since(entries, roots, None)
+    }
+
+    /// Reconcile the index with a scan that skipped files modified before `cutoff`
+    ///
+    /// Like `reconcile`, except that indexed files last modified before
+    /// `cutoff` are kept even though the scan did not report them (see
+    /// `indexing.max_age_days`). With no cutoff this is `reconcile`.
+    pub fn reconcile_since(
+        &self,
+        entries: &[FileEntry],
+        roots: &[PathBuf],
+        cutoff: Option<SystemTime>,
+    ) -> SqliteResult<ReconcileStats> {
+        let mut indexed: HashMap<String, (i64, (i64, i64))> = HashMap::new();
+        {
+            let mut stmt = self.connection.prepare_cached("SELECT path, size, modified_time, modified_nsec FROM files")?;
+            let rows = stmt.query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    (row.get::<_, i64>(1)?, (row.get::<_, i64>(2)?, row.get::<_, i64>(3)?)),
+                ))
+            })?;
+            for row in rows {
+                let (path, state) = row?;
+                indexed.insert(path, state);
+            }
+        }
+
+        let mut operations = Vec::new();
+        for entry in entries {
+            let path = entry.path.to_string_lossy().to_string();
+            let state = (entry.size as i64, system_time_to_timestamp_nanos(entry.modified_time));
+            if indexed.remove(&path) != Some(state) {
+                operations.push(IndexOperation::Update(entry.clone()));
+            }
+        }
+        let updated = operations.len();
+
+        // Whatever is left was not seen by the scan, or is on an unmounted filesystem
+        let cutoff = cutoff.map(system_time_to_timestamp_nanos);
+        let offline = self.offline_mounts()?;
+        for (path, (_, modified)) in indexed {
+            if cutoff.is_some_and(|cutoff| modified < cutoff) {
+                continue;
+            }
+            let path = PathBuf::from(path);
+            if roots.iter().any(|root| path.starts_with(root)) && !offline.iter().any(|mount| path.starts_with(mount)) {
+                operations.push(IndexOperation::Delete(path));
+            }
+        }
+        let deleted = operations.len() - updated;
+
+        self.execute_batch(&operations)?;
+
+        Ok(ReconcileStats { updated, deleted })
+    }
+
+    /// Replace every indexed entry with `entries` in a single transaction
+    ///
+    /// A scan that comes back with fewer than `min_fraction` of the currently
+    /// indexed entries (or with none at all) usually means the include paths
+    /// were unavailable rather than emptied, so the replacement is refused and
+    /// the existing index is left as it was. A `min_fraction` of 0 disables the check.
+    pub fn replace_all(&self, entries: &[FileEntry], min_fraction: f64) -> SqliteResult<ReplaceOutcome> {
+        let previous = self.count_files()? as usize;
+        let scanned = entries.len();
+
+        if min_fraction > 0.0
+            && previous > 0
+            && (scanned == 0 || (scanned as f64) < previous as f64 * min_fraction)
+        {
+            return Ok(ReplaceOutcome::Refused { previous, scanned });
+        }
+
+        self.execute_with_retry(|| {
+            let tx = self.connection.unchecked_transaction()?;
+            tx.execute(&format!("DELETE FROM {}", self.storage.table()), [])?;
+            if self.storage == PathStorage::Interned {
+                tx.execute("DELETE FROM directories", [])?;
+            }
+            let entries: Vec<&FileEntry> =
+                entries.iter().filter(|entry| self.within_path_limit(&entry.path)).collect();
+            upsert_files(&tx, self.storage, self.normalization, &entries)?;
+            tx.commit()
+        })?;
+
+        Ok(ReplaceOutcome::Replaced { previous, indexed: self.count_files()? as usize })
+    }
+
+    /// Get the indexed files last modified before `cutoff` that `scanned` does not include
+    ///
+    /// A scan limited by `indexing.max_age_days` skips these, so a full rebuild
+    /// adds them back to keep them indexed, as `reconcile_since` does. Entries
+    /// come with their stored extended attributes, content, repository and owner.
+    pub fn aged_out_entries(&self, cutoff: SystemTime, scanned: &[FileEntry]) -> SqliteResult<Vec<FileEntry>> {
+        let scanned: HashSet<&Path> = scanned.iter().map(|entry| entry.path.as_path()).collect();
+        let (cutoff_secs, cutoff_nsec) = system_time_to_timestamp_nanos(cutoff);
+        let mut stmt = self.connection.prepare_cached(
+            "SELECT f.id, f.filename, f.path, f.size, f.modified_time, f.file_type, f.indexed_time,
+                    f.modified_nsec, f.is_executable, f.repo_root, f.uid, f.gid, c.content
+             FROM files f
+             LEFT JOIN file_content c ON c.rowid = f.id
+             WHERE f.modified_time < ? OR (f.modified_time = ? AND f.modified_nsec < ?)",
+        )?;
+        let rows = stmt.query_map(params![cutoff_secs, cutoff_secs, cutoff_nsec], |row| {
+            let mut entry = row_to_file_entry(row)?;
+            entry.repo_root = row.get::<_, Option<String>>(9)?.map(PathBuf::from);
+            entry.uid = row.get(10)?;
+            entry.gid = row.get(11)?;
+            entry.content = row.get(12)?;
+            Ok(entry)
+        })?;
+
+        let mut entries = Vec::new();
+        for row in rows {
+            let mut entry = row?;
+            if scanned.contains(entry.path.as_path()) {
+                continue;
+            }
+            let xattrs = self.get_xattrs(&entry.path)?;
+            entry.xattrs = (!xattrs.is_empty()).then_some(xattrs);
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+
+    /// Get the count of indexed files
+    pub fn count_files(&self) -> SqliteResult<i64> {
+        self.connection.query_row(
+            "SELECT COUNT(*) FROM files",
+            [],
+            |row| row.get(0),
+        )
+    }
+
+    /// Record that a file was launched/opened
+    pub fn record_file_launch<P: AsRef<Path>>(&self, path: P) -> SqliteResult<()> {
+        self.record_launch_at(path.as_ref(), current_timestamp())?;
+        Ok(())
+    }
+
+    /// Record launches buffered by the daemon in one transaction
+    ///
+    /// Each launch is a path and the Unix time it was launched at. Paths that
+    /// are not indexed are skipped. Returns the number of launches recorded.
+    pub fn record_file_launches(&self, launches: &[(PathBuf, i64)]) -> SqliteResult<usize> {
+        if launches.is_empty() {
+            return Ok(0);
+        }
+
+        self.execute_with_retry(|| {
+            let tx = self.connection.unchecked_transaction()?;
+            let mut recorded = 0;
+            for (path, launched_at) in launches {
+                if self.record_launch_at(path, *launched_at)? {
+                    recorded += 1;
+                }
+            }
+            tx.commit()?;
+            Ok(recorded)
+        })
+    }
+
+    /// Count one launch of `path` at `launched_at`; false if the path is not indexed
+    fn record_launch_at(&self, path: &Path, launched_at: i64) -> SqliteResult<bool> {
+        let file_id = match self.file_id(path)? {
+            Some(file_id) => file_id,
+            None => return Ok(false),
+        };
+
+        // A buffered launch may be older than one already recorded
+        self.connection.prepare_cached(
+            "INSERT INTO usage_stats (file_id, launch_count, last_launched)
+             VALUES (?1, 1, ?2)
+             ON CONFLICT(file_id) DO UPDATE SET
+                launch_count = launch_count + 1,
+                last_launched = MAX(COALESCE(last_launched, 0), excluded.last_launched)",
+        )?.execute(params![file_id, launched_at])?;
+        Ok(true)
+    }
+
+    /// Merge imported usage statistics into an indexed file
+    ///
+    /// Keeps the larger launch count and the later launch time, so importing the
+    /// same statistics twice is harmless. Returns false if the path is not indexed.
+    pub fn merge_file_usage<P: AsRef<Path>>(
+        &self,
+        path: P,
+        launch_count: i32,
+        last_launched: Option<i64>,
+    ) -> SqliteResult<bool> {
+        let file_id = match self.file_id(path.as_ref())? {
+            Some(file_id) => file_id,
+            None => return Ok(false),
+        };
+        
+        self.connection.execute(
+            "INSERT INTO usage_stats (file_id, launch_count, last_launched)
+             VALUES (?1, ?2, ?3)
+             ON CONFLICT(file_id) DO UPDATE SET
+                launch_count = MAX(launch_count, excluded.launch_count),
+                last_launched = MAX(COALESCE(last_launched, 0), COALESCE(excluded.last_launched, 0))",
+            params![file_id, launch_count, last_launched],
+        )?;
+        
+        Ok(true)
+    }
+
+    /// Get usage statistics for a file
+    pub fn get_file_usage<P: AsRef<Path>>(&self, path: P) -> SqliteResult<Option<(i32, i64)>> {
+        let file_id = match self.file_id(path.as_ref())? {
+            Some(file_id) => file_id,
+            None => return Ok(None),
+        };
+        
+        let result = self.connection.prepare_cached(
+            "SELECT launch_count, last_launched FROM usage_stats WHERE file_id = ?",
+        )?
+        .query_row(params![file_id], |row| Ok((row.get::<_, i32>(0)?, row.get::<_, i64>(1)?)))
+        .optional()?;
+        
+        Ok(result)
+    }
+
+    /// Get the row id of the entry indexed at `path`
+    fn file_id(&self, path: &Path) -> SqliteResult<Option<i64>> {
+        self.connection
+            .prepare_cached(&format!(
+                "SELECT id FROM {} WHERE {}",
+                self.storage.table(),
+                self.storage.path_condition(),
+            ))?
+            .query_row(params_from_iter(self.storage.path_params(path)), |row| row.get(0))
+            .optional()
+    }
+
+    /// Get most frequently used files
+    pub fn get_most_used_files(&self, limit: usize) -> SqliteResult<Vec<FileEntry>> {
+        let mut stmt = self.connection.prepare_cached(&format!(
+            "SELECT f.id, f.filename, f.path, f.size, f.modified_time, f.file_type, f.indexed_time,
+                    f.modified_nsec, f.is_executable
+             FROM files f
+             JOIN usage_stats u ON f.id = u.file_id
+             WHERE {}
+             ORDER BY u.launch_count DESC, u.last_launched DESC
+             LIMIT ?",
+            ONLINE_CONDITION
+        ))?;
+
+        let entries = stmt.query_map(
+            params![limit as i64],
+            row_to_file_entry,
+        )?;
+
+        entries.collect()
+    }
+
+    /// Get recently launched and recently modified files as one feed, newest activity first
+    ///
+    /// Roughly `launch_weight` (0 to 1) of the `limit` slots go to recently
+    /// launched files and the rest to recently modified regular files, whether
+    /// or not those were ever launched. Slots one side cannot fill go to the
+    /// other, and a file found by both appears once. Files dated further in
+    /// the future than `models::MAX_CLOCK_SKEW` are left out of the modified
+    /// side, so a wrong clock cannot pin them to the top.
+    pub fn recent_activity(&self, limit: usize, launch_weight: f64) -> SqliteResult<Vec<FileEntry>> {
+        let read_row = |row: &rusqlite::Row| Ok((row_to_file_entry(row)?, row.get::<_, i64>(9)?));
+
+        let launched: Vec<(FileEntry, i64)> = self.connection.prepare_cached(&format!(
+            "SELECT f.id, f.filename, f.path, f.size, f.modified_time, f.file_type, f.indexed_time,
+                    f.modified_nsec, f.is_executable, u.last_launched
+             FROM files f
+             JOIN usage_stats u ON f.id = u.file_id
+             WHERE u.last_launched IS NOT NULL AND {}
+             ORDER BY u.last_launched DESC
+             LIMIT ?",
+            ONLINE_CONDITION
+        ))?.query_map(params![limit as i64], read_row)?.collect::<SqliteResult<_>>()?;
+
+        // Launched files may also be among the newest modified ones, so fetch enough to skip them
+        let newest_plausible = system_time_to_timestamp(SystemTime::now() + models::MAX_CLOCK_SKEW);
+        let modified: Vec<(FileEntry, i64)> = self.connection.prepare_cached(&format!(
+            "SELECT id, filename, path, size, modified_time, file_type, indexed_time, modified_nsec,
+                    is_executable, modified_time
+             FROM files f
+             WHERE file_type = 'regular' AND modified_time <= ? AND {}
+             ORDER BY modified_time DESC, modified_nsec DESC
+             LIMIT ?",
+            ONLINE_CONDITION
+        ))?.query_map(params![newest_plausible, (limit + launched.len()) as i64], read_row)?.collect::<SqliteResult<_>>()?;
+
+        let launch_slots = (limit as f64 * launch_weight.clamp(0.0, 1.0)).round() as usize;
+        let mut feed: Vec<(FileEntry, i64)> = Vec::with_capacity(limit);
+        let mut seen: HashSet<PathBuf> = HashSet::new();
+        let mut take = |source: &[(FileEntry, i64)], count: usize, feed: &mut Vec<(FileEntry, i64)>| {
+            let target = feed.len() + count;
+            for (entry, activity) in source {
+                if feed.len() >= target {
+                    break;
+                }
+                if seen.insert(entry.path.clone()) {
+                    feed.push((entry.clone(), *activity));
+                }
+            }
+        };
+        take(&launched, launch_slots, &mut feed);
+        take(&modified, limit.saturating_sub(feed.len()), &mut feed);
+        take(&launched, limit.saturating_sub(feed.len()), &mut feed);
+
+        feed.sort_by_key(|(_, activity)| std::cmp::Reverse(*activity));
+        Ok(feed.into_iter().map(|(entry, _)| entry).collect())
+    }
+
+    /// Get the largest regular files, biggest first
+    pub fn largest_files(&self, limit: usize) -> SqliteResult<Vec<FileEntry>> {
+        let mut stmt = self.connection.prepare_cached(&format!(
+            "SELECT id, filename, path, size, modified_time, file_type, indexed_time, modified_nsec,
+                    is_executable
+             FROM files f
+             WHERE file_type = 'regular' AND {}
+             ORDER BY size DESC
+             LIMIT ?",
+            ONLINE_CONDITION
+        ))?;
+
+        let entries = stmt.query_map(
+            params![limit as i64],
+            row_to_file_entry,
+        )?;
+
+        entries.collect()
+    }
+
+    /// Find entries whose filename satisfies `predicate`, in filename order
+    ///
+    /// A fallback for matches SQL cannot express, such as regular expressions:
+    /// rows are read one by one in the order of the filename index and handed
+    /// to `predicate` until `limit` entries matched. Much slower than the
+    /// indexed queries when few names match, since that reads the whole index.
+    pub fn scan_matching<F: Fn(&str) -> bool>(&self, predicate: F, limit: usize) -> SqliteResult<Vec<FileEntry>> {
+        let mut matches = Vec::new();
+        if limit == 0 {
+            return Ok(matches);
+        }
+
+        let mut stmt = self.connection.prepare_cached(&format!(
+            "SELECT id, filename, path, size, modified_time, file_type, indexed_time, modified_nsec,
+                    is_executable
+             FROM files f
+             WHERE {}
+             ORDER BY filename COLLATE NOCASE, path",
+            ONLINE_CONDITION
+        ))?;
+
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            if predicate(row.get_ref(1)?.as_str()?) {
+                matches.push(row_to_file_entry(row)?);
+                if matches.len() == limit {
+                    break;
+                }
+            }
+        }
+        Ok(matches)
+    }
+
+    /// Get the entries most recently added to the index, newest first
+    ///
+    /// Ordered by row id rather than `indexed_time`, which updates and
+    /// verification passes also refresh: a row keeps its id until the entry is
+    /// deleted, so only paths new to the index come first. After a full
+    /// reindex every row is new and the order follows the scan.
+    pub fn recently_indexed(&self, limit: usize) -> SqliteResult<Vec<FileEntry>> {
+        let mut stmt = self.connection.prepare_cached(&format!(
+            "SELECT id, filename, path, size, modified_time, file_type, indexed_time, modified_nsec,
+                    is_executable
+             FROM files f
+             WHERE {}
+             ORDER BY id DESC
+             LIMIT ?",
+            ONLINE_CONDITION
+        ))?;
+
+        let entries = stmt.query_map(
+            params![limit as i64],
+            row_to_file_entry,
+        )?;
+
+        entries.collect()
+    }
+
+    /// Search the stored text of content-indexed files, best matches first
+    ///
+    /// Every word of `query` has to appear in a file for it to match; words
+    /// are compared as whole tokens, ignoring case.
+    pub fn search_content(&self, query: &str, limit: usize) -> SqliteResult<Vec<FileEntry>> {
+        // Quoted so FTS5 operators and punctuation in the query are taken literally
+        let terms: Vec<String> = query
+            .split_whitespace()
+            .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+            .collect();
+        if terms.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut stmt = self.connection.prepare_cached(&format!(
+            "SELECT f.id, f.filename, f.path, f.size, f.modified_time, f.file_type, f.indexed_time,
+                    f.modified_nsec, f.is_executable
+             FROM file_content c
+             JOIN files f ON f.id = c.rowid
+             WHERE file_content MATCH ? AND {}
+             ORDER BY c.rank
+             LIMIT ?",
+            ONLINE_CONDITION
+        ))?;
+
+        let entries = stmt.query_map(params![terms.join(" "), limit as i64], row_to_file_entry)?;
+
+        entries.collect()
+    }
+
+    /// Get the entry indexed at exactly `path`
+    pub fn get_entry_by_path<P: AsRef<Path>>(&self, path: P) -> SqliteResult<Option<FileEntry>> {
+        self.connection
+            .prepare_cached(
+                "SELECT id, filename, path, size, modified_time, file_type, indexed_time, modified_nsec,
+                        is_executable
+                 FROM files
+                 WHERE path = ?",
+            )?
+            .query_row(params![path.as_ref().to_string_lossy()], row_to_file_entry)
+            .optional()
+    }
+
+    /// Get every entry indexed below the directory `prefix`, sorted by path
+    ///
+    /// The directory's own entry is not included; see `get_entry_by_path`.
+    pub fn entries_under_prefix<P: AsRef<Path>>(&self, prefix: P) -> SqliteResult<Vec<FileEntry>> {
+        // Paths under a prefix sort between "prefix/" and "prefix0" ('0' follows '/')
+        let prefix = prefix.as_ref().to_string_lossy();
+        let prefix = prefix.trim_end_matches('/');
+        let mut stmt = self.connection.prepare_cached(
+            "SELECT id, filename, path, size, modified_time, file_type, indexed_time, modified_nsec,
+                    is_executable
+             FROM files
+             WHERE path >= ? AND path < ?
+             ORDER BY path",
+        )?;
+
+        let entries = stmt.query_map(
+            params![format!("{}/", prefix), format!("{}0", prefix)],
+            row_to_file_entry,
+        )?;
+
+        entries.collect()
+    }
+
+    /// Remove the entries of paths that no longer exist on disk
+    ///
+    /// Catches deletions the watcher never saw, such as those made while the
+    /// daemon was stopped. Returns the number of entries removed.
+    pub fn prune_missing(&self, roots: &[PathBuf]) -> SqliteResult<usize> {
+        self.prune_missing_with_progress(roots, |_, _| {})
+    }
+
+    /// Like `prune_missing`, calling `progress` with the number of entries
+    /// checked and the total every `PRUNE_PROGRESS_INTERVAL` entries
+    ///
+    /// Missing paths are deleted through `execute_batch`, `PRUNE_BATCH_SIZE`
+    /// at a time. Dangling symlinks count as present, as they do for
+    /// `exists_on_disk`, and so do paths that cannot be checked for any
+    /// other reason. Entries under one of the include `roots` that is missing
+    /// or not mounted, or under an offline mount, are kept until it is back.
+    pub fn prune_missing_with_progress<F>(&self, roots: &[PathBuf], mut progress: F) -> SqliteResult<usize>
+    where
+        F: FnMut(usize, usize),
+    {
+        let paths: Vec<PathBuf> = {
+            let mut stmt = self.connection.prepare("SELECT path FROM files ORDER BY path")?;
+            let paths = stmt.query_map([], |row| row.get::<_, String>(0))?;
+            paths.map(|path| path.map(PathBuf::from)).collect::<SqliteResult<_>>()?
+        };
+        let absent_roots: Vec<&Path> = roots
+            .iter()
+            .map(PathBuf::as_path)
+            .filter(|root| !models::root_present(root))
+            .collect();
+        let offline = self.offline_mounts()?;
+
+        let mut removed = 0;
+        let mut batch = Vec::new();
+        for (checked, path) in paths.iter().enumerate() {
+            if checked > 0 && checked % PRUNE_PROGRESS_INTERVAL == 0 {
+                progress(checked, paths.len());
+            }
+            let missing = path
+                .symlink_metadata()
+                .is_err_and(|e| e.kind() == std::io::ErrorKind::NotFound);
+            let absent = models::containing_root(path, roots).is_some_and(|root| absent_roots.contains(&root))
+                || offline.iter().any(|mount| path.starts_with(mount));
+            if missing && !absent {
+                batch.push(IndexOperation::Delete(path.clone()));
+            }
+            if batch.len() >= PRUNE_BATCH_SIZE {
+                removed += self.execute_batch(&batch)?.deleted;
+                batch.clear();
+            }
+        }
+        removed += self.execute_batch(&batch)?.deleted;
+        progress(paths.len(), paths.len());
+
+        Ok(removed)
+    }
+
+    /// Hide the entries under `mount` from searches until `set_mount_online`
+    ///
+    /// For a filesystem that was unmounted: its entries are kept, so they come
+    /// back with their usage statistics once it is mounted again, and
+    /// `reconcile` leaves them alone in the meantime.
+    pub fn set_mount_offline(&self, mount: &Path) -> SqliteResult<()> {
+        self.connection.execute(
+            "INSERT OR IGNORE INTO offline_mounts (path) VALUES (?)",
+            [mount.to_string_lossy()],
+        )?;
+        Ok(())
+    }
+
+    /// Show the entries under `mount` again, returning whether it was offline
+    pub fn set_mount_online(&self, mount: &Path) -> SqliteResult<bool> {
+        let removed = self.connection.execute("DELETE FROM offline_mounts WHERE path = ?", [mount.to_string_lossy()])?;
+        Ok(removed > 0)
+    }
+
+    /// Get the mounts whose entries are hidden, sorted
+    pub fn offline_mounts(&self) -> SqliteResult<Vec<PathBuf>> {
+        let mut stmt = self.connection.prepare_cached("SELECT path FROM offline_mounts ORDER BY path")?;
+        let paths = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        paths.map(|path| path.map(PathBuf::from)).collect()
+    }
+
+    /// Get entries that have not been re-verified for longer than `older_than`
+    ///
+    /// Results are ordered oldest-verified first so a background task can
+    /// re-stat and refresh them incrementally. Entries on offline mounts are
+    /// left out, since they cannot be checked until the mount is back.
+    pub fn stale_entries(&self, older_than: Duration, limit: usize) -> SqliteResult<Vec<FileEntry>> {
+        self.stale_entries_outside(older_than, &[], limit)
+    }
+
+    /// Get stale entries like `stale_entries`, leaving out those under `skip`
+    ///
+    /// Skipped entries do not take up any of the `limit`, so a pass over
+    /// entries that cannot be checked yet still reaches the ones after them.
+    pub fn stale_entries_outside(
+        &self,
+        older_than: Duration,
+        skip: &[&Path],
+        limit: usize,
+    ) -> SqliteResult<Vec<FileEntry>> {
+        let cutoff = current_timestamp() - older_than.as_secs() as i64;
+        let mut conditions = vec!["f.indexed_time < ?1".to_string(), ONLINE_CONDITION.to_string()];
+        let mut values = vec![Value::Integer(cutoff), Value::Integer(limit as i64)];
+        for prefix in skip {
+            let prefix = prefix.to_string_lossy();
+            let prefix = prefix.trim_end_matches('/');
+            values.push(Value::Text(format!("{}/", prefix)));
+            values.push(Value::Text(format!("{}0", prefix)));
+            conditions.push(format!("NOT (f.path >= ?{} AND f.path < ?{})", values.len() - 1, values.len()));
+        }
+
+        let mut stmt = self.connection.prepare_cached(&format!(
+            "SELECT f.id, f.filename, f.path, f.size, f.modified_time, f.file_type, f.indexed_time,
+                    f.modified_nsec, f.is_executable
+             FROM files f
+             WHERE {}
+             ORDER BY f.indexed_time ASC, f.id ASC
+             LIMIT ?2",
+            conditions.join(" AND ")
+        ))?;
+
+        let entries = stmt.query_map(params_from_iter(values), row_to_file_entry)?;
+
+        entries.collect()
+    }
+
+    /// Reset the verification age of `paths` after confirming they are unchanged
+    pub fn mark_verified(&self, paths: &[PathBuf]) -> SqliteResult<()> {
+        let tx = self.connection.unchecked_transaction()?;
+        {
+            let mut stmt = tx.prepare_cached(&format!(
+                "UPDATE {} SET indexed_time = ? WHERE {}",
+                self.storage.table(),
+                self.storage.path_condition(),
+            ))?;
+            let now = current_timestamp();
+            for path in paths {
+                let mut values = vec![Value::Integer(now)];
+                values.extend(self.storage.path_params(path));
+                stmt.execute(params_from_iter(values))?;
+            }
+        }
+        tx.commit()
+    }
+
+    /// Find files carrying the extended attribute `name` with exactly `value`
+    pub fn query_by_xattr(&self, name: &str, value: &str) -> SqliteResult<Vec<FileEntry>> {
+        let mut stmt = self.connection.prepare_cached(&format!(
+            "SELECT f.id, f.filename, f.path, f.size, f.modified_time, f.file_type, f.indexed_time,
+                    f.modified_nsec, f.is_executable
+             FROM xattrs x
+             JOIN files f ON f.id = x.file_id
+             WHERE x.name = ? AND x.value = ? AND {}
+             ORDER BY f.path",
+            ONLINE_CONDITION
+        ))?;
+
+        let entries = stmt.query_map(params![name, value], row_to_file_entry)?;
+
+        entries.collect()
+    }
+
+    /// Find entries of the git repository rooted at `repo_root` whose filename contains `query`
+    ///
+    /// Results are ordered by filename (case-insensitive), then path. Entries
+    /// only have a repository when `indexing.detect_git_repos` is enabled.
+    pub fn query_in_repo<P: AsRef<Path>>(
+        &self,
+        repo_root: P,
+        query: &str,
+        limit: usize,
+    ) -> SqliteResult<Vec<FileEntry>> {
+        let repo_root = repo_root.as_ref().to_string_lossy().to_string();
+        let mut stmt = self.connection.prepare_cached(&format!(
+            "SELECT id, filename, path, size, modified_time, file_type, indexed_time,
+                    modified_nsec, is_executable
+             FROM files f
+             WHERE repo_root = ? AND normalized_name LIKE '%' || ? || '%' ESCAPE '\\' AND {}
+             ORDER BY filename COLLATE NOCASE, path
+             LIMIT ?",
+            ONLINE_CONDITION
+        ))?;
+
+        let entries = stmt.query_map(
+            params![repo_root, escape_like(&self.normalization.normalize(query)), limit as i64],
+            |row| {
+                let mut entry = row_to_file_entry(row)?;
+                entry.repo_root = Some(PathBuf::from(&repo_root));
+                Ok(entry)
+            },
+        )?;
+
+        entries.collect()
+    }
+
+    /// Find entries owned by the user `uid` whose filename contains `query`
+    ///
+    /// An empty query lists everything the user owns. Results are ordered by
+    /// filename (case-insensitive), then path, and carry their owner and group.
+    /// Ownership is only stored when `indexing.index_ownership` is enabled.
+    pub fn query_by_owner(&self, uid: u32, query: &str, limit: usize) -> SqliteResult<Vec<FileEntry>> {
+        let mut stmt = self.connection.prepare_cached(&format!(
+            "SELECT id, filename, path, size, modified_time, file_type, indexed_time,
+                    modified_nsec, is_executable, gid
+             FROM files f
+             WHERE uid = ? AND normalized_name LIKE '%' || ? || '%' ESCAPE '\\' AND {}
+             ORDER BY filename COLLATE NOCASE, path
+             LIMIT ?",
+            ONLINE_CONDITION
+        ))?;
+
+        let query = self.normalization.normalize(query.trim());
+        let entries = stmt.query_map(
+            params![uid, escape_like(&query), limit as i64],
+            |row| {
+                let mut entry = row_to_file_entry(row)?;
+                entry.uid = Some(uid);
+                entry.gid = row.get(9)?;
+                Ok(entry)
+            },
+        )?;
+
+        entries.collect()
+    }
+
+    /// Get the stored extended attributes of an indexed file, sorted by name
+    pub fn get_xattrs<P: AsRef<Path>>(&self, path: P) -> SqliteResult<Vec<(String, String)>> {
+        let file_id = match self.file_id(path.as_ref())? {
+            Some(file_id) => file_id,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut stmt = self.connection.prepare_cached(
+            "SELECT name, value FROM xattrs WHERE file_id = ? ORDER BY name"
+        )?;
+
+        let xattrs = stmt.query_map(params![file_id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+        xattrs.collect()
+    }
+
+    /// Remove usage statistics for rarely launched files not opened recently
+    ///
+    /// Deletes rows with fewer than `min_count` launches whose last launch is
+    /// older than `older_than`, returning the number of rows removed.
+    pub fn compact_usage(&self, min_count: i32, older_than: Duration) -> SqliteResult<usize> {
+        let cutoff = current_timestamp() - older_than.as_secs() as i64;
+        
+        self.connection.execute(
+            "DELETE FROM usage_stats
+             WHERE launch_count < ? AND COALESCE(last_launched, 0) < ?",
+            params![min_count, cutoff],
+        )
+    }
+
+    /// Remove all usage statistics, leaving the indexed files untouched
+    pub fn clear_usage_stats(&self) -> SqliteResult<()> {
+        self.connection.execute("DELETE FROM usage_stats", [])?;
+        Ok(())
+    }
+}
+
+/// Move the database built at `replacement` over the one at `path`
+///
+/// The replacement's write-ahead log is folded into it first, and leftover
+/// log files of the database being replaced are removed, so the rename swaps
+/// one complete index for another. Nothing may have either database open.
+pub fn install_database_file(replacement: &Path, path: &Path) -> Result<(), DatabaseError> {
+    let sqlite_error = |e: rusqlite::Error| DatabaseError::SqliteError(e.to_string());
+    let io_error = |e: std::io::Error| DatabaseError::SqliteError(format!("{}: {}", path.display(), e));
+
+    let connection = Connection::open(replacement).map_err(sqlite_error)?;
+    connection.query_row("PRAGMA journal_mode = DELETE", [], |_| Ok(())).map_err(sqlite_error)?;
+    drop(connection);
+
+    for suffix in ["-wal", "-shm"] {
+        let mut log_path = path.as_os_str().to_owned();
+        log_path.push(suffix);
+        match std::fs::remove_file(&log_path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(io_error(e)),
+            _ => {}
+        }
+    }
+    std::fs::rename(replacement, path).map_err(io_error)
+}
+
+/// Errors opening the index database
+#[derive(Debug)]
+pub enum DatabaseError {
+    /// The database directory cannot be created or written to
+    NotWritable(String),
+    SqliteError(String),
+}
+
+impl std::fmt::Display for DatabaseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DatabaseError::NotWritable(msg) => write!(f, "Database directory is not writable: {}", msg),
+            DatabaseError::SqliteError(msg) => write!(f, "Database error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for DatabaseError {}
+
+/// Check whether a trimmed query would match every entry
+fn is_unfiltered_empty_query(query: &str, options: &SearchOptions) -> bool {
+    query.is_empty() && options.file_types.is_empty() && options.extensions.is_empty()
+}
+
+/// Build the filename search used by `search` and `search_ranked`
+///
+/// Selects the nine `row_to_file_entry` columns followed by the match tier
+/// (0 exact, 1 prefix, 2 substring), the launch count and the launch count
+/// weighted by `options.directory_weight`, ordered by `options.order_by`. Names are matched in `normalization`.
+fn build_search_sql(query: &str, options: &SearchOptions, normalization: NameNormalization) -> (String, Vec<Value>) {
+    let query = normalization.normalize(query);
+    let query = query.as_str();
+    // A filename match is also a path match, so the wider scopes only need the path test
+    let matched_column = match options.scope {
+        SearchScope::Filename => "f.normalized_name".to_string(),
+        SearchScope::Path | SearchScope::Both => format!("normalize_text(f.path, '{}')", normalization.as_str()),
+    };
+    let filename_tiers = "WHEN f.normalized_name = ?1 THEN 0
+                    WHEN f.normalized_name LIKE ?3 || '%' ESCAPE '\\' THEN 1";
+    let match_tier = match options.scope {
+        SearchScope::Filename => format!("CASE {} ELSE 2 END", filename_tiers),
+        SearchScope::Path => "0".to_string(),
+        SearchScope::Both => format!(
+            "CASE {}
+                    WHEN f.normalized_name LIKE '%' || ?3 || '%' ESCAPE '\\' THEN 2
+                    ELSE 3 END",
+            filename_tiers
+        ),
+    };
+    let mut conditions = vec![format!("{} LIKE '%' || ?3 || '%' ESCAPE '\\'", matched_column)];
+    let mut values: Vec<Value> = vec![
+        Value::Text(query.to_string()),
+        Value::Integer(options.limit as i64),
+        Value::Text(escape_like(query)),
+        Value::Real(options.directory_weight),
+    ];
+
+    if !options.file_types.is_empty() {
+        let placeholders: Vec<String> = options.file_types
+            .iter()
+            .map(|file_type| {
+                values.push(Value::Text(file_type.as_str().to_string()));
+                format!("?{}", values.len())
+            })
+            .collect();
+        conditions.push(format!("f.file_type IN ({})", placeholders.join(", ")));
+    }
+
+    // Case-insensitive like the name match; a leading dot is accepted and ignored
+    if !options.extensions.is_empty() {
+        let alternatives: Vec<String> = options.extensions
+            .iter()
+            .map(|extension| {
+                let extension = normalization.normalize(extension.trim_start_matches('.'));
+                values.push(Value::Text(escape_like(&extension)));
+                format!("f.normalized_name LIKE '%.' || ?{} ESCAPE '\\'", values.len())
+            })
+            .collect();
+        conditions.push(format!("({})", alternatives.join(" OR ")));
+    }
+
+    // Paths under a prefix sort between "prefix/" and "prefix0" ('0' follows '/'),
+    // which is an exact, case-sensitive test that needs no LIKE escaping
+    for prefix in &options.exclude_prefixes {
+        let prefix = prefix.to_string_lossy();
+        let prefix = prefix.trim_end_matches('/');
+        values.push(Value::Text(format!("{}/", prefix)));
+        values.push(Value::Text(format!("{}0", prefix)));
+        conditions.push(format!(
+            "NOT (f.path >= ?{} AND f.path < ?{})",
+            values.len() - 1,
+            values.len()
+        ));
+    }
+
+    // Same range test for the filesystems that are unmounted for now
+    conditions.push(ONLINE_CONDITION.to_string());
+
+    let sql = format!(
+        "SELECT f.id, f.filename, f.path, f.size, f.modified_time, f.file_type, f.indexed_time,
+                f.modified_nsec, f.is_executable,
+                {} AS match_tier,
+                COALESCE(u.launch_count, 0) AS launch_count,
+                COALESCE(u.launch_count, 0) * CASE WHEN f.file_type = 'directory' THEN ?4 ELSE 1 END
+                    AS weighted_launches
+         FROM files f
+         LEFT JOIN usage_stats u ON f.id = u.file_id
+         WHERE {}
+         ORDER BY {}
+         LIMIT ?2",
+        match_tier,
+        conditions.join("\n           AND "),
+        options.order_by.order_by_sql()
+    );
+
+    (sql, values)
+}
+
+/// Split the `-word` exclusions off a filename query
+///
+/// Returns the text to match and the excluded words. A lone `-` is matched
+/// literally, as is a word written `\-word`. A query without exclusions or
+/// escapes is returned as it is; otherwise the remaining words are rejoined
+/// with single spaces and still matched together as one piece of text.
+pub(crate) fn split_exclusions(query: &str) -> (String, Vec<String>) {
+    let is_special = |word: &str| (word.starts_with('-') && word.len() > 1) || word.starts_with("\\-");
+    if !query.split_whitespace().any(is_special) {
+        return (query.to_string(), Vec::new());
+    }
+
+    let mut included = Vec::new();
+    let mut excluded = Vec::new();
+    for word in query.split_whitespace() {
+        if let Some(literal) = word.strip_prefix('\\').filter(|rest| rest.starts_with('-')) {
+            included.push(literal);
+        } else if let Some(exclusion) = word.strip_prefix('-').filter(|rest| !rest.is_empty()) {
+            excluded.push(exclusion.to_string());
+        } else {
+            included.push(word);
+        }
+    }
+    (included.join(" "), excluded)
+}
+
+/// Escape LIKE wildcards (and the escape character itself) so `query` matches literally
+///
+/// The result must be used with `ESCAPE '\'`.
+pub(crate) fn escape_like(query: &str) -> String {
+    let mut escaped = String::with_capacity(query.len());
+    for c in query.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Delete the entry indexed at `path`, if any
+fn delete_path(connection: &Connection, storage: PathStorage, path: &Path) -> SqliteResult<usize> {
+    connection.prepare_cached(&format!(
+        "DELETE FROM {} WHERE {}",
+        storage.table(),
+        storage.path_condition(),
+    ))?.execute(params_from_iter(storage.path_params(path)))
+}
+
+/// Delete the rows below the directory `path`, returning how many were deleted
+fn delete_descendants(connection: &Connection, storage: PathStorage, path: &Path) -> SqliteResult<usize> {
+    connection.prepare_cached(&format!(
+        "DELETE FROM {} WHERE {}",
+        storage.table(),
+        storage.descendant_condition(),
+    ))?.execute(params_from_iter(storage.descendant_params(path)))
+}
+
+/// Rewrite the paths of the rows below the directory `from` to be below `to`
+///
+/// Rows keep their ids, and with them their launch statistics. Rows already
+/// indexed below `to` are replaced. Returns how many rows were moved.
+fn move_descendants(connection: &Connection, storage: PathStorage, from: &Path, to: &Path) -> SqliteResult<usize> {
+    if from != to {
+        delete_descendants(connection, storage, to)?;
+    }
+    // substr() and length() both count characters, so the prefix is cut cleanly
+    let to = Value::Text(to.to_string_lossy().trim_end_matches('/').to_string());
+    let from_text = Value::Text(from.to_string_lossy().trim_end_matches('/').to_string());
+    match storage {
+        PathStorage::Full => {
+            let mut values = vec![to, from_text];
+            values.extend(storage.descendant_params(from));
+            connection.prepare_cached(&format!(
+                "UPDATE files SET path = ? || substr(path, length(?) + 1) WHERE {}",
+                storage.descendant_condition(),
+            ))?.execute(params_from_iter(values))
+        }
+        PathStorage::Interned => {
+            let mut values = vec![to.clone(), from_text.clone()];
+            values.extend(storage.descendant_params(from));
+            connection.prepare_cached(
+                "INSERT OR IGNORE INTO directories (path)
+                 SELECT ? || substr(path, length(?) + 1) FROM directories
+                 WHERE path = ? OR (path >= ? AND path < ?)",
+            )?.execute(params_from_iter(values.iter()))?;
+            connection.prepare_cached(&format!(
+                "UPDATE file_rows SET dir_id = (
+                    SELECT n.id FROM directories o
+                    JOIN directories n ON n.path = ? || substr(o.path, length(?) + 1)
+                    WHERE o.id = file_rows.dir_id
+                 )
+                 WHERE {}",
+                storage.descendant_condition(),
+            ))?.execute(params_from_iter(values))
+        }
+    }
+}
+
+/// Insert or update many file entries with multi-row statements
+///
+/// Rows are chunked so no statement binds more than `MAX_BOUND_PARAMETERS`.
+/// Later entries for the same path win, as with repeated single-row upserts.
+/// Returns the number of rows inserted and the number updated.
+fn upsert_files(
+    connection: &Connection,
+    storage: PathStorage,
+    normalization: NameNormalization,
+    entries: &[&FileEntry],
+) -> SqliteResult<(usize, usize)> {
+    if entries.is_empty() {
+        return Ok((0, 0));
+    }
+    storage.intern_directories(connection, entries.iter().map(|entry| entry.path.as_path()))?;
+
+    // Ids are AUTOINCREMENT, so rows inserted below get ids above every existing one
+    let max_id: i64 = connection.query_row(
+        &format!("SELECT COALESCE(MAX(id), 0) FROM {}", storage.table()),
+        [],
+        |row| row.get(0),
+    )?;
+    let mut inserted: HashSet<i64> = HashSet::new();
+    let mut updated = 0;
+    
+    for chunk in entries.chunks(MAX_BOUND_PARAMETERS / UPSERT_COLUMNS) {
+        let row = format!("(?, {}, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)", storage.path_placeholder());
+        let rows = vec![row.as_str(); chunk.len()].join(", ");
+        let sql = format!(
+            "INSERT INTO {} (filename, {}, size, modified_time, modified_nsec, file_type, indexed_time,
+                             is_executable, repo_root, normalized_name, uid, gid)
+             VALUES {}
+             ON CONFLICT({}) DO UPDATE SET
+                filename = excluded.filename,
+                size = excluded.size,
+                modified_time = excluded.modified_time,
+                modified_nsec = excluded.modified_nsec,
+                file_type = excluded.file_type,
+                indexed_time = excluded.indexed_time,
+                is_executable = excluded.is_executable,
+                repo_root = excluded.repo_root,
+                normalized_name = excluded.normalized_name,
+                uid = excluded.uid,
+                gid = excluded.gid
+             RETURNING id",
+            storage.table(),
+            storage.path_column(),
+            rows,
+            storage.conflict_target(),
+        );
+        
+        let mut values: Vec<Value> = Vec::with_capacity(chunk.len() * UPSERT_COLUMNS);
+        for entry in chunk {
+            let (modified_time, modified_nsec) = system_time_to_timestamp_nanos(entry.modified_time);
+            values.extend([
+                Value::Text(storage.stored_filename(&entry.filename, &entry.path)),
+                storage.path_value(&entry.path),
+                Value::Integer(entry.size as i64),
+                Value::Integer(modified_time),
+                Value::Integer(modified_nsec),
+                Value::Text(entry.file_type.as_str().to_string()),
+                Value::Integer(system_time_to_timestamp(entry.indexed_time)),
+                Value::Integer(entry.is_executable as i64),
+                entry.repo_root.as_ref().map_or(Value::Null, |root| Value::Text(root.to_string_lossy().to_string())),
+                Value::Text(normalization.normalize(&entry.filename)),
+                entry.uid.map_or(Value::Null, |uid| Value::Integer(uid.into())),
+                entry.gid.map_or(Value::Null, |gid| Value::Integer(gid.into())),
+            ]);
+        }
+        let mut stmt = connection.prepare_cached(&sql)?;
+        let mut rows = stmt.query(params_from_iter(values))?;
+        while let Some(row) = rows.next()? {
+            let id: i64 = row.get(0)?;
+            // A path repeated in the batch is inserted once and then updated
+            if id <= max_id || !inserted.insert(id) {
+                updated += 1;
+            }
+        }
+        
+        for entry in chunk {
+            if let Some(xattrs) = &entry.xattrs {
+                replace_xattrs(connection, storage, &entry.path, xattrs)?;
+            }
+            if let Some(content) = &entry.content {
+                replace_content(connection, storage, &entry.path, content)?;
+            }
+        }
+    }
+    
+    Ok((inserted.len(), updated))
+}
+
+/// Replace the stored extended attributes of the indexed file at `path`
+fn replace_xattrs(
+    connection: &Connection,
+    storage: PathStorage,
+    path: &Path,
+    xattrs: &[(String, String)],
+) -> SqliteResult<()> {
+    connection.prepare_cached(&format!(
+        "DELETE FROM xattrs WHERE file_id = (SELECT id FROM {} WHERE {})",
+        storage.table(),
+        storage.path_condition(),
+    ))?.execute(params_from_iter(storage.path_params(path)))?;
+    
+    let mut stmt = connection.prepare_cached(&format!(
+        "INSERT INTO xattrs (file_id, name, value) SELECT id, ?, ? FROM {} WHERE {}",
+        storage.table(),
+        storage.path_condition(),
+    ))?;
+    for (name, value) in xattrs {
+        let mut values = vec![Value::Text(name.clone()), Value::Text(value.clone())];
+        values.extend(storage.path_params(path));
+        stmt.execute(params_from_iter(values))?;
+    }
+    
+    Ok(())
+}
+
+/// Replace the stored text of the indexed file at `path`
+fn replace_content(connection: &Connection, storage: PathStorage, path: &Path, content: &str) -> SqliteResult<()> {
+    connection.prepare_cached(&format!(
+        "DELETE FROM file_content WHERE rowid = (SELECT id FROM {} WHERE {})",
+        storage.table(),
+        storage.path_condition(),
+    ))?.execute(params_from_iter(storage.path_params(path)))?;
+    
+    let mut values = vec![Value::Text(content.to_string())];
+    values.extend(storage.path_params(path));
+    connection.prepare_cached(&format!(
+        "INSERT INTO file_content (rowid, content) SELECT id, ? FROM {} WHERE {}",
+        storage.table(),
+        storage.path_condition(),
+    ))?.execute(params_from_iter(values))?;
+    
+    Ok(())
+}
+
+/// Build a FileEntry from a row whose first nine columns are id, filename, path,
+/// size, modified_time, file_type, indexed_time, modified_nsec, is_executable
+pub(crate) fn row_to_file_entry(row: &rusqlite::Row) -> SqliteResult<FileEntry> {
+    Ok(FileEntry {
+        id: Some(row.get(0)?),
+        filename: row.get(1)?,
+        path: PathBuf::from(row.get::<_, String>(2)?),
+        size: row.get::<_, i64>(3)? as u64,
+        modified_time: timestamp_nanos_to_system_time(row.get(4)?, row.get(7)?),
+        file_type: FileType::from_str(&row.get::<_, String>(5)?),
+        indexed_time: timestamp_to_system_time(row.get(6)?),
+        is_executable: row.get(8)?,
+        xattrs: None,
+        repo_root: None,
+        content: None,
+        uid: None,
+        gid: None,
+    })
+}
+
+/// Get current Unix timestamp
+pub fn current_timestamp() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
+/// Convert SystemTime to Unix timestamp
+fn system_time_to_timestamp(time: SystemTime) -> i64 {
+    system_time_to_timestamp_nanos(time).0
+}
+
+/// Convert Unix timestamp to SystemTime
+///
+/// Negative timestamps are before the epoch. The few SystemTime cannot
+/// represent read as the epoch rather than overflowing.
+fn timestamp_to_system_time(timestamp: i64) -> SystemTime {
+    let offset = Duration::from_secs(timestamp.unsigned_abs());
+    let time = if timestamp >= 0 {
+        UNIX_EPOCH.checked_add(offset)
+    } else {
+        UNIX_EPOCH.checked_sub(offset)
+    };
+    time.unwrap_or(UNIX_EPOCH)
+}
+
+/// Split SystemTime into whole Unix seconds and the sub-second nanoseconds
+///
+/// The seconds keep the `modified_time` column compatible with readers that
+/// only know about whole seconds; the nanoseconds go to `modified_nsec`.
+/// Times before the epoch get negative seconds, rounded down so the
+/// nanoseconds stay positive and (seconds, nanoseconds) pairs still sort in
+/// time order.
+pub(crate) fn system_time_to_timestamp_nanos(time: SystemTime) -> (i64, i64) {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(since_epoch) => (
+            i64::try_from(since_epoch.as_secs()).unwrap_or(i64::MAX),
+            since_epoch.subsec_nanos() as i64,
+        ),
+        Err(err) => {
+            let before_epoch = err.duration();
+            let secs = i64::try_from(before_epoch.as_secs()).unwrap_or(i64::MAX);
+            match before_epoch.subsec_nanos() {
+                0 => (-secs, 0),
+                nanos => (-secs - 1, 1_000_000_000 - nanos as i64),
+            }
+        }
+    }
+}
+
+/// Convert Unix seconds plus sub-second nanoseconds to SystemTime
+fn timestamp_nanos_to_system_time(timestamp: i64, nanos: i64) -> SystemTime {
+    let time = timestamp_to_system_time(timestamp);
+    time.checked_add(Duration::from_nanos(nanos.max(0) as u64)).unwrap_or(time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_nfd_name_on_disk_matches_nfc_query() {
+        let dir = tempfile::tempdir().unwrap();
+        // "Café.txt" spelled with a combining acute accent, as macOS writes it
+        let nfd_name = "Cafe\u{301}.txt";
+        let path = dir.path().join(nfd_name);
+        std::fs::write(&path, "menu").unwrap();
+        let nfc_query = "caf\u{e9}";
+
+        let mut db = Database::open_in_memory().unwrap();
+        db.insert_file(&FileEntry::new(
+            models::entry_name(&path),
+            path.clone(),
+            4,
+            SystemTime::now(),
+            FileType::Regular,
+        )).unwrap();
+
+        let results = db.query_files(nfc_query, 10).unwrap();
+        assert_eq!(results.len(), 1);
+        // The name is still reported as it is on disk
+        assert_eq!(results[0].filename, nfd_name);
+        assert_eq!(results[0].path, path);
+        assert_eq!(db.search(nfc_query, &SearchOptions::default()).unwrap().len(), 1);
+        assert_eq!(db.count_matches(nfc_query).unwrap(), 1);
+
+        // Either form works, also after switching forms and path storage
+        db.set_name_normalization(NameNormalization::Nfd).unwrap();
+        db.set_path_storage(PathStorage::Interned).unwrap();
+        assert_eq!(db.query_files(nfc_query, 10).unwrap().len(), 1);
+        assert_eq!(db.query_files("cafe\u{301}", 10).unwrap().len(), 1);
+        assert_eq!(db.query_directories(nfc_query, 10).unwrap().len(), 0);
+        assert_eq!(db.get_metadata("name_normalization").unwrap().as_deref(), Some("nfd"));
+    }
+
+    #[test]
+    fn test_directory_weight_ranks_files_above_equally_used_directories() {
+        let db = Database::open_in_memory().unwrap();
+        for (name, file_type) in [("report-archive", FileType::Directory), ("report.pdf", FileType::Regular)] {
+            let path = PathBuf::from("/home/user").join(name);
+            db.insert_file(&FileEntry::new(name.to_string(), path.clone(), 1, SystemTime::now(), file_type)).unwrap();
+            for _ in 0..3 {
+                db.record_file_launch(&path).unwrap();
+            }
+        }
+
+        let names = |directory_weight: f64| -> Vec<String> {
+            let options = SearchOptions { directory_weight, ..SearchOptions::default() };
+            db.search("report", &options).unwrap().into_iter().map(|e| e.filename).collect()
+        };
+        // Equal usage falls back to the name
+        assert_eq!(names(1.0), vec!["report-archive", "report.pdf"]);
+        assert_eq!(names(0.5), vec!["report.pdf", "report-archive"]);
+    }
+
+    #[test]
+    fn test_scan_matching_regex() {
+        let db = Database::open_in_memory().unwrap();
+        for name in ["IMG_0001.jpg", "IMG_0002.JPG", "img-final.jpg", "IMG_0003.png", "notes.txt"] {
+            db.insert_file(&FileEntry::new(
+                name.to_string(),
+                PathBuf::from("/home/user/photos").join(name),
+                1,
+                SystemTime::now(),
+                FileType::Regular,
+            )).unwrap();
+        }
+
+        let camera = regex::Regex::new(r"(?i)^IMG_\d{4}\.jpe?g$").unwrap();
+        let names = |entries: Vec<FileEntry>| -> Vec<String> { entries.into_iter().map(|e| e.filename).collect() };
+        assert_eq!(
+            names(db.scan_matching(|name| camera.is_match(name), 10).unwrap()),
+            vec!["IMG_0001.jpg", "IMG_0002.JPG"]
+        );
+        assert_eq!(names(db.scan_matching(|name| camera.is_match(name), 1).unwrap()), vec!["IMG_0001.jpg"]);
+        assert!(db.scan_matching(|_| true, 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_recently_indexed_newest_first() {
+        let db = Database::open_in_memory().unwrap();
+        let entry = |name: &str| FileEntry::new(
+            name.to_string(),
+            PathBuf::from("/home/user").join(name),
+            1,
+            SystemTime::now(),
+            FileType::Regular,
+        );
+
+        db.insert_file(&entry("old.txt")).unwrap();
+        db.insert_file(&entry("older.txt")).unwrap();
+        db.execute_batch(&[IndexOperation::Add(entry("new.txt"))]).unwrap();
+        // Re-indexing an existing entry does not make it new
+        db.update_file(&entry("old.txt")).unwrap();
+
+        let names: Vec<String> = db.recently_indexed(2).unwrap().into_iter().map(|e| e.filename).collect();
+        assert_eq!(names, vec!["new.txt", "older.txt"]);
+    }
+
+    #[test]
+    fn test_query_files_fts_prefix_words_and_special_characters() {
+        let db = Database::open_in_memory().unwrap();
+        for name in ["my_report.pdf", "Report 2023.txt", "support.txt", "say \"hi\".txt", "a*b.txt"] {
+            db.insert_file(&FileEntry::new(
+                name.to_string(),
+                PathBuf::from("/home/user").join(name),
+                1,
+                SystemTime::now(),
+                FileType::Regular,
+            )).unwrap();
+        }
+        let names = |query: &str| -> Vec<String> {
+            let mut names: Vec<String> = db.query_files_fts(query, 10).unwrap().into_iter().map(|e| e.filename).collect();
+            names.sort();
+            names
+        };
+
+        assert_eq!(names("rep"), vec!["Report 2023.txt", "my_report.pdf"]);
+        assert_eq!(names("report 20"), vec!["Report 2023.txt"]);
+        assert!(names("port").is_empty());
+
+        // FTS5 syntax in the query is matched as text rather than failing
+        assert_eq!(names("\"hi\""), vec!["say \"hi\".txt"]);
+        assert_eq!(names("hi\" OR \"report"), Vec::<String>::new());
+        assert!(names("NEAR(a b) report:*").is_empty());
+        // Queries with nothing to tokenize use substring matching
+        assert_eq!(names("*"), vec!["a*b.txt"]);
+        assert_eq!(names("\""), vec!["say \"hi\".txt"]);
+    }
+
+    #[test]
+    fn test_name_index_follows_moves_and_deletes() {
+        let entry = |name: &str| FileEntry::new(
+            name.to_string(),
+            PathBuf::from("/home/user").join(name),
+            1,
+            SystemTime::now(),
+            FileType::Regular,
+        );
+
+        for storage in [PathStorage::Full, PathStorage::Interned] {
+            let mut db = Database::open_in_memory().unwrap();
+            db.insert_file(&entry("draft.txt")).unwrap();
+            db.insert_file(&entry("notes.txt")).unwrap();
+            // Converting keeps the index and moves its triggers to the new table
+            db.set_path_storage(storage).unwrap();
+
+            db.execute_batch(&[
+                IndexOperation::Move { from: PathBuf::from("/home/user/draft.txt"), to: PathBuf::from("/home/user/final.txt") },
+                IndexOperation::Delete(PathBuf::from("/home/user/notes.txt")),
+                IndexOperation::Add(entry("agenda.txt")),
+                IndexOperation::Update(entry("agenda.txt")),
+            ]).unwrap();
+
+            assert!(db.query_files_fts("draft", 10).unwrap().is_empty(), "{:?}", storage);
+            assert!(db.query_files_fts("notes", 10).unwrap().is_empty(), "{:?}", storage);
+            let moved = db.query_files_fts("final", 10).unwrap();
+            assert_eq!(moved[0].path, PathBuf::from("/home/user/final.txt"), "{:?}", storage);
+            assert_eq!(db.query_files_fts("agenda", 10).unwrap().len(), 1, "{:?}", storage);
+
+            let indexed: i64 = db.connection.query_row("SELECT COUNT(*) FROM files_fts", [], |row| row.get(0)).unwrap();
+            assert_eq!(indexed, 2, "{:?}", storage);
+            let path: String = db.connection.query_row(
+                "SELECT path FROM files_fts WHERE files_fts MATCH 'filename : final'",
+                [],
+                |row| row.get(0),
+            ).unwrap();
+            assert_eq!(path, "/home/user/final.txt", "{:?}", storage);
+        }
+    }
+
+    #[test]
+    fn test_short_query_skips_index() {
+        let mut db = Database::open_in_memory().unwrap();
+        db.insert_file(&FileEntry::new(
+            "abc.txt".to_string(),
+            PathBuf::from("/home/user/abc.txt"),
+            1,
+            SystemTime::now(),
+            FileType::Regular,
+        )).unwrap();
+        assert_eq!(db.query_files("a", 10).unwrap().len(), 1);
+
+        db.set_min_query_length(3);
+        assert!(db.query_files(" ab ", 10).unwrap().is_empty());
+        assert_eq!(db.query_files("abc", 10).unwrap().len(), 1);
+
+        // With the table gone only a query that never reaches it can succeed
+        db.connection.execute_batch("DROP TABLE files").unwrap();
+        assert!(db.query_files("ab", 10).unwrap().is_empty());
+        assert!(db.query_files("abc", 10).is_err());
+    }
+
+    #[test]
+    fn test_pre_epoch_mtime_round_trips() {
+        let db = Database::open_in_memory().unwrap();
+        let modified = UNIX_EPOCH - Duration::from_millis(86_400_500);
+        let path = PathBuf::from("/home/user/scan.tif");
+        db.insert_file(&FileEntry::new("scan.tif".to_string(), path.clone(), 1, modified, FileType::Regular)).unwrap();
+
+        assert_eq!(system_time_to_timestamp_nanos(modified), (-86_401, 500_000_000));
+        let stored = db.get_entry_by_path(&path).unwrap().unwrap();
+        assert_eq!(stored.modified_time, modified);
+    }
+
+    #[test]
+    fn test_far_future_mtime_kept_but_not_recent() {
+        let db = Database::open_in_memory().unwrap();
+        let now = SystemTime::now();
+        let future = now + Duration::from_secs(100 * 365 * 24 * 60 * 60);
+        for (name, modified) in [("skewed.txt", future), ("edited.txt", now)] {
+            db.insert_file(&FileEntry::new(
+                name.to_string(),
+                PathBuf::from("/home/user").join(name),
+                1,
+                modified,
+                FileType::Regular,
+            )).unwrap();
+        }
+
+        let stored = db.get_entry_by_path(Path::new("/home/user/skewed.txt")).unwrap().unwrap();
+        assert_eq!(stored.modified_time, future);
+        assert!(models::is_future_dated(stored.modified_time));
+
+        let feed: Vec<String> = db.recent_activity(5, 0.0).unwrap().into_iter().map(|e| e.filename).collect();
+        assert_eq!(feed, vec!["edited.txt"]);
+    }
+
+    #[test]
+    fn test_swap_in_replaces_whole_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index.db");
+        let replacement = dir.path().join("index.db.new");
+        let entry = |name: &str| FileEntry::new(
+            name.to_string(),
+            PathBuf::from("/home/user").join(name),
+            1,
+            SystemTime::now(),
+            FileType::Regular,
+        );
+
+        let mut db = Database::open(&path).unwrap();
+        db.insert_file(&entry("old.txt")).unwrap();
+        db.insert_file(&entry("kept.txt")).unwrap();
+
+        let new_db = Database::open(&replacement).unwrap();
+        new_db.replace_all(&[entry("kept.txt"), entry("new.txt"), entry("other.txt")], 0.0).unwrap();
+
+        // The old index keeps serving while the new one is built
+        assert_eq!(db.count_files().unwrap(), 2);
+        assert_eq!(db.query_files("old.txt", 10).unwrap().len(), 1);
+        drop(new_db);
+
+        db.swap_in(&path, &replacement).unwrap();
+        assert!(!replacement.exists());
+        assert_eq!(db.count_files().unwrap(), 3);
+        assert!(db.query_files("old.txt", 10).unwrap().is_empty());
+        assert_eq!(db.query_files("new.txt", 10).unwrap().len(), 1);
+
+        // The swapped-in file is a complete database on its own
+        drop(db);
+        let reopened = Database::open(&path).unwrap();
+        assert_eq!(reopened.count_files().unwrap(), 3);
+        reopened.delete_file("/home/user/other.txt").unwrap();
+        assert_eq!(reopened.count_files().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_read_snapshot_ignores_later_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index.db");
+        let reader_db = Database::open(&path).unwrap();
+        let writer_db = Database::open(&path).unwrap();
+
+        let entry = |name: &str| FileEntry::new(
+            name.to_string(),
+            PathBuf::from("/home/user").join(name),
+            1,
+            SystemTime::now(),
+            FileType::Regular,
+        );
+        writer_db.insert_file(&entry("before.txt")).unwrap();
+
+        {
+            let snapshot = reader_db.read_snapshot().unwrap();
+            assert_eq!(snapshot.count_files().unwrap(), 1);
+
+            // The writer is not blocked by the open snapshot
+            writer_db.execute_batch(&[IndexOperation::Add(entry("during.txt"))]).unwrap();
+            assert_eq!(snapshot.count_files().unwrap(), 1);
+            assert!(snapshot.query_files("during", 10).unwrap().is_empty());
+        }
+
+        assert_eq!(reader_db.count_files().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_open_unwritable_directory() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+
+        // A path whose parent is a regular file can never be created
+        let blocker = dir.path().join("blocker");
+        std::fs::write(&blocker, b"").unwrap();
+        let err = Database::open_writable(blocker.join("index.db")).err().unwrap();
+        assert!(matches!(err, DatabaseError::NotWritable(_)));
+        assert!(err.to_string().contains("--data-dir"));
+
+        // Permission bits do not stop root, so only check them as a normal user
+        if unsafe { libc::geteuid() } != 0 {
+            let readonly = dir.path().join("readonly");
+            std::fs::create_dir(&readonly).unwrap();
+            std::fs::set_permissions(&readonly, std::fs::Permissions::from_mode(0o555)).unwrap();
+            let err = Database::open_writable(readonly.join("index.db")).err().unwrap();
+            assert!(matches!(err, DatabaseError::NotWritable(_)));
+            assert!(err.to_string().contains(&readonly.display().to_string()));
+        }
+
+        let db = Database::open_writable(dir.path().join("data").join("index.db")).unwrap();
+        assert_eq!(db.count_files().unwrap(), 0);
+        // Only the database (and its write-ahead log) is left behind, not the probe
+        let leftovers = std::fs::read_dir(dir.path().join("data"))
+            .unwrap()
+            .filter(|entry| entry.as_ref().unwrap().file_name().to_string_lossy().starts_with(".write-test"))
+            .count();
+        assert_eq!(leftovers, 0);
+    }
+
+    #[test]
+    fn test_database_creation() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+        
+        // Verify files table exists
+        let table_exists: i32 = db.connection()
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='files'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(table_exists, 1);
+
+        // Verify metadata table exists
+        let table_exists: i32 = db.connection()
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='metadata'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(table_exists, 1);
+    }
+
+    #[test]
+    fn test_long_paths_skipped_or_stored() {
+        let deep = "d/".repeat(10_000);
+        let long_path = |name: &str| PathBuf::from(format!("/home/user/{}{}", deep, name));
+        let add = |path: PathBuf| IndexOperation::Add(FileEntry::new(
+            models::entry_name(&path),
+            path,
+            1,
+            SystemTime::now(),
+            FileType::Regular,
+        ));
+
+        for storage in [PathStorage::Full, PathStorage::Interned] {
+            let mut db = Database::open_in_memory().unwrap();
+            db.set_path_storage(storage).unwrap();
+
+            // Stored: a 20 kB path is indexed once, however often it is written
+            db.execute_batch(&[add(long_path("stored.txt")), add(long_path("stored.txt"))]).unwrap();
+            db.execute_batch(&[add(long_path("stored.txt"))]).unwrap();
+            assert_eq!(db.count_files().unwrap(), 1);
+            assert!(db.get_entry_by_path(long_path("stored.txt")).unwrap().is_some());
+
+            // Skipped: nothing over the limit is written, and moving onto such a path drops the entry
+            db.set_max_path_length(Some(4096));
+            let changes = db.execute_batch(&[
+                add(long_path("skipped.txt")),
+                add(PathBuf::from("/home/user/short.txt")),
+                IndexOperation::Move { from: PathBuf::from("/home/user/short.txt"), to: long_path("moved.txt") },
+            ]).unwrap();
+            assert_eq!((changes.added, changes.deleted, changes.moved), (1, 1, 0));
+            assert_eq!(db.count_files().unwrap(), 1);
+            assert!(db.query_files("skipped", 10).unwrap().is_empty());
+
+            let integrity: String = db.connection().query_row("PRAGMA integrity_check", [], |row| row.get(0)).unwrap();
+            assert_eq!(integrity, "ok", "{:?}", storage);
+        }
+    }
+
+    #[test]
+    fn test_prune_missing() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db = Database::open_in_memory().unwrap();
+        for filename in ["kept.txt", "deleted.txt"] {
+            let path = temp_dir.path().join(filename);
+            std::fs::write(&path, "content").unwrap();
+            db.insert_file(&FileEntry::new(filename.to_string(), path, 7, SystemTime::now(), FileType::Regular)).unwrap();
+        }
+        std::fs::remove_file(temp_dir.path().join("deleted.txt")).unwrap();
+
+        let mut reports = Vec::new();
+        let roots = [temp_dir.path().to_path_buf()];
+        assert_eq!(db.prune_missing_with_progress(&roots, |checked, total| reports.push((checked, total))).unwrap(), 1);
+        assert_eq!(reports, vec![(2, 2)]);
+        assert_eq!(db.count_files().unwrap(), 1);
+        assert_eq!(db.query_files("kept", 10).unwrap().len(), 1);
+        assert_eq!(db.prune_missing(&roots).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_prune_missing_keeps_entries_of_absent_roots() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let unmounted = temp_dir.path().join("usb");
+        let removed = temp_dir.path().join("backup");
+        std::fs::create_dir(&unmounted).unwrap();
+        let db = Database::open_in_memory().unwrap();
+        for path in [unmounted.join("photo.jpg"), removed.join("notes.txt")] {
+            db.insert_file(&FileEntry::new(models::entry_name(&path), path, 7, SystemTime::now(), FileType::Regular)).unwrap();
+        }
+
+        // An empty mount point and a root that is gone both keep their entries
+        assert_eq!(db.prune_missing(&[unmounted.clone(), removed.clone()]).unwrap(), 0);
+        assert_eq!(db.count_files().unwrap(), 2);
+
+        // Once the root is back, what is missing from it is pruned
+        std::fs::write(unmounted.join("other.jpg"), "content").unwrap();
+        assert_eq!(db.prune_missing(&[unmounted, removed]).unwrap(), 1);
+        assert_eq!(db.query_files("notes", 10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_open_uses_wal_with_busy_timeout() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+        let pragma = |name: &str| -> i64 {
+            db.connection().query_row(&format!("PRAGMA {}", name), [], |row| row.get(0)).unwrap()
+        };
+
+        let journal_mode: String = db.connection().query_row("PRAGMA journal_mode", [], |row| row.get(0)).unwrap();
+        assert_eq!(journal_mode, "wal");
+        // 1 is NORMAL
+        assert_eq!(pragma("synchronous"), 1);
+        assert_eq!(pragma("busy_timeout"), 5000);
+    }
+
+    #[test]
+    fn test_public_schema_version_on_fresh_database() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+
+        assert_eq!(db.schema_version().unwrap(), SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_schema_version() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+        
+        let version = db.get_schema_version().unwrap();
+        assert_eq!(version, SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_indexes_created() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+        
+        // Check for filename index
+        let index_exists: i32 = db.connection()
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE type='index' AND name='idx_filename'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(index_exists, 1);
+
+        // Check for path index
+        let index_exists: i32 = db.connection()
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE type='index' AND name='idx_path'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(index_exists, 1);
+
+        // Check for modified_time index
+        let index_exists: i32 = db.connection()
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE type='index' AND name='idx_modified_time'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(index_exists, 1);
+    }
+
+    #[test]
+    fn test_reopen_existing_database() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let path = temp_file.path().to_path_buf();
+        
+        // Create database
+        {
+            let _db = Database::open(&path).unwrap();
+        }
+        
+        // Reopen database
+        let db = Database::open(&path).unwrap();
+        let version = db.get_schema_version().unwrap();
+        assert_eq!(version, SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_metadata_roundtrip() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+        
+        assert_eq!(db.get_metadata("scan_errors").unwrap(), None);
+        db.set_metadata("scan_errors", "[]").unwrap();
+        db.set_metadata("scan_errors", "[1]").unwrap();
+        assert_eq!(db.get_metadata("scan_errors").unwrap(), Some("[1]".to_string()));
+    }
+
+    #[test]
+    fn test_insert_file() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+        
+        let entry = FileEntry::new(
+            "test.txt".to_string(),
+            PathBuf::from("/home/user/test.txt"),
+            1024,
+            SystemTime::now(),
+            FileType::Regular,
+        );
+        
+        let id = db.insert_file(&entry).unwrap();
+        assert!(id > 0);
+        
+        let count = db.count_files().unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn test_update_file() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+        
+        let entry = FileEntry::new(
+            "test.txt".to_string(),
+            PathBuf::from("/home/user/test.txt"),
+            1024,
+            SystemTime::now(),
+            FileType::Regular,
+        );
+        
+        db.insert_file(&entry).unwrap();
+        
+        let updated_entry = FileEntry::new(
+            "test.txt".to_string(),
+            PathBuf::from("/home/user/test.txt"),
+            2048,
+            SystemTime::now(),
+            FileType::Regular,
+        );
+        
+        db.update_file(&updated_entry).unwrap();
+        
+        let count = db.count_files().unwrap();
+        assert_eq!(count, 1);
+        
+        let results = db.query_files("test", 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].size, 2048);
+    }
+
+    #[test]
+    fn test_delete_file() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+        
+        let entry = FileEntry::new(
+            "test.txt".to_string(),
+            PathBuf::from("/home/user/test.txt"),
+            1024,
+            SystemTime::now(),
+            FileType::Regular,
+        );
+        
+        db.insert_file(&entry).unwrap();
+        assert_eq!(db.count_files().unwrap(), 1);
+        
+        db.delete_file(&entry.path).unwrap();
+        assert_eq!(db.count_files().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_move_file() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+        
+        let entry = FileEntry::new(
+            "test.txt".to_string(),
+            PathBuf::from("/home/user/test.txt"),
+            1024,
+            SystemTime::now(),
+            FileType::Regular,
+        );
+        
+        db.insert_file(&entry).unwrap();
+        
+        let new_path = PathBuf::from("/home/user/documents/test.txt");
+        db.move_file(&entry.path, &new_path).unwrap();
+        
+        let results = db.query_files("test", 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].path, new_path);
+        assert_eq!(results[0].filename, "test.txt");
+    }
+
+    #[test]
+    fn test_move_directory_moves_its_contents() {
+        for storage in [PathStorage::Full, PathStorage::Interned] {
+            let mut db = Database::open_in_memory().unwrap();
+            db.set_path_storage(storage).unwrap();
+            for (path, file_type) in [
+                ("/home/user/drafts", FileType::Directory),
+                ("/home/user/drafts/letter.txt", FileType::Regular),
+                ("/home/user/drafts/old/notes.txt", FileType::Regular),
+                ("/home/user/drafts-2023/keep.txt", FileType::Regular),
+            ] {
+                let path = PathBuf::from(path);
+                db.insert_file(&FileEntry::new(models::entry_name(&path), path, 10, SystemTime::now(), file_type))
+                    .unwrap();
+            }
+            db.record_file_launch("/home/user/drafts/letter.txt").unwrap();
+
+            db.move_file("/home/user/drafts", "/home/user/archive").unwrap();
+
+            let mut paths: Vec<PathBuf> = db.query_files("t", 10).unwrap().into_iter().map(|e| e.path).collect();
+            paths.sort();
+            assert_eq!(paths, [
+                PathBuf::from("/home/user/archive/letter.txt"),
+                PathBuf::from("/home/user/archive/old/notes.txt"),
+                PathBuf::from("/home/user/drafts-2023/keep.txt"),
+            ], "{:?}", storage);
+            assert!(db.get_entry_by_path("/home/user/archive").unwrap().is_some());
+            assert_eq!(db.get_file_usage("/home/user/archive/letter.txt").unwrap().map(|(count, _)| count), Some(1));
+            let options = SearchOptions { scope: SearchScope::Path, ..SearchOptions::default() };
+            assert_eq!(db.search("archive/old", &options).unwrap().len(), 1);
+            assert!(db.search("drafts/old", &options).unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_case_only_rename_over_existing_row() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+        
+        // The new-case path may already be indexed from a create event
+        for path in ["/home/user/File.txt", "/home/user/file.txt"] {
+            let path = PathBuf::from(path);
+            let entry = FileEntry::new(
+                path.file_name().unwrap().to_string_lossy().to_string(),
+                path,
+                1024,
+                SystemTime::now(),
+                FileType::Regular,
+            );
+            db.insert_file(&entry).unwrap();
+        }
+        
+        db.move_file(Path::new("/home/user/File.txt"), Path::new("/home/user/file.txt")).unwrap();
+        
+        let results = db.query_files("file", 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].path, PathBuf::from("/home/user/file.txt"));
+        assert_eq!(results[0].filename, "file.txt");
+    }
+
+    #[test]
+    fn test_query_files() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+        
+        // Insert multiple files
+        let files = vec![
+            ("test.txt", "/home/user/test.txt"),
+            ("testing.txt", "/home/user/testing.txt"),
+            ("document.txt", "/home/user/document.txt"),
+            ("test_file.txt", "/home/user/test_file.txt"),
+        ];
+        
+        for (filename, path) in files {
+            let entry = FileEntry::new(
+                filename.to_string(),
+                PathBuf::from(path),
+                1024,
+                SystemTime::now(),
+                FileType::Regular,
+            );
+            db.insert_file(&entry).unwrap();
+        }
+        
+        // Query for "test"
+        let results = db.query_files("test", 10).unwrap();
+        assert_eq!(results.len(), 3);
+        
+        // Verify ranking: exact match first
+        assert_eq!(results[0].filename, "test.txt");
+        
+        // Query with limit
+        let results = db.query_files("test", 2).unwrap();
+        assert_eq!(results.len(), 2);
+    }
+
+    #[test]
+    fn test_query_files_escapes_like_wildcards() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+        
+        for filename in ["50%.txt", "500.txt", "50_off.txt", "50x_off.txt", "a\\b.txt", "ab.txt"] {
+            let entry = FileEntry::new(
+                filename.to_string(),
+                PathBuf::from(format!("/home/user/{}", filename)),
+                1024,
+                SystemTime::now(),
+                FileType::Regular,
+            );
+            db.insert_file(&entry).unwrap();
+        }
+        
+        let filenames = |results: Vec<FileEntry>| -> Vec<String> {
+            results.into_iter().map(|e| e.filename).collect()
+        };
+        assert_eq!(filenames(db.query_files("50%", 10).unwrap()), vec!["50%.txt"]);
+        assert_eq!(filenames(db.query_files("0_", 10).unwrap()), vec!["50_off.txt"]);
+        assert_eq!(filenames(db.query_files("a\\", 10).unwrap()), vec!["a\\b.txt"]);
+        
+        let results = db.search("50%", &SearchOptions::default()).unwrap();
+        assert_eq!(filenames(results), vec!["50%.txt"]);
+        let ranked = db.search_ranked("50%", &SearchOptions::default()).unwrap();
+        assert_eq!(ranked.len(), 1);
+        assert_eq!(ranked[0].match_kind, MatchKind::Prefix);
+    }
+
+    #[test]
+    fn test_underscore_in_query_is_not_a_wildcard() {
+        let db = Database::open_in_memory().unwrap();
+        for filename in ["a_b.txt", "aXb.txt", "report_2024.pdf", "reportX2024.pdf"] {
+            db.insert_file(&FileEntry::new(
+                filename.to_string(),
+                PathBuf::from(format!("/home/user/{}", filename)),
+                1024,
+                SystemTime::now(),
+                FileType::Regular,
+            )).unwrap();
+        }
+
+        let filenames = |results: Vec<FileEntry>| -> Vec<String> {
+            results.into_iter().map(|e| e.filename).collect()
+        };
+        assert_eq!(filenames(db.query_files("a_b", 10).unwrap()), vec!["a_b.txt"]);
+        assert_eq!(filenames(db.query_files("report_2024", 10).unwrap()), vec!["report_2024.pdf"]);
+        assert_eq!(db.count_matches("a_b").unwrap(), 1);
+    }
+
+    #[test]
+    fn test_query_files_exclusions() {
+        let db = Database::open_in_memory().unwrap();
+        for filename in ["report.pdf", "report-draft.pdf", "Report DRAFT 2.odt", "report-final.pdf", "-draft notes.txt"] {
+            db.insert_file(&FileEntry::new(
+                filename.to_string(),
+                PathBuf::from(format!("/home/user/{}", filename)),
+                1024,
+                SystemTime::now(),
+                FileType::Regular,
+            )).unwrap();
+        }
+
+        let filenames = |results: Vec<FileEntry>| -> Vec<String> {
+            let mut names: Vec<String> = results.into_iter().map(|e| e.filename).collect();
+            names.sort();
+            names
+        };
+        assert_eq!(filenames(db.query_files("report -draft", 10).unwrap()), vec!["report-final.pdf", "report.pdf"]);
+        assert_eq!(filenames(db.query_files("-draft report -final", 10).unwrap()), vec!["report.pdf"]);
+        assert_eq!(filenames(db.query_files("report", 10).unwrap()).len(), 4);
+
+        // An escaped dash is part of the text; a query of only exclusions matches nothing
+        assert_eq!(filenames(db.query_files("\\-draft", 10).unwrap()), vec!["-draft notes.txt", "report-draft.pdf"]);
+        assert!(db.query_files("-draft", 10).unwrap().is_empty());
+
+        assert_eq!(split_exclusions("a  b - c"), ("a  b - c".to_string(), Vec::new()));
+        assert_eq!(split_exclusions("a  b -c"), ("a b".to_string(), vec!["c".to_string()]));
+    }
+
+    #[test]
+    fn test_count_matches() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+        
+        for filename in ["report.pdf", "Report-2024.PDF", "notes.txt", "50%.txt", "500.txt", "photo.jpg"] {
+            db.insert_file(&FileEntry::new(
+                filename.to_string(),
+                PathBuf::from(format!("/home/user/{}", filename)),
+                1024,
+                SystemTime::now(),
+                FileType::Regular,
+            )).unwrap();
+        }
+        
+        for query in ["", "report", "REPORT", ".txt", "50%", "missing", "report -2024", "-report", "\\-2024"] {
+            let expected = db.query_files(query, usize::MAX).unwrap().len();
+            assert_eq!(db.count_matches(query).unwrap(), expected, "query {:?}", query);
+        }
+        assert_eq!(db.count_matches("report").unwrap(), 2);
+    }
+
+    #[test]
+    fn test_empty_and_whitespace_queries() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+        
+        for filename in ["report.pdf", "notes.txt", "photo.jpg"] {
+            db.insert_file(&FileEntry::new(
+                filename.to_string(),
+                PathBuf::from(format!("/home/user/{}", filename)),
+                1024,
+                SystemTime::now(),
+                FileType::Regular,
+            )).unwrap();
+        }
+        db.record_file_launch("/home/user/notes.txt").unwrap();
+        
+        for query in ["", "   ", "\t\n"] {
+            assert!(db.query_files(query, 10).unwrap().is_empty());
+            assert!(db.query_with_stats(query, 10).unwrap().is_empty());
+            assert_eq!(db.count_matches(query).unwrap(), 0);
+            assert!(db.search(query, &SearchOptions::default()).unwrap().is_empty());
+            
+            let frequent = SearchOptions { empty_query: EmptyQuery::Frequent, ..SearchOptions::default() };
+            let results = db.search(query, &frequent).unwrap();
+            assert_eq!(results.len(), 1);
+            assert_eq!(results[0].filename, "notes.txt");
+            assert!(db.search_ranked(query, &frequent).unwrap().is_empty());
+        }
+        
+        // Surrounding whitespace is ignored
+        assert_eq!(db.query_files("  report ", 10).unwrap().len(), 1);
+        assert_eq!(db.count_matches(" report\t").unwrap(), 1);
+        
+        // A type filter makes an empty query meaningful on its own
+        let regular = SearchOptions { file_types: vec![FileType::Regular], ..SearchOptions::default() };
+        assert_eq!(db.search(" ", &regular).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_search_filtered_drops_stale_entries() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db = Database::open(temp_dir.path().join("index.db")).unwrap();
+        
+        // Every other file is removed from disk after indexing
+        for i in 0..6 {
+            let path = temp_dir.path().join(format!("report{}.txt", i));
+            std::fs::write(&path, "content").unwrap();
+            let entry = FileEntry::new(
+                format!("report{}.txt", i),
+                path.clone(),
+                7,
+                SystemTime::now(),
+                FileType::Regular,
+            );
+            db.insert_file(&entry).unwrap();
+            if i % 2 == 0 {
+                std::fs::remove_file(&path).unwrap();
+            }
+        }
+        
+        let options = SearchOptions { limit: 3, ..SearchOptions::default() };
+        let filters: Vec<ResultFilter> = vec![Box::new(exists_on_disk)];
+        let results = db.search_filtered("report", &options, &filters).unwrap();
+        
+        let filenames: Vec<&str> = results.iter().map(|e| e.filename.as_str()).collect();
+        assert_eq!(filenames, vec!["report1.txt", "report3.txt", "report5.txt"]);
+        
+        // Asking for more than remain returns what is left
+        let options = SearchOptions { limit: 10, ..SearchOptions::default() };
+        assert_eq!(db.search_filtered("report", &options, &filters).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_largest_files() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+        
+        let files = [
+            ("small.txt", 10, FileType::Regular),
+            ("huge.iso", 4_000_000_000, FileType::Regular),
+            ("medium.jpg", 2_500_000, FileType::Regular),
+            ("folder", 8_000_000_000, FileType::Directory),
+            ("large.mkv", 700_000_000, FileType::Regular),
+        ];
+        for (filename, size, file_type) in files {
+            let entry = FileEntry::new(
+                filename.to_string(),
+                PathBuf::from(format!("/home/user/{}", filename)),
+                size,
+                SystemTime::now(),
+                file_type,
+            );
+            db.insert_file(&entry).unwrap();
+        }
+        
+        let largest = db.largest_files(3).unwrap();
+        let filenames: Vec<&str> = largest.iter().map(|e| e.filename.as_str()).collect();
+        assert_eq!(filenames, vec!["huge.iso", "large.mkv", "medium.jpg"]);
+        assert!(largest.windows(2).all(|pair| pair[0].size >= pair[1].size));
+    }
+
+    #[test]
+    fn test_search_content() {
+        let mut db = Database::open_in_memory().unwrap();
+        let content_rows = |db: &Database| -> i64 {
+            db.connection.query_row("SELECT COUNT(*) FROM file_content", [], |row| row.get(0)).unwrap()
+        };
+        
+        let mut notes = FileEntry::new(
+            "notes.md".to_string(),
+            PathBuf::from("/home/user/notes.md"),
+            64,
+            SystemTime::now(),
+            FileType::Regular,
+        );
+        notes.content = Some("Meeting notes: discuss the Zephyr rollout\n".to_string());
+        let mut other = FileEntry::new(
+            "todo.txt".to_string(),
+            PathBuf::from("/home/user/todo.txt"),
+            16,
+            SystemTime::now(),
+            FileType::Regular,
+        );
+        other.content = Some("buy milk".to_string());
+        db.execute_batch(&[IndexOperation::Add(notes.clone()), IndexOperation::Add(other)]).unwrap();
+        
+        let paths = |results: Vec<FileEntry>| -> Vec<PathBuf> { results.into_iter().map(|e| e.path).collect() };
+        assert_eq!(paths(db.search_content("zephyr", 10).unwrap()), vec![notes.path.clone()]);
+        assert_eq!(paths(db.search_content("ROLLOUT zephyr", 10).unwrap()), vec![notes.path.clone()]);
+        // Whole words only, and every word has to match
+        assert!(db.search_content("zeph", 10).unwrap().is_empty());
+        assert!(db.search_content("zephyr milk", 10).unwrap().is_empty());
+        // Query syntax is not interpreted
+        assert!(db.search_content("\"notes:\" OR", 10).unwrap().is_empty());
+        
+        // Stored text follows the entry through a storage conversion, a move and a delete
+        db.set_path_storage(PathStorage::Interned).unwrap();
+        assert_eq!(paths(db.search_content("zephyr", 10).unwrap()), vec![notes.path.clone()]);
+        let moved = PathBuf::from("/home/user/archive/notes.md");
+        db.execute_batch(&[IndexOperation::Move { from: notes.path.clone(), to: moved.clone() }]).unwrap();
+        assert_eq!(paths(db.search_content("zephyr", 10).unwrap()), vec![moved.clone()]);
+        db.execute_batch(&[IndexOperation::Delete(moved)]).unwrap();
+        assert!(db.search_content("zephyr", 10).unwrap().is_empty());
+        assert_eq!(content_rows(&db), 1);
+    }
+
+    #[test]
+    fn test_get_entry_by_path_and_prefix() {
+        let db = Database::open_in_memory().unwrap();
+        let modified = UNIX_EPOCH + Duration::new(1_700_000_000, 250);
+        
+        for (path, size, file_type) in [
+            ("/home/user/docs", 4096, FileType::Directory),
+            ("/home/user/docs/report.pdf", 52_431, FileType::Regular),
+            ("/home/user/docs/notes/todo.txt", 12, FileType::Regular),
+            ("/home/user/docs2/other.txt", 1, FileType::Regular),
+        ] {
+            let path = PathBuf::from(path);
+            let mut entry = FileEntry::new(models::entry_name(&path), path, size, modified, file_type);
+            entry.is_executable = size == 12;
+            db.insert_file(&entry).unwrap();
+        }
+        db.record_file_launch("/home/user/docs/report.pdf").unwrap();
+        
+        let entry = db.get_entry_by_path("/home/user/docs/report.pdf").unwrap().unwrap();
+        assert_eq!(entry.filename, "report.pdf");
+        assert_eq!(entry.size, 52_431);
+        assert_eq!(entry.modified_time, modified);
+        assert_eq!(entry.file_type, FileType::Regular);
+        assert!(!entry.is_executable);
+        assert_eq!(db.get_file_usage(&entry.path).unwrap().map(|(count, _)| count), Some(1));
+        assert!(db.get_entry_by_path("/home/user/docs/missing.txt").unwrap().is_none());
+        
+        // Only entries below the directory, not the directory or its siblings
+        let under: Vec<PathBuf> = db.entries_under_prefix("/home/user/docs/")
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.path)
+            .collect();
+        assert_eq!(
+            under,
+            vec![PathBuf::from("/home/user/docs/notes/todo.txt"), PathBuf::from("/home/user/docs/report.pdf")]
+        );
+    }
+
+    #[test]
+    fn test_compact_usage() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
         
-        self.connection.execute(
-            "INSERT INTO files (filename, path, size, modified_time, file_type, indexed_time)
-             VALUES (?, ?, ?, ?, ?, ?)
-             ON CONFLICT(path) DO UPDATE SET
-                filename = excluded.filename,
-                size = excluded.size,
-                modified_time = excluded.modified_time,
-                file_type = excluded.file_type,
-                indexed_time = excluded.indexed_time",
-            params![
-                entry.filename,
-                entry.path.to_string_lossy().to_string(),
-                entry.size as i64,
-                modified_time,
-                entry.file_type.as_str(),
-                indexed_time,
-            ],
-        )?;
+        let old_id = db.insert_file(&FileEntry::new(
+            "old.txt".to_string(),
+            PathBuf::from("/home/user/old.txt"),
+            1024,
+            SystemTime::now(),
+            FileType::Regular,
+        )).unwrap();
+        let recent_id = db.insert_file(&FileEntry::new(
+            "recent.txt".to_string(),
+            PathBuf::from("/home/user/recent.txt"),
+            1024,
+            SystemTime::now(),
+            FileType::Regular,
+        )).unwrap();
         
-        Ok(())
+        let year_ago = current_timestamp() - 365 * 24 * 60 * 60;
+        db.connection().execute(
+            "INSERT INTO usage_stats (file_id, launch_count, last_launched) VALUES (?, 1, ?)",
+            params![old_id, year_ago],
+        ).unwrap();
+        db.connection().execute(
+            "INSERT INTO usage_stats (file_id, launch_count, last_launched) VALUES (?, 20, ?)",
+            params![recent_id, current_timestamp()],
+        ).unwrap();
+        
+        let removed = db.compact_usage(3, Duration::from_secs(90 * 24 * 60 * 60)).unwrap();
+        assert_eq!(removed, 1);
+        
+        assert!(db.get_file_usage("/home/user/old.txt").unwrap().is_none());
+        let (launch_count, _) = db.get_file_usage("/home/user/recent.txt").unwrap().unwrap();
+        assert_eq!(launch_count, 20);
     }
 
-    /// Delete a file entry by path
-    pub fn delete_file<P: AsRef<Path>>(&self, path: P) -> SqliteResult<()> {
-        self.connection.execute(
-            "DELETE FROM files WHERE path = ?",
-            params![path.as_ref().to_string_lossy().to_string()],
-        )?;
-        Ok(())
+    #[test]
+    fn test_clear_usage_stats() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+        
+        for name in ["notes.txt", "notes2.txt"] {
+            db.insert_file(&FileEntry::new(
+                name.to_string(),
+                PathBuf::from("/home/user").join(name),
+                1024,
+                SystemTime::now(),
+                FileType::Regular,
+            )).unwrap();
+        }
+        for _ in 0..3 {
+            db.record_file_launch("/home/user/notes2.txt").unwrap();
+        }
+        assert_eq!(db.get_most_used_files(10).unwrap().len(), 1);
+        assert_eq!(db.search("notes", &SearchOptions::default()).unwrap()[0].filename, "notes2.txt");
+        
+        db.clear_usage_stats().unwrap();
+        
+        assert!(db.get_file_usage("/home/user/notes2.txt").unwrap().is_none());
+        assert!(db.get_most_used_files(10).unwrap().is_empty());
+        assert_eq!(db.count_files().unwrap(), 2);
+        
+        // Ranking falls back to the name alone
+        let results = db.search("notes", &SearchOptions::default()).unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].filename, "notes.txt");
     }
 
-    /// Move a file entry (update its path)
-    pub fn move_file<P: AsRef<Path>>(&self, from: P, to: P) -> SqliteResult<()> {
-        let to_path = to.as_ref();
-        let filename = to_path
-            .file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("")
-            .to_string();
+    #[test]
+    fn test_recent_activity_blends_launches_and_modifications() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+        let now = SystemTime::now();
+        let week_ago = now - Duration::from_secs(7 * 24 * 60 * 60);
         
-        self.connection.execute(
-            "UPDATE files SET path = ?, filename = ? WHERE path = ?",
-            params![
-                to_path.to_string_lossy().to_string(),
-                filename,
-                from.as_ref().to_string_lossy().to_string(),
-            ],
-        )?;
-        Ok(())
+        for (name, modified) in [
+            ("edited.txt", now),
+            ("opened.pdf", week_ago),
+            ("old.txt", week_ago - Duration::from_secs(60)),
+        ] {
+            db.insert_file(&FileEntry::new(
+                name.to_string(),
+                PathBuf::from("/home/user").join(name),
+                10,
+                modified,
+                FileType::Regular,
+            )).unwrap();
+        }
+        db.record_file_launch("/home/user/opened.pdf").unwrap();
+        
+        let names = |entries: Vec<FileEntry>| -> Vec<String> {
+            entries.into_iter().map(|e| e.filename).collect()
+        };
+        
+        // Both a modified-never-opened file and an opened file make the feed
+        let feed = names(db.recent_activity(2, 0.5).unwrap());
+        assert_eq!(feed.len(), 2);
+        assert!(feed.contains(&"edited.txt".to_string()));
+        assert!(feed.contains(&"opened.pdf".to_string()));
+        
+        // Without launch slots the newest modifications win
+        assert_eq!(names(db.recent_activity(2, 0.0).unwrap()), vec!["edited.txt", "opened.pdf"]);
+        
+        // Unused launch slots go to modified files, and nothing is listed twice
+        assert_eq!(names(db.recent_activity(10, 1.0).unwrap()), vec!["opened.pdf", "edited.txt", "old.txt"]);
     }
 
-    /// Query files by filename pattern with usage-based ranking
-    pub fn query_files(&self, query: &str, limit: usize) -> SqliteResult<Vec<FileEntry>> {
-        let mut stmt = self.connection.prepare(
-            "SELECT f.id, f.filename, f.path, f.size, f.modified_time, f.file_type, f.indexed_time,
-                    COALESCE(u.launch_count, 0) as launch_count,
-                    COALESCE(u.last_launched, 0) as last_launched
-             FROM files f
-             LEFT JOIN usage_stats u ON f.id = u.file_id
-             WHERE f.filename LIKE '%' || ? || '%'
-             ORDER BY 
-                CASE 
-                    WHEN f.filename = ? THEN 0
-                    WHEN f.filename LIKE ? || '%' THEN 1
-                    ELSE 2
-                END,
-                COALESCE(u.launch_count, 0) DESC,
-                f.filename COLLATE NOCASE
-             LIMIT ?"
-        )?;
+    #[test]
+    fn test_stale_entries() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+        let now = SystemTime::now();
+        let day = Duration::from_secs(24 * 60 * 60);
+        
+        let files = vec![
+            ("week.txt", now - day * 7),
+            ("fresh.txt", now),
+            ("month.txt", now - day * 30),
+            ("twodays.txt", now - day * 2),
+        ];
+        
+        for (filename, indexed_time) in files {
+            let mut entry = FileEntry::new(
+                filename.to_string(),
+                PathBuf::from("/home/user").join(filename),
+                1024,
+                now,
+                FileType::Regular,
+            );
+            entry.indexed_time = indexed_time;
+            db.insert_file(&entry).unwrap();
+        }
+        
+        let stale = db.stale_entries(day, 10).unwrap();
+        let names: Vec<&str> = stale.iter().map(|e| e.filename.as_str()).collect();
+        assert_eq!(names, vec!["month.txt", "week.txt", "twodays.txt"]);
+        
+        let stale = db.stale_entries(day, 1).unwrap();
+        assert_eq!(stale.len(), 1);
+        assert_eq!(stale[0].filename, "month.txt");
+    }
 
-        let entries = stmt.query_map(
-            params![query, query, query, limit as i64],
-            |row| {
-                Ok(FileEntry {
-                    id: Some(row.get(0)?),
-                    filename: row.get(1)?,
-                    path: PathBuf::from(row.get::<_, String>(2)?),
-                    size: row.get::<_, i64>(3)? as u64,
-                    modified_time: timestamp_to_system_time(row.get(4)?),
-                    file_type: FileType::from_str(&row.get::<_, String>(5)?),
-                    indexed_time: timestamp_to_system_time(row.get(6)?),
-                })
-            },
-        )?;
+    #[test]
+    fn test_search_scopes() {
+        let db = Database::open_in_memory().unwrap();
+        for path in [
+            "/home/user/alpha/readme.md",
+            "/home/user/alpha/todo.txt",
+            "/home/user/docs/alpha-notes.txt",
+            "/home/user/docs/other.txt",
+        ] {
+            let path = PathBuf::from(path);
+            db.insert_file(&FileEntry::new(models::entry_name(&path), path, 1, SystemTime::now(), FileType::Regular)).unwrap();
+        }
+        db.record_file_launch("/home/user/alpha/todo.txt").unwrap();
 
-        entries.collect()
+        let names = |scope: SearchScope| -> Vec<String> {
+            db.search("alpha", &SearchOptions { scope, ..SearchOptions::default() })
+                .unwrap()
+                .into_iter()
+                .map(|e| e.filename)
+                .collect()
+        };
+
+        assert_eq!(names(SearchScope::Filename), vec!["alpha-notes.txt"]);
+        // Usage decides among path matches
+        assert_eq!(names(SearchScope::Path), vec!["todo.txt", "alpha-notes.txt", "readme.md"]);
+        // Filename matches come before files that only sit in a matching directory
+        assert_eq!(names(SearchScope::Both), vec!["alpha-notes.txt", "todo.txt", "readme.md"]);
     }
 
-    /// Execute a batch of operations with retry logic
-    pub fn execute_batch(&self, operations: &[IndexOperation]) -> SqliteResult<()> {
-        let max_retries = 5;
-        let mut delay_ms = 100;
-        
-        for attempt in 0..max_retries {
-            match self.try_execute_batch(operations) {
-                Ok(()) => return Ok(()),
-                Err(rusqlite::Error::SqliteFailure(err, _)) 
-                    if err.code == rusqlite::ErrorCode::DatabaseBusy 
-                    || err.code == rusqlite::ErrorCode::DatabaseLocked => 
-                {
-                    if attempt < max_retries - 1 {
-                        std::thread::sleep(Duration::from_millis(delay_ms));
-                        delay_ms = (delay_ms * 2).min(1600); // Cap at 1600ms
-                    } else {
-                        return Err(rusqlite::Error::SqliteFailure(err, None));
-                    }
-                }
-                Err(e) => return Err(e),
-            }
+    #[test]
+    fn test_offline_mount_entries_hidden_and_kept() {
+        let db = Database::open_in_memory().unwrap();
+        let usb = PathBuf::from("/media/user/usb");
+        for path in ["/media/user/usb/report.pdf", "/home/user/report.txt"] {
+            let path = PathBuf::from(path);
+            db.insert_file(&FileEntry::new(models::entry_name(&path), path, 1, SystemTime::now(), FileType::Regular)).unwrap();
         }
-        
-        unreachable!()
+        db.record_file_launch("/media/user/usb/report.pdf").unwrap();
+        let found = |db: &Database| db.search("report", &SearchOptions::default()).unwrap().len();
+
+        db.set_mount_offline(&usb).unwrap();
+        assert_eq!(db.offline_mounts().unwrap(), vec![usb.clone()]);
+        assert_eq!(found(&db), 1);
+        assert_eq!(db.query_files("report", 10).unwrap().len(), 1);
+        assert_eq!(db.query_files_fts("report", 10).unwrap().len(), 1);
+        assert_eq!(db.query_with_stats("report", 10).unwrap().len(), 1);
+        assert_eq!(db.count_matches("report").unwrap(), 1);
+        assert!(db.get_most_used_files(10).unwrap().is_empty());
+
+        // A reconcile of the include path that holds the mount keeps its entries
+        let home = db.get_entry_by_path("/home/user/report.txt").unwrap().unwrap();
+        let stats = db.reconcile(&[home], &[PathBuf::from("/")]).unwrap();
+        assert_eq!(stats.deleted, 0);
+        assert_eq!(db.count_files().unwrap(), 2);
+
+        assert!(db.set_mount_online(&usb).unwrap());
+        assert!(!db.set_mount_online(&usb).unwrap());
+        assert_eq!(found(&db), 2);
     }
 
-    /// Try to execute a batch of operations (helper for retry logic)
-    fn try_execute_batch(&self, operations: &[IndexOperation]) -> SqliteResult<()> {
-        // Use unchecked_transaction to work with immutable self
-        let tx = self.connection.unchecked_transaction()?;
-            
-            for operation in operations {
-                match operation {
-                    IndexOperation::Add(entry) | IndexOperation::Update(entry) => {
-                        let modified_time = system_time_to_timestamp(entry.modified_time);
-                        let indexed_time = system_time_to_timestamp(entry.indexed_time);
-                        
-                        tx.execute(
-                            "INSERT INTO files (filename, path, size, modified_time, file_type, indexed_time)
-                             VALUES (?, ?, ?, ?, ?, ?)
-                             ON CONFLICT(path) DO UPDATE SET
-                                filename = excluded.filename,
-                                size = excluded.size,
-                                modified_time = excluded.modified_time,
-                                file_type = excluded.file_type,
-                                indexed_time = excluded.indexed_time",
-                            params![
-                                entry.filename,
-                                entry.path.to_string_lossy().to_string(),
-                                entry.size as i64,
-                                modified_time,
-                                entry.file_type.as_str(),
-                                indexed_time,
-                            ],
-                        )?;
-                    }
-                    IndexOperation::Delete(path) => {
-                        tx.execute(
-                            "DELETE FROM files WHERE path = ?",
-                            params![path.to_string_lossy().to_string()],
-                        )?;
-                    }
-                    IndexOperation::Move { from, to } => {
-                        let filename = to
-                            .file_name()
-                            .and_then(|n| n.to_str())
-                            .unwrap_or("")
-                            .to_string();
-                        
-                        tx.execute(
-                            "UPDATE files SET path = ?, filename = ? WHERE path = ?",
-                            params![
-                                to.to_string_lossy().to_string(),
-                                filename,
-                                from.to_string_lossy().to_string(),
-                            ],
-                        )?;
-                    }
-                }
-            }
-            
-            tx.commit()?;
-            Ok(())
+    #[test]
+    fn test_path_scopes_match_normalized_directories() {
+        let db = Database::open_in_memory().unwrap();
+        // A directory created on macOS, with a decomposed "é"
+        let path = PathBuf::from("/home/user/Cafe\u{301}/menu.txt");
+        db.insert_file(&FileEntry::new(models::entry_name(&path), path, 1, SystemTime::now(), FileType::Regular)).unwrap();
+
+        let count = |scope: SearchScope| db.search("Caf\u{e9}", &SearchOptions { scope, ..SearchOptions::default() }).unwrap().len();
+        assert_eq!(count(SearchScope::Filename), 0);
+        assert_eq!(count(SearchScope::Path), 1);
+        assert_eq!(count(SearchScope::Both), 1);
     }
 
-    /// Execute an operation with exponential backoff retry logic
-    fn execute_with_retry<F, T>(&self, mut operation: F) -> SqliteResult<T>
-    where
-        F: FnMut() -> SqliteResult<T>,
-    {
-        let max_retries = 5;
-        let mut delay_ms = 100;
+    #[test]
+    fn test_query_files_types() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
         
-        for attempt in 0..max_retries {
-            match operation() {
-                Ok(result) => return Ok(result),
-                Err(rusqlite::Error::SqliteFailure(err, _)) 
-                    if err.code == rusqlite::ErrorCode::DatabaseBusy 
-                    || err.code == rusqlite::ErrorCode::DatabaseLocked => 
-                {
-                    if attempt < max_retries - 1 {
-                        std::thread::sleep(Duration::from_millis(delay_ms));
-                        delay_ms = (delay_ms * 2).min(1600); // Cap at 1600ms
-                    } else {
-                        return Err(rusqlite::Error::SqliteFailure(err, None));
-                    }
-                }
-                Err(e) => return Err(e),
-            }
+        let files = vec![
+            ("report.txt", FileType::Regular),
+            ("reports", FileType::Directory),
+            ("report-link", FileType::Symlink),
+            ("report.fifo", FileType::Other),
+        ];
+        
+        for (filename, file_type) in files {
+            db.insert_file(&FileEntry::new(
+                filename.to_string(),
+                PathBuf::from("/home/user").join(filename),
+                0,
+                SystemTime::now(),
+                file_type,
+            )).unwrap();
         }
         
-        unreachable!()
+        let results = db.query_files_types("report", &[FileType::Regular, FileType::Symlink], 10).unwrap();
+        let mut names: Vec<&str> = results.iter().map(|e| e.filename.as_str()).collect();
+        names.sort();
+        assert_eq!(names, vec!["report-link", "report.txt"]);
+        
+        let results = db.query_files_types("report", &[FileType::Directory], 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].file_type, FileType::Directory);
+        
+        // Empty slice means all types
+        let results = db.query_files_types("report", &[], 10).unwrap();
+        assert_eq!(results.len(), 4);
+        
+        let results = db.query_files_types("report", &[FileType::Regular, FileType::Directory, FileType::Symlink], 2).unwrap();
+        assert_eq!(results.len(), 2);
     }
 
-    /// Get the count of indexed files
-    pub fn count_files(&self) -> SqliteResult<i64> {
-        self.connection.query_row(
-            "SELECT COUNT(*) FROM files",
-            [],
-            |row| row.get(0),
-        )
+    #[test]
+    fn test_query_directories() {
+        let db = Database::open_in_memory().unwrap();
+
+        for (path, file_type) in [
+            ("/home/user/projects", FileType::Directory),
+            ("/home/user/old-projects", FileType::Directory),
+            ("/home/user/projects.txt", FileType::Regular),
+            ("/home/user/projects-link", FileType::Symlink),
+        ] {
+            let path = PathBuf::from(path);
+            db.insert_file(&FileEntry::new(models::entry_name(&path), path, 0, SystemTime::now(), file_type)).unwrap();
+        }
+        db.record_file_launch(Path::new("/home/user/old-projects")).unwrap();
+
+        let results = db.query_directories("projects", 10).unwrap();
+        let names: Vec<&str> = results.iter().map(|e| e.filename.as_str()).collect();
+        // The opened directory ranks ahead of the exact match
+        assert_eq!(names, vec!["old-projects", "projects"]);
+        assert!(results.iter().all(|e| e.file_type == FileType::Directory));
+
+        let plan: String = db.connection().query_row(
+            &format!("EXPLAIN QUERY PLAN {}", QUERY_DIRECTORIES_SQL),
+            params!["projects", "projects", "projects", 10],
+            |row| row.get(3),
+        ).unwrap();
+        assert!(plan.contains("idx_directory_filename"), "{}", plan);
     }
 
-    /// Record that a file was launched/opened
-    pub fn record_file_launch<P: AsRef<Path>>(&self, path: P) -> SqliteResult<()> {
-        let path_str = path.as_ref().to_string_lossy().to_string();
-        let current_time = current_timestamp();
+    #[test]
+    fn test_reconcile() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+        let mtime = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
         
-        // First, get the file ID
-        let file_id: Option<i64> = self.connection.query_row(
-            "SELECT id FROM files WHERE path = ?",
-            params![path_str],
-            |row| row.get(0),
-        ).optional()?;
+        let entry = |name: &str, size: u64| FileEntry::new(
+            name.to_string(),
+            PathBuf::from("/home/user").join(name),
+            size,
+            mtime,
+            FileType::Regular,
+        );
         
-        if let Some(file_id) = file_id {
-            // Insert or update usage stats
-            self.connection.execute(
-                "INSERT INTO usage_stats (file_id, launch_count, last_launched)
-                 VALUES (?, 1, ?)
-                 ON CONFLICT(file_id) DO UPDATE SET
-                    launch_count = launch_count + 1,
-                    last_launched = ?",
-                params![file_id, current_time, current_time],
-            )?;
-        }
+        db.insert_file(&entry("same.txt", 10)).unwrap();
+        db.insert_file(&entry("changed.txt", 10)).unwrap();
+        db.insert_file(&entry("gone.txt", 10)).unwrap();
+        db.insert_file(&FileEntry::new(
+            "elsewhere.txt".to_string(),
+            PathBuf::from("/mnt/other/elsewhere.txt"),
+            10,
+            mtime,
+            FileType::Regular,
+        )).unwrap();
         
-        Ok(())
+        let scanned = vec![entry("same.txt", 10), entry("changed.txt", 20), entry("new.txt", 5)];
+        let stats = db.reconcile(&scanned, &[PathBuf::from("/home/user")]).unwrap();
+        
+        assert_eq!(stats, ReconcileStats { updated: 2, deleted: 1 });
+        assert_eq!(db.count_files().unwrap(), 4);
+        assert!(db.query_files("gone", 10).unwrap().is_empty());
+        assert_eq!(db.query_files("changed", 10).unwrap()[0].size, 20);
+        assert_eq!(db.query_files("elsewhere", 10).unwrap().len(), 1);
     }
 
-    /// Get usage statistics for a file
-    pub fn get_file_usage<P: AsRef<Path>>(&self, path: P) -> SqliteResult<Option<(i32, i64)>> {
-        let path_str = path.as_ref().to_string_lossy().to_string();
+    #[test]
+    fn test_replace_all_refuses_implausible_scan() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+        let mtime = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
         
-        let result = self.connection.query_row(
-            "SELECT u.launch_count, u.last_launched
-             FROM files f
-             JOIN usage_stats u ON f.id = u.file_id
-             WHERE f.path = ?",
-            params![path_str],
-            |row| Ok((row.get::<_, i32>(0)?, row.get::<_, i64>(1)?)),
-        ).optional()?;
+        let entry = |name: &str| FileEntry::new(
+            name.to_string(),
+            PathBuf::from("/home/user").join(name),
+            10,
+            mtime,
+            FileType::Regular,
+        );
+        for i in 0..10 {
+            db.insert_file(&entry(&format!("file{}.txt", i))).unwrap();
+        }
         
-        Ok(result)
+        // A scan that found nothing keeps the existing index
+        let outcome = db.replace_all(&[], 0.5).unwrap();
+        assert_eq!(outcome, ReplaceOutcome::Refused { previous: 10, scanned: 0 });
+        assert_eq!(db.count_files().unwrap(), 10);
+        
+        // So does one well below the configured fraction
+        let outcome = db.replace_all(&[entry("a.txt"), entry("b.txt")], 0.5).unwrap();
+        assert_eq!(outcome, ReplaceOutcome::Refused { previous: 10, scanned: 2 });
+        assert_eq!(db.query_files("file", 20).unwrap().len(), 10);
+        
+        // A plausible scan replaces everything
+        let scanned: Vec<_> = (0..6).map(|i| entry(&format!("new{}.txt", i))).collect();
+        let outcome = db.replace_all(&scanned, 0.5).unwrap();
+        assert_eq!(outcome, ReplaceOutcome::Replaced { previous: 10, indexed: 6 });
+        assert!(db.query_files("file", 20).unwrap().is_empty());
+        
+        // Disabling the check allows clearing the index
+        let outcome = db.replace_all(&[], 0.0).unwrap();
+        assert_eq!(outcome, ReplaceOutcome::Replaced { previous: 6, indexed: 0 });
     }
 
-    /// Get most frequently used files
-    pub fn get_most_used_files(&self, limit: usize) -> SqliteResult<Vec<FileEntry>> {
-        let mut stmt = self.connection.prepare(
-            "SELECT f.id, f.filename, f.path, f.size, f.modified_time, f.file_type, f.indexed_time
-             FROM files f
-             JOIN usage_stats u ON f.id = u.file_id
-             ORDER BY u.launch_count DESC, u.last_launched DESC
-             LIMIT ?"
-        )?;
+    #[test]
+    fn test_apply_config() {
+        let mut db = Database::open_in_memory().unwrap();
+        db.insert_file(&FileEntry::new(
+            "notes.txt".to_string(),
+            PathBuf::from("/home/user/notes.txt"),
+            10,
+            SystemTime::now(),
+            FileType::Regular,
+        )).unwrap();
 
-        let entries = stmt.query_map(
-            params![limit as i64],
-            |row| {
-                Ok(FileEntry {
-                    id: Some(row.get(0)?),
-                    filename: row.get(1)?,
-                    path: PathBuf::from(row.get::<_, String>(2)?),
-                    size: row.get::<_, i64>(3)? as u64,
-                    modified_time: timestamp_to_system_time(row.get(4)?),
-                    file_type: FileType::from_str(&row.get::<_, String>(5)?),
-                    indexed_time: timestamp_to_system_time(row.get(6)?),
-                })
-            },
-        )?;
+        let mut config = Config::default();
+        config.indexing.intern_directories = true;
+        config.ui.min_query_length = 3;
+        db.apply_config(&config).unwrap();
 
-        entries.collect()
+        assert_eq!(db.path_storage(), PathStorage::Interned);
+        assert_eq!(db.min_query_length(), 3);
+        assert_eq!(db.max_path_length(), config.indexing.path_length_limit());
+        assert_eq!(db.query_files("notes", 10).unwrap().len(), 1);
+        assert!(db.query_files("no", 10).unwrap().is_empty());
     }
-}
-
-/// Get current Unix timestamp
-pub fn current_timestamp() -> i64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs() as i64
-}
 
-/// Convert SystemTime to Unix timestamp
-fn system_time_to_timestamp(time: SystemTime) -> i64 {
-    time.duration_since(UNIX_EPOCH)
-        .unwrap_or(Duration::from_secs(0))
-        .as_secs() as i64
-}
+    #[test]
+    fn test_aged_out_entries_survive_replace_all() {
+        let db = Database::open_in_memory().unwrap();
+        let cutoff = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let entry = |name: &str, modified: SystemTime| FileEntry::new(
+            name.to_string(),
+            PathBuf::from("/home/user").join(name),
+            10,
+            modified,
+            FileType::Regular,
+        );
+        let mut old = entry("old.txt", cutoff - Duration::from_secs(60));
+        old.xattrs = Some(vec![("user.tag".to_string(), "archive".to_string())]);
+        old.content = Some("quarterly figures".to_string());
+        db.execute_batch(&[
+            IndexOperation::Add(old),
+            IndexOperation::Add(entry("rescanned.txt", cutoff - Duration::from_secs(60))),
+            IndexOperation::Add(entry("deleted.txt", cutoff + Duration::from_secs(60))),
+        ]).unwrap();
 
-/// Convert Unix timestamp to SystemTime
-fn timestamp_to_system_time(timestamp: i64) -> SystemTime {
-    UNIX_EPOCH + Duration::from_secs(timestamp as u64)
-}
+        // Only old files the scan did not report are handed back
+        let mut entries = vec![entry("rescanned.txt", cutoff - Duration::from_secs(60))];
+        let kept = db.aged_out_entries(cutoff, &entries).unwrap();
+        assert_eq!(kept.iter().map(|e| e.filename.as_str()).collect::<Vec<_>>(), ["old.txt"]);
+        entries.extend(kept);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tempfile::NamedTempFile;
+        db.replace_all(&entries, 0.0).unwrap();
+        assert_eq!(db.count_files().unwrap(), 2);
+        assert!(db.get_entry_by_path("/home/user/deleted.txt").unwrap().is_none());
+        assert_eq!(db.get_xattrs("/home/user/old.txt").unwrap(), [("user.tag".to_string(), "archive".to_string())]);
+        assert_eq!(db.search_content("quarterly", 10).unwrap().len(), 1);
+    }
 
     #[test]
-    fn test_database_creation() {
+    fn test_query_with_stats() {
         let temp_file = NamedTempFile::new().unwrap();
-        let db = Database::open(temp_file.path()).unwrap();
+        let mut db = Database::open(temp_file.path()).unwrap();
         
-        // Verify files table exists
-        let table_exists: i32 = db.connection()
-            .query_row(
-                "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='files'",
-                [],
-                |row| row.get(0),
-            )
-            .unwrap();
-        assert_eq!(table_exists, 1);
+        for name in ["notes.txt", "notes-old.txt"] {
+            db.insert_file(&FileEntry::new(
+                name.to_string(),
+                PathBuf::from("/home/user").join(name),
+                1024,
+                SystemTime::now(),
+                FileType::Regular,
+            )).unwrap();
+        }
+        
+        for _ in 0..3 {
+            db.record_file_launch("/home/user/notes.txt").unwrap();
+        }
+        
+        let results = db.query_with_stats("notes", 10).unwrap();
+        assert_eq!(results.len(), 2);
+        
+        assert_eq!(results[0].entry.filename, "notes.txt");
+        assert_eq!(results[0].launch_count, 3);
+        assert!(results[0].last_launched.is_some());
+        
+        assert_eq!(results[1].entry.filename, "notes-old.txt");
+        assert_eq!(results[1].launch_count, 0);
+        assert!(results[1].last_launched.is_none());
 
-        // Verify metadata table exists
-        let table_exists: i32 = db.connection()
-            .query_row(
-                "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='metadata'",
-                [],
-                |row| row.get(0),
-            )
-            .unwrap();
-        assert_eq!(table_exists, 1);
+        // Exclusions and the minimum length apply as in query_files
+        let results = db.query_with_stats("notes -old", 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].entry.filename, "notes.txt");
+        db.set_min_query_length(6);
+        assert!(db.query_with_stats("notes", 10).unwrap().is_empty());
     }
 
     #[test]
-    fn test_schema_version() {
+    fn test_migrate_v2_usage_stats_unique() {
         let temp_file = NamedTempFile::new().unwrap();
+        {
+            let db = Database::open(temp_file.path()).unwrap();
+            db.insert_file(&FileEntry::new(
+                "app.desktop".to_string(),
+                PathBuf::from("/usr/share/applications/app.desktop"),
+                100,
+                SystemTime::now(),
+                FileType::Regular,
+            )).unwrap();
+            
+            // Recreate the version 2 layout: non-unique index with duplicate rows
+            db.connection().execute("DROP INDEX idx_usage_file_id", []).unwrap();
+            db.connection().execute("CREATE INDEX idx_usage_file_id ON usage_stats(file_id)", []).unwrap();
+            db.connection().execute("INSERT INTO usage_stats (file_id, launch_count) VALUES (1, 1)", []).unwrap();
+            db.connection().execute("INSERT INTO usage_stats (file_id, launch_count) VALUES (1, 4)", []).unwrap();
+            db.set_schema_version(2).unwrap();
+        }
+        
         let db = Database::open(temp_file.path()).unwrap();
+        assert_eq!(db.get_schema_version().unwrap(), SCHEMA_VERSION);
         
-        let version = db.get_schema_version().unwrap();
-        assert_eq!(version, SCHEMA_VERSION);
+        db.record_file_launch("/usr/share/applications/app.desktop").unwrap();
+        let (launch_count, _) = db.get_file_usage("/usr/share/applications/app.desktop").unwrap().unwrap();
+        assert_eq!(launch_count, 5);
     }
 
     #[test]
-    fn test_indexes_created() {
+    fn test_search_order_modes() {
         let temp_file = NamedTempFile::new().unwrap();
         let db = Database::open(temp_file.path()).unwrap();
+        let base = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
         
-        // Check for filename index
-        let index_exists: i32 = db.connection()
-            .query_row(
-                "SELECT COUNT(*) FROM sqlite_master WHERE type='index' AND name='idx_filename'",
-                [],
-                |row| row.get(0),
-            )
-            .unwrap();
-        assert_eq!(index_exists, 1);
-
-        // Check for path index
-        let index_exists: i32 = db.connection()
-            .query_row(
-                "SELECT COUNT(*) FROM sqlite_master WHERE type='index' AND name='idx_path'",
-                [],
-                |row| row.get(0),
-            )
-            .unwrap();
-        assert_eq!(index_exists, 1);
-
-        // Check for modified_time index
-        let index_exists: i32 = db.connection()
-            .query_row(
-                "SELECT COUNT(*) FROM sqlite_master WHERE type='index' AND name='idx_modified_time'",
-                [],
-                |row| row.get(0),
-            )
-            .unwrap();
-        assert_eq!(index_exists, 1);
+        // (path, size, age in seconds)
+        for (path, size, age) in [
+            ("/home/user/b/report-final.txt", 300, 10),
+            ("/home/user/a/report.txt", 100, 30),
+            ("/home/user/c/Report-draft.txt", 200, 20),
+            ("/home/user/a/old-report.txt", 400, 0),
+        ] {
+            let path = PathBuf::from(path);
+            db.insert_file(&FileEntry::new(
+                path.file_name().unwrap().to_string_lossy().to_string(),
+                path.clone(),
+                size,
+                base + Duration::from_secs(age),
+                FileType::Regular,
+            )).unwrap();
+        }
+        db.record_file_launch("/home/user/b/report-final.txt").unwrap();
+        
+        let search = |order_by: SearchOrder| -> Vec<String> {
+            let options = SearchOptions { order_by, ..SearchOptions::default() };
+            db.search("report", &options)
+                .unwrap()
+                .into_iter()
+                .map(|e| e.path.to_string_lossy().into_owned())
+                .collect()
+        };
+        
+        assert_eq!(search(SearchOrder::Relevance), vec![
+            "/home/user/b/report-final.txt",
+            "/home/user/c/Report-draft.txt",
+            "/home/user/a/report.txt",
+            "/home/user/a/old-report.txt",
+        ]);
+        assert_eq!(search(SearchOrder::ModifiedDesc), vec![
+            "/home/user/a/report.txt",
+            "/home/user/c/Report-draft.txt",
+            "/home/user/b/report-final.txt",
+            "/home/user/a/old-report.txt",
+        ]);
+        assert_eq!(search(SearchOrder::SizeDesc), vec![
+            "/home/user/a/old-report.txt",
+            "/home/user/b/report-final.txt",
+            "/home/user/c/Report-draft.txt",
+            "/home/user/a/report.txt",
+        ]);
+        assert_eq!(search(SearchOrder::NameAsc), vec![
+            "/home/user/a/old-report.txt",
+            "/home/user/c/Report-draft.txt",
+            "/home/user/b/report-final.txt",
+            "/home/user/a/report.txt",
+        ]);
+        assert_eq!(search(SearchOrder::PathAsc), vec![
+            "/home/user/a/old-report.txt",
+            "/home/user/a/report.txt",
+            "/home/user/b/report-final.txt",
+            "/home/user/c/Report-draft.txt",
+        ]);
+        
+        assert_eq!("size".parse::<SearchOrder>(), Ok(SearchOrder::SizeDesc));
+        assert!("largest".parse::<SearchOrder>().is_err());
     }
 
     #[test]
-    fn test_reopen_existing_database() {
+    fn test_search_name_with_extension_set() {
         let temp_file = NamedTempFile::new().unwrap();
-        let path = temp_file.path().to_path_buf();
+        let db = Database::open(temp_file.path()).unwrap();
         
-        // Create database
-        {
-            let _db = Database::open(&path).unwrap();
+        for name in [
+            "config.toml",
+            "config.YAML",
+            "app-config.json",
+            "config.rs",
+            "config.toml.bak",
+            "settings.toml",
+            "settings.json",
+        ] {
+            db.insert_file(&FileEntry::new(
+                name.to_string(),
+                PathBuf::from("/home/user/project").join(name),
+                10,
+                SystemTime::now(),
+                FileType::Regular,
+            )).unwrap();
         }
         
-        // Reopen database
-        let db = Database::open(&path).unwrap();
-        let version = db.get_schema_version().unwrap();
-        assert_eq!(version, SCHEMA_VERSION);
+        let search = |query: &str, extensions: &[&str]| -> Vec<String> {
+            let options = SearchOptions {
+                extensions: extensions.iter().map(|e| e.to_string()).collect(),
+                ..SearchOptions::default()
+            };
+            db.search(query, &options).unwrap().into_iter().map(|e| e.filename).collect()
+        };
+        
+        // Intersection, ranked: prefix matches before the substring match
+        assert_eq!(search("config", &["toml", ".yaml", "json"]), vec![
+            "config.toml",
+            "config.YAML",
+            "app-config.json",
+        ]);
+        
+        // Neither half alone is enough
+        assert_eq!(search("config", &[]).len(), 5);
+        assert_eq!(search("", &["toml", "yaml", "json"]).len(), 5);
+        
+        // Wildcards in an extension are literal
+        assert!(search("config", &["t_ml"]).is_empty());
     }
 
     #[test]
-    fn test_insert_file() {
+    fn test_search_exclude_prefixes() {
         let temp_file = NamedTempFile::new().unwrap();
         let db = Database::open(temp_file.path()).unwrap();
         
-        let entry = FileEntry::new(
-            "test.txt".to_string(),
-            PathBuf::from("/home/user/test.txt"),
-            1024,
-            SystemTime::now(),
-            FileType::Regular,
-        );
+        for path in [
+            "/usr/share/doc/readme.txt",
+            "/usr/readme.txt",
+            "/usrlocal/readme.txt",
+            "/home/user/readme.txt",
+            "/home/user/USR/readme.txt",
+        ] {
+            let path = PathBuf::from(path);
+            db.insert_file(&FileEntry::new(
+                "readme.txt".to_string(),
+                path,
+                10,
+                SystemTime::now(),
+                FileType::Regular,
+            )).unwrap();
+        }
         
-        let id = db.insert_file(&entry).unwrap();
-        assert!(id > 0);
+        let options = SearchOptions {
+            exclude_prefixes: vec![PathBuf::from("/usr/")],
+            ..SearchOptions::default()
+        };
+        let results = db.search("readme", &options).unwrap();
+        let mut paths: Vec<PathBuf> = results.into_iter().map(|e| e.path).collect();
+        paths.sort();
         
-        let count = db.count_files().unwrap();
-        assert_eq!(count, 1);
+        assert_eq!(paths, vec![
+            PathBuf::from("/home/user/USR/readme.txt"),
+            PathBuf::from("/home/user/readme.txt"),
+            PathBuf::from("/usrlocal/readme.txt"),
+        ]);
+        
+        // Combined with a type filter and multiple prefixes
+        let options = SearchOptions {
+            file_types: vec![FileType::Regular],
+            exclude_prefixes: vec![PathBuf::from("/usr"), PathBuf::from("/home/user")],
+            ..SearchOptions::default()
+        };
+        let results = db.search("readme", &options).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].path, PathBuf::from("/usrlocal/readme.txt"));
     }
 
     #[test]
-    fn test_update_file() {
+    fn test_subsecond_modified_time() {
         let temp_file = NamedTempFile::new().unwrap();
         let db = Database::open(temp_file.path()).unwrap();
+        let second = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
         
-        let entry = FileEntry::new(
-            "test.txt".to_string(),
-            PathBuf::from("/home/user/test.txt"),
-            1024,
-            SystemTime::now(),
+        let mut entry = FileEntry::new(
+            "log.txt".to_string(),
+            PathBuf::from("/home/user/log.txt"),
+            10,
+            second + Duration::from_millis(100),
             FileType::Regular,
         );
-        
         db.insert_file(&entry).unwrap();
         
-        let updated_entry = FileEntry::new(
-            "test.txt".to_string(),
-            PathBuf::from("/home/user/test.txt"),
-            2048,
-            SystemTime::now(),
-            FileType::Regular,
-        );
+        // A second write within the same wall-clock second
+        entry.modified_time = second + Duration::from_millis(600);
+        let stats = db.reconcile(std::slice::from_ref(&entry), &[PathBuf::from("/home/user")]).unwrap();
+        assert_eq!(stats.updated, 1);
         
-        db.update_file(&updated_entry).unwrap();
+        let (seconds, nanos): (i64, i64) = db.connection().query_row(
+            "SELECT modified_time, modified_nsec FROM files WHERE path = '/home/user/log.txt'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).unwrap();
+        assert_eq!(seconds, 1_700_000_000);
+        assert_eq!(nanos, 600_000_000);
         
-        let count = db.count_files().unwrap();
-        assert_eq!(count, 1);
+        let results = db.query_files("log", 10).unwrap();
+        assert_eq!(results[0].modified_time, entry.modified_time);
         
-        let results = db.query_files("test", 10).unwrap();
-        assert_eq!(results.len(), 1);
-        assert_eq!(results[0].size, 2048);
+        // Unchanged entries are not rewritten
+        let stats = db.reconcile(std::slice::from_ref(&entry), &[PathBuf::from("/home/user")]).unwrap();
+        assert_eq!(stats.updated, 0);
     }
 
     #[test]
-    fn test_delete_file() {
+    fn test_migrate_v3_adds_modified_nsec() {
         let temp_file = NamedTempFile::new().unwrap();
+        {
+            let db = Database::open(temp_file.path()).unwrap();
+            db.insert_file(&FileEntry::new(
+                "old.txt".to_string(),
+                PathBuf::from("/home/user/old.txt"),
+                10,
+                UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+                FileType::Regular,
+            )).unwrap();
+            db.connection().execute("ALTER TABLE files DROP COLUMN modified_nsec", []).unwrap();
+            db.set_schema_version(3).unwrap();
+        }
+        
         let db = Database::open(temp_file.path()).unwrap();
+        assert_eq!(db.get_schema_version().unwrap(), SCHEMA_VERSION);
         
-        let entry = FileEntry::new(
-            "test.txt".to_string(),
-            PathBuf::from("/home/user/test.txt"),
-            1024,
-            SystemTime::now(),
-            FileType::Regular,
+        let results = db.query_files("old", 10).unwrap();
+        assert_eq!(results[0].modified_time, UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+    }
+
+    #[test]
+    fn test_enabling_ownership_fills_existing_entries() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db = Database::open_in_memory().unwrap();
+        let present = temp_dir.path().join("present.txt");
+        let gone = temp_dir.path().join("gone.txt");
+        std::fs::write(&present, "content").unwrap();
+        for path in [&present, &gone] {
+            db.insert_file(&FileEntry::new(models::entry_name(path), path.clone(), 7, SystemTime::now(), FileType::Regular)).unwrap();
+        }
+        let owned = |db: &Database| {
+            let (uid, _) = models::ownership(&std::fs::metadata(&present).unwrap());
+            db.query_by_owner(uid, "", 10).unwrap().len()
+        };
+        assert_eq!(owned(&db), 0);
+
+        assert_eq!(db.set_index_ownership(true).unwrap(), 1);
+        assert_eq!(owned(&db), 1);
+        assert_eq!(db.set_index_ownership(true).unwrap(), 0);
+
+        // Entries indexed while it was off are filled in when it is turned back on
+        db.set_index_ownership(false).unwrap();
+        db.delete_file(present.to_str().unwrap()).unwrap();
+        db.insert_file(&FileEntry::new(models::entry_name(&present), present.clone(), 7, SystemTime::now(), FileType::Regular)).unwrap();
+        assert_eq!(owned(&db), 0);
+        assert_eq!(db.set_index_ownership(true).unwrap(), 1);
+        assert_eq!(owned(&db), 1);
+    }
+
+    #[test]
+    fn test_migrate_interned_adds_owner_columns() {
+        let temp_file = NamedTempFile::new().unwrap();
+        {
+            let mut db = Database::open(temp_file.path()).unwrap();
+            db.set_path_storage(PathStorage::Interned).unwrap();
+            db.insert_file(&FileEntry::new(
+                "old.txt".to_string(),
+                PathBuf::from("/home/user/old.txt"),
+                10,
+                SystemTime::now(),
+                FileType::Regular,
+            )).unwrap();
+            db.connection().execute_batch(
+                "DROP VIEW files;
+                 DROP TABLE files_fts;
+                 DROP TRIGGER trg_file_rows_insert_fts;
+                 DROP TRIGGER trg_file_rows_delete_fts;
+                 DROP TRIGGER trg_file_rows_update_fts;
+                 DROP INDEX idx_file_rows_uid;
+                 ALTER TABLE file_rows DROP COLUMN uid;
+                 ALTER TABLE file_rows DROP COLUMN gid;
+                 CREATE VIEW files AS
+                    SELECT f.id, f.filename, d.path || '/' || f.filename AS path, f.size, f.modified_time,
+                           f.file_type, f.indexed_time, f.modified_nsec, f.is_executable, f.repo_root,
+                           f.normalized_name
+                    FROM file_rows f
+                    JOIN directories d ON d.id = f.dir_id;",
+            ).unwrap();
+            // Version 11 recreates the view, which version 12 reads to fill the name
+            // index, before version 14 adds the columns
+            db.set_schema_version(11).unwrap();
+        }
+
+        let db = Database::open(temp_file.path()).unwrap();
+        assert_eq!(db.get_schema_version().unwrap(), SCHEMA_VERSION);
+        assert_eq!(db.query_files("old", 10).unwrap().len(), 1);
+        assert_eq!(db.query_files_fts("old", 10).unwrap().len(), 1);
+        assert!(db.query_by_owner(1000, "", 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_interned_paths_without_last_component() {
+        let mut db = Database::open_in_memory().unwrap();
+        db.set_path_storage(PathStorage::Interned).unwrap();
+        let entry = |path: &str| {
+            let path = PathBuf::from(path);
+            FileEntry::new(models::entry_name(&path), path, 4096, SystemTime::now(), FileType::Directory)
+        };
+        let paths = ["/", "/home/user/.."];
+        db.execute_batch(&paths.map(|path| IndexOperation::Add(entry(path)))).unwrap();
+        let check = |db: &Database| {
+            for path in paths {
+                let stored = db.get_entry_by_path(path).unwrap().unwrap();
+                assert_eq!(stored.path, PathBuf::from(path));
+                assert_eq!(stored.filename, path);
+            }
+        };
+        check(&db);
+
+        // The rows are found again by updates, conversions and deletes
+        let result = db.execute_batch(&paths.map(|path| IndexOperation::Update(entry(path)))).unwrap();
+        assert_eq!(result.updated, 2);
+        assert_eq!(db.count_files().unwrap(), 2);
+        db.set_path_storage(PathStorage::Full).unwrap();
+        check(&db);
+        db.set_path_storage(PathStorage::Interned).unwrap();
+        check(&db);
+        db.execute_batch(&paths.map(|path| IndexOperation::Delete(PathBuf::from(path)))).unwrap();
+        assert_eq!(db.count_files().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_migrate_interned_rows_stored_under_whole_path() {
+        let temp_file = NamedTempFile::new().unwrap();
+        {
+            let mut db = Database::open(temp_file.path()).unwrap();
+            db.set_path_storage(PathStorage::Interned).unwrap();
+            db.insert_file(&FileEntry::new(
+                "/".to_string(),
+                PathBuf::from("/"),
+                4096,
+                SystemTime::now(),
+                FileType::Directory,
+            )).unwrap();
+            // How version 14 stored and viewed it
+            db.connection().execute_batch(
+                "UPDATE file_rows SET filename = '/';
+                 DROP VIEW files;
+                 CREATE VIEW files AS
+                    SELECT f.id, f.filename, d.path || '/' || f.filename AS path, f.size, f.modified_time,
+                           f.file_type, f.indexed_time, f.modified_nsec, f.is_executable, f.repo_root,
+                           f.normalized_name, f.uid, f.gid
+                    FROM file_rows f
+                    JOIN directories d ON d.id = f.dir_id;",
+            ).unwrap();
+            db.set_schema_version(14).unwrap();
+            assert!(db.get_entry_by_path("/").unwrap().is_none());
+        }
+
+        let db = Database::open(temp_file.path()).unwrap();
+        assert_eq!(db.get_schema_version().unwrap(), SCHEMA_VERSION);
+        assert_eq!(db.get_entry_by_path("/").unwrap().unwrap().filename, "/");
+        db.delete_file("/").unwrap();
+        assert_eq!(db.count_files().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_cached_statements_repeated_queries() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+        
+        for i in 0..20 {
+            db.insert_file(&FileEntry::new(
+                format!("report{}.pdf", i),
+                PathBuf::from(format!("/home/user/report{}.pdf", i)),
+                100,
+                SystemTime::now(),
+                FileType::Regular,
+            )).unwrap();
+        }
+        
+        // The same cached statements must see data changed between executions
+        for round in 0..5 {
+            let results = db.query_files("report1", 50).unwrap();
+            assert_eq!(results.len(), 11 - round.min(1));
+            assert_eq!(results[0].filename, "report1.pdf");
+            
+            let options = SearchOptions { limit: 3, ..SearchOptions::default() };
+            assert_eq!(db.search("report", &options).unwrap().len(), 3);
+            
+            if round == 0 {
+                db.delete_file("/home/user/report10.pdf").unwrap();
+            }
+        }
+        
+        for _ in 0..3 {
+            db.record_file_launch("/home/user/report3.pdf").unwrap();
+        }
+        assert_eq!(db.get_file_usage("/home/user/report3.pdf").unwrap().unwrap().0, 3);
+        assert_eq!(db.get_most_used_files(5).unwrap()[0].filename, "report3.pdf");
+    }
+
+    #[test]
+    fn test_foreign_keys_follow_path_storage() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let foreign_keys = |db: &Database| -> i64 {
+            db.connection().query_row("PRAGMA foreign_keys", [], |row| row.get(0)).unwrap()
+        };
+        {
+            let mut db = Database::open(temp_file.path()).unwrap();
+            assert_eq!(foreign_keys(&db), 1);
+            db.set_path_storage(PathStorage::Interned).unwrap();
+            assert_eq!(foreign_keys(&db), 0);
+        }
+
+        // A reopened index gets the same setting as a converted one
+        let mut db = Database::open(temp_file.path()).unwrap();
+        assert_eq!(foreign_keys(&db), 0);
+        db.set_path_storage(PathStorage::Full).unwrap();
+        drop(db);
+        let db = Database::open(temp_file.path()).unwrap();
+        assert_eq!(foreign_keys(&db), 1);
+    }
+
+    #[test]
+    fn test_interned_paths_reconstructed() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let mut db = Database::open(temp_file.path()).unwrap();
+        
+        let entry = |path: &str| {
+            let path = PathBuf::from(path);
+            let filename = path.file_name().unwrap().to_string_lossy().into_owned();
+            FileEntry::new(filename, path, 100, SystemTime::now(), FileType::Regular)
+        };
+        let mut tagged = entry("/home/user/docs/plan.odt");
+        tagged.xattrs = Some(vec![("user.tags".to_string(), "work".to_string())]);
+        db.execute_batch(&[
+            IndexOperation::Add(entry("/home/user/docs/report.pdf")),
+            IndexOperation::Add(entry("/home/user/docs/notes.txt")),
+            IndexOperation::Add(entry("/home/user/report-draft.txt")),
+            IndexOperation::Add(tagged),
+            IndexOperation::Add(entry("/top.txt")),
+        ]).unwrap();
+        db.record_file_launch("/home/user/docs/report.pdf").unwrap();
+        let report_id = db.query_files("report.pdf", 1).unwrap()[0].id;
+        
+        db.set_path_storage(PathStorage::Interned).unwrap();
+        assert_eq!(db.path_storage(), PathStorage::Interned);
+        let directories: i64 = db.connection()
+            .query_row("SELECT COUNT(*) FROM directories", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(directories, 3);
+        
+        let paths = |entries: Vec<FileEntry>| -> Vec<String> {
+            entries.into_iter().map(|e| e.path.to_string_lossy().into_owned()).collect()
+        };
+        let results = db.query_files("report", 10).unwrap();
+        assert_eq!(results[0].id, report_id);
+        assert_eq!(paths(results), vec!["/home/user/docs/report.pdf", "/home/user/report-draft.txt"]);
+        assert_eq!(paths(db.query_files("top", 10).unwrap()), vec!["/top.txt"]);
+        let options = SearchOptions {
+            exclude_prefixes: vec![PathBuf::from("/home/user/docs")],
+            ..SearchOptions::default()
+        };
+        assert_eq!(paths(db.search("report", &options).unwrap()), vec!["/home/user/report-draft.txt"]);
+        db.record_file_launch("/home/user/docs/report.pdf").unwrap();
+        assert_eq!(db.get_file_usage("/home/user/docs/report.pdf").unwrap().unwrap().0, 2);
+        assert_eq!(
+            paths(db.query_by_xattr("user.tags", "work").unwrap()),
+            vec!["/home/user/docs/plan.odt"],
         );
         
-        db.insert_file(&entry).unwrap();
-        assert_eq!(db.count_files().unwrap(), 1);
+        // Writes go to the interned rows, creating directories as needed
+        db.execute_batch(&[
+            IndexOperation::Move {
+                from: PathBuf::from("/home/user/docs/notes.txt"),
+                to: PathBuf::from("/home/user/archive/notes-2024.txt"),
+            },
+            IndexOperation::Delete(PathBuf::from("/home/user/report-draft.txt")),
+            IndexOperation::Update(entry("/home/user/docs/report.pdf")),
+        ]).unwrap();
+        assert_eq!(paths(db.query_files("notes", 10).unwrap()), vec!["/home/user/archive/notes-2024.txt"]);
+        assert_eq!(paths(db.query_files("report", 10).unwrap()), vec!["/home/user/docs/report.pdf"]);
+        assert_eq!(db.count_files().unwrap(), 4);
         
-        db.delete_file(&entry.path).unwrap();
-        assert_eq!(db.count_files().unwrap(), 0);
+        // The layout is detected on open and can be converted back
+        drop(db);
+        let mut db = Database::open(temp_file.path()).unwrap();
+        assert_eq!(db.path_storage(), PathStorage::Interned);
+        db.set_path_storage(PathStorage::Full).unwrap();
+        // The launched file ranks first, so its usage statistics survived
+        assert_eq!(paths(db.query_files("o", 10).unwrap()), vec![
+            "/home/user/docs/report.pdf",
+            "/home/user/archive/notes-2024.txt",
+            "/home/user/docs/plan.odt",
+            "/top.txt",
+        ]);
+        assert_eq!(db.query_files("report.pdf", 1).unwrap()[0].id, report_id);
+        assert_eq!(db.get_xattrs("/home/user/docs/plan.odt").unwrap().len(), 1);
     }
 
     #[test]
-    fn test_move_file() {
+    fn test_xattrs_replace_query_and_cleanup() {
         let temp_file = NamedTempFile::new().unwrap();
         let db = Database::open(temp_file.path()).unwrap();
         
-        let entry = FileEntry::new(
-            "test.txt".to_string(),
-            PathBuf::from("/home/user/test.txt"),
-            1024,
+        let mut tagged = FileEntry::new(
+            "plan.odt".to_string(),
+            PathBuf::from("/home/user/plan.odt"),
+            100,
             SystemTime::now(),
             FileType::Regular,
         );
+        tagged.xattrs = Some(vec![("user.tags".to_string(), "work".to_string())]);
+        db.insert_file(&tagged).unwrap();
         
-        db.insert_file(&entry).unwrap();
+        let results = db.query_by_xattr("user.tags", "work").unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].path, PathBuf::from("/home/user/plan.odt"));
         
-        let new_path = PathBuf::from("/home/user/documents/test.txt");
-        db.move_file(&entry.path, &new_path).unwrap();
+        // An update that did not read attributes leaves them alone
+        tagged.xattrs = None;
+        db.update_file(&tagged).unwrap();
+        assert_eq!(db.query_by_xattr("user.tags", "work").unwrap().len(), 1);
         
-        let results = db.query_files("test", 10).unwrap();
-        assert_eq!(results.len(), 1);
-        assert_eq!(results[0].path, new_path);
-        assert_eq!(results[0].filename, "test.txt");
+        // An update with attributes replaces them
+        tagged.xattrs = Some(vec![("user.tags".to_string(), "archive".to_string())]);
+        db.update_file(&tagged).unwrap();
+        assert!(db.query_by_xattr("user.tags", "work").unwrap().is_empty());
+        assert_eq!(
+            db.get_xattrs("/home/user/plan.odt").unwrap(),
+            vec![("user.tags".to_string(), "archive".to_string())]
+        );
+        
+        db.delete_file("/home/user/plan.odt").unwrap();
+        let remaining: i64 = db.connection()
+            .query_row("SELECT COUNT(*) FROM xattrs", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(remaining, 0);
     }
 
     #[test]
-    fn test_query_files() {
+    fn test_search_ranked_match_kinds() {
         let temp_file = NamedTempFile::new().unwrap();
         let db = Database::open(temp_file.path()).unwrap();
         
-        // Insert multiple files
-        let files = vec![
-            ("test.txt", "/home/user/test.txt"),
-            ("testing.txt", "/home/user/testing.txt"),
-            ("document.txt", "/home/user/document.txt"),
-            ("test_file.txt", "/home/user/test_file.txt"),
-        ];
-        
-        for (filename, path) in files {
-            let entry = FileEntry::new(
-                filename.to_string(),
-                PathBuf::from(path),
-                1024,
+        for name in ["notes", "notes.txt", "old-notes.txt"] {
+            db.insert_file(&FileEntry::new(
+                name.to_string(),
+                PathBuf::from("/home/user").join(name),
+                10,
                 SystemTime::now(),
                 FileType::Regular,
-            );
-            db.insert_file(&entry).unwrap();
+            )).unwrap();
         }
+        db.record_file_launch("/home/user/old-notes.txt").unwrap();
         
-        // Query for "test"
-        let results = db.query_files("test", 10).unwrap();
-        assert_eq!(results.len(), 3);
+        let results = db.search_ranked("notes", &SearchOptions::default()).unwrap();
+        let kinds: Vec<(&str, MatchKind)> = results
+            .iter()
+            .map(|r| (r.entry.filename.as_str(), r.match_kind))
+            .collect();
+        assert_eq!(kinds, vec![
+            ("notes", MatchKind::Exact),
+            ("notes.txt", MatchKind::Prefix),
+            ("old-notes.txt", MatchKind::Substring),
+        ]);
         
-        // Verify ranking: exact match first
-        assert_eq!(results[0].filename, "test.txt");
+        // Scores follow the result order
+        assert!(results.windows(2).all(|w| w[0].score > w[1].score));
         
-        // Query with limit
-        let results = db.query_files("test", 2).unwrap();
-        assert_eq!(results.len(), 2);
+        // Within a tier, launches raise the score
+        let substring = db.search_ranked("otes", &SearchOptions::default()).unwrap();
+        assert_eq!(substring[0].entry.filename, "old-notes.txt");
+        assert!(substring.iter().all(|r| r.match_kind == MatchKind::Substring));
+        assert!(substring[0].score > substring[1].score);
+    }
+
+    #[test]
+    fn test_execute_batch_reports_changes() {
+        let entry = |name: &str| FileEntry::new(
+            name.to_string(),
+            PathBuf::from("/home/user").join(name),
+            1,
+            SystemTime::now(),
+            FileType::Regular,
+        );
+
+        for storage in [PathStorage::Full, PathStorage::Interned] {
+            let mut db = Database::open_in_memory().unwrap();
+            db.set_path_storage(storage).unwrap();
+            db.execute_batch(&[IndexOperation::Add(entry("a.txt")), IndexOperation::Add(entry("b.txt"))]).unwrap();
+
+            let result = db.execute_batch(&[
+                IndexOperation::Add(entry("c.txt")),
+                IndexOperation::Add(entry("a.txt")),
+                IndexOperation::Update(entry("c.txt")),
+                IndexOperation::Delete(PathBuf::from("/home/user/b.txt")),
+                IndexOperation::Delete(PathBuf::from("/home/user/never-indexed.txt")),
+                IndexOperation::Move { from: PathBuf::from("/home/user/a.txt"), to: PathBuf::from("/home/user/d.txt") },
+                IndexOperation::UpdatePermissions { path: PathBuf::from("/home/user/d.txt"), is_executable: true },
+            ]).unwrap();
+
+            assert_eq!(result, BatchResult { added: 1, updated: 3, deleted: 1, moved: 1 }, "{:?}", storage);
+            assert_eq!(db.count_files().unwrap(), 2);
+        }
     }
 
     #[test]
@@ -816,4 +5211,37 @@ mod tests {
         let results = db.query_files("file2", 10).unwrap();
         assert_eq!(results.len(), 1);
     }
+
+    #[test]
+    fn test_batch_larger_than_parameter_limit() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+        
+        // More rows than fit even under SQLite's raised limit of 32766 parameters
+        let row_count = 32766 / UPSERT_COLUMNS + 500;
+        let entry = |i: usize, size: u64| FileEntry::new(
+            format!("file{}.txt", i),
+            PathBuf::from(format!("/home/user/bulk/file{}.txt", i)),
+            size,
+            SystemTime::now(),
+            FileType::Regular,
+        );
+        
+        let mut operations: Vec<IndexOperation> = (0..row_count)
+            .map(|i| IndexOperation::Add(entry(i, 1)))
+            .collect();
+        // Order is kept across chunks and around other operations
+        operations.push(IndexOperation::Delete(PathBuf::from("/home/user/bulk/file0.txt")));
+        operations.push(IndexOperation::Update(entry(1, 2)));
+        operations.push(IndexOperation::Update(entry(1, 3)));
+        
+        db.execute_batch(&operations).unwrap();
+        
+        assert_eq!(db.count_files().unwrap(), row_count as i64 - 1);
+        assert!(db.query_files("file0.txt", 10).unwrap().is_empty());
+        let results = db.query_files("file1.txt", 10).unwrap();
+        assert_eq!(results[0].size, 3);
+        let last = format!("file{}.txt", row_count - 1);
+        assert_eq!(db.query_files(&last, 10).unwrap().len(), 1);
+    }
 }
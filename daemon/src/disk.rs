@@ -0,0 +1,145 @@
+use std::ffi::CString;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+/// Get the space available to unprivileged users on the filesystem holding `path`
+pub fn free_space_bytes(path: &Path) -> io::Result<u64> {
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: c_path is a valid NUL-terminated string and stats is a writable statvfs
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stats) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    #[allow(clippy::unnecessary_cast)]
+    Ok(stats.f_bavail as u64 * stats.f_frsize as u64)
+}
+
+/// Pauses index writes while the database's filesystem is nearly full
+///
+/// Warnings are logged only when the state changes, so calling `check` on
+/// every flush does not flood the log. That includes free space becoming
+/// impossible to read and readable again.
+pub struct DiskSpaceGuard {
+    path: PathBuf,
+    min_free_bytes: u64,
+    paused: bool,
+    /// The last reading of free space failed
+    unreadable: bool,
+}
+
+impl DiskSpaceGuard {
+    /// Create a guard for the filesystem holding `path`; a threshold of 0 disables it
+    pub fn new<P: AsRef<Path>>(path: P, min_free_mb: u64) -> Self {
+        DiskSpaceGuard {
+            path: path.as_ref().to_path_buf(),
+            min_free_bytes: min_free_mb * 1024 * 1024,
+            paused: false,
+            unreadable: false,
+        }
+    }
+
+    /// Re-check free space and report whether indexing may write to the database
+    pub fn check(&mut self) -> bool {
+        if self.min_free_bytes == 0 {
+            return true;
+        }
+
+        match free_space_bytes(&self.path) {
+            Ok(free_bytes) => {
+                if self.unreadable {
+                    println!("Free disk space for {} can be checked again", self.path.display());
+                    self.unreadable = false;
+                }
+                self.update(free_bytes)
+            }
+            Err(e) => {
+                // Don't stop indexing just because the query itself failed
+                if !self.unreadable {
+                    eprintln!("Warning: Cannot check free disk space for {}: {}", self.path.display(), e);
+                    self.unreadable = true;
+                }
+                true
+            }
+        }
+    }
+
+    /// Check whether indexing is currently paused for lack of space
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Apply a free-space reading, logging pause/resume transitions
+    fn update(&mut self, free_bytes: u64) -> bool {
+        let low = free_bytes < self.min_free_bytes;
+
+        if low && !self.paused {
+            eprintln!(
+                "Warning: Only {} MB free on the filesystem holding {} (minimum {} MB); pausing indexing",
+                free_bytes / (1024 * 1024),
+                self.path.display(),
+                self.min_free_bytes / (1024 * 1024)
+            );
+        } else if !low && self.paused {
+            println!("Free disk space recovered; resuming indexing");
+        }
+
+        self.paused = low;
+        !low
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MB: u64 = 1024 * 1024;
+
+    #[test]
+    fn test_free_space_bytes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        assert!(free_space_bytes(temp_dir.path()).unwrap() > 0);
+        assert!(free_space_bytes(Path::new("/nonexistent/path/for/statvfs")).is_err());
+    }
+
+    #[test]
+    fn test_guard_pauses_and_resumes() {
+        let mut guard = DiskSpaceGuard::new("/", 100);
+
+        assert!(guard.update(500 * MB));
+        assert!(!guard.is_paused());
+
+        assert!(!guard.update(99 * MB));
+        assert!(guard.is_paused());
+        assert!(!guard.update(50 * MB));
+
+        assert!(guard.update(100 * MB));
+        assert!(!guard.is_paused());
+    }
+
+    #[test]
+    fn test_guard_keeps_indexing_when_space_is_unreadable() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("data");
+        let mut guard = DiskSpaceGuard::new(&path, 100);
+
+        // The failure is logged on the first check only
+        assert!(guard.check());
+        assert!(guard.unreadable);
+        assert!(guard.check());
+
+        std::fs::create_dir(&path).unwrap();
+        guard.check();
+        assert!(!guard.unreadable);
+    }
+
+    #[test]
+    fn test_guard_disabled_with_zero_threshold() {
+        let mut guard = DiskSpaceGuard::new("/nonexistent/path/for/statvfs", 0);
+        assert!(guard.check());
+        assert!(!guard.is_paused());
+    }
+}
@@ -0,0 +1,170 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How paths are rendered for output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PathDisplayMode {
+    /// Replace invalid UTF-8 with U+FFFD (human-friendly, not reversible)
+    #[default]
+    Lossy,
+    /// Percent-encode invalid bytes and literal `%` (reversible, always valid UTF-8)
+    Lossless,
+}
+
+/// Convert a path to a printable string using the given mode
+pub fn display_path(path: &Path, mode: PathDisplayMode) -> String {
+    match mode {
+        PathDisplayMode::Lossy => path.to_string_lossy().into_owned(),
+        PathDisplayMode::Lossless => percent_encode_path(path),
+    }
+}
+
+/// Replace a leading `home` directory in `path` with `~`
+///
+/// Only whole components match, so `/home/username` is not shortened for a
+/// home of `/home/user`. Paths outside `home`, and any path when `home` is the
+/// root directory, are returned unchanged. Meant for output only; stored paths
+/// stay absolute.
+pub fn collapse_home(path: &Path, home: &Path) -> PathBuf {
+    if home.parent().is_none() {
+        return path.to_path_buf();
+    }
+    match path.strip_prefix(home) {
+        Ok(rest) => Path::new("~").join(rest),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+/// Format a byte count for humans using binary units (e.g. "1.5 MB")
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Format a time as UTC, e.g. "2023-11-14 22:13:20 UTC"
+///
+/// Times before the Unix epoch are clamped to it.
+pub fn format_timestamp(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let (days, rest) = (secs / 86_400, secs % 86_400);
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        rest / 3600,
+        rest % 3600 / 60,
+        rest % 60
+    )
+}
+
+/// Percent-encode a path's raw bytes, passing valid UTF-8 through untouched
+#[cfg(unix)]
+fn percent_encode_path(path: &Path) -> String {
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut output = String::new();
+    for chunk in path.as_os_str().as_bytes().utf8_chunks() {
+        for c in chunk.valid().chars() {
+            if c == '%' {
+                output.push_str("%25");
+            } else {
+                output.push(c);
+            }
+        }
+        for byte in chunk.invalid() {
+            output.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    output
+}
+
+#[cfg(not(unix))]
+fn percent_encode_path(path: &Path) -> String {
+    path.to_string_lossy().replace('%', "%25")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    fn invalid_path() -> &'static Path {
+        Path::new(OsStr::from_bytes(b"/home/user/caf\xe9/50%.txt"))
+    }
+
+    #[test]
+    fn test_lossy_display() {
+        let displayed = display_path(invalid_path(), PathDisplayMode::Lossy);
+        assert_eq!(displayed, "/home/user/caf\u{FFFD}/50%.txt");
+    }
+
+    #[test]
+    fn test_lossless_display() {
+        let displayed = display_path(invalid_path(), PathDisplayMode::Lossless);
+        assert_eq!(displayed, "/home/user/caf%E9/50%25.txt");
+    }
+
+    #[test]
+    fn test_collapse_home() {
+        let home = Path::new("/home/user");
+        assert_eq!(collapse_home(Path::new("/home/user/Documents/report.pdf"), home), Path::new("~/Documents/report.pdf"));
+        assert_eq!(collapse_home(Path::new("/home/user"), home), Path::new("~"));
+        assert_eq!(collapse_home(Path::new("/home/username/notes.txt"), home), Path::new("/home/username/notes.txt"));
+        assert_eq!(collapse_home(Path::new("/etc/hosts"), home), Path::new("/etc/hosts"));
+        assert_eq!(collapse_home(Path::new("/etc/hosts"), Path::new("/")), Path::new("/etc/hosts"));
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(5 * 1024 * 1024), "5.0 MB");
+        assert_eq!(format_size(3 * 1024 * 1024 * 1024), "3.0 GB");
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        use std::time::Duration;
+
+        assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_timestamp(UNIX_EPOCH + Duration::from_secs(1_700_000_000)), "2023-11-14 22:13:20 UTC");
+        assert_eq!(format_timestamp(UNIX_EPOCH + Duration::from_secs(951_782_400)), "2000-02-29 00:00:00 UTC");
+        assert_eq!(format_timestamp(UNIX_EPOCH - Duration::from_secs(1)), "1970-01-01 00:00:00 UTC");
+    }
+
+    #[test]
+    fn test_valid_utf8_unchanged() {
+        let path = Path::new("/home/user/Документы/notes.txt");
+        assert_eq!(display_path(path, PathDisplayMode::Lossy), "/home/user/Документы/notes.txt");
+        assert_eq!(display_path(path, PathDisplayMode::Lossless), "/home/user/Документы/notes.txt");
+    }
+}
@@ -0,0 +1,185 @@
+use crate::config::Config;
+use crate::database::{Database, DatabaseError, ReplaceOutcome, SearchOptions};
+use crate::models::FileEntry;
+use crate::paths;
+use crate::scanner::{self, Scanner};
+use crate::watcher::{EventProcessor, FilesystemWatcher, WatchReport, WatcherError};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// The indexing engine for programs that embed it instead of talking to the daemon
+///
+/// Bundles the index database with the scanner and filesystem watcher that keep
+/// it up to date. Nothing runs in the background: `scan` indexes the configured
+/// paths, `watch` starts monitoring them, and `process_events` applies the
+/// changes seen since the last call.
+pub struct NovaSearch {
+    config: Config,
+    db: Database,
+    watcher: Option<FilesystemWatcher>,
+    processor: EventProcessor,
+}
+
+impl NovaSearch {
+    /// Open the index in the data directory (see `paths::get_database_path`)
+    pub fn open(config: Config) -> Result<Self, EngineError> {
+        let db = Database::open_writable(paths::get_database_path())?;
+        Self::with_database(config, db)
+    }
+
+    /// Keep the index in memory; it is lost when this is dropped
+    pub fn open_in_memory(config: Config) -> Result<Self, EngineError> {
+        let db = Database::open_in_memory()?;
+        Self::with_database(config, db)
+    }
+
+    fn with_database(config: Config, mut db: Database) -> Result<Self, EngineError> {
+        db.apply_config(&config)?;
+        let processor = EventProcessor::from_config(&config);
+
+        Ok(NovaSearch {
+            config,
+            db,
+            watcher: None,
+            processor,
+        })
+    }
+
+    /// Get the configuration the engine was opened with
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Get the underlying database for queries not covered here
+    pub fn database(&self) -> &Database {
+        &self.db
+    }
+
+    /// Scan the configured paths and replace the index with what was found
+    ///
+    /// Like `novasearch-daemon reindex`, the index is kept when the scan finds
+    /// fewer than `indexing.reindex_min_fraction` of the entries already indexed.
+    pub fn scan(&self) -> Result<ReplaceOutcome, EngineError> {
+        let scanner = Scanner::new(self.config.clone());
+        let mut entries = scanner.scan_parallel();
+        if let Some(cutoff) = self.config.indexing.aged_out_cutoff() {
+            entries.extend(self.db.aged_out_entries(cutoff, &entries)?);
+        }
+        let outcome = self.db.replace_all(&entries, self.config.indexing.reindex_min_fraction)?;
+        scanner::store_scan_errors(&self.db, &scanner.recent_errors())?;
+        Ok(outcome)
+    }
+
+    /// Start watching the configured paths for changes
+    ///
+    /// Paths that cannot be watched are listed in the report rather than
+    /// failing the call; only creating the watcher itself is an error. Long
+    /// path lists are registered in batches of `performance.watch_batch_size`,
+    /// with `performance.watch_batch_delay_ms` between them.
+    pub fn watch(&mut self) -> Result<WatchReport, EngineError> {
+        let include_paths = self.config.expand_paths();
+        let mut paths = include_paths.clone();
+        for app_dir in Scanner::new(self.config.clone()).application_watch_directories() {
+            if app_dir.exists() && !paths.contains(&app_dir) {
+                paths.push(app_dir);
+            }
+        }
+
+        let mut watcher = FilesystemWatcher::new(&self.config)?;
+        let report = watcher.watch_paths_batched(
+            &paths,
+            &include_paths,
+            self.config.performance.watch_batch_size,
+            Duration::from_millis(self.config.performance.watch_batch_delay_ms),
+            |_, _| {},
+        );
+        self.watcher = Some(watcher);
+        Ok(report)
+    }
+
+    /// Index the changes the watcher reported since the last call
+    ///
+    /// Events are debounced, so a change usually shows up one call after it is
+    /// first seen. Watched paths that lost events (an inotify queue overflow)
+    /// are rescanned when `indexing.rescan_on_overflow` is on. Returns the
+    /// number of index operations applied.
+    pub fn process_events(&mut self) -> Result<usize, EngineError> {
+        if let Some(watcher) = &mut self.watcher {
+            watcher.retry_degraded();
+            while let Some(event) = watcher.try_recv_event() {
+                self.processor.add_event(event);
+            }
+        }
+
+        let operations = self.processor.process_pending();
+        self.db.execute_batch(&operations)?;
+        let mut applied = operations.len();
+
+        // Roots that lost events are rescanned in place
+        if let Some(watcher) = &self.watcher {
+            let roots: Vec<PathBuf> = watcher.watched_paths().iter().chain(watcher.polled_paths()).cloned().collect();
+            let roots = self.processor.take_rescan_roots(&roots);
+            if !roots.is_empty() {
+                let scanner = Scanner::new(self.config.clone());
+                for root in roots {
+                    let entries = scanner.scan_subtree(&root);
+                    let cutoff = self.config.indexing.aged_out_cutoff();
+                    let stats = self.db.reconcile_since(&entries, std::slice::from_ref(&root), cutoff)?;
+                    applied += stats.updated + stats.deleted;
+                }
+            }
+        }
+        Ok(applied)
+    }
+
+    /// Get the watched paths whose errors are ignored after failing repeatedly
+    pub fn degraded_paths(&self) -> Vec<PathBuf> {
+        self.watcher.as_ref().map(FilesystemWatcher::degraded_paths).unwrap_or_default()
+    }
+
+    /// Search indexed filenames
+    pub fn search(&self, query: &str, options: &SearchOptions) -> Result<Vec<FileEntry>, EngineError> {
+        Ok(self.db.search(query, options)?)
+    }
+
+    /// Search inside files indexed with `indexing.index_content`
+    pub fn search_content(&self, query: &str, limit: usize) -> Result<Vec<FileEntry>, EngineError> {
+        Ok(self.db.search_content(query, limit)?)
+    }
+}
+
+/// Errors from the embedded engine
+#[derive(Debug)]
+pub enum EngineError {
+    Database(String),
+    Watcher(String),
+}
+
+impl std::fmt::Display for EngineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EngineError::Database(msg) => write!(f, "Database error: {}", msg),
+            EngineError::Watcher(msg) => write!(f, "Watcher error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+impl From<rusqlite::Error> for EngineError {
+    fn from(err: rusqlite::Error) -> Self {
+        EngineError::Database(err.to_string())
+    }
+}
+
+impl From<DatabaseError> for EngineError {
+    fn from(err: DatabaseError) -> Self {
+        EngineError::Database(err.to_string())
+    }
+}
+
+impl From<WatcherError> for EngineError {
+    fn from(err: WatcherError) -> Self {
+        EngineError::Watcher(err.to_string())
+    }
+}
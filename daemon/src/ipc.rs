@@ -0,0 +1,504 @@
+use crate::database::{Database, DatabaseError, SearchOptions};
+use crate::metrics::MetricsSnapshot;
+use rusqlite::Result as SqliteResult;
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt};
+use tokio::net::UnixListener;
+
+/// Longest request line accepted; a longer one gets an error and the connection is closed
+const MAX_REQUEST_BYTES: usize = 64 * 1024;
+
+/// Wait before accepting again after `accept` fails (e.g. out of file descriptors)
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Command sent to the running daemon, one JSON object per line
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "lowercase")]
+pub enum Request {
+    /// Stop processing events and flushing until resumed
+    Pause,
+    /// Resume processing after a pause
+    Resume,
+    /// Report the daemon's state without changing it
+    Status,
+    /// Report the daemon's counters and gauges
+    Metrics,
+    /// Replace the index with the one rebuilt at `paths::get_rebuild_database_path`
+    SwapIndex,
+    /// Search the index by filename
+    Query {
+        query: String,
+        /// Requested number of results, capped by the daemon
+        #[serde(default)]
+        limit: Option<usize>,
+    },
+    /// Count a launch of an indexed path; written to the index with the next batch
+    Launch { path: PathBuf },
+}
+
+/// Reply to a request, one JSON object per line
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Response {
+    /// Daemon state, returned for pause, resume and status
+    State {
+        paused: bool,
+        /// Watched paths whose errors are ignored after failing repeatedly
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        degraded: Vec<PathBuf>,
+    },
+    /// Matching paths in ranked order
+    Results {
+        paths: Vec<PathBuf>,
+        /// More results exist beyond the returned ones
+        truncated: bool,
+    },
+    /// Current metrics values
+    Metrics { metrics: MetricsSnapshot },
+    /// The launch was queued and will be written with the next batch
+    Queued,
+    /// The request could not be served
+    Error { error: ErrorInfo },
+}
+
+impl Response {
+    /// Build an error response
+    pub fn error(code: ErrorCode, message: impl Into<String>) -> Self {
+        Response::Error { error: ErrorInfo { code, message: message.into() } }
+    }
+
+    /// Build the error response for a failed database call
+    pub fn from_error<E>(err: &E) -> Self
+    where
+        for<'a> ErrorInfo: From<&'a E>,
+    {
+        Response::Error { error: ErrorInfo::from(err) }
+    }
+}
+
+/// Why a request failed, serialized as `"error": {"code": ..., "message": ...}`
+///
+/// Clients should branch on `code`, which is stable; `message` is for humans.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorInfo {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+/// Stable error codes sent to clients
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The request line is not valid JSON
+    InvalidJson,
+    /// The request names a command this daemon does not know
+    UnsupportedCommand,
+    /// A known command with missing or mistyped fields
+    InvalidRequest,
+    /// The index is locked by another writer; retrying later may succeed
+    DatabaseBusy,
+    /// The index cannot be opened or written
+    DatabaseUnavailable,
+    /// Any other database failure
+    DatabaseError,
+    /// The daemon failed while handling the request
+    Internal,
+}
+
+impl From<&rusqlite::Error> for ErrorInfo {
+    fn from(err: &rusqlite::Error) -> Self {
+        let code = match err.sqlite_error_code() {
+            Some(rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked) => ErrorCode::DatabaseBusy,
+            Some(rusqlite::ErrorCode::CannotOpen | rusqlite::ErrorCode::ReadOnly) => ErrorCode::DatabaseUnavailable,
+            _ => ErrorCode::DatabaseError,
+        };
+        ErrorInfo { code, message: err.to_string() }
+    }
+}
+
+impl From<&DatabaseError> for ErrorInfo {
+    fn from(err: &DatabaseError) -> Self {
+        let code = match err {
+            DatabaseError::NotWritable(_) => ErrorCode::DatabaseUnavailable,
+            DatabaseError::SqliteError(_) => ErrorCode::DatabaseError,
+        };
+        ErrorInfo { code, message: err.to_string() }
+    }
+}
+
+/// Parse one request line, describing what is wrong with it otherwise
+pub fn parse_request(line: &str) -> Result<Request, ErrorInfo> {
+    let value: serde_json::Value = serde_json::from_str(line).map_err(|e| ErrorInfo {
+        code: ErrorCode::InvalidJson,
+        message: e.to_string(),
+    })?;
+
+    let command = value.get("command").and_then(serde_json::Value::as_str).map(str::to_string);
+    serde_json::from_value(value).map_err(|e| match command {
+        // serde names the tag it did not recognize this way; other errors are about the fields
+        Some(command) if e.to_string().starts_with(&format!("unknown variant `{}`", command)) => ErrorInfo {
+            code: ErrorCode::UnsupportedCommand,
+            message: format!("Unsupported command: {}", command),
+        },
+        _ => ErrorInfo { code: ErrorCode::InvalidRequest, message: e.to_string() },
+    })
+}
+
+/// Answer a query with at most `max_results` paths
+///
+/// `limit` is honoured when it is smaller than `max_results`. One extra row
+/// is fetched to tell whether the results were truncated. The other search
+/// settings come from `options`. Non-empty queries shorter than the database's
+/// `min_query_length` get no results.
+pub fn query_response(
+    db: &Database,
+    query: &str,
+    limit: Option<usize>,
+    max_results: usize,
+    options: &SearchOptions,
+) -> SqliteResult<Response> {
+    let length = query.trim().chars().count();
+    if length > 0 && length < db.min_query_length() {
+        return Ok(Response::Results { paths: Vec::new(), truncated: false });
+    }
+
+    let cap = limit.map_or(max_results, |limit| limit.min(max_results));
+    let options = SearchOptions {
+        limit: cap.saturating_add(1),
+        ..options.clone()
+    };
+
+    let mut paths: Vec<PathBuf> = db.search(query, &options)?
+        .into_iter()
+        .map(|entry| entry.path)
+        .collect();
+    let truncated = paths.len() > cap;
+    paths.truncate(cap);
+
+    Ok(Response::Results { paths, truncated })
+}
+
+/// Bind the control socket, replacing a stale socket left by a previous run
+///
+/// A socket is only stale when connecting to it is refused; one that still
+/// accepts connections belongs to a running daemon and fails with `AddrInUse`.
+pub fn bind(path: &Path) -> io::Result<UnixListener> {
+    match UnixStream::connect(path) {
+        Ok(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("another daemon is listening on {}", path.display()),
+            ))
+        }
+        Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => std::fs::remove_file(path)?,
+        Err(_) => {}
+    }
+    UnixListener::bind(path)
+}
+
+/// Answer requests on `listener` with `handler` until the task is dropped
+///
+/// Each connection may send any number of requests. Malformed lines are
+/// answered with an error response and the connection stays open, as it does
+/// when the handler panics. A line longer than `MAX_REQUEST_BYTES` is answered
+/// with an error and closes the connection. The handler runs on the blocking
+/// thread pool, so it may wait on locks or the database.
+pub async fn serve<F>(listener: UnixListener, handler: F)
+where
+    F: Fn(Request) -> Response + Send + Sync + 'static,
+{
+    let handler = Arc::new(handler);
+
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                eprintln!("Control socket error: {}", e);
+                tokio::time::sleep(ACCEPT_RETRY_DELAY).await;
+                continue;
+            }
+        };
+
+        let handler = Arc::clone(&handler);
+        tokio::spawn(async move {
+            let (reader, mut writer) = stream.into_split();
+            let mut reader = tokio::io::BufReader::new(reader);
+            let mut line = Vec::new();
+
+            loop {
+                line.clear();
+                // One byte over the limit tells a long line from one that is exactly at it
+                let limit = MAX_REQUEST_BYTES as u64 + 1;
+                match (&mut reader).take(limit).read_until(b'\n', &mut line).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) => {}
+                }
+                if line.last() == Some(&b'\n') {
+                    line.pop();
+                }
+                let too_long = line.len() > MAX_REQUEST_BYTES;
+
+                let request = if too_long {
+                    Err(ErrorInfo {
+                        code: ErrorCode::InvalidRequest,
+                        message: format!("Request longer than {} bytes", MAX_REQUEST_BYTES),
+                    })
+                } else {
+                    parse_request(&String::from_utf8_lossy(&line))
+                };
+                let response = match request {
+                    Ok(request) => {
+                        let handler = Arc::clone(&handler);
+                        tokio::task::spawn_blocking(move || handler(request))
+                            .await
+                            .unwrap_or_else(|e| Response::error(ErrorCode::Internal, e.to_string()))
+                    }
+                    Err(error) => {
+                        eprintln!("Invalid control request: {}", error.message);
+                        Response::Error { error }
+                    }
+                };
+                let mut reply = match serde_json::to_string(&response) {
+                    Ok(reply) => reply,
+                    Err(_) => break,
+                };
+                reply.push('\n');
+                if writer.write_all(reply.as_bytes()).await.is_err() || too_long {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+/// Send a request to the daemon listening on `path` and wait for its response
+pub fn send_request(path: &Path, request: Request) -> Result<Response, IpcError> {
+    let mut stream = UnixStream::connect(path)
+        .map_err(|e| IpcError::ConnectionError(e.to_string()))?;
+
+    let mut line = serde_json::to_string(&request)
+        .map_err(|e| IpcError::ProtocolError(e.to_string()))?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())
+        .map_err(|e| IpcError::ConnectionError(e.to_string()))?;
+
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)
+        .map_err(|e| IpcError::ConnectionError(e.to_string()))?;
+
+    serde_json::from_str(&reply).map_err(|e| IpcError::ProtocolError(e.to_string()))
+}
+
+/// IPC error types
+#[derive(Debug)]
+pub enum IpcError {
+    ConnectionError(String),
+    ProtocolError(String),
+}
+
+impl std::fmt::Display for IpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IpcError::ConnectionError(msg) => write!(f, "Connection error: {}", msg),
+            IpcError::ProtocolError(msg) => write!(f, "Protocol error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for IpcError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[tokio::test]
+    async fn test_bind_replaces_only_stale_sockets() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let socket_path = temp_dir.path().join("daemon.sock");
+
+        let listener = bind(&socket_path).unwrap();
+        assert_eq!(bind(&socket_path).unwrap_err().kind(), io::ErrorKind::AddrInUse);
+
+        // The socket file outlives its listener
+        drop(listener);
+        assert!(socket_path.exists());
+        bind(&socket_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pause_and_resume_over_socket() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let socket_path = temp_dir.path().join("daemon.sock");
+        let paused = Arc::new(AtomicBool::new(false));
+
+        let listener = bind(&socket_path).unwrap();
+        let flag = Arc::clone(&paused);
+        let server = tokio::spawn(serve(listener, move |request| {
+            match request {
+                Request::Pause => flag.store(true, Ordering::Relaxed),
+                Request::Resume => flag.store(false, Ordering::Relaxed),
+                Request::Status | Request::Metrics | Request::SwapIndex | Request::Query { .. } | Request::Launch { .. } => {}
+            }
+            Response::State { paused: flag.load(Ordering::Relaxed), degraded: Vec::new() }
+        }));
+
+        let send = |request: Request| {
+            let socket_path = socket_path.clone();
+            tokio::task::spawn_blocking(move || send_request(&socket_path, request).unwrap())
+        };
+
+        assert_eq!(send(Request::Pause).await.unwrap(), Response::State { paused: true, degraded: Vec::new() });
+        assert!(paused.load(Ordering::Relaxed));
+        assert_eq!(send(Request::Status).await.unwrap(), Response::State { paused: true, degraded: Vec::new() });
+        assert_eq!(send(Request::Resume).await.unwrap(), Response::State { paused: false, degraded: Vec::new() });
+        assert!(!paused.load(Ordering::Relaxed));
+
+        server.abort();
+    }
+
+    #[test]
+    fn test_query_response_truncated_at_cap() {
+        use crate::models::{FileEntry, FileType};
+        use std::time::SystemTime;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db = Database::open(temp_dir.path().join("index.db")).unwrap();
+        for i in 0..10 {
+            let path = PathBuf::from(format!("/home/user/notes{}.txt", i));
+            let entry = FileEntry::new(
+                format!("notes{}.txt", i),
+                path,
+                10,
+                SystemTime::now(),
+                FileType::Regular,
+            );
+            db.insert_file(&entry).unwrap();
+        }
+
+        match query_response(&db, "notes", None, 4, &SearchOptions::default()).unwrap() {
+            Response::Results { paths, truncated } => {
+                assert_eq!(paths.len(), 4);
+                assert!(truncated);
+            }
+            other => panic!("Expected Results, got {:?}", other),
+        }
+
+        // A larger client limit is still capped, a smaller one is honoured
+        assert!(matches!(
+            query_response(&db, "notes", Some(100), 4, &SearchOptions::default()).unwrap(),
+            Response::Results { paths, truncated: true } if paths.len() == 4
+        ));
+        assert!(matches!(
+            query_response(&db, "notes", Some(2), 4, &SearchOptions::default()).unwrap(),
+            Response::Results { paths, truncated: true } if paths.len() == 2
+        ));
+        assert!(matches!(
+            query_response(&db, "notes", None, 20, &SearchOptions::default()).unwrap(),
+            Response::Results { paths, truncated: false } if paths.len() == 10
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_malformed_requests_get_error_envelopes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let socket_path = temp_dir.path().join("daemon.sock");
+
+        let listener = bind(&socket_path).unwrap();
+        let server = tokio::spawn(serve(listener, |_| Response::State { paused: false, degraded: Vec::new() }));
+
+        let replies = tokio::task::spawn_blocking(move || {
+            let mut stream = UnixStream::connect(&socket_path).unwrap();
+            stream.write_all(b"{not json\n{\"command\":\"reboot\"}\n{\"command\":\"query\"}\n{\"command\":\"status\"}\n").unwrap();
+            BufReader::new(stream)
+                .lines()
+                .take(4)
+                .map(|line| serde_json::from_str::<serde_json::Value>(&line.unwrap()).unwrap())
+                .collect::<Vec<_>>()
+        }).await.unwrap();
+
+        let codes: Vec<_> = replies[..3].iter().map(|reply| reply["error"]["code"].as_str().unwrap()).collect();
+        assert_eq!(codes, vec!["invalid_json", "unsupported_command", "invalid_request"]);
+        assert!(replies[1]["error"]["message"].as_str().unwrap().contains("reboot"));
+
+        // The connection survives the bad lines
+        assert_eq!(replies[3], serde_json::json!({"type": "state", "paused": false}));
+
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn test_overlong_request_closes_connection() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let socket_path = temp_dir.path().join("daemon.sock");
+
+        let listener = bind(&socket_path).unwrap();
+        let server = tokio::spawn(serve(listener, |_| Response::State { paused: false, degraded: Vec::new() }));
+
+        let replies = tokio::task::spawn_blocking(move || {
+            let mut stream = UnixStream::connect(&socket_path).unwrap();
+            let mut request = format!("{{\"command\":\"query\",\"query\":\"{}\"}}\n", "a".repeat(MAX_REQUEST_BYTES));
+            request.push_str("{\"command\":\"status\"}\n");
+            // The daemon may close the connection before reading all of it
+            let _ = stream.write_all(request.as_bytes());
+            BufReader::new(stream).lines().map_while(Result::ok).collect::<Vec<_>>()
+        }).await.unwrap();
+
+        assert_eq!(replies.len(), 1);
+        let reply: serde_json::Value = serde_json::from_str(&replies[0]).unwrap();
+        assert_eq!(reply["error"]["code"], "invalid_request");
+
+        server.abort();
+    }
+
+    #[test]
+    fn test_every_command_is_supported() {
+        let requests = [
+            Request::Pause,
+            Request::Resume,
+            Request::Status,
+            Request::Metrics,
+            Request::SwapIndex,
+            Request::Query { query: "notes".to_string(), limit: None },
+            Request::Launch { path: PathBuf::from("/home/user/notes.txt") },
+        ];
+        for request in requests {
+            let mut value = serde_json::to_value(&request).unwrap();
+            assert_eq!(parse_request(&value.to_string()), Ok(request));
+
+            // A mistyped field is not mistaken for an unknown command
+            value["query"] = serde_json::json!(1);
+            value["path"] = serde_json::json!(1);
+            if let Err(error) = parse_request(&value.to_string()) {
+                assert_eq!(error.code, ErrorCode::InvalidRequest, "{}", value);
+            }
+        }
+        assert_eq!(parse_request(r#"{"command":"reboot"}"#).unwrap_err().code, ErrorCode::UnsupportedCommand);
+    }
+
+    #[test]
+    fn test_database_error_codes() {
+        let busy = rusqlite::Error::SqliteFailure(rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY), None);
+        assert_eq!(ErrorInfo::from(&busy).code, ErrorCode::DatabaseBusy);
+        assert_eq!(ErrorInfo::from(&rusqlite::Error::InvalidQuery).code, ErrorCode::DatabaseError);
+        assert_eq!(
+            ErrorInfo::from(&DatabaseError::NotWritable("/data".to_string())).code,
+            ErrorCode::DatabaseUnavailable
+        );
+
+        let reply = serde_json::to_value(Response::from_error(&busy)).unwrap();
+        assert_eq!(reply["error"]["code"], "database_busy");
+    }
+
+    #[test]
+    fn test_send_request_without_daemon() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let result = send_request(&temp_dir.path().join("missing.sock"), Request::Status);
+        assert!(matches!(result, Err(IpcError::ConnectionError(_))));
+    }
+}
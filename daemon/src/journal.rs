@@ -0,0 +1,231 @@
+use crate::database::Database;
+use crate::models::IndexOperation;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Append-only log of queued operations that have not reached the database yet
+///
+/// Operations are appended as they are queued, one JSON record per line, and
+/// the log is rewritten with whatever is still queued after every successful
+/// flush. If the daemon is killed before flushing, `recover` applies the
+/// logged operations on the next start, so at most the operations of the last
+/// unfinished append are lost.
+pub struct OperationJournal {
+    path: PathBuf,
+    file: File,
+}
+
+impl OperationJournal {
+    /// Open the journal at `path` for appending, creating it if needed
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, JournalError> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| JournalError::IoError(e.to_string()))?;
+        Ok(OperationJournal { path, file })
+    }
+
+    /// Record newly queued operations
+    pub fn append(&mut self, operations: &[IndexOperation]) -> Result<(), JournalError> {
+        if operations.is_empty() {
+            return Ok(());
+        }
+
+        // Written in one call so a kill leaves at most one torn line at the end
+        let mut buffer = Vec::new();
+        write_records(&mut buffer, operations)?;
+        self.file.write_all(&buffer).map_err(|e| JournalError::IoError(e.to_string()))
+    }
+
+    /// Replace the journal with the operations still queued after a flush
+    ///
+    /// The new contents are written next to the journal and renamed over it,
+    /// so a kill during the rewrite leaves the previous journal intact.
+    pub fn checkpoint<'a, I>(&mut self, pending: I) -> Result<(), JournalError>
+    where
+        I: IntoIterator<Item = &'a IndexOperation>,
+    {
+        let pending: Vec<IndexOperation> = pending.into_iter().cloned().collect();
+        let temp_path = self.path.with_extension("tmp");
+        {
+            let mut writer = BufWriter::new(
+                File::create(&temp_path).map_err(|e| JournalError::IoError(e.to_string()))?,
+            );
+            write_records(&mut writer, &pending)?;
+            writer.flush().map_err(|e| JournalError::IoError(e.to_string()))?;
+        }
+        std::fs::rename(&temp_path, &self.path).map_err(|e| JournalError::IoError(e.to_string()))?;
+
+        *self = Self::open(&self.path)?;
+        Ok(())
+    }
+}
+
+/// Read the operations logged in the journal at `path`, oldest first
+///
+/// A missing journal reads as empty. A torn last line, left by a kill in the
+/// middle of an append, is skipped.
+pub fn read_journal(path: &Path) -> Result<Vec<IndexOperation>, JournalError> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(JournalError::IoError(e.to_string())),
+    };
+
+    let mut operations = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| JournalError::IoError(e.to_string()))?;
+        match serde_json::from_str(&line) {
+            Ok(operation) => operations.push(operation),
+            Err(e) => eprintln!("Warning: Skipping unreadable journal record: {}", e),
+        }
+    }
+    Ok(operations)
+}
+
+/// Apply operations left in the journal at `path` by a daemon that did not flush them
+///
+/// The journal is emptied once they are committed. Returns the number of
+/// operations replayed.
+pub fn recover(db: &Database, path: &Path) -> Result<usize, JournalError> {
+    let operations = read_journal(path)?;
+    if !operations.is_empty() {
+        db.execute_batch(&operations).map_err(|e| JournalError::DatabaseError(e.to_string()))?;
+    }
+    if path.exists() {
+        File::create(path).map_err(|e| JournalError::IoError(e.to_string()))?;
+    }
+    Ok(operations.len())
+}
+
+fn write_records<W: Write>(writer: &mut W, operations: &[IndexOperation]) -> Result<(), JournalError> {
+    for operation in operations {
+        serde_json::to_writer(&mut *writer, operation).map_err(|e| JournalError::ParseError(e.to_string()))?;
+        writer.write_all(b"\n").map_err(|e| JournalError::IoError(e.to_string()))?;
+    }
+    Ok(())
+}
+
+/// Journal error types
+#[derive(Debug)]
+pub enum JournalError {
+    IoError(String),
+    ParseError(String),
+    DatabaseError(String),
+}
+
+impl std::fmt::Display for JournalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JournalError::IoError(msg) => write!(f, "Journal IO error: {}", msg),
+            JournalError::ParseError(msg) => write!(f, "Journal record error: {}", msg),
+            JournalError::DatabaseError(msg) => write!(f, "Journal replay error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for JournalError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{FileEntry, FileType};
+    use crate::watcher::EventProcessor;
+    use std::time::{Duration, SystemTime};
+
+    fn add(path: &str) -> IndexOperation {
+        let path = PathBuf::from(path);
+        IndexOperation::Add(FileEntry::new(
+            crate::models::entry_name(&path),
+            path,
+            1,
+            SystemTime::now(),
+            FileType::Regular,
+        ))
+    }
+
+    #[test]
+    fn test_unflushed_operations_replayed() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let journal_path = temp_dir.path().join("pending.log");
+        let db = Database::open(temp_dir.path().join("index.db")).unwrap();
+
+        // A first run flushes one operation, queues two more and is killed
+        {
+            let mut journal = OperationJournal::open(&journal_path).unwrap();
+            let flushed = add("/home/user/flushed.txt");
+            journal.append(std::slice::from_ref(&flushed)).unwrap();
+            db.execute_batch(std::slice::from_ref(&flushed)).unwrap();
+            journal.checkpoint(&[]).unwrap();
+
+            journal.append(&[add("/home/user/a.txt"), add("/home/user/b.txt")]).unwrap();
+        }
+        // The kill tore the last record
+        let mut file = OpenOptions::new().append(true).open(&journal_path).unwrap();
+        file.write_all(b"{\"Delete\":\"/home/us").unwrap();
+        drop(file);
+
+        assert_eq!(recover(&db, &journal_path).unwrap(), 2);
+        assert_eq!(db.count_files().unwrap(), 3);
+        assert_eq!(db.query_files("b.txt", 10).unwrap().len(), 1);
+
+        // Replayed operations are not applied a second time
+        assert!(read_journal(&journal_path).unwrap().is_empty());
+        assert_eq!(recover(&db, &journal_path).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_failed_flush_stays_journaled() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let journal_path = temp_dir.path().join("pending.log");
+        let db = Database::open(temp_dir.path().join("index.db")).unwrap();
+        let mut journal = OperationJournal::open(&journal_path).unwrap();
+        let mut processor = EventProcessor::new(Duration::from_millis(0), 100);
+
+        // The first flush fails, so its operations go back to the queue
+        let queued = [add("/home/user/a.txt"), add("/home/user/b.txt")];
+        journal.append(&queued).unwrap();
+        for operation in queued {
+            processor.enqueue_operation(operation).unwrap();
+        }
+        let operations = processor.take_batch(10, false);
+        db.connection().execute_batch("PRAGMA query_only = ON").unwrap();
+        assert!(db.execute_batch(&operations).is_err());
+        processor.requeue_front(operations);
+        db.connection().execute_batch("PRAGMA query_only = OFF").unwrap();
+
+        // They are retried first, and the checkpoint keeps whatever is still queued
+        let later = add("/home/user/c.txt");
+        journal.append(std::slice::from_ref(&later)).unwrap();
+        processor.enqueue_operation(later).unwrap();
+        let operations = processor.take_batch(1, false);
+        db.execute_batch(&operations).unwrap();
+        journal.checkpoint(processor.queued_operations()).unwrap();
+
+        assert_eq!(db.query_files("a.txt", 10).unwrap().len(), 1);
+        let logged = read_journal(&journal_path).unwrap();
+        assert_eq!(logged.len(), 2);
+        assert!(matches!(&logged[0], IndexOperation::Add(entry) if entry.filename == "b.txt"));
+        assert!(matches!(&logged[1], IndexOperation::Add(entry) if entry.filename == "c.txt"));
+    }
+
+    #[test]
+    fn test_checkpoint_keeps_pending_operations() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let journal_path = temp_dir.path().join("pending.log");
+
+        let mut journal = OperationJournal::open(&journal_path).unwrap();
+        let pending = [add("/home/user/c.txt")];
+        journal.append(&[add("/home/user/a.txt"), add("/home/user/b.txt")]).unwrap();
+        journal.checkpoint(&pending).unwrap();
+        journal.append(&[IndexOperation::Delete(PathBuf::from("/home/user/d.txt"))]).unwrap();
+
+        let logged = read_journal(&journal_path).unwrap();
+        assert_eq!(logged.len(), 2);
+        assert!(matches!(&logged[0], IndexOperation::Add(entry) if entry.filename == "c.txt"));
+        assert!(matches!(&logged[1], IndexOperation::Delete(path) if path == Path::new("/home/user/d.txt")));
+    }
+}
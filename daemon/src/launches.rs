@@ -0,0 +1,117 @@
+use crate::database::{self, Database};
+use rusqlite::Result as SqliteResult;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+
+/// Most launches kept waiting for a flush; older ones are dropped past this
+pub const MAX_PENDING_LAUNCHES: usize = 10000;
+
+/// Launches reported to the daemon that have not reached the database yet
+///
+/// Opening a file from the panel should not wait on the index, so launches
+/// are queued with the time they happened and written together by `flush`,
+/// which the daemon calls every `performance.launch_flush_interval_ms`.
+#[derive(Debug, Default)]
+pub struct LaunchBuffer {
+    pending: VecDeque<(PathBuf, i64)>,
+}
+
+impl LaunchBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a launch of `path` happening now
+    pub fn push(&mut self, path: PathBuf) {
+        self.push_at(path, database::current_timestamp());
+    }
+
+    /// Queue a launch of `path` at the Unix time `launched_at`
+    pub fn push_at(&mut self, path: PathBuf, launched_at: i64) {
+        if self.pending.len() >= MAX_PENDING_LAUNCHES {
+            self.pending.pop_front();
+        }
+        self.pending.push_back((path, launched_at));
+    }
+
+    /// Get the number of launches waiting for a flush
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Take the queued launches, leaving the buffer empty
+    pub fn take(&mut self) -> Vec<(PathBuf, i64)> {
+        std::mem::take(&mut self.pending).into()
+    }
+
+    /// Put back launches whose flush failed, ahead of any queued since
+    pub fn restore(&mut self, launches: Vec<(PathBuf, i64)>) {
+        let mut launches = VecDeque::from(launches);
+        launches.append(&mut self.pending);
+        let excess = launches.len().saturating_sub(MAX_PENDING_LAUNCHES);
+        launches.drain(..excess);
+        self.pending = launches;
+    }
+
+    /// Write the queued launches to `db` in one transaction
+    ///
+    /// On failure the launches stay queued for the next flush. Returns the
+    /// number recorded; launches of paths that are not indexed are dropped.
+    pub fn flush(&mut self, db: &Database) -> SqliteResult<usize> {
+        let launches = self.take();
+        db.record_file_launches(&launches).inspect_err(|_| self.restore(launches))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{FileEntry, FileType};
+    use std::time::SystemTime;
+
+    #[test]
+    fn test_buffered_launches_are_persisted() {
+        let db = Database::open_in_memory().unwrap();
+        for filename in ["notes.txt", "report.pdf"] {
+            db.insert_file(&FileEntry::new(
+                filename.to_string(),
+                PathBuf::from(format!("/home/user/{}", filename)),
+                1024,
+                SystemTime::now(),
+                FileType::Regular,
+            )).unwrap();
+        }
+
+        let mut buffer = LaunchBuffer::new();
+        buffer.push_at(PathBuf::from("/home/user/notes.txt"), 1_700_000_100);
+        buffer.push_at(PathBuf::from("/home/user/notes.txt"), 1_700_000_000);
+        buffer.push_at(PathBuf::from("/home/user/report.pdf"), 1_700_000_050);
+        buffer.push(PathBuf::from("/home/user/missing.txt"));
+
+        // Nothing is written until the buffer is flushed
+        assert!(db.get_file_usage("/home/user/notes.txt").unwrap().is_none());
+
+        assert_eq!(buffer.flush(&db).unwrap(), 3);
+        assert!(buffer.is_empty());
+        assert_eq!(db.get_file_usage("/home/user/notes.txt").unwrap(), Some((2, 1_700_000_100)));
+        assert_eq!(db.get_file_usage("/home/user/report.pdf").unwrap(), Some((1, 1_700_000_050)));
+    }
+
+    #[test]
+    fn test_restore_keeps_order_and_bound() {
+        let mut buffer = LaunchBuffer::new();
+        buffer.push_at(PathBuf::from("/b"), 2);
+        buffer.restore(vec![(PathBuf::from("/a"), 1)]);
+        assert_eq!(buffer.take(), vec![(PathBuf::from("/a"), 1), (PathBuf::from("/b"), 2)]);
+
+        for i in 0..MAX_PENDING_LAUNCHES as i64 + 1 {
+            buffer.push_at(PathBuf::from("/c"), i);
+        }
+        assert_eq!(buffer.len(), MAX_PENDING_LAUNCHES);
+        assert_eq!(buffer.take()[0].1, 1);
+    }
+}
@@ -1,6 +1,22 @@
 pub mod paths;
 pub mod database;
+pub mod query;
 pub mod models;
 pub mod config;
 pub mod watcher;
 pub mod scanner;
+pub mod snapshot;
+pub mod display;
+pub mod tasks;
+pub mod disk;
+pub mod ipc;
+pub mod metrics;
+pub mod repo;
+pub mod selftest;
+pub mod engine;
+pub mod verify;
+pub mod mounts;
+pub mod journal;
+pub mod launches;
+
+pub use engine::{EngineError, NovaSearch};
@@ -1,21 +1,24 @@
-mod paths;
-mod database;
-mod models;
-mod config;
-mod watcher;
-mod scanner;
-
 use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use tokio::time::{interval, Duration};
+use tokio::time::{interval, Duration, Instant};
 use std::sync::atomic::{AtomicBool, Ordering};
 
-use config::{Config, ConfigWatcher};
-use database::Database;
-use watcher::{FilesystemWatcher, EventProcessor};
-use scanner::Scanner;
+use novasearch_daemon::{database, ipc, journal, models, mounts, paths, scanner, selftest, snapshot, tasks, verify};
+use novasearch_daemon::disk::DiskSpaceGuard;
+use novasearch_daemon::config::{Config, ConfigWatcher};
+use novasearch_daemon::display::{collapse_home, display_path, format_size, format_timestamp};
+use novasearch_daemon::database::{
+    Database, DatabaseError, ReplaceOutcome, ResultFilter, SearchOptions, SearchOrder, SCHEMA_VERSION,
+};
+use novasearch_daemon::watcher::{FilesystemWatcher, EventProcessor, WatchReport};
+use novasearch_daemon::scanner::{ConfiguredPathKind, Scanner};
+use novasearch_daemon::metrics::Metrics;
+use novasearch_daemon::journal::OperationJournal;
+use novasearch_daemon::launches::LaunchBuffer;
 
 /// NovaSearch Indexing Daemon
 #[derive(Parser)]
@@ -26,6 +29,14 @@ struct Cli {
     #[arg(short, long, value_name = "FILE")]
     config: Option<PathBuf>,
 
+    /// Directory for the index database and control socket
+    #[arg(long, value_name = "DIR", global = true)]
+    data_dir: Option<PathBuf>,
+
+    /// Print absolute paths instead of shortening the home directory to ~
+    #[arg(long, global = true)]
+    full_paths: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -33,11 +44,111 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Start the indexing daemon
-    Start,
+    Start {
+        /// Keep the index in memory if the database directory is not writable
+        #[arg(long)]
+        in_memory_fallback: bool,
+    },
     /// Query indexing status
     Status,
+    /// Pause indexing in the running daemon
+    Pause,
+    /// Resume indexing in the running daemon
+    Resume,
+    /// Print the running daemon's metrics in Prometheus text format
+    Metrics,
+    /// Search the index by filename
+    Search {
+        /// Text to search for
+        query: String,
+        /// Maximum number of results (defaults to ui.max_results)
+        #[arg(short, long)]
+        limit: Option<usize>,
+        /// Hide results under this directory (repeatable)
+        #[arg(long = "exclude", value_name = "PATH")]
+        exclude_prefixes: Vec<PathBuf>,
+        /// Hide results that no longer exist on disk
+        #[arg(long)]
+        existing: bool,
+        /// Only show files with this extension (repeatable)
+        #[arg(long = "ext", value_name = "EXT")]
+        extensions: Vec<String>,
+        /// Result order: relevance, modified, size, name or path
+        #[arg(long, default_value = "relevance")]
+        sort: SearchOrder,
+        /// Match words inside files instead of names (needs indexing.index_content)
+        #[arg(long)]
+        content: bool,
+    },
+    /// List the largest indexed files
+    Largest {
+        /// Number of files to show
+        #[arg(default_value_t = 20)]
+        count: usize,
+    },
+    /// Print the stored metadata of an indexed path
+    Show {
+        /// File or directory to look up
+        path: PathBuf,
+        /// Also print every entry indexed below the directory
+        #[arg(short, long)]
+        recursive: bool,
+    },
+    /// List recently launched and recently modified files
+    Recent {
+        /// Number of files to show
+        #[arg(default_value_t = 20)]
+        count: usize,
+    },
     /// Force a full re-index
-    Reindex,
+    Reindex {
+        /// Replace the index even if the scan finds far fewer entries than are indexed
+        #[arg(long)]
+        force: bool,
+    },
+    /// Rebuild the index in a separate file and swap it in once complete,
+    /// leaving the current index searchable meanwhile
+    ReindexOffline {
+        /// Swap in the new index even if the scan finds far fewer entries than are indexed
+        #[arg(long)]
+        force: bool,
+    },
+    /// Export an index snapshot (one JSON record per line, sorted by path)
+    Export {
+        /// Snapshot file to write
+        output: PathBuf,
+    },
+    /// Export usage statistics (launch counts) keyed by path
+    ExportStats {
+        /// File to write
+        output: PathBuf,
+    },
+    /// Delete all usage statistics (launch counts) without touching the index
+    ClearStats,
+    /// Remove index entries for files that no longer exist on disk
+    Prune,
+    /// Import usage statistics for paths present in the index
+    ImportStats {
+        /// File previously written by export-stats
+        input: PathBuf,
+    },
+    /// Count a launch of an indexed file, queued by the running daemon if there is one
+    RecordLaunch {
+        /// File that was opened
+        path: PathBuf,
+    },
+    /// Report added, removed and modified paths between two snapshots
+    Diff {
+        /// Older snapshot file
+        old: PathBuf,
+        /// Newer snapshot file
+        new: PathBuf,
+    },
+    /// Print the include paths and application directories as they resolve,
+    /// marking the ones that do not exist
+    Paths,
+    /// Check that scanning, indexing, searching and watching work on this system
+    SelfTest,
     /// Show version information
     Version,
     /// Show about information
@@ -48,42 +159,94 @@ enum Commands {
 
 /// Main daemon structure
 struct IndexingDaemon {
-    db: Arc<Database>,
+    db: Arc<Mutex<Database>>,
     watcher: Arc<Mutex<FilesystemWatcher>>,
     config: Config,
+    /// Watched for changes, which are applied as far as they can be without a restart
+    config_path: PathBuf,
     event_processor: Arc<Mutex<EventProcessor>>,
+    /// Log of queued operations, when `performance.operation_journal` is on
+    journal: Option<Arc<Mutex<OperationJournal>>>,
+    /// Launches reported over the control socket, written every `performance.launch_flush_interval_ms`
+    launches: Arc<Mutex<LaunchBuffer>>,
     running: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+    metrics: Arc<Metrics>,
+    /// Set on shutdown to stop a background scan
+    cancel_scan: Arc<AtomicBool>,
+    /// The initial scan hit its timeout and is finished in the background
+    resume_scan: bool,
+    /// The index lives in memory because the database directory is not writable
+    in_memory: bool,
 }
 
 impl IndexingDaemon {
     /// Create a new indexing daemon
-    async fn new(config: Config) -> Result<Self, Box<dyn std::error::Error>> {
+    async fn new(
+        config: Config,
+        config_path: PathBuf,
+        in_memory_fallback: bool,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
         // Open database
         let db_path = paths::get_database_path();
-        if let Some(parent) = db_path.parent() {
-            std::fs::create_dir_all(parent)?;
+        let (mut db, in_memory) = match Database::open_writable(&db_path) {
+            Ok(db) => (db, false),
+            Err(DatabaseError::NotWritable(msg)) if in_memory_fallback => {
+                eprintln!("WARNING: Database directory is not writable: {}", msg);
+                eprintln!("WARNING: Keeping the index in memory; it will be lost when the daemon stops");
+                (Database::open_in_memory()?, true)
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if db.path_storage() != config.indexing.path_storage() {
+            println!("Converting the index to {:?} path storage...", config.indexing.path_storage());
+        }
+        if db.name_normalization() != config.indexing.unicode_normalization {
+            println!("Normalizing indexed names to {:?}...", config.indexing.unicode_normalization);
         }
-        let db = Arc::new(Database::open(&db_path)?);
+        let filled = db.apply_config(&config)?;
+        if filled > 0 {
+            println!("Recorded the owner of {} indexed entries", filled);
+        }
+
+        // Apply operations a previous run queued but never flushed
+        let journal = if config.performance.operation_journal && !in_memory {
+            let journal_path = paths::get_journal_path();
+            match journal::recover(&db, &journal_path) {
+                Ok(0) => {}
+                Ok(count) => println!("Replayed {} operations left unflushed by the last run", count),
+                Err(e) => eprintln!("Warning: Failed to replay the operation journal: {}", e),
+            }
+            Some(Arc::new(Mutex::new(OperationJournal::open(&journal_path)?)))
+        } else {
+            None
+        };
+        let db = Arc::new(Mutex::new(db));
 
         // Create filesystem watcher
         let watcher = Arc::new(Mutex::new(FilesystemWatcher::new(&config)?));
 
         // Create event processor
-        let debounce_duration = Duration::from_millis(200);
-        let max_queue_size = 10000;
-        let event_processor = Arc::new(Mutex::new(EventProcessor::new(
-            debounce_duration,
-            max_queue_size,
-        )));
+        let event_processor = Arc::new(Mutex::new(EventProcessor::from_config(&config)));
 
         let running = Arc::new(AtomicBool::new(true));
+        let paused = Arc::new(AtomicBool::new(false));
+        let metrics = Arc::new(Metrics::new());
 
         Ok(IndexingDaemon {
             db,
             watcher,
             config,
+            config_path,
             event_processor,
+            journal,
+            launches: Arc::new(Mutex::new(LaunchBuffer::new())),
             running,
+            paused,
+            metrics,
+            cancel_scan: Arc::new(AtomicBool::new(false)),
+            resume_scan: false,
+            in_memory,
         })
     }
 
@@ -91,45 +254,110 @@ impl IndexingDaemon {
     async fn initialize(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         println!("Initializing NovaSearch daemon...");
 
-        // Perform initial filesystem scan
+        // Perform initial filesystem scan, checkpointed root by root so an
+        // interrupted scan can resume where it stopped
         println!("Performing initial filesystem scan...");
-        let scanner = Scanner::new(self.config.clone());
-        let entries = scanner.scan();
-        println!("Found {} files/directories", entries.len());
-
-        // Batch insert entries into database
-        println!("Indexing files...");
+        let mut scanner = Scanner::new(self.config.clone());
+        // The background pass writes through its own connection to the
+        // database file, so an in-memory index is always scanned in full here
+        if let Some(timeout) = self.config.initial_scan_timeout().filter(|_| !self.in_memory) {
+            scanner.set_deadline(Some(std::time::Instant::now() + timeout));
+        }
+        // A shutdown signal stops the walk; what was indexed so far is kept
+        scanner.set_cancel_token(Arc::clone(&self.cancel_scan));
         let batch_size = self.config.performance.batch_size;
-        for chunk in entries.chunks(batch_size) {
-            let operations: Vec<_> = chunk
-                .iter()
-                .map(|entry| models::IndexOperation::Add(entry.clone()))
-                .collect();
-            self.db.execute_batch(&operations)?;
+        let mut disk_guard = self.disk_guard();
+        let db = Arc::clone(&self.db);
+        let metrics = Arc::clone(&self.metrics);
+        // Walked on a blocking thread so the runtime stays free to handle the signal
+        let (scanner, completed, indexed) = tokio::task::spawn_blocking(move || -> rusqlite::Result<_> {
+            let mut indexed = 0;
+            let scan_started = Instant::now();
+            let db = db.blocking_lock();
+            let completed = scanner.scan_resumable(&db, |entries| {
+                for chunk in entries.chunks(batch_size) {
+                    if !disk_guard.check() {
+                        return Ok(false);
+                    }
+                    let operations: Vec<_> = chunk
+                        .iter()
+                        .map(|entry| models::IndexOperation::Add(entry.clone()))
+                        .collect();
+                    db.execute_batch(&operations)?;
+                }
+                indexed += entries.len();
+                Ok(true)
+            })?;
+            scanner::store_scan_errors(&db, &scanner.recent_errors())?;
+            metrics.set_last_scan_duration(scan_started.elapsed());
+            metrics.set_files_indexed(db.count_files()? as u64);
+            Ok((scanner, completed, indexed))
+        })
+        .await??;
+        println!("Indexed {} files/directories", indexed);
+        if completed {
+            println!("Initial indexing complete");
+        } else if self.cancel_scan.load(Ordering::Relaxed) {
+            println!("Initial scan interrupted; it will resume on the next start");
+            return Ok(());
+        } else if scanner.timed_out() {
+            println!("Initial scan timed out; the rest will be indexed in the background");
+            self.resume_scan = true;
+        } else {
+            eprintln!("Warning: Initial indexing stopped early; it will resume on the next start");
         }
-        println!("Initial indexing complete");
 
         // Start watching configured paths
         println!("Starting filesystem monitoring...");
-        let mut paths = self.config.expand_paths();
+        let include_paths = self.config.expand_paths();
+        let mut paths = include_paths.clone();
         
-        // Always add application directories to watch list
-        let app_dirs = self.get_application_directories();
+        // Add application directories to the watch list
+        let app_dirs = scanner.application_watch_directories();
         for app_dir in app_dirs {
             if app_dir.exists() && !paths.contains(&app_dir) {
                 paths.push(app_dir);
             }
         }
         
+        // User include paths take priority when the watch budget is limited;
+        // the first batch is watched now and the rest in the background
         let mut watcher = self.watcher.lock().await;
-        let errors = watcher.watch_paths(&paths);
-        if !errors.is_empty() {
-            eprintln!("Warning: Some paths could not be watched:");
-            for error in errors {
-                eprintln!("  {}", error);
-            }
+        let planned = watcher.plan_watches(&paths, &include_paths);
+        let batch_size = match self.config.performance.watch_batch_size {
+            0 => planned.len().max(1),
+            size => size,
+        };
+        let mut batches = planned.chunks(batch_size).map(<[PathBuf]>::to_vec);
+        let first = batches.next().unwrap_or_default();
+        let report = watcher.watch_paths_prioritized(&first, &include_paths);
+        let remaining: Vec<Vec<PathBuf>> = batches.collect();
+        if remaining.is_empty() {
+            print_watch_report(report);
+            println!("Monitoring {} paths", watcher.watched_paths().len());
+            return Ok(());
         }
-        println!("Monitoring {} paths", watcher.watched_paths().len());
+        drop(watcher);
+
+        let watcher = Arc::clone(&self.watcher);
+        let running = Arc::clone(&self.running);
+        let pause = Duration::from_millis(self.config.performance.watch_batch_delay_ms);
+        let total = planned.len();
+        tokio::spawn(async move {
+            let mut report = report;
+            let mut done = first.len();
+            for batch in remaining {
+                tokio::time::sleep(pause).await;
+                if !running.load(Ordering::Relaxed) {
+                    return;
+                }
+                report.extend(watcher.lock().await.watch_paths_prioritized(&batch, &include_paths));
+                done += batch.len();
+                println!("Watching paths: {}/{}", done, total);
+            }
+            print_watch_report(report);
+            println!("Monitoring {} paths", watcher.lock().await.watched_paths().len());
+        });
 
         Ok(())
     }
@@ -141,25 +369,235 @@ impl IndexingDaemon {
         // Set up flush interval
         let flush_interval_duration = self.config.flush_interval();
         let mut flush_timer = interval(flush_interval_duration);
+        let mut launch_timer = interval(self.config.launch_flush_interval());
 
         // Clone Arc references for tasks
         let watcher = Arc::clone(&self.watcher);
         let event_processor = Arc::clone(&self.event_processor);
         let db = Arc::clone(&self.db);
+        let journal = self.journal.clone();
+        let launches = Arc::clone(&self.launches);
         let running = Arc::clone(&self.running);
+        let paused = Arc::clone(&self.paused);
         let batch_size = self.config.performance.batch_size;
+        let coalesce = self.config.performance.coalesce_operations;
+        let pause_policy = self.config.performance.pause_policy;
+        let metrics = Arc::clone(&self.metrics);
+        let mut disk_guard = self.disk_guard();
+        let rescanning = Arc::new(AtomicBool::new(false));
+
+        let mut config_watcher = match ConfigWatcher::with_removal_policy(
+            self.config_path.clone(),
+            self.config.performance.on_config_removed,
+        ) {
+            Ok(config_watcher) => Some(config_watcher),
+            Err(e) => {
+                eprintln!("Warning: Cannot watch the config file, changes need a restart: {}", e);
+                None
+            }
+        };
+
+        // Accept pause/resume requests from the CLI
+        let control_task = match ipc::bind(&paths::get_socket_path()) {
+            Ok(listener) => {
+                let paused = Arc::clone(&self.paused);
+                let db = Arc::clone(&self.db);
+                let metrics = Arc::clone(&self.metrics);
+                let launches = Arc::clone(&self.launches);
+                let max_ipc_results = self.config.performance.max_ipc_results;
+                let search_options = SearchOptions {
+                    scope: self.config.ui.search_scope,
+                    directory_weight: self.config.ranking.directory_weight,
+                    ..SearchOptions::default()
+                };
+                let watch_errors = self.watcher.lock().await.error_tracker();
+                let in_memory = self.in_memory;
+                Some(tokio::spawn(ipc::serve(listener, move |request| {
+                    match request {
+                        ipc::Request::Pause => paused.store(true, Ordering::Relaxed),
+                        ipc::Request::Resume => paused.store(false, Ordering::Relaxed),
+                        ipc::Request::Status => {}
+                        ipc::Request::SwapIndex => {
+                            if in_memory {
+                                return ipc::Response::error(
+                                    ipc::ErrorCode::DatabaseUnavailable,
+                                    "the index is kept in memory and cannot be swapped",
+                                );
+                            }
+                            // Holding the lock keeps flushes out until the new index is open
+                            let mut db = db.blocking_lock();
+                            if let Err(e) = db.swap_in(&paths::get_database_path(), &paths::get_rebuild_database_path()) {
+                                eprintln!("Failed to swap in the rebuilt index: {}", e);
+                                return ipc::Response::from_error(&e);
+                            }
+                            println!("Swapped in the rebuilt index");
+                        }
+                        ipc::Request::Metrics => {
+                            // Counted on demand rather than after every flush
+                            match db.blocking_lock().count_files() {
+                                Ok(count) => metrics.set_files_indexed(count as u64),
+                                Err(e) => return ipc::Response::from_error(&e),
+                            }
+                            return ipc::Response::Metrics { metrics: metrics.snapshot() };
+                        }
+                        ipc::Request::Query { query, limit } => {
+                            let db = db.blocking_lock();
+                            return ipc::query_response(&db, &query, limit, max_ipc_results, &search_options)
+                                .unwrap_or_else(|e| ipc::Response::from_error(&e));
+                        }
+                        ipc::Request::Launch { path } => {
+                            // Answered before touching the index so the panel never waits on a write
+                            launches.blocking_lock().push(path);
+                            return ipc::Response::Queued;
+                        }
+                    }
+                    ipc::Response::State {
+                        paused: paused.load(Ordering::Relaxed),
+                        degraded: watch_errors.lock().unwrap().degraded_paths(),
+                    }
+                })))
+            }
+            Err(e) => {
+                eprintln!("Warning: Failed to open control socket: {}", e);
+                None
+            }
+        };
+
+        // Schedule periodic reconciliation to repair drift from missed events
+        let reindex_task = self.config.reindex_interval().map(|period| {
+            let config = self.config.clone();
+            let db = Arc::clone(&self.db);
+            let paused = Arc::clone(&self.paused);
+            let metrics = Arc::clone(&self.metrics);
+            let disk_guard = std::sync::Mutex::new(self.disk_guard());
+            tasks::spawn_periodic_blocking(period, Arc::clone(&running), move || {
+                if paused.load(Ordering::Relaxed) || !disk_guard.lock().unwrap().check() {
+                    return;
+                }
+                let scan_started = Instant::now();
+                let scanner = Scanner::new(config.clone());
+                let entries = scanner.scan_parallel();
+                let roots = scanner.scan_roots();
+                metrics.set_last_scan_duration(scan_started.elapsed());
+                match db.blocking_lock().reconcile_since(&entries, &roots, config.indexing.aged_out_cutoff()) {
+                    Ok(stats) => println!(
+                        "Periodic reindex: {} updated, {} removed",
+                        stats.updated, stats.deleted
+                    ),
+                    Err(e) => eprintln!("Periodic reindex failed: {}", e),
+                }
+            })
+        });
+
+        // Re-check the longest-unverified entries a few at a time
+        let verify_task = (self.config.performance.verify_rate_per_sec > 0).then(|| {
+            let config = self.config.clone();
+            let db = Arc::clone(&self.db);
+            let paused = Arc::clone(&self.paused);
+            tasks::spawn_periodic_blocking(verify::VERIFY_PERIOD, Arc::clone(&running), move || {
+                if paused.load(Ordering::Relaxed) {
+                    return;
+                }
+                match verify::verify_stale_entries(&db.blocking_lock(), &config, verify::VERIFY_MIN_AGE) {
+                    Ok(stats) if stats.updated > 0 || stats.deleted > 0 => println!(
+                        "Verification: {} updated, {} removed",
+                        stats.updated, stats.deleted
+                    ),
+                    Ok(_) => {}
+                    Err(e) => eprintln!("Verification failed: {}", e),
+                }
+            })
+        });
+
+        // Index filesystems mounted under the include paths from now on
+        let mount_task = if self.config.indexing.watch_mounts {
+            match mounts::MountTable::load() {
+                Ok(table) => {
+                    let table = std::sync::Mutex::new(table);
+                    let config = self.config.clone();
+                    let db = Arc::clone(&self.db);
+                    let watcher = Arc::clone(&self.watcher);
+                    let paused = Arc::clone(&self.paused);
+                    Some(tasks::spawn_periodic_blocking(mounts::MOUNT_POLL_INTERVAL, Arc::clone(&running), move || {
+                        // Left unread while paused so the mounts are still new after resuming
+                        if paused.load(Ordering::Relaxed) {
+                            return;
+                        }
+                        let changes = match table.lock().unwrap().refresh() {
+                            Ok(changes) => changes,
+                            Err(e) => {
+                                eprintln!("Warning: Cannot read the mount table: {}", e);
+                                return;
+                            }
+                        };
+                        for mount in mounts::mounts_to_index(&changes.removed, &config) {
+                            println!("{} was unmounted; hiding its entries until it is back", mount.display());
+                            if let Err(e) = db.blocking_lock().set_mount_offline(&mount) {
+                                eprintln!("Warning: Failed to hide the entries of {}: {}", mount.display(), e);
+                            }
+                        }
+                        for mount in mounts::mounts_to_index(&changes.added, &config) {
+                            index_mount(&config, &db, &watcher, &mount);
+                        }
+                    }))
+                }
+                Err(e) => {
+                    eprintln!("Warning: Cannot read the mount table, new mounts will not be indexed: {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        // Finish an initial scan that ran out of time
+        if self.resume_scan {
+            let config = self.config.clone();
+            let cancel_scan = Arc::clone(&self.cancel_scan);
+            let metrics = Arc::clone(&self.metrics);
+            tokio::task::spawn_blocking(move || {
+                if let Err(e) = finish_initial_scan(config, cancel_scan, &metrics) {
+                    eprintln!("Background scan failed: {}", e);
+                }
+            });
+        }
 
         // Main event loop
         while running.load(Ordering::Relaxed) {
             tokio::select! {
                 // Process filesystem events
                 _ = tokio::time::sleep(Duration::from_millis(50)) => {
+                    // What is recorded about new changes follows the config file;
+                    // everything else is read once at startup
+                    if let Some(new_config) = config_watcher.as_mut().and_then(ConfigWatcher::try_recv_reload) {
+                        println!("Configuration reloaded; settings other than what is recorded for new changes apply after a restart");
+                        event_processor.lock().await.apply_entry_settings(&new_config);
+                        db.lock().await.set_min_query_length(new_config.ui.min_query_length);
+                    }
+
+                    // Apply pause/resume requests
+                    {
+                        let mut processor = event_processor.lock().await;
+                        let pause_requested = paused.load(Ordering::Relaxed);
+                        if pause_requested && !processor.is_paused() {
+                            println!("Indexing paused");
+                            processor.pause(pause_policy);
+                        } else if !pause_requested && processor.is_paused() {
+                            println!("Indexing resumed");
+                            processor.resume();
+                        }
+                    }
+
                     // Receive filesystem events from watcher
-                    let watcher = watcher.lock().await;
+                    let mut watcher = watcher.lock().await;
+                    watcher.retry_degraded();
                     while let Some(event) = watcher.try_recv_event() {
                         let mut processor = event_processor.lock().await;
                         processor.add_event(event);
+                        metrics.add_events_processed(1);
                     }
+                    let watched_roots: Vec<PathBuf> =
+                        watcher.watched_paths().iter().chain(watcher.polled_paths()).cloned().collect();
                     drop(watcher);
 
                     // Process pending events
@@ -167,49 +605,119 @@ impl IndexingDaemon {
                     let operations = processor.process_pending();
                     
                     // Enqueue operations
+                    let mut enqueued = Vec::new();
                     for operation in operations {
+                        let logged = journal.as_ref().map(|_| operation.clone());
                         if let Err(e) = processor.enqueue_operation(operation) {
                             eprintln!("Warning: Failed to enqueue operation: {}", e);
+                            metrics.add_dropped_operations(1);
+                        } else {
+                            enqueued.extend(logged);
+                        }
+                    }
+                    if let Some(journal) = &journal {
+                        if let Err(e) = journal.lock().await.append(&enqueued) {
+                            eprintln!("Warning: Failed to write the operation journal: {}", e);
+                        }
+                    }
+                    metrics.set_queue_depth(processor.queued_operation_count() as u64);
+
+                    // Recover from lost events; a later loss waits for the running rescan
+                    if !rescanning.load(Ordering::Relaxed) {
+                        let roots = processor.take_rescan_roots(&watched_roots);
+                        if !roots.is_empty() {
+                            rescanning.store(true, Ordering::Relaxed);
+                            let config = self.config.clone();
+                            let db = Arc::clone(&db);
+                            let rescanning = Arc::clone(&rescanning);
+                            tokio::task::spawn_blocking(move || {
+                                rescan_roots(&config, &db, &roots);
+                                rescanning.store(false, Ordering::Relaxed);
+                            });
                         }
                     }
                 }
 
                 // Flush operations to database periodically
                 _ = flush_timer.tick() => {
-                    let mut processor = event_processor.lock().await;
-                    let mut operations = Vec::new();
-                    
-                    // Dequeue up to batch_size operations
-                    for _ in 0..batch_size {
-                        if let Some(op) = processor.dequeue_operation() {
-                            operations.push(op);
-                        } else {
-                            break;
-                        }
+                    // Operations stay queued while the disk is nearly full
+                    if !disk_guard.check() {
+                        continue;
                     }
                     
+                    // Dequeue up to batch_size operations (none while paused)
+                    let mut processor = event_processor.lock().await;
+                    let operations = processor.take_batch(batch_size, coalesce);
+                    metrics.set_queue_depth(processor.queued_operation_count() as u64);
+                    drop(processor);
+                    
                     if !operations.is_empty() {
-                        match db.execute_batch(&operations) {
-                            Ok(()) => {
-                                // Success
+                        let result = db.lock().await.execute_batch(&operations);
+                        match result {
+                            Ok(changes) => {
+                                metrics.add_index_changes(&changes);
+                                // Drop the flushed operations from the journal
+                                if let Some(journal) = &journal {
+                                    let processor = event_processor.lock().await;
+                                    if let Err(e) = journal.lock().await.checkpoint(processor.queued_operations()) {
+                                        eprintln!("Warning: Failed to write the operation journal: {}", e);
+                                    }
+                                }
                             }
+                            // Retried on the next tick; the journal still holds them
                             Err(e) => {
                                 eprintln!("Error executing batch: {}", e);
+                                let mut processor = event_processor.lock().await;
+                                processor.requeue_front(operations);
+                                metrics.set_queue_depth(processor.queued_operation_count() as u64);
                             }
                         }
                     }
                 }
+
+                // Write launches reported over the control socket
+                _ = launch_timer.tick() => {
+                    if paused.load(Ordering::Relaxed) {
+                        continue;
+                    }
+                    let mut launches = launches.lock().await;
+                    if !launches.is_empty() {
+                        if let Err(e) = launches.flush(&*db.lock().await) {
+                            eprintln!("Error recording launches: {}", e);
+                        }
+                    }
+                }
             }
         }
 
+        if let Some(task) = reindex_task {
+            task.abort();
+        }
+        if let Some(task) = verify_task {
+            task.abort();
+        }
+        if let Some(task) = mount_task {
+            task.abort();
+        }
+        if let Some(task) = control_task {
+            task.abort();
+            let _ = std::fs::remove_file(paths::get_socket_path());
+        }
+
         println!("Daemon shutting down...");
         Ok(())
     }
 
+    /// Create a free-space guard for the database's filesystem
+    fn disk_guard(&self) -> DiskSpaceGuard {
+        DiskSpaceGuard::new(paths::get_database_dir(), self.config.performance.min_free_disk_mb)
+    }
+
     /// Gracefully shutdown the daemon
     async fn shutdown(&self) {
         println!("Shutting down gracefully...");
         self.running.store(false, Ordering::Relaxed);
+        self.cancel_scan.store(true, Ordering::Relaxed);
 
         // Flush remaining operations
         let mut processor = self.event_processor.lock().await;
@@ -217,60 +725,127 @@ impl IndexingDaemon {
         while let Some(op) = processor.dequeue_operation() {
             operations.push(op);
         }
+        if self.config.performance.coalesce_operations {
+            operations = EventProcessor::coalesce_operations(operations);
+        }
 
+        let mut flushed = true;
         if !operations.is_empty() {
             println!("Flushing {} pending operations...", operations.len());
-            if let Err(e) = self.db.execute_batch(&operations) {
+            if let Err(e) = self.db.lock().await.execute_batch(&operations) {
                 eprintln!("Error flushing operations: {}", e);
+                flushed = false;
+            }
+        }
+        // After a failed flush the journal keeps the operations for the next start
+        if let (Some(journal), true) = (&self.journal, flushed) {
+            if let Err(e) = journal.lock().await.checkpoint(&[]) {
+                eprintln!("Warning: Failed to clear the operation journal: {}", e);
+            }
+        }
+
+        let mut launches = self.launches.lock().await;
+        if !launches.is_empty() {
+            if let Err(e) = launches.flush(&*self.db.lock().await) {
+                eprintln!("Error recording launches: {}", e);
             }
         }
 
         println!("Shutdown complete");
     }
+}
 
-    /// Get standard application directories that contain .desktop files
-    fn get_application_directories(&self) -> Vec<PathBuf> {
-        let mut app_dirs = Vec::new();
-        
-        // System application directories
-        app_dirs.push(PathBuf::from("/usr/share/applications"));
-        app_dirs.push(PathBuf::from("/usr/local/share/applications"));
-        
-        // User application directory
-        if let Ok(home) = std::env::var("HOME") {
-            let home_path = PathBuf::from(&home);
-            app_dirs.push(home_path.join(".local/share/applications"));
+/// Rescan watched roots that lost filesystem events and repair their entries
+fn rescan_roots(config: &Config, db: &Mutex<Database>, roots: &[PathBuf]) {
+    let scanner = Scanner::new(config.clone());
+    for root in roots {
+        println!("Filesystem events were lost, rescanning {}", root.display());
+        let entries = scanner.scan_subtree(root);
+        match db.blocking_lock().reconcile_since(&entries, std::slice::from_ref(root), config.indexing.aged_out_cutoff()) {
+            Ok(stats) => println!(
+                "Rescanned {}: {} updated, {} removed",
+                root.display(),
+                stats.updated,
+                stats.deleted
+            ),
+            Err(e) => eprintln!("Rescan of {} failed: {}", root.display(), e),
         }
-        
-        // Snap applications
-        app_dirs.push(PathBuf::from("/var/lib/snapd/desktop/applications"));
-        if let Ok(home) = std::env::var("HOME") {
-            let home_path = PathBuf::from(&home);
-            app_dirs.push(home_path.join("snap"));
+    }
+}
+
+/// Scan a newly mounted filesystem into the index and start watching it
+///
+/// A filesystem mounted before has its hidden entries shown again, and
+/// reconciled with what is on it now.
+fn index_mount(config: &Config, db: &Mutex<Database>, watcher: &Mutex<FilesystemWatcher>, mount: &Path) {
+    println!("Indexing newly mounted {}", mount.display());
+    let entries = Scanner::new(config.clone()).scan_subtree(mount);
+    let db = db.blocking_lock();
+    let reconciled = db
+        .set_mount_online(mount)
+        .and_then(|_| db.reconcile_since(&entries, &[mount.to_path_buf()], config.indexing.aged_out_cutoff()));
+    drop(db);
+    match reconciled {
+        Ok(stats) => println!(
+            "Indexed {} from {}: {} updated, {} removed",
+            entries.len(),
+            mount.display(),
+            stats.updated,
+            stats.deleted
+        ),
+        Err(e) => {
+            eprintln!("Failed to index {}: {}", mount.display(), e);
+            return;
         }
-        
-        // Flatpak applications
-        app_dirs.push(PathBuf::from("/var/lib/flatpak/exports/share/applications"));
-        if let Ok(home) = std::env::var("HOME") {
-            let home_path = PathBuf::from(&home);
-            app_dirs.push(home_path.join(".local/share/flatpak/exports/share/applications"));
+    }
+
+    if let Err(e) = watcher.blocking_lock().watch_path(mount) {
+        eprintln!("Warning: {}", e);
+    }
+}
+
+/// Index the roots an interrupted initial scan did not reach
+///
+/// Runs on its own database connection so the event loop can keep writing
+/// while roots are walked. Stops without finishing when `cancel` is set; the
+/// next start resumes from the checkpoint.
+fn finish_initial_scan(
+    config: Config,
+    cancel: Arc<AtomicBool>,
+    metrics: &Metrics,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let db = Database::open(paths::get_database_path())?;
+    let batch_size = config.performance.batch_size;
+    let mut scanner = Scanner::new(config);
+    scanner.set_cancel_token(Arc::clone(&cancel));
+
+    let scan_started = std::time::Instant::now();
+    let mut indexed = 0;
+    let completed = scanner.scan_resumable(&db, |entries| {
+        for chunk in entries.chunks(batch_size) {
+            if cancel.load(Ordering::Relaxed) {
+                return Ok(false);
+            }
+            let operations: Vec<_> = chunk
+                .iter()
+                .map(|entry| models::IndexOperation::Add(entry.clone()))
+                .collect();
+            db.execute_batch(&operations)?;
         }
-        
-        // AppImage applications (common locations)
-        if let Ok(home) = std::env::var("HOME") {
-            let home_path = PathBuf::from(&home);
-            app_dirs.push(home_path.join("Applications"));
-            app_dirs.push(home_path.join(".local/bin"));
-            app_dirs.push(home_path.join("AppImages"));
-        }
-        app_dirs.push(PathBuf::from("/opt"));
-        
-        app_dirs
+        indexed += entries.len();
+        Ok(true)
+    })?;
+    scanner::store_scan_errors(&db, &scanner.recent_errors())?;
+
+    if completed {
+        metrics.set_last_scan_duration(scan_started.elapsed());
+        println!("Background scan complete: indexed {} more files/directories", indexed);
     }
+    Ok(())
 }
 
 /// Query and display indexing status
-async fn show_status() -> Result<(), Box<dyn std::error::Error>> {
+async fn show_status(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
     let db_path = paths::get_database_path();
     
     if !db_path.exists() {
@@ -280,45 +855,467 @@ async fn show_status() -> Result<(), Box<dyn std::error::Error>> {
 
     let db = Database::open(&db_path)?;
     let file_count = db.count_files()?;
+    let schema_version = db.schema_version()?;
 
     println!("NovaSearch Indexing Status");
     println!("===========================");
-    println!("Database: {}", db_path.display());
+    println!("Database: {}", show_path(config, &db_path));
     println!("Indexed files: {}", file_count);
-    println!("Status: Running");
+    match schema_version.cmp(&SCHEMA_VERSION) {
+        std::cmp::Ordering::Less => {
+            println!("Schema version: {} (migration to {} pending)", schema_version, SCHEMA_VERSION)
+        }
+        std::cmp::Ordering::Equal => println!("Schema version: {}", schema_version),
+        std::cmp::Ordering::Greater => println!(
+            "Schema version: {} (newer than supported version {})",
+            schema_version, SCHEMA_VERSION
+        ),
+    }
+    println!("SQLite: {}", rusqlite::version());
+    let socket_path = paths::get_socket_path();
+    let mut degraded = Vec::new();
+    let running = match ipc::send_request(&socket_path, ipc::Request::Status) {
+        Ok(ipc::Response::State { paused, degraded: paths }) => {
+            println!("Status: {}", if paused { "Paused" } else { "Running" });
+            degraded = paths;
+            true
+        }
+        Ok(_) | Err(_) => {
+            println!("Status: Not running");
+            false
+        }
+    };
+    if running {
+        if let Ok(ipc::Response::Metrics { metrics }) = ipc::send_request(&socket_path, ipc::Request::Metrics) {
+            println!("Events processed: {}", metrics.events_processed);
+            println!("Queued operations: {}", metrics.queue_depth);
+            println!("Dropped operations: {}", metrics.dropped_operations);
+            println!("Last scan: {:.1}s", metrics.last_scan_duration_ms as f64 / 1000.0);
+        }
+    }
+
+    if !degraded.is_empty() {
+        println!();
+        println!("Degraded watches (errors ignored, changes may be missed): {}", degraded.len());
+        for path in &degraded {
+            println!("  {}", show_path(config, path));
+        }
+    }
+
+    let scan_errors = scanner::load_scan_errors(&db)?;
+    if !scan_errors.is_empty() {
+        println!();
+        println!("Recent scan errors: {}", scan_errors.len());
+        for error in scan_errors.iter().rev().take(10) {
+            match &error.path {
+                Some(path) => println!("  {}: {}", show_path(config, path), error.message),
+                None => println!("  {}", error.message),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Print the paths that could not be watched or fell back to polling
+fn print_watch_report(report: WatchReport) {
+    if !report.errors.is_empty() {
+        eprintln!("Warning: Some paths could not be watched:");
+        for error in report.errors {
+            eprintln!("  {}", error);
+        }
+    }
+    if !report.skipped.is_empty() {
+        eprintln!("Warning: Watch limit reached, polling {} paths instead:", report.skipped.len());
+        for path in &report.skipped {
+            eprintln!("  {}", path.display());
+        }
+    }
+}
+
+/// Ask the running daemon to pause or resume indexing
+fn send_control(request: ipc::Request) -> Result<(), Box<dyn std::error::Error>> {
+    let response = ipc::send_request(&paths::get_socket_path(), request)
+        .map_err(|e| format!("Could not reach the daemon (is it running?): {}", e))?;
+    match response {
+        ipc::Response::State { paused: true, .. } => println!("Indexing paused"),
+        ipc::Response::State { paused: false, .. } => println!("Indexing running"),
+        ipc::Response::Error { error } => return Err(error.message.into()),
+        other => return Err(format!("Unexpected response from daemon: {:?}", other).into()),
+    }
+    Ok(())
+}
+
+/// Print the running daemon's metrics in Prometheus text format
+fn show_metrics() -> Result<(), Box<dyn std::error::Error>> {
+    let response = ipc::send_request(&paths::get_socket_path(), ipc::Request::Metrics)
+        .map_err(|e| format!("Could not reach the daemon (is it running?): {}", e))?;
+    match response {
+        ipc::Response::Metrics { metrics } => print!("{}", metrics.render_prometheus()),
+        ipc::Response::Error { error } => return Err(error.message.into()),
+        other => return Err(format!("Unexpected response from daemon: {:?}", other).into()),
+    }
+    Ok(())
+}
+
+/// Run the pipeline self-test and print the result of each stage
+fn self_test() -> Result<(), Box<dyn std::error::Error>> {
+    let results = selftest::run_self_test();
+    for result in &results {
+        match &result.outcome {
+            Ok(summary) => println!("PASS  {:<12} {}", result.name, summary),
+            Err(error) => println!("FAIL  {:<12} {}", result.name, error),
+        }
+    }
+
+    if results.iter().all(|result| result.passed()) {
+        println!("Self-test passed");
+        Ok(())
+    } else {
+        Err("Self-test failed".into())
+    }
+}
+
+/// Print the configured top-level directories with tildes expanded
+///
+/// Paths are always printed in full, since checking the expansion is the point.
+fn show_configured_paths(config: &Config) {
+    let configured = Scanner::new(config.clone()).configured_paths();
+    for (kind, heading) in [
+        (ConfiguredPathKind::Include, "Include paths:"),
+        (ConfiguredPathKind::Applications, "Application directories:"),
+    ] {
+        let paths: Vec<_> = configured.iter().filter(|path| path.kind == kind).collect();
+        if paths.is_empty() {
+            continue;
+        }
+
+        println!("{}", heading);
+        for path in paths {
+            let mut notes = Vec::new();
+            if !path.exists {
+                notes.push("missing");
+            }
+            if !path.recursive {
+                notes.push("not recursive");
+            }
+            if path.covered {
+                notes.push("scanned with the include paths");
+            }
+            if !path.watched {
+                notes.push("scanned, not watched");
+            }
+            if notes.is_empty() {
+                println!("  {}", path.path.display());
+            } else {
+                println!("  {} ({})", path.path.display(), notes.join(", "));
+            }
+        }
+    }
+}
+
+/// Render a path for command output following the `ui` settings
+fn show_path(config: &Config, path: &Path) -> String {
+    match std::env::var_os("HOME") {
+        Some(home) if config.ui.shorten_home_paths => {
+            display_path(&collapse_home(path, Path::new(&home)), config.ui.path_display)
+        }
+        _ => display_path(path, config.ui.path_display),
+    }
+}
+
+/// Search the index and print matching paths
+fn search(
+    config: &Config,
+    query: &str,
+    options: &SearchOptions,
+    filters: &[ResultFilter],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let db = Database::open(paths::get_database_path())?;
+
+    for entry in db.search_filtered(query, options, filters)? {
+        println!("{}", show_path(config, &entry.path));
+    }
+
+    Ok(())
+}
+
+/// Search the stored text of files and print matching paths, best matches first
+fn search_content(
+    config: &Config,
+    query: &str,
+    limit: usize,
+    filters: &[ResultFilter],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let db = Database::open(paths::get_database_path())?;
+
+    for entry in db.search_content(query, limit)? {
+        if filters.iter().all(|filter| filter(&entry)) {
+            println!("{}", show_path(config, &entry.path));
+        }
+    }
+
+    Ok(())
+}
+
+/// Print the recent activity feed, newest first
+fn show_recent(config: &Config, count: usize) -> Result<(), Box<dyn std::error::Error>> {
+    let db = Database::open(paths::get_database_path())?;
+
+    for entry in db.recent_activity(count, config.ui.recent_launch_weight)? {
+        println!("{}", show_path(config, &entry.path));
+    }
+
+    Ok(())
+}
+
+/// Print what the index stores for `path`, and with `recursive` for everything below it
+fn show_entries(config: &Config, path: &Path, recursive: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let db = Database::open(paths::get_database_path())?;
+    // Stored paths are absolute, without `.` components or a trailing slash
+    let path: PathBuf = std::path::absolute(path)?.components().collect();
+
+    let mut entries: Vec<_> = db.get_entry_by_path(&path)?.into_iter().collect();
+    if recursive {
+        entries.extend(db.entries_under_prefix(&path)?);
+    }
+    if entries.is_empty() {
+        return Err(format!("{} is not indexed", path.display()).into());
+    }
+
+    for (i, entry) in entries.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!("{}", show_path(config, &entry.path));
+        println!("  type:       {}", entry.file_type.as_str());
+        println!("  size:       {} ({} bytes)", format_size(entry.size), entry.size);
+        println!("  modified:   {}", format_timestamp(entry.modified_time));
+        println!("  indexed:    {}", format_timestamp(entry.indexed_time));
+        println!("  executable: {}", if entry.is_executable { "yes" } else { "no" });
+        match db.get_file_usage(&entry.path)? {
+            Some((count, last_launched)) => println!(
+                "  launches:   {} (last {})",
+                count,
+                format_timestamp(std::time::UNIX_EPOCH + Duration::from_secs(last_launched.max(0) as u64))
+            ),
+            None => println!("  launches:   0"),
+        }
+    }
+
+    Ok(())
+}
+
+/// Print the largest indexed files with human-readable sizes
+fn show_largest(config: &Config, count: usize) -> Result<(), Box<dyn std::error::Error>> {
+    let db = Database::open(paths::get_database_path())?;
+
+    for entry in db.largest_files(count)? {
+        println!("{:>10}  {}", format_size(entry.size), show_path(config, &entry.path));
+    }
 
     Ok(())
 }
 
 /// Force a full re-index
-async fn reindex(config: Config) -> Result<(), Box<dyn std::error::Error>> {
+async fn reindex(config: Config, force: bool) -> Result<(), Box<dyn std::error::Error>> {
     println!("Starting full re-index...");
 
     let db_path = paths::get_database_path();
     let db = Database::open(&db_path)?;
 
-    // Clear existing index
-    println!("Clearing existing index...");
-    db.connection().execute("DELETE FROM files", [])?;
-
     // Perform scan
     println!("Scanning filesystem...");
     let scanner = Scanner::new(config.clone());
-    let entries = scanner.scan();
+    let mut entries = scanner.scan_parallel();
     println!("Found {} files/directories", entries.len());
 
-    // Batch insert
+    // Files the scan skipped for their age stay indexed, as with a rescan
+    if let Some(cutoff) = config.indexing.aged_out_cutoff() {
+        entries.extend(db.aged_out_entries(cutoff, &entries)?);
+    }
+
+    // Swap the index contents only if the scan looks complete
     println!("Indexing files...");
-    let batch_size = config.performance.batch_size;
-    for chunk in entries.chunks(batch_size) {
-        let operations: Vec<_> = chunk
-            .iter()
-            .map(|entry| models::IndexOperation::Add(entry.clone()))
-            .collect();
-        db.execute_batch(&operations)?;
+    let min_fraction = if force { 0.0 } else { config.indexing.reindex_min_fraction };
+    match db.replace_all(&entries, min_fraction)? {
+        ReplaceOutcome::Replaced { previous, indexed } => {
+            scanner::store_scan_errors(&db, &scanner.recent_errors())?;
+            println!("Re-index complete ({} entries, previously {})", indexed, previous);
+        }
+        ReplaceOutcome::Refused { previous, scanned } => {
+            eprintln!(
+                "Warning: scan found only {} entries but {} are indexed; keeping the existing index",
+                scanned, previous
+            );
+            eprintln!("Check that the include paths are mounted, or rerun with --force");
+            return Err("re-index aborted".into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Build a new index next to the current one and swap it in when complete
+///
+/// A running daemon is asked to do the swap, so it reopens the new index
+/// instead of writing to the replaced file; otherwise the file is moved into
+/// place here. Launch statistics are carried over for paths still indexed.
+fn reindex_offline(config: Config, force: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let db_path = paths::get_database_path();
+    let new_path = paths::get_rebuild_database_path();
+
+    println!("Scanning filesystem...");
+    let scanner = Scanner::new(config.clone());
+    let mut entries = scanner.scan_parallel();
+    println!("Found {} files/directories", entries.len());
+
+    // Copied while the current index is only read, along with the files the
+    // scan skipped for their age, which stay indexed as with a rescan
+    let mut usage_stats = Vec::new();
+    paths::ensure_database_dir()?;
+    let previous = {
+        let db = Database::open(&db_path)?;
+        snapshot::export_usage_stats(&db, &mut usage_stats)?;
+        if let Some(cutoff) = config.indexing.aged_out_cutoff() {
+            entries.extend(db.aged_out_entries(cutoff, &entries)?);
+        }
+        db.count_files()? as usize
+    };
+    let min_fraction = if force { 0.0 } else { config.indexing.reindex_min_fraction };
+    if min_fraction > 0.0 && previous > 0 && (entries.len() as f64) < previous as f64 * min_fraction {
+        eprintln!(
+            "Warning: scan found only {} entries but {} are indexed; keeping the existing index",
+            entries.len(),
+            previous
+        );
+        eprintln!("Check that the include paths are mounted, or rerun with --force");
+        return Err("re-index aborted".into());
+    }
+
+    // Anything left by an earlier rebuild that did not finish is discarded
+    println!("Building the new index...");
+    for suffix in ["", "-wal", "-shm"] {
+        let mut path = new_path.clone().into_os_string();
+        path.push(suffix);
+        if Path::new(&path).exists() {
+            std::fs::remove_file(&path)?;
+        }
+    }
+    {
+        let mut new_db = Database::open(&new_path)?;
+        new_db.apply_config(&config)?;
+        new_db.replace_all(&entries, 0.0)?;
+        scanner::store_scan_errors(&new_db, &scanner.recent_errors())?;
+        snapshot::import_usage_stats(&new_db, usage_stats.as_slice())?;
+    }
+
+    let socket_path = paths::get_socket_path();
+    if std::os::unix::net::UnixStream::connect(&socket_path).is_err() {
+        database::install_database_file(&new_path, &db_path)?;
+    } else {
+        match ipc::send_request(&socket_path, ipc::Request::SwapIndex)? {
+            ipc::Response::State { .. } => println!("The daemon switched to the new index"),
+            ipc::Response::Error { error } => return Err(error.message.into()),
+            other => return Err(format!("Unexpected response from daemon: {:?}", other).into()),
+        }
     }
+    println!("Re-index complete ({} entries, previously {})", entries.len(), previous);
+
+    Ok(())
+}
+
+/// Export an index snapshot to a file
+fn export_snapshot(output: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let db = Database::open(paths::get_database_path())?;
+    let writer = BufWriter::new(File::create(output)?);
+    let count = snapshot::export_snapshot(&db, writer)?;
+    println!("Exported {} entries to {}", count, output.display());
+    Ok(())
+}
+
+/// Export usage statistics to a file
+fn export_usage_stats(output: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let db = Database::open(paths::get_database_path())?;
+    let writer = BufWriter::new(File::create(output)?);
+    let count = snapshot::export_usage_stats(&db, writer)?;
+    println!("Exported usage statistics for {} files to {}", count, output.display());
+    Ok(())
+}
+
+/// Delete all usage statistics
+fn clear_usage_stats() -> Result<(), Box<dyn std::error::Error>> {
+    let db = Database::open(paths::get_database_path())?;
+    db.clear_usage_stats()?;
+    println!("Cleared usage statistics");
+    Ok(())
+}
+
+/// Remove entries for files deleted while nothing was watching them
+fn prune_missing(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    let db = Database::open(paths::get_database_path())?;
+    let removed = db.prune_missing_with_progress(&config.indexing.include_roots(), |checked, total| {
+        if checked < total {
+            println!("Checked {}/{} entries", checked, total);
+        }
+    })?;
+    println!("Removed {} entries for files no longer on disk", removed);
+    Ok(())
+}
+
+/// Import usage statistics from a file
+fn import_usage_stats(input: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let db = Database::open(paths::get_database_path())?;
+    let reader = BufReader::new(File::open(input)?);
+    let stats = snapshot::import_usage_stats(&db, reader)?;
+    println!(
+        "Imported usage statistics for {} files ({} paths not in the index were skipped)",
+        stats.imported, stats.skipped
+    );
+    Ok(())
+}
+
+/// Count a launch through the running daemon, or directly when none is running
+fn record_launch(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let path = std::path::absolute(path)?;
+    match ipc::send_request(&paths::get_socket_path(), ipc::Request::Launch { path: path.clone() }) {
+        Ok(ipc::Response::Queued) => return Ok(()),
+        Ok(ipc::Response::Error { error }) => return Err(error.message.into()),
+        Ok(response) => return Err(format!("Unexpected response from daemon: {:?}", response).into()),
+        // No daemon listening; the index is not being written, so write it here
+        Err(ipc::IpcError::ConnectionError(_)) => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    let db = Database::open(paths::get_database_path())?;
+    if db.record_file_launches(&[(path.clone(), database::current_timestamp())])? == 0 {
+        return Err(format!("{} is not indexed", path.display()).into());
+    }
+    Ok(())
+}
+
+/// Diff two snapshot files
+fn diff_snapshots(old: &Path, new: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let old_reader = BufReader::new(File::open(old)?);
+    let new_reader = BufReader::new(File::open(new)?);
+    let diff = snapshot::diff_snapshots(old_reader, new_reader)?;
+
+    for path in &diff.added {
+        println!("+ {}", path);
+    }
+    for path in &diff.removed {
+        println!("- {}", path);
+    }
+    for path in &diff.modified {
+        println!("~ {}", path);
+    }
+    println!(
+        "{} added, {} removed, {} modified",
+        diff.added.len(),
+        diff.removed.len(),
+        diff.modified.len()
+    );
 
-    println!("Re-index complete");
     Ok(())
 }
 
@@ -326,6 +1323,8 @@ async fn reindex(config: Config) -> Result<(), Box<dyn std::error::Error>> {
 fn show_version() {
     println!("NovaSearch Daemon");
     println!("Version: 0.1.0");
+    println!("Schema version: {}", SCHEMA_VERSION);
+    println!("SQLite: {}", rusqlite::version());
     println!("Build: Release");
     println!("Platform: Linux");
 }
@@ -376,40 +1375,114 @@ fn show_author() {
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
+    if let Some(dir) = cli.data_dir {
+        paths::set_data_dir(dir);
+    }
 
     // Load configuration
-    let config_path = cli.config.unwrap_or_else(|| paths::get_config_path());
-    let config = Config::load_from_file(&config_path)?;
+    let config_path = cli.config.unwrap_or_else(paths::get_config_path);
+    let mut config = Config::load_from_file(&config_path)?;
+    if cli.full_paths {
+        config.ui.shorten_home_paths = false;
+    }
 
     match cli.command {
-        Commands::Start => {
-            // Set up signal handlers for graceful shutdown
-            let running = Arc::new(AtomicBool::new(true));
-            let r = running.clone();
+        Commands::Start { in_memory_fallback } => {
+            let mut daemon = IndexingDaemon::new(config.clone(), config_path.clone(), in_memory_fallback).await?;
 
+            // Set up signal handlers for graceful shutdown; a signal during the
+            // initial scan stops it as well
+            let running = Arc::clone(&daemon.running);
+            let cancel_scan = Arc::clone(&daemon.cancel_scan);
             ctrlc::set_handler(move || {
                 println!("\nReceived shutdown signal");
-                r.store(false, Ordering::Relaxed);
+                running.store(false, Ordering::Relaxed);
+                cancel_scan.store(true, Ordering::Relaxed);
             })?;
 
-            // Create and initialize daemon
-            let mut daemon = IndexingDaemon::new(config.clone()).await?;
             daemon.initialize().await?;
 
-            // Set the daemon's running flag to match our signal handler
-            daemon.running = running;
-
-            // Run the daemon
-            daemon.run().await?;
+            // Run the daemon, unless it was stopped during initialization
+            if daemon.running.load(Ordering::Relaxed) {
+                daemon.run().await?;
+            }
 
             // Shutdown
             daemon.shutdown().await;
         }
         Commands::Status => {
-            show_status().await?;
+            show_status(&config).await?;
+        }
+        Commands::Pause => {
+            send_control(ipc::Request::Pause)?;
+        }
+        Commands::Resume => {
+            send_control(ipc::Request::Resume)?;
+        }
+        Commands::Metrics => {
+            show_metrics()?;
+        }
+        Commands::Search { query, limit, exclude_prefixes, existing, extensions, sort, content } => {
+            let options = SearchOptions {
+                limit: limit.unwrap_or(config.ui.max_results),
+                exclude_prefixes,
+                extensions,
+                order_by: sort,
+                scope: config.ui.search_scope,
+                directory_weight: config.ranking.directory_weight,
+                ..SearchOptions::default()
+            };
+            let mut filters: Vec<ResultFilter> = Vec::new();
+            if existing {
+                filters.push(Box::new(database::exists_on_disk));
+            }
+            if content {
+                search_content(&config, &query, options.limit, &filters)?;
+            } else {
+                search(&config, &query, &options, &filters)?;
+            }
+        }
+        Commands::Largest { count } => {
+            show_largest(&config, count)?;
+        }
+        Commands::Recent { count } => {
+            show_recent(&config, count)?;
+        }
+        Commands::Show { path, recursive } => {
+            show_entries(&config, &path, recursive)?;
+        }
+        Commands::Reindex { force } => {
+            reindex(config, force).await?;
+        }
+        Commands::ReindexOffline { force } => {
+            reindex_offline(config, force)?;
+        }
+        Commands::Export { output } => {
+            export_snapshot(&output)?;
+        }
+        Commands::ClearStats => {
+            clear_usage_stats()?;
+        }
+        Commands::Prune => {
+            prune_missing(&config)?;
+        }
+        Commands::ExportStats { output } => {
+            export_usage_stats(&output)?;
+        }
+        Commands::ImportStats { input } => {
+            import_usage_stats(&input)?;
+        }
+        Commands::RecordLaunch { path } => {
+            record_launch(&path)?;
+        }
+        Commands::Diff { old, new } => {
+            diff_snapshots(&old, &new)?;
+        }
+        Commands::Paths => {
+            show_configured_paths(&config);
         }
-        Commands::Reindex => {
-            reindex(config).await?;
+        Commands::SelfTest => {
+            self_test()?;
         }
         Commands::Version => {
             show_version();
@@ -0,0 +1,200 @@
+use crate::database::BatchResult;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Counters and gauges updated by the running daemon
+///
+/// Shared behind an `Arc`; every update is a single relaxed atomic operation,
+/// so the event loop can record freely without taking locks.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    files_indexed: AtomicU64,
+    events_processed: AtomicU64,
+    queue_depth: AtomicU64,
+    dropped_operations: AtomicU64,
+    last_scan_duration_ms: AtomicU64,
+    entries_added: AtomicU64,
+    entries_updated: AtomicU64,
+    entries_deleted: AtomicU64,
+    entries_moved: AtomicU64,
+}
+
+impl Metrics {
+    /// Create a registry with every value at zero
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the number of entries currently in the index
+    pub fn set_files_indexed(&self, count: u64) {
+        self.files_indexed.store(count, Ordering::Relaxed);
+    }
+
+    /// Count filesystem events received from the watcher
+    pub fn add_events_processed(&self, count: u64) {
+        self.events_processed.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Set the number of operations waiting to be written
+    pub fn set_queue_depth(&self, depth: u64) {
+        self.queue_depth.store(depth, Ordering::Relaxed);
+    }
+
+    /// Count operations that were discarded because the queue was full
+    pub fn add_dropped_operations(&self, count: u64) {
+        self.dropped_operations.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Record how long the most recent full scan took
+    pub fn set_last_scan_duration(&self, duration: Duration) {
+        self.last_scan_duration_ms.store(duration.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// Count the index rows a flushed batch changed
+    pub fn add_index_changes(&self, changes: &BatchResult) {
+        self.entries_added.fetch_add(changes.added as u64, Ordering::Relaxed);
+        self.entries_updated.fetch_add(changes.updated as u64, Ordering::Relaxed);
+        self.entries_deleted.fetch_add(changes.deleted as u64, Ordering::Relaxed);
+        self.entries_moved.fetch_add(changes.moved as u64, Ordering::Relaxed);
+    }
+
+    /// Copy the current values
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            files_indexed: self.files_indexed.load(Ordering::Relaxed),
+            events_processed: self.events_processed.load(Ordering::Relaxed),
+            queue_depth: self.queue_depth.load(Ordering::Relaxed),
+            dropped_operations: self.dropped_operations.load(Ordering::Relaxed),
+            last_scan_duration_ms: self.last_scan_duration_ms.load(Ordering::Relaxed),
+            entries_added: self.entries_added.load(Ordering::Relaxed),
+            entries_updated: self.entries_updated.load(Ordering::Relaxed),
+            entries_deleted: self.entries_deleted.load(Ordering::Relaxed),
+            entries_moved: self.entries_moved.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Point-in-time copy of the daemon's metrics, as sent over the control socket
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MetricsSnapshot {
+    pub files_indexed: u64,
+    pub events_processed: u64,
+    pub queue_depth: u64,
+    pub dropped_operations: u64,
+    pub last_scan_duration_ms: u64,
+    /// Index rows changed by flushed batches, by kind of change
+    #[serde(default)]
+    pub entries_added: u64,
+    #[serde(default)]
+    pub entries_updated: u64,
+    #[serde(default)]
+    pub entries_deleted: u64,
+    #[serde(default)]
+    pub entries_moved: u64,
+}
+
+impl MetricsSnapshot {
+    /// Render the metrics in the Prometheus text exposition format
+    pub fn render_prometheus(&self) -> String {
+        let metrics: [(&str, &str, &str, String); 9] = [
+            (
+                "novasearch_files_indexed",
+                "gauge",
+                "Number of entries in the index.",
+                self.files_indexed.to_string(),
+            ),
+            (
+                "novasearch_events_processed_total",
+                "counter",
+                "Filesystem events received from the watcher.",
+                self.events_processed.to_string(),
+            ),
+            (
+                "novasearch_queue_depth",
+                "gauge",
+                "Index operations waiting to be written.",
+                self.queue_depth.to_string(),
+            ),
+            (
+                "novasearch_dropped_operations_total",
+                "counter",
+                "Index operations discarded because the queue was full.",
+                self.dropped_operations.to_string(),
+            ),
+            (
+                "novasearch_last_scan_duration_seconds",
+                "gauge",
+                "Duration of the most recent full scan.",
+                format!("{:.3}", self.last_scan_duration_ms as f64 / 1000.0),
+            ),
+            (
+                "novasearch_entries_added_total",
+                "counter",
+                "Entries added to the index by flushed operations.",
+                self.entries_added.to_string(),
+            ),
+            (
+                "novasearch_entries_updated_total",
+                "counter",
+                "Indexed entries updated by flushed operations.",
+                self.entries_updated.to_string(),
+            ),
+            (
+                "novasearch_entries_deleted_total",
+                "counter",
+                "Entries deleted from the index by flushed operations.",
+                self.entries_deleted.to_string(),
+            ),
+            (
+                "novasearch_entries_moved_total",
+                "counter",
+                "Indexed entries moved to a new path by flushed operations.",
+                self.entries_moved.to_string(),
+            ),
+        ];
+
+        let mut text = String::new();
+        for (name, kind, help, value) in metrics {
+            let _ = writeln!(text, "# HELP {} {}", name, help);
+            let _ = writeln!(text, "# TYPE {} {}", name, kind);
+            let _ = writeln!(text, "{} {}", name, value);
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_prometheus() {
+        let metrics = Metrics::new();
+        metrics.set_files_indexed(1234);
+        metrics.add_events_processed(10);
+        metrics.add_events_processed(5);
+        metrics.set_queue_depth(3);
+        metrics.add_dropped_operations(2);
+        metrics.set_last_scan_duration(Duration::from_millis(1500));
+        metrics.add_index_changes(&BatchResult { added: 4, updated: 1, deleted: 2, moved: 0 });
+
+        let text = metrics.snapshot().render_prometheus();
+        let samples: Vec<&str> = text.lines().filter(|line| !line.starts_with('#')).collect();
+        assert_eq!(samples, vec![
+            "novasearch_files_indexed 1234",
+            "novasearch_events_processed_total 15",
+            "novasearch_queue_depth 3",
+            "novasearch_dropped_operations_total 2",
+            "novasearch_last_scan_duration_seconds 1.500",
+            "novasearch_entries_added_total 4",
+            "novasearch_entries_updated_total 1",
+            "novasearch_entries_deleted_total 2",
+            "novasearch_entries_moved_total 0",
+        ]);
+        assert!(text.contains("# TYPE novasearch_events_processed_total counter\n"));
+        assert!(text.contains("# TYPE novasearch_queue_depth gauge\n"));
+        assert!(text.ends_with('\n'));
+    }
+}
@@ -1,8 +1,69 @@
-use std::path::PathBuf;
-use std::time::SystemTime;
+use serde::{Deserialize, Serialize};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// How far past the current time a modification time may lie before it counts as bogus
+///
+/// Covers clocks that disagree by a few hours, e.g. on network filesystems.
+pub const MAX_CLOCK_SKEW: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Freedesktop icon names for regular files, by lowercase extension
+const ICON_HINTS: &[(&str, &str)] = &[
+    ("pdf", "application-pdf"),
+    ("txt", "text-x-generic"),
+    ("md", "text-x-generic"),
+    ("log", "text-x-generic"),
+    ("html", "text-html"),
+    ("htm", "text-html"),
+    ("sh", "text-x-script"),
+    ("py", "text-x-script"),
+    ("pl", "text-x-script"),
+    ("rb", "text-x-script"),
+    ("rs", "text-x-generic"),
+    ("c", "text-x-generic"),
+    ("h", "text-x-generic"),
+    ("desktop", "application-x-desktop"),
+    ("appimage", "application-x-executable"),
+    ("png", "image-x-generic"),
+    ("jpg", "image-x-generic"),
+    ("jpeg", "image-x-generic"),
+    ("gif", "image-x-generic"),
+    ("svg", "image-x-generic"),
+    ("webp", "image-x-generic"),
+    ("mp3", "audio-x-generic"),
+    ("ogg", "audio-x-generic"),
+    ("flac", "audio-x-generic"),
+    ("wav", "audio-x-generic"),
+    ("mp4", "video-x-generic"),
+    ("mkv", "video-x-generic"),
+    ("webm", "video-x-generic"),
+    ("avi", "video-x-generic"),
+    ("zip", "package-x-generic"),
+    ("tar", "package-x-generic"),
+    ("gz", "package-x-generic"),
+    ("xz", "package-x-generic"),
+    ("7z", "package-x-generic"),
+    ("deb", "package-x-generic"),
+    ("ttf", "font-x-generic"),
+    ("otf", "font-x-generic"),
+    ("odt", "x-office-document"),
+    ("doc", "x-office-document"),
+    ("docx", "x-office-document"),
+    ("ods", "x-office-spreadsheet"),
+    ("xls", "x-office-spreadsheet"),
+    ("xlsx", "x-office-spreadsheet"),
+    ("csv", "x-office-spreadsheet"),
+    ("odp", "x-office-presentation"),
+    ("ppt", "x-office-presentation"),
+    ("pptx", "x-office-presentation"),
+];
+
+/// Icon name for files nothing more specific is known about
+const GENERIC_ICON: &str = "application-x-generic";
 
 /// File type enumeration
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum FileType {
     Regular,
     Directory,
@@ -20,6 +81,7 @@ impl FileType {
         }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Self {
         match s {
             "regular" => FileType::Regular,
@@ -30,8 +92,73 @@ impl FileType {
     }
 }
 
+/// Check whether metadata describes a regular file with an execute bit set
+pub fn is_executable(metadata: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+}
+
+/// Get the owning user and group ids from `metadata`
+pub fn ownership(metadata: &std::fs::Metadata) -> (u32, u32) {
+    use std::os::unix::fs::MetadataExt;
+
+    (metadata.uid(), metadata.gid())
+}
+
+/// Check whether `time` lies further in the future than `MAX_CLOCK_SKEW` allows
+///
+/// Such times come from a wrong clock on whatever wrote the file; they are
+/// indexed as they are, but never count as recent activity.
+pub fn is_future_dated(time: SystemTime) -> bool {
+    time.duration_since(SystemTime::now()).is_ok_and(|ahead| ahead > MAX_CLOCK_SKEW)
+}
+
+/// Read a file's content for full-text indexing
+///
+/// Returns `None` for files larger than `max_size` bytes, files that cannot be
+/// read, and binary files (those containing a NUL byte). Invalid UTF-8 is
+/// replaced rather than rejecting the file.
+pub fn read_text_content(path: &Path, max_size: u64) -> Option<String> {
+    use std::io::Read;
+
+    let mut bytes = Vec::new();
+    // One byte over the limit tells a file that grew since it was stat'ed
+    std::fs::File::open(path).ok()?.take(max_size + 1).read_to_end(&mut bytes).ok()?;
+    if bytes.len() as u64 > max_size || bytes.contains(&0) {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Get the name a path is indexed under
+///
+/// This is the last path component. Paths without one (the root `/`, or a
+/// path ending in `..`) are indexed under the whole path instead of being
+/// skipped, so a watched or scanned root can still be found by name.
+pub fn entry_name(path: &Path) -> String {
+    path.file_name().unwrap_or(path.as_os_str()).to_string_lossy().to_string()
+}
+
+/// Get the deepest of `roots` containing `path`
+pub fn containing_root<'a>(path: &Path, roots: &'a [PathBuf]) -> Option<&'a Path> {
+    roots
+        .iter()
+        .filter(|root| path.starts_with(root))
+        .max_by_key(|root| root.components().count())
+        .map(PathBuf::as_path)
+}
+
+/// Check whether an include root is there to look in
+///
+/// An empty directory counts as missing: that is all an unmounted mount
+/// point leaves behind, and its entries must not be taken for deleted files.
+pub fn root_present(root: &Path) -> bool {
+    std::fs::read_dir(root).is_ok_and(|mut entries| entries.next().is_some())
+}
+
 /// Represents a file entry in the index
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileEntry {
     pub id: Option<i64>,
     pub filename: String,
@@ -40,6 +167,21 @@ pub struct FileEntry {
     pub modified_time: SystemTime,
     pub file_type: FileType,
     pub indexed_time: SystemTime,
+    /// Whether any execute permission bit is set (regular files only)
+    pub is_executable: bool,
+    /// Extended attributes as (name, value); `None` when they were not read
+    pub xattrs: Option<Vec<(String, String)>>,
+    /// Root of the git repository containing the entry; `None` outside a
+    /// repository, when detection is off, or when it was not loaded
+    pub repo_root: Option<PathBuf>,
+    /// Text content for full-text search; `None` when it was not read
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    /// Owning user and group ids; `None` when ownership indexing is off or they were not loaded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gid: Option<u32>,
 }
 
 impl FileEntry {
@@ -59,15 +201,200 @@ impl FileEntry {
             modified_time,
             file_type,
             indexed_time: SystemTime::now(),
+            is_executable: false,
+            xattrs: None,
+            repo_root: None,
+            content: None,
+            uid: None,
+            gid: None,
+        }
+    }
+
+    /// Get a freedesktop icon name for showing the entry, e.g. `folder` or `application-pdf`
+    ///
+    /// Picked from the file type and the extension, falling back to
+    /// `application-x-executable` for other executables and
+    /// `application-x-generic` for anything unknown. The file is not read.
+    pub fn icon_hint(&self) -> &'static str {
+        match self.file_type {
+            FileType::Directory => return "folder",
+            FileType::Symlink => return "inode-symlink",
+            FileType::Other => return GENERIC_ICON,
+            FileType::Regular => {}
+        }
+
+        let extension = Path::new(&self.filename)
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        if let Some(extension) = extension {
+            if let Some((_, icon)) = ICON_HINTS.iter().find(|(known, _)| *known == extension) {
+                return icon;
+            }
+        }
+        if self.is_executable {
+            "application-x-executable"
+        } else {
+            GENERIC_ICON
         }
     }
 }
 
-/// Indexing operation types
+/// Entries are equal when they describe the same path with the same size and
+/// modification time. `id` and `indexed_time` are bookkeeping of the index and
+/// do not participate; `filename` and `file_type` are derived from the path and
+/// its metadata, so a real change to them also shows up in size or mtime.
+/// `xattrs`, `repo_root`, `content`, `uid` and `gid` are optional extra data and do not participate either.
+impl PartialEq for FileEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path
+            && self.size == other.size
+            && self.modified_time == other.modified_time
+    }
+}
+
+impl Eq for FileEntry {}
+
+impl Hash for FileEntry {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.path.hash(state);
+        self.size.hash(state);
+        self.modified_time.hash(state);
+    }
+}
+
+/// A file entry together with its usage statistics
+#[derive(Debug, Clone)]
+pub struct FileEntryWithStats {
+    pub entry: FileEntry,
+    pub launch_count: i32,
+    pub last_launched: Option<SystemTime>,
+}
+
+/// Why a search result matched the query
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchKind {
+    /// The filename equals the query
+    Exact,
+    /// The filename starts with the query
+    Prefix,
+    /// The query appears elsewhere in the filename
+    Substring,
+}
+
+impl MatchKind {
+    /// Map a ranking tier from the search query (0 = best) to a match kind
+    pub fn from_tier(tier: i64) -> Self {
+        match tier {
+            0 => MatchKind::Exact,
+            1 => MatchKind::Prefix,
+            _ => MatchKind::Substring,
+        }
+    }
+
+    /// Relevance score for this kind of match given the file's launch count
+    ///
+    /// Each kind gets a whole-number base (exact highest) and launches add a
+    /// bonus below 1, so scores order results exactly like the search does.
+    pub fn score(&self, launch_count: i64) -> f64 {
+        let base = match self {
+            MatchKind::Exact => 3.0,
+            MatchKind::Prefix => 2.0,
+            MatchKind::Substring => 1.0,
+        };
+        let launches = launch_count.max(0) as f64;
+        base + launches / (launches + 1.0)
+    }
+}
+
+/// A ranked search result
 #[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub entry: FileEntry,
+    pub score: f64,
+    pub match_kind: MatchKind,
+}
+
+/// Indexing operation types
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum IndexOperation {
     Add(FileEntry),
     Update(FileEntry),
     Delete(PathBuf),
     Move { from: PathBuf, to: PathBuf },
+    /// Refresh only the permission-derived fields of an indexed file
+    UpdatePermissions { path: PathBuf, is_executable: bool },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn entry(path: &str, size: u64, mtime_secs: u64) -> FileEntry {
+        let path = PathBuf::from(path);
+        FileEntry::new(
+            path.file_name().unwrap().to_string_lossy().to_string(),
+            path,
+            size,
+            UNIX_EPOCH + Duration::from_secs(mtime_secs),
+            FileType::Regular,
+        )
+    }
+
+    #[test]
+    fn test_file_entry_equality_ignores_bookkeeping() {
+        let a = entry("/home/user/notes.txt", 100, 1_700_000_000);
+        let mut b = a.clone();
+        b.id = Some(42);
+        b.indexed_time = a.indexed_time + Duration::from_secs(3600);
+
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_file_entry_equality_detects_changes() {
+        let a = entry("/home/user/notes.txt", 100, 1_700_000_000);
+
+        assert_ne!(a, entry("/home/user/other.txt", 100, 1_700_000_000));
+        assert_ne!(a, entry("/home/user/notes.txt", 200, 1_700_000_000));
+        assert_ne!(a, entry("/home/user/notes.txt", 100, 1_700_000_001));
+    }
+
+    #[test]
+    fn test_file_entry_hash_set() {
+        let a = entry("/home/user/notes.txt", 100, 1_700_000_000);
+        let mut reindexed = a.clone();
+        reindexed.id = Some(7);
+
+        let mut set = HashSet::new();
+        set.insert(a);
+        assert!(!set.insert(reindexed));
+        assert!(set.insert(entry("/home/user/notes.txt", 150, 1_700_000_000)));
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn test_icon_hint() {
+        let hint = |name: &str, file_type: FileType| {
+            FileEntry::new(name.to_string(), PathBuf::from("/home/user").join(name), 1, UNIX_EPOCH, file_type).icon_hint()
+        };
+
+        assert_eq!(hint("Documents", FileType::Directory), "folder");
+        assert_eq!(hint("archive.pdf", FileType::Directory), "folder");
+        assert_eq!(hint("Invoice.PDF", FileType::Regular), "application-pdf");
+        assert_eq!(hint("notes.txt", FileType::Regular), "text-x-generic");
+        assert_eq!(hint("holiday.jpeg", FileType::Regular), "image-x-generic");
+        assert_eq!(hint("firefox.desktop", FileType::Regular), "application-x-desktop");
+        assert_eq!(hint("current", FileType::Symlink), "inode-symlink");
+
+        // Unknown types
+        assert_eq!(hint("data.xyz", FileType::Regular), "application-x-generic");
+        assert_eq!(hint("README", FileType::Regular), "application-x-generic");
+        assert_eq!(hint("socket", FileType::Other), "application-x-generic");
+
+        let mut tool = entry("/home/user/bin/tool", 1, 0);
+        tool.is_executable = true;
+        assert_eq!(tool.icon_hint(), "application-x-executable");
+    }
 }
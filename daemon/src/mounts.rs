@@ -0,0 +1,170 @@
+use crate::config::Config;
+use crate::watcher::FilesystemWatcher;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Mount table of the daemon's mount namespace
+const MOUNTINFO_PATH: &str = "/proc/self/mountinfo";
+
+/// How often the daemon compares the mount table, when `indexing.watch_mounts` is on
+pub const MOUNT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Mount points that appeared or disappeared between two reads of the mount table
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MountChanges {
+    pub added: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+}
+
+/// The set of mount points seen last, for spotting filesystems mounted later
+#[derive(Debug, Default)]
+pub struct MountTable {
+    mounts: HashSet<PathBuf>,
+}
+
+impl MountTable {
+    /// Read the current mount table as the baseline
+    pub fn load() -> std::io::Result<Self> {
+        let mut table = MountTable::default();
+        table.update(&std::fs::read_to_string(MOUNTINFO_PATH)?);
+        Ok(table)
+    }
+
+    /// Re-read the mount table and report what changed since the last read
+    pub fn refresh(&mut self) -> std::io::Result<MountChanges> {
+        Ok(self.update(&std::fs::read_to_string(MOUNTINFO_PATH)?))
+    }
+
+    /// Replace the known mount points with those in `mountinfo`, returning the difference
+    pub fn update(&mut self, mountinfo: &str) -> MountChanges {
+        let current: HashSet<PathBuf> = parse_mountinfo(mountinfo).into_iter().collect();
+
+        let mut changes = MountChanges {
+            added: current.difference(&self.mounts).cloned().collect(),
+            removed: self.mounts.difference(&current).cloned().collect(),
+        };
+        changes.added.sort();
+        changes.removed.sort();
+
+        self.mounts = current;
+        changes
+    }
+}
+
+/// Get the mount points listed in the contents of a `mountinfo` file
+///
+/// The mount point is the fifth field of each line, with spaces, tabs,
+/// newlines and backslashes escaped as octal (`\040` and so on).
+pub fn parse_mountinfo(mountinfo: &str) -> Vec<PathBuf> {
+    mountinfo
+        .lines()
+        .filter_map(|line| line.split(' ').nth(4))
+        .map(|field| PathBuf::from(unescape_octal(field)))
+        .collect()
+}
+
+/// Pick the mount points, new or removed, whose contents belong in the index
+///
+/// A mount counts when it is an include path itself, or lies under a recursive
+/// include path without being excluded (hidden, matching an exclude pattern,
+/// or inside the daemon's own data directory).
+pub fn mounts_to_index(added: &[PathBuf], config: &Config) -> Vec<PathBuf> {
+    let include_paths = config.expand_paths();
+    added
+        .iter()
+        .filter(|mount| {
+            include_paths.iter().any(|root| {
+                *mount == root
+                    || (mount.starts_with(root)
+                        && config.indexing.is_recursive_root(root)
+                        && !FilesystemWatcher::is_excluded(mount, &config.indexing))
+            })
+        })
+        .cloned()
+        .collect()
+}
+
+/// Undo the kernel's `\ooo` escaping of mount paths
+fn unescape_octal(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut output = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let digits = bytes.get(i + 1..i + 4).filter(|d| d.iter().all(|b| (b'0'..=b'7').contains(b)));
+        match digits {
+            Some(digits) if bytes[i] == b'\\' => {
+                let value = digits.iter().fold(0u32, |acc, d| acc * 8 + u32::from(d - b'0'));
+                output.push(value as u8);
+                i += 4;
+            }
+            _ => {
+                output.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&output).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BEFORE: &str = "\
+22 1 8:2 / / rw,relatime shared:1 - ext4 /dev/sda2 rw
+23 22 0:21 / /proc rw,nosuid,nodev,noexec,relatime shared:12 - proc proc rw
+";
+
+    const AFTER: &str = "\
+22 1 8:2 / / rw,relatime shared:1 - ext4 /dev/sda2 rw
+40 22 8:17 / /home/user/My\\040Drive rw,nosuid,nodev shared:30 - vfat /dev/sdb1 rw
+41 40 8:18 / /home/user/My\\040Drive/inner rw shared:31 - ext4 /dev/sdb2 rw
+";
+
+    #[test]
+    fn test_parse_mountinfo() {
+        assert_eq!(
+            parse_mountinfo(AFTER),
+            vec![
+                PathBuf::from("/"),
+                PathBuf::from("/home/user/My Drive"),
+                PathBuf::from("/home/user/My Drive/inner"),
+            ]
+        );
+        assert_eq!(unescape_octal("a\\011b\\134c\\9"), "a\tb\\c\\9");
+    }
+
+    #[test]
+    fn test_mount_table_diff() {
+        let mut table = MountTable::default();
+        table.update(BEFORE);
+
+        let changes = table.update(AFTER);
+        assert_eq!(
+            changes.added,
+            vec![PathBuf::from("/home/user/My Drive"), PathBuf::from("/home/user/My Drive/inner")]
+        );
+        assert_eq!(changes.removed, vec![PathBuf::from("/proc")]);
+
+        assert_eq!(table.update(AFTER), MountChanges::default());
+    }
+
+    #[test]
+    fn test_mounts_to_index() {
+        let mut config = Config::default();
+        config.indexing.include_paths = vec!["/home/user".into(), "/srv/shared".into()];
+        config.indexing.index_applications = false;
+
+        let added = vec![
+            PathBuf::from("/home/user/usb"),
+            PathBuf::from("/home/user/.cache/fuse"),
+            PathBuf::from("/srv/shared"),
+            PathBuf::from("/media/other"),
+        ];
+        assert_eq!(
+            mounts_to_index(&added, &config),
+            vec![PathBuf::from("/home/user/usb"), PathBuf::from("/srv/shared")]
+        );
+    }
+}
@@ -1,7 +1,21 @@
 use std::path::PathBuf;
+use std::sync::OnceLock;
 
-/// Get the database directory path: ~/.local/share/novasearch/
+static DATA_DIR_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Keep the database and control socket in `dir` instead of the default location
+///
+/// Only the first call has an effect; it must happen before any path is used.
+pub fn set_data_dir(dir: PathBuf) {
+    let _ = DATA_DIR_OVERRIDE.set(dir);
+}
+
+/// Get the database directory path: ~/.local/share/novasearch/, unless
+/// overridden with `set_data_dir`
 pub fn get_database_dir() -> PathBuf {
+    if let Some(dir) = DATA_DIR_OVERRIDE.get() {
+        return dir.clone();
+    }
     let home = std::env::var("HOME").expect("HOME environment variable not set");
     PathBuf::from(home)
         .join(".local")
@@ -14,6 +28,22 @@ pub fn get_database_path() -> PathBuf {
     get_database_dir().join("index.db")
 }
 
+/// Get the path an offline re-index builds the new database at:
+/// ~/.local/share/novasearch/index.db.new
+pub fn get_rebuild_database_path() -> PathBuf {
+    get_database_dir().join("index.db.new")
+}
+
+/// Get the operation journal path: ~/.local/share/novasearch/pending.log
+pub fn get_journal_path() -> PathBuf {
+    get_database_dir().join("pending.log")
+}
+
+/// Get the control socket path: ~/.local/share/novasearch/daemon.sock
+pub fn get_socket_path() -> PathBuf {
+    get_database_dir().join("daemon.sock")
+}
+
 /// Get the config directory path: ~/.config/novasearch/
 pub fn get_config_dir() -> PathBuf {
     let home = std::env::var("HOME").expect("HOME environment variable not set");
@@ -27,6 +57,14 @@ pub fn get_config_path() -> PathBuf {
     get_config_dir().join("config.toml")
 }
 
+/// Get the directories the daemon itself writes to, which are never indexed or watched
+///
+/// Indexing them would make every database write show up as a filesystem
+/// event, which in turn causes another write.
+pub fn internal_dirs() -> Vec<PathBuf> {
+    vec![get_database_dir(), get_config_dir()]
+}
+
 /// Ensure the database directory exists
 pub fn ensure_database_dir() -> std::io::Result<()> {
     let dir = get_database_dir();
@@ -55,6 +93,12 @@ mod tests {
         assert!(db_path.to_string_lossy().contains(".local/share/novasearch/index.db"));
     }
 
+    #[test]
+    fn test_socket_path() {
+        let socket_path = get_socket_path();
+        assert!(socket_path.to_string_lossy().contains(".local/share/novasearch/daemon.sock"));
+    }
+
     #[test]
     fn test_config_path() {
         let config_path = get_config_path();
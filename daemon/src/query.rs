@@ -0,0 +1,275 @@
+use crate::database::{escape_like, row_to_file_entry, system_time_to_timestamp_nanos, Database, NameNormalization};
+use crate::models::{FileEntry, FileType};
+use rusqlite::types::Value;
+use rusqlite::{params_from_iter, Result as SqliteResult};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Composable file query; only the filters that were set end up in the SQL
+///
+/// Results are ordered by filename (case-insensitive), then path.
+#[derive(Debug, Clone, Default)]
+pub struct QueryBuilder {
+    name_contains: Option<String>,
+    file_types: Vec<FileType>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    modified_after: Option<SystemTime>,
+    modified_before: Option<SystemTime>,
+    extensions: Vec<String>,
+    under_path: Option<PathBuf>,
+    limit: Option<usize>,
+    offset: usize,
+}
+
+impl QueryBuilder {
+    /// Create a query that matches every indexed entry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only match filenames containing `text` (case-insensitive for ASCII)
+    pub fn name_contains(mut self, text: &str) -> Self {
+        self.name_contains = Some(text.to_string());
+        self
+    }
+
+    /// Only match this file type; repeat to allow several
+    pub fn file_type(mut self, file_type: FileType) -> Self {
+        self.file_types.push(file_type);
+        self
+    }
+
+    /// Only match sizes within `min..=max` bytes; `None` leaves that side open
+    pub fn size_range(mut self, min: Option<u64>, max: Option<u64>) -> Self {
+        self.min_size = min;
+        self.max_size = max;
+        self
+    }
+
+    /// Only match modification times within `after..=before`; `None` leaves that side open
+    pub fn modified_range(mut self, after: Option<SystemTime>, before: Option<SystemTime>) -> Self {
+        self.modified_after = after;
+        self.modified_before = before;
+        self
+    }
+
+    /// Only match filenames with this extension (leading dot optional, case-insensitive);
+    /// repeat to allow several
+    pub fn extension(mut self, extension: &str) -> Self {
+        self.extensions.push(extension.trim_start_matches('.').to_string());
+        self
+    }
+
+    /// Only match entries located below `path`
+    pub fn under_path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.under_path = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Return at most `limit` entries
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Skip the first `offset` matching entries
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Run the query against `db`
+    pub fn execute(&self, db: &Database) -> SqliteResult<Vec<FileEntry>> {
+        let (sql, values) = self.to_sql(db.name_normalization());
+
+        let mut stmt = db.connection().prepare_cached(&sql)?;
+        let entries = stmt.query_map(params_from_iter(values), row_to_file_entry)?;
+
+        entries.collect()
+    }
+
+    /// Build the SQL statement and its positional parameters
+    fn to_sql(&self, normalization: NameNormalization) -> (String, Vec<Value>) {
+        let mut conditions: Vec<String> = Vec::new();
+        let mut values: Vec<Value> = Vec::new();
+
+        if let Some(text) = &self.name_contains {
+            values.push(Value::Text(escape_like(&normalization.normalize(text))));
+            conditions.push("normalized_name LIKE '%' || ? || '%' ESCAPE '\\'".to_string());
+        }
+
+        if !self.file_types.is_empty() {
+            let placeholders = vec!["?"; self.file_types.len()].join(", ");
+            values.extend(self.file_types.iter().map(|t| Value::Text(t.as_str().to_string())));
+            conditions.push(format!("file_type IN ({})", placeholders));
+        }
+
+        if let Some(min) = self.min_size {
+            values.push(Value::Integer(min as i64));
+            conditions.push("size >= ?".to_string());
+        }
+        if let Some(max) = self.max_size {
+            values.push(Value::Integer(max as i64));
+            conditions.push("size <= ?".to_string());
+        }
+
+        if let Some(after) = self.modified_after {
+            let (secs, nsec) = system_time_to_timestamp_nanos(after);
+            values.extend([Value::Integer(secs), Value::Integer(nsec)]);
+            conditions.push("(modified_time, modified_nsec) >= (?, ?)".to_string());
+        }
+        if let Some(before) = self.modified_before {
+            let (secs, nsec) = system_time_to_timestamp_nanos(before);
+            values.extend([Value::Integer(secs), Value::Integer(nsec)]);
+            conditions.push("(modified_time, modified_nsec) <= (?, ?)".to_string());
+        }
+
+        if !self.extensions.is_empty() {
+            let alternatives = vec!["normalized_name LIKE '%.' || ? ESCAPE '\\'"; self.extensions.len()].join(" OR ");
+            values.extend(self.extensions.iter().map(|e| Value::Text(escape_like(&normalization.normalize(e)))));
+            conditions.push(format!("({})", alternatives));
+        }
+
+        // Same range test as the search exclusions: below "p/" and before "p0"
+        if let Some(path) = &self.under_path {
+            let path = path.to_string_lossy();
+            let path = path.trim_end_matches('/');
+            values.push(Value::Text(format!("{}/", path)));
+            values.push(Value::Text(format!("{}0", path)));
+            conditions.push("(path >= ? AND path < ?)".to_string());
+        }
+
+        let mut sql = String::from(
+            "SELECT id, filename, path, size, modified_time, file_type, indexed_time, modified_nsec,
+                    is_executable
+             FROM files",
+        );
+        if !conditions.is_empty() {
+            sql.push_str("\n             WHERE ");
+            sql.push_str(&conditions.join("\n               AND "));
+        }
+        sql.push_str("\n             ORDER BY filename COLLATE NOCASE, path");
+
+        // SQLite needs a LIMIT before OFFSET; -1 means no limit
+        if self.limit.is_some() || self.offset > 0 {
+            values.push(Value::Integer(self.limit.map_or(-1, |limit| limit as i64)));
+            values.push(Value::Integer(self.offset as i64));
+            sql.push_str("\n             LIMIT ? OFFSET ?");
+        }
+
+        (sql, values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tempfile::NamedTempFile;
+
+    fn test_db() -> (NamedTempFile, Database) {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+
+        let base = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let files = [
+            ("report.pdf", "/home/user/docs/report.pdf", 2_000_000, 0, FileType::Regular),
+            ("Report-old.PDF", "/home/user/docs/old/Report-old.PDF", 500, 10, FileType::Regular),
+            ("notes.txt", "/home/user/docs/notes.txt", 100, 20, FileType::Regular),
+            ("photo.jpg", "/home/user/pictures/photo.jpg", 3_000_000, 30, FileType::Regular),
+            ("docs", "/home/user/docs", 4096, 40, FileType::Directory),
+            ("report.pdf", "/home/user/docs-archive/report.pdf", 1_000, 50, FileType::Regular),
+        ];
+        for (filename, path, size, age, file_type) in files {
+            let entry = FileEntry::new(
+                filename.to_string(),
+                PathBuf::from(path),
+                size,
+                base + Duration::from_secs(age),
+                file_type,
+            );
+            db.insert_file(&entry).unwrap();
+        }
+
+        (temp_file, db)
+    }
+
+    fn paths(entries: Vec<FileEntry>) -> Vec<String> {
+        entries.into_iter().map(|e| e.path.to_string_lossy().into_owned()).collect()
+    }
+
+    #[test]
+    fn test_empty_query_matches_everything() {
+        let (_file, db) = test_db();
+        assert_eq!(QueryBuilder::new().execute(&db).unwrap().len(), 6);
+    }
+
+    #[test]
+    fn test_name_extension_and_scope() {
+        let (_file, db) = test_db();
+
+        let results = QueryBuilder::new()
+            .name_contains("report")
+            .extension(".pdf")
+            .under_path("/home/user/docs")
+            .execute(&db)
+            .unwrap();
+        assert_eq!(paths(results), vec![
+            "/home/user/docs/old/Report-old.PDF",
+            "/home/user/docs/report.pdf",
+        ]);
+    }
+
+    #[test]
+    fn test_name_with_extension_set() {
+        let (_file, db) = test_db();
+
+        let results = QueryBuilder::new()
+            .name_contains("o")
+            .extension("txt")
+            .extension(".JPG")
+            .execute(&db)
+            .unwrap();
+        assert_eq!(paths(results), vec![
+            "/home/user/docs/notes.txt",
+            "/home/user/pictures/photo.jpg",
+        ]);
+    }
+
+    #[test]
+    fn test_type_size_and_modified_ranges() {
+        let (_file, db) = test_db();
+        let base = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        let results = QueryBuilder::new()
+            .file_type(FileType::Regular)
+            .size_range(Some(1_000), None)
+            .modified_range(Some(base), Some(base + Duration::from_secs(30)))
+            .execute(&db)
+            .unwrap();
+        assert_eq!(paths(results), vec![
+            "/home/user/pictures/photo.jpg",
+            "/home/user/docs/report.pdf",
+        ]);
+
+        let results = QueryBuilder::new()
+            .file_type(FileType::Directory)
+            .size_range(None, Some(4096))
+            .execute(&db)
+            .unwrap();
+        assert_eq!(paths(results), vec!["/home/user/docs"]);
+    }
+
+    #[test]
+    fn test_limit_and_offset() {
+        let (_file, db) = test_db();
+
+        let all = paths(QueryBuilder::new().execute(&db).unwrap());
+        let page = paths(QueryBuilder::new().limit(2).offset(1).execute(&db).unwrap());
+        assert_eq!(page, all[1..3].to_vec());
+
+        let rest = paths(QueryBuilder::new().offset(4).execute(&db).unwrap());
+        assert_eq!(rest, all[4..].to_vec());
+    }
+}
@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Resolves the git repository an indexed path belongs to
+///
+/// Every directory looked at while walking up is remembered, so the files of
+/// one directory (and its siblings) share a single walk to the repository root.
+#[derive(Debug, Default)]
+pub struct RepoRootCache {
+    roots: HashMap<PathBuf, Option<PathBuf>>,
+}
+
+impl RepoRootCache {
+    /// Create an empty cache
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Find the nearest enclosing directory that contains a `.git` entry
+    ///
+    /// A directory is looked up from itself, anything else from its parent.
+    /// `.git` may be a directory or, for worktrees and submodules, a file.
+    pub fn repo_root(&mut self, path: &Path, is_dir: bool) -> Option<PathBuf> {
+        let start = if is_dir { path } else { path.parent()? };
+        self.resolve(start)
+    }
+
+    fn resolve(&mut self, dir: &Path) -> Option<PathBuf> {
+        if let Some(root) = self.roots.get(dir) {
+            return root.clone();
+        }
+
+        let root = if dir.join(".git").exists() {
+            Some(dir.to_path_buf())
+        } else {
+            dir.parent().and_then(|parent| self.resolve(parent))
+        };
+        self.roots.insert(dir.to_path_buf(), root.clone());
+        root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_repo_root_resolution() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = temp_dir.path().join("project");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::create_dir_all(repo.join("src/nested")).unwrap();
+        fs::create_dir_all(temp_dir.path().join("loose")).unwrap();
+
+        let mut cache = RepoRootCache::new();
+        assert_eq!(cache.repo_root(&repo.join("src/nested/main.rs"), false), Some(repo.clone()));
+        assert_eq!(cache.repo_root(&repo.join("src"), true), Some(repo.clone()));
+        assert_eq!(cache.repo_root(&repo, true), Some(repo.clone()));
+        assert_eq!(cache.repo_root(&temp_dir.path().join("loose/notes.txt"), false), None);
+
+        // Resolved directories are answered from the cache
+        fs::remove_dir(repo.join(".git")).unwrap();
+        assert_eq!(cache.repo_root(&repo.join("src/lib.rs"), false), Some(repo));
+    }
+}
@@ -1,10 +1,17 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::SystemTime;
+use std::time::{Instant, SystemTime};
 use walkdir::{WalkDir, DirEntry};
 use glob::Pattern;
-use crate::models::{FileEntry, FileType};
-use crate::config::Config;
+use crate::models::{self, FileEntry, FileType};
+use crate::config::{self, Config};
+use crate::database::Database;
+use crate::repo::RepoRootCache;
+use rusqlite::Result as SqliteResult;
 
 /// Progress tracking for filesystem scanning
 #[derive(Debug, Clone)]
@@ -12,6 +19,15 @@ pub struct ScanProgress {
     pub files_scanned: usize,
     pub directories_scanned: usize,
     pub errors_encountered: usize,
+    /// Errors in the current scan past `performance.max_logged_scan_errors`, counted but not logged
+    pub errors_suppressed: usize,
+    pub paths_too_long: usize,
+    /// Entries that vanished or were replaced between being listed and stat'ed
+    pub changed_during_scan: usize,
+    /// Entries modified further in the future than `models::MAX_CLOCK_SKEW` allows
+    pub future_dated: usize,
+    /// Directories of the current scan reached again through a symlink and not walked twice
+    pub already_scanned: usize,
     pub current_path: Option<PathBuf>,
 }
 
@@ -21,15 +37,201 @@ impl ScanProgress {
             files_scanned: 0,
             directories_scanned: 0,
             errors_encountered: 0,
+            errors_suppressed: 0,
+            paths_too_long: 0,
+            changed_during_scan: 0,
+            future_dated: 0,
+            already_scanned: 0,
             current_path: None,
         }
     }
 }
 
+impl Default for ScanProgress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Largest extended attribute value that is indexed
+const MAX_XATTR_VALUE_BYTES: usize = 4096;
+
+/// Number of scan errors retained for troubleshooting
+const MAX_RECENT_SCAN_ERRORS: usize = 100;
+
+/// A path that could not be scanned, kept for troubleshooting
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanError {
+    pub path: Option<PathBuf>,
+    pub message: String,
+}
+
+/// Metadata key under which the last scan's errors are persisted
+const SCAN_ERRORS_KEY: &str = "scan_errors";
+
+/// Metadata key under which an in-progress resumable scan is checkpointed
+const SCAN_STATE_KEY: &str = "scan_state";
+
+/// Checkpoint of a resumable scan: the roots that were fully indexed so far
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanState {
+    pub completed_roots: Vec<PathBuf>,
+}
+
+/// Read the extended attributes of `path` whose names start with an allowlisted prefix
+///
+/// Symlinks are not followed. Values larger than `MAX_XATTR_VALUE_BYTES` are
+/// skipped, and filesystems without xattr support simply yield no attributes.
+pub fn read_xattrs(path: &Path, allowlist: &[String]) -> Vec<(String, String)> {
+    let names = match xattr::list(path) {
+        Ok(names) => names,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut xattrs = Vec::new();
+    for name in names {
+        let name = name.to_string_lossy().into_owned();
+        if !allowlist.iter().any(|prefix| name.starts_with(prefix.as_str())) {
+            continue;
+        }
+        if let Ok(Some(value)) = xattr::get(path, &name) {
+            if value.len() <= MAX_XATTR_VALUE_BYTES {
+                xattrs.push((name, String::from_utf8_lossy(&value).into_owned()));
+            }
+        }
+    }
+    xattrs.sort();
+    xattrs
+}
+
+/// What a configured top-level directory is and how the daemon treats it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfiguredPathKind {
+    /// An entry of `include_paths` or `include_paths_file`
+    Include,
+    /// A directory searched for applications when `index_applications` is on
+    Applications,
+}
+
+/// A top-level directory from the configuration, resolved to an absolute path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfiguredPath {
+    pub path: PathBuf,
+    pub kind: ConfiguredPathKind,
+    /// Whether directories below it are indexed too
+    pub recursive: bool,
+    /// Whether it is watched for changes, not only scanned
+    pub watched: bool,
+    /// Whether an include path already scans it, so it is not walked on its own
+    pub covered: bool,
+    pub exists: bool,
+}
+
+/// Get system-wide application directories shared by all users
+pub fn system_application_directories() -> Vec<PathBuf> {
+    vec![
+        PathBuf::from("/usr/share/applications"),
+        PathBuf::from("/usr/local/share/applications"),
+        // Snap applications
+        PathBuf::from("/var/lib/snapd/desktop/applications"),
+        // Flatpak applications
+        PathBuf::from("/var/lib/flatpak/exports/share/applications"),
+        // AppImage applications
+        PathBuf::from("/opt"),
+    ]
+}
+
+/// Get per-user application directories under the home directory
+pub fn user_application_directories() -> Vec<PathBuf> {
+    let home = match std::env::var("HOME") {
+        Ok(home) => PathBuf::from(home),
+        Err(_) => return Vec::new(),
+    };
+
+    vec![
+        home.join(".local/share/applications"),
+        // Snap applications
+        home.join("snap"),
+        // Flatpak applications
+        home.join(".local/share/flatpak/exports/share/applications"),
+        // AppImage applications (common locations)
+        home.join("Applications"),
+        home.join(".local/bin"),
+        home.join("AppImages"),
+    ]
+}
+
+/// Persist scan errors in the database so `status` can report them later
+pub fn store_scan_errors(db: &Database, errors: &[ScanError]) -> SqliteResult<()> {
+    let value = serde_json::to_string(errors)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    db.set_metadata(SCAN_ERRORS_KEY, &value)
+}
+
+/// Load the scan errors persisted by the last scan
+pub fn load_scan_errors(db: &Database) -> SqliteResult<Vec<ScanError>> {
+    Ok(db.get_metadata(SCAN_ERRORS_KEY)?
+        .and_then(|value| serde_json::from_str(&value).ok())
+        .unwrap_or_default())
+}
+
+/// Load the checkpoint left by an unfinished resumable scan, if any
+pub fn load_scan_state(db: &Database) -> SqliteResult<Option<ScanState>> {
+    Ok(db.get_metadata(SCAN_STATE_KEY)?
+        .and_then(|value| serde_json::from_str(&value).ok()))
+}
+
+/// Persist the checkpoint of a resumable scan
+fn store_scan_state(db: &Database, state: &ScanState) -> SqliteResult<()> {
+    let value = serde_json::to_string(state)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    db.set_metadata(SCAN_STATE_KEY, &value)
+}
+
+/// Check whether a followed symlink leads to a directory outside all of `roots`
+///
+/// Links whose target cannot be resolved count as outside.
+fn links_outside(entry: &DirEntry, roots: &[PathBuf]) -> bool {
+    if !entry.path_is_symlink() || !entry.file_type().is_dir() {
+        return false;
+    }
+    match std::fs::canonicalize(entry.path()) {
+        Ok(target) => !roots.iter().any(|root| target.starts_with(root)),
+        Err(_) => true,
+    }
+}
+
+/// Check whether a walk error means the path no longer exists
+fn is_vanished(err: &walkdir::Error) -> bool {
+    err.io_error().is_some_and(|e| e.kind() == std::io::ErrorKind::NotFound)
+}
+
+/// Compiled `exclude_patterns` for one walk
+struct ExcludePatterns {
+    /// Patterns matched against entry names
+    names: Vec<Pattern>,
+    /// Patterns containing a `/`, matched against paths relative to `include_roots`
+    paths: Vec<Pattern>,
+    include_roots: Vec<PathBuf>,
+}
+
 /// Filesystem scanner for initial indexing
 pub struct Scanner {
     config: Config,
     progress: Arc<Mutex<ScanProgress>>,
+    recent_errors: Arc<Mutex<VecDeque<ScanError>>>,
+    /// (device, inode) of every directory entered by the current scan when following symlinks
+    visited_dirs: Mutex<HashSet<(u64, u64)>>,
+    /// Errors logged individually by the current scan
+    logged_errors: AtomicUsize,
+    /// Walks stop once this passes
+    deadline: Option<Instant>,
+    /// Walks stop once this is set
+    cancel_token: Option<Arc<AtomicBool>>,
+    /// Set when the consumer of a walk asks it to stop early
+    stopped: AtomicBool,
+    /// Git repository of each directory seen, when `detect_git_repos` is on
+    repo_roots: Mutex<RepoRootCache>,
 }
 
 impl Scanner {
@@ -38,76 +240,382 @@ impl Scanner {
         Scanner {
             config,
             progress: Arc::new(Mutex::new(ScanProgress::new())),
+            recent_errors: Arc::new(Mutex::new(VecDeque::new())),
+            visited_dirs: Mutex::new(HashSet::new()),
+            logged_errors: AtomicUsize::new(0),
+            deadline: None,
+            cancel_token: None,
+            stopped: AtomicBool::new(false),
+            repo_roots: Mutex::new(RepoRootCache::new()),
         }
     }
 
+    /// Stop walking once `deadline` passes, leaving the scan incomplete
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+    }
+
+    /// Stop walking once `token` is set (e.g. on shutdown), leaving the scan incomplete
+    pub fn set_cancel_token(&mut self, token: Arc<AtomicBool>) {
+        self.cancel_token = Some(token);
+    }
+
+    /// Check whether the deadline set with `set_deadline` has passed
+    pub fn timed_out(&self) -> bool {
+        self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// Check whether the walk should stop for a passed deadline or a cancellation
+    fn should_stop(&self) -> bool {
+        self.timed_out()
+            || self.stopped.load(Ordering::Relaxed)
+            || self.cancel_token.as_ref().is_some_and(|token| token.load(Ordering::Relaxed))
+    }
+
     /// Get a clone of the current progress
     pub fn get_progress(&self) -> ScanProgress {
         self.progress.lock().unwrap().clone()
     }
 
+    /// Get the most recent scan errors, oldest first
+    pub fn recent_errors(&self) -> Vec<ScanError> {
+        self.recent_errors.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Count an error and remember it, evicting the oldest once full
+    ///
+    /// Returns whether the caller should log the error; past
+    /// `performance.max_logged_scan_errors` in one scan the error is only
+    /// counted, and `warn_skipped_entries` reports how many were held back.
+    fn record_error(&self, path: Option<&Path>, message: String) -> bool {
+        let log = self.logged_errors.fetch_add(1, Ordering::Relaxed) < self.config.performance.max_logged_scan_errors;
+        {
+            let mut progress = self.progress.lock().unwrap();
+            progress.errors_encountered += 1;
+            if !log {
+                progress.errors_suppressed += 1;
+            }
+        }
+
+        let mut recent_errors = self.recent_errors.lock().unwrap();
+        if recent_errors.len() >= MAX_RECENT_SCAN_ERRORS {
+            recent_errors.pop_front();
+        }
+        recent_errors.push_back(ScanError {
+            path: path.map(Path::to_path_buf),
+            message,
+        });
+        log
+    }
+
+    /// Reset the state kept for the duration of a single scan
+    fn begin_scan(&self) {
+        self.visited_dirs.lock().unwrap().clear();
+        self.logged_errors.store(0, Ordering::Relaxed);
+        self.stopped.store(false, Ordering::Relaxed);
+        let mut progress = self.progress.lock().unwrap();
+        progress.errors_suppressed = 0;
+        progress.already_scanned = 0;
+    }
+
     /// Scan all configured directories and return file entries
+    ///
+    /// The same walk as `scan_parallel`, which with the default
+    /// `performance.scan_parallelism` of 1 visits one root at a time.
     pub fn scan(&self) -> Vec<FileEntry> {
-        let mut entries = Vec::new();
-        
-        // Always scan application directories first (regardless of user config)
-        let app_dirs = self.get_application_directories();
-        for path in app_dirs {
-            if path.exists() {
-                entries.extend(self.scan_application_directory(&path));
+        self.scan_parallel()
+    }
+
+    /// Scan all configured directories, walking roots concurrently
+    ///
+    /// Up to `performance.scan_parallelism` roots are scanned at once, each by a
+    /// single blocking thread. Application directories come first, then the
+    /// include paths, in configuration order whatever order the walks finish in.
+    pub fn scan_parallel(&self) -> Vec<FileEntry> {
+        self.begin_scan();
+        for path in self.config.expand_paths() {
+            if !path.exists() {
+                eprintln!("Warning: Include path does not exist: {}", path.display());
             }
         }
-        
-        // Then scan user-configured paths
-        let include_paths = self.config.expand_paths();
-        for path in include_paths {
-            if path.exists() {
-                entries.extend(self.scan_directory(&path));
+
+        let roots = self.scan_roots();
+        let mut results = vec![Vec::new(); roots.len()];
+        self.walk_roots(&roots, |index, entries| {
+            results[index] = entries;
+            true
+        });
+
+        self.warn_skipped_entries();
+        results.into_iter().flatten().collect()
+    }
+
+    /// Walk `roots` on up to `performance.scan_parallelism` threads
+    ///
+    /// Each root is walked as an application directory or an include path.
+    /// `on_root` runs on the calling thread with the index of a root in
+    /// `roots` and its entries, in the order the walks finish; returning
+    /// `false` stops the walks still running. A root is only handed to a
+    /// walker after the previous results were taken, so once the scan has to
+    /// stop no further root is started.
+    fn walk_roots<F>(&self, roots: &[PathBuf], mut on_root: F)
+    where
+        F: FnMut(usize, Vec<FileEntry>) -> bool,
+    {
+        let app_dirs = self.application_scan_directories();
+        let workers = self.config.performance.scan_parallelism.clamp(1, roots.len().max(1));
+        let (work_sender, work_receiver) = std::sync::mpsc::channel::<usize>();
+        let work_receiver = Mutex::new(work_receiver);
+        let (result_sender, result_receiver) = std::sync::mpsc::channel();
+
+        std::thread::scope(|scope| {
+            for _ in 0..workers {
+                let result_sender = result_sender.clone();
+                let (app_dirs, work_receiver) = (&app_dirs, &work_receiver);
+                scope.spawn(move || {
+                    while let Ok(index) = work_receiver.lock().unwrap().recv() {
+                        let root = &roots[index];
+                        let entries = if app_dirs.contains(root) {
+                            self.scan_application_directory(root)
+                        } else {
+                            self.scan_directory(root)
+                        };
+                        if result_sender.send((index, entries)).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+
+            let mut next_root = 0;
+            let mut in_flight = 0;
+            while next_root < roots.len() && in_flight < workers && !self.should_stop() {
+                let _ = work_sender.send(next_root);
+                next_root += 1;
+                in_flight += 1;
+            }
+            while in_flight > 0 {
+                let Ok((index, entries)) = result_receiver.recv() else {
+                    break;
+                };
+                in_flight -= 1;
+                if !on_root(index, entries) {
+                    self.stopped.store(true, Ordering::Relaxed);
+                    break;
+                }
+                if next_root < roots.len() && !self.should_stop() {
+                    let _ = work_sender.send(next_root);
+                    next_root += 1;
+                    in_flight += 1;
+                }
+            }
+            drop(work_sender);
+        });
+    }
+
+    /// Report entries the last scan skipped for being too long, changing mid-walk or being seen before
+    fn warn_skipped_entries(&self) {
+        let progress = self.progress.lock().unwrap();
+        if progress.errors_suppressed > 0 {
+            eprintln!("Warning: {} more scan errors were not logged individually", progress.errors_suppressed);
+        }
+        if progress.paths_too_long > 0 {
+            eprintln!(
+                "Warning: Skipped {} paths longer than {} bytes",
+                progress.paths_too_long, self.config.indexing.max_path_length
+            );
+        }
+        if progress.changed_during_scan > 0 {
+            eprintln!("Warning: Skipped {} entries that changed during the scan", progress.changed_during_scan);
+        }
+        if progress.already_scanned > 0 {
+            eprintln!(
+                "Warning: Skipped {} directories already scanned, reached again through symlinks",
+                progress.already_scanned
+            );
+        }
+        if progress.future_dated > 0 {
+            eprintln!(
+                "Warning: {} entries have modification times in the future; check the clock of whatever wrote them",
+                progress.future_dated
+            );
+        }
+    }
+
+    /// Count an entry that disappeared or was replaced while the walk was running
+    ///
+    /// These are expected on a live system and are left to the watcher, so they
+    /// are neither logged nor recorded as scan errors.
+    fn record_changed_during_scan(&self) {
+        self.progress.lock().unwrap().changed_during_scan += 1;
+    }
+
+    /// Scan root by root, checkpointing each finished root in the database
+    ///
+    /// Roots are walked like `scan_parallel` does. `index` stores the entries
+    /// of one root and returns `false` to stop early (e.g. when disk space runs
+    /// low); passing the deadline from `set_deadline` or setting the cancel
+    /// token stops the scan the same way. The checkpoint is kept in that case,
+    /// and the next call passes only the roots that were not finished to
+    /// `index`. The completed ones are walked again and reconciled with the
+    /// index, so changes made to them in between are picked up. Returns
+    /// whether every root was indexed; the checkpoint is cleared once that happens.
+    pub fn scan_resumable<F>(&self, db: &Database, mut index: F) -> SqliteResult<bool>
+    where
+        F: FnMut(&[FileEntry]) -> SqliteResult<bool>,
+    {
+        self.begin_scan();
+        let mut state = match load_scan_state(db)? {
+            Some(state) => {
+                println!("Resuming interrupted scan ({} roots already indexed)", state.completed_roots.len());
+                state
+            }
+            None => {
+                let state = ScanState::default();
+                store_scan_state(db, &state)?;
+                state
+            }
+        };
+
+        // Unfinished roots first, then the ones to catch up on
+        let (completed, unfinished): (Vec<PathBuf>, Vec<PathBuf>) =
+            self.scan_roots().into_iter().partition(|root| state.completed_roots.contains(root));
+        let roots: Vec<PathBuf> = unfinished.iter().chain(&completed).cloned().collect();
+        let cutoff = self.config.indexing.aged_out_cutoff();
+        let mut finished = 0;
+        let mut result = Ok(());
+
+        self.walk_roots(&roots, |position, entries| {
+            let root = &roots[position];
+            let step = if position >= unfinished.len() {
+                // A walk cut short would reconcile away everything it did not reach
+                if self.should_stop() {
+                    return false;
+                }
+                db.reconcile_since(&entries, std::slice::from_ref(root), cutoff).map(|_| true)
             } else {
-                eprintln!("Warning: Include path does not exist: {}", path.display());
+                // A root cut short by the deadline or a cancellation is indexed as far
+                // as it got but not checkpointed, so the next call walks it again
+                index(&entries).and_then(|indexed| {
+                    if !indexed || self.should_stop() {
+                        return Ok(false);
+                    }
+                    state.completed_roots.push(root.clone());
+                    store_scan_state(db, &state).map(|_| true)
+                })
+            };
+            match step {
+                Ok(true) => {
+                    finished += 1;
+                    true
+                }
+                Ok(false) => false,
+                Err(e) => {
+                    result = Err(e);
+                    false
+                }
             }
+        });
+        result?;
+
+        self.warn_skipped_entries();
+        if finished < roots.len() {
+            return Ok(false);
         }
+        db.delete_metadata(SCAN_STATE_KEY)?;
+        Ok(true)
+    }
 
+    /// Scan one directory below the include paths on its own
+    ///
+    /// Used for subtrees that appear all at once after the initial scan, such
+    /// as a filesystem mounted under an include path.
+    pub fn scan_subtree(&self, path: &Path) -> Vec<FileEntry> {
+        self.begin_scan();
+        let entries = self.scan_directory(path);
+        self.warn_skipped_entries();
         entries
     }
 
+    /// Get the existing top-level directories a scan covers
+    pub fn scan_roots(&self) -> Vec<PathBuf> {
+        self.application_scan_directories()
+            .into_iter()
+            .chain(self.config.expand_paths())
+            .filter(|path| path.exists())
+            .collect()
+    }
+
+    /// Get every configured top-level directory, existing or not, with tildes expanded
+    ///
+    /// Include paths come first, followed by the application directories. It
+    /// covers the same directories as `scan_roots` and the daemon's watches,
+    /// for checking what a configuration resolves to.
+    pub fn configured_paths(&self) -> Vec<ConfiguredPath> {
+        let watched_apps = self.application_watch_directories();
+        let scanned_apps = self.application_scan_directories();
+        let include_paths = self.config.expand_paths().into_iter().map(|path| ConfiguredPath {
+            recursive: self.config.indexing.is_recursive_root(&path),
+            kind: ConfiguredPathKind::Include,
+            watched: true,
+            covered: false,
+            exists: path.exists(),
+            path,
+        });
+        let app_dirs = self.get_application_directories().into_iter().map(|path| {
+            let covered = !scanned_apps.contains(&path);
+            ConfiguredPath {
+                kind: ConfiguredPathKind::Applications,
+                recursive: true,
+                // Covered directories are watched along with their include path
+                watched: covered || watched_apps.contains(&path),
+                covered,
+                exists: path.exists(),
+                path,
+            }
+        });
+        include_paths.chain(app_dirs).collect()
+    }
+
     /// Get standard application directories that contain .desktop files
     fn get_application_directories(&self) -> Vec<PathBuf> {
-        let mut app_dirs = Vec::new();
-        
-        // System application directories
-        app_dirs.push(PathBuf::from("/usr/share/applications"));
-        app_dirs.push(PathBuf::from("/usr/local/share/applications"));
-        
-        // User application directory
-        if let Ok(home) = std::env::var("HOME") {
-            let home_path = PathBuf::from(&home);
-            app_dirs.push(home_path.join(".local/share/applications"));
+        if !self.config.indexing.index_applications {
+            return Vec::new();
         }
-        
-        // Snap applications
-        app_dirs.push(PathBuf::from("/var/lib/snapd/desktop/applications"));
-        if let Ok(home) = std::env::var("HOME") {
-            let home_path = PathBuf::from(&home);
-            app_dirs.push(home_path.join("snap"));
+
+        let mut app_dirs = system_application_directories();
+        app_dirs.extend(user_application_directories());
+        app_dirs
+    }
+
+    /// Get the application directories scanned on their own for applications
+    ///
+    /// With `skip_covered_applications`, directories that the walk of an
+    /// include path reaches anyway are left out, so they are not scanned twice.
+    fn application_scan_directories(&self) -> Vec<PathBuf> {
+        let mut app_dirs = self.get_application_directories();
+        if self.config.indexing.skip_covered_applications {
+            app_dirs.retain(|dir| !self.config.indexing.include_walk_covers(dir));
         }
-        
-        // Flatpak applications
-        app_dirs.push(PathBuf::from("/var/lib/flatpak/exports/share/applications"));
-        if let Ok(home) = std::env::var("HOME") {
-            let home_path = PathBuf::from(&home);
-            app_dirs.push(home_path.join(".local/share/flatpak/exports/share/applications"));
+        app_dirs
+    }
+
+    /// Get the application directories that should be watched for changes
+    ///
+    /// System-wide directories are shared by every user on the host, so they can
+    /// be left unwatched to avoid each user's daemon spending inotify watches on
+    /// the same paths. Changes there are then only indexed by a `reindex`, or a
+    /// periodic one when `reindex_interval_hours` is set.
+    pub fn application_watch_directories(&self) -> Vec<PathBuf> {
+        if !self.config.indexing.index_applications {
+            return Vec::new();
         }
-        
-        // AppImage applications (common locations)
-        if let Ok(home) = std::env::var("HOME") {
-            let home_path = PathBuf::from(&home);
-            app_dirs.push(home_path.join("Applications"));
-            app_dirs.push(home_path.join(".local/bin"));
-            app_dirs.push(home_path.join("AppImages"));
+
+        let mut app_dirs = Vec::new();
+        if self.config.indexing.watch_system_applications {
+            app_dirs.extend(system_application_directories());
         }
-        app_dirs.push(PathBuf::from("/opt"));
-        
+        app_dirs.extend(user_application_directories());
         app_dirs
     }
 
@@ -118,22 +626,15 @@ impl Scanner {
         for entry_result in WalkDir::new(path)
             .follow_links(false)
             .into_iter()
+            .filter_entry(|e| !self.exceeds_max_path_length(e.path()))
         {
+            if self.should_stop() {
+                break;
+            }
             match entry_result {
                 Ok(entry) => {
                     let entry_path = entry.path();
-                    
-                    // Update progress
-                    {
-                        let mut progress = self.progress.lock().unwrap();
-                        progress.current_path = Some(entry_path.to_path_buf());
-                        
-                        if entry.file_type().is_dir() {
-                            progress.directories_scanned += 1;
-                        } else {
-                            progress.files_scanned += 1;
-                        }
-                    }
+                    self.record_visit(&entry);
 
                     // Check if this is a .desktop file or AppImage
                     let should_include = if entry.file_type().is_file() {
@@ -155,18 +656,17 @@ impl Scanner {
                     };
 
                     if should_include {
-                        if let Some(file_entry) = self.extract_file_entry(&entry) {
+                        if let Some(file_entry) = self.extract_file_entry(&entry, None) {
                             entries.push(file_entry);
                         }
                     }
                 }
+                Err(err) if is_vanished(&err) => self.record_changed_during_scan(),
                 Err(err) => {
                     // Handle permission errors gracefully for system directories
-                    if !err.to_string().contains("Permission denied") {
+                    if self.record_error(err.path(), err.to_string()) && !err.to_string().contains("Permission denied") {
                         eprintln!("Warning: Failed to access application path: {}", err);
                     }
-                    let mut progress = self.progress.lock().unwrap();
-                    progress.errors_encountered += 1;
                 }
             }
         }
@@ -190,64 +690,136 @@ impl Scanner {
         false
     }
 
-    /// Scan a single directory recursively
+    /// Scan a single directory, recursively unless it is a non-recursive include root
     fn scan_directory(&self, path: &Path) -> Vec<FileEntry> {
         let mut entries = Vec::new();
         
-        // Create glob patterns for exclusion
-        let exclude_patterns: Vec<Pattern> = self.config.indexing.exclude_patterns
+        // Create glob patterns for exclusion, split into name and relative path patterns
+        let all_patterns = self.config.indexing.effective_exclude_patterns();
+        let (path_patterns, name_patterns): (Vec<&String>, Vec<&String>) = all_patterns
             .iter()
-            .filter_map(|pattern| {
-                Pattern::new(pattern).ok()
-            })
-            .collect();
+            .partition(|pattern| config::is_path_pattern(pattern));
+        let compile = |patterns: Vec<&String>| -> Vec<Pattern> {
+            patterns.into_iter().filter_map(|pattern| Pattern::new(pattern).ok()).collect()
+        };
+        let exclude_patterns = ExcludePatterns {
+            names: compile(name_patterns),
+            paths: compile(path_patterns),
+            include_roots: self.config.expand_paths(),
+        };
 
         let root_path = path.to_path_buf();
+        let max_age_cutoff = self.config.indexing.max_age_cutoff();
+        let link_roots = self.symlink_target_roots();
+        // Links pruned for leading outside the include paths, indexed as plain symlinks
+        let outside_links = Mutex::new(Vec::new());
 
-        for entry_result in WalkDir::new(path)
-            .follow_links(false)
+        let mut walker = WalkDir::new(path).follow_links(self.config.indexing.follow_symlinks);
+        if !self.config.indexing.is_recursive_root(path) {
+            walker = walker.max_depth(1);
+        }
+
+        for entry_result in walker
             .into_iter()
-            .filter_entry(|e| self.should_include_entry(e, &exclude_patterns, &root_path))
+            .filter_entry(|e| {
+                if !self.should_include_entry(e, &exclude_patterns, &root_path) {
+                    return false;
+                }
+                if link_roots.as_deref().is_some_and(|roots| links_outside(e, roots)) {
+                    if let Some(link) = self.symlink_entry(e.path()) {
+                        outside_links.lock().unwrap().push(link);
+                    }
+                    return false;
+                }
+                // Following symlinks can reach a directory twice, within one root (a cycle)
+                // or across roots, so each one is only walked the first time it is seen
+                !(self.config.indexing.follow_symlinks && e.file_type().is_dir() && !self.first_visit(e))
+            })
         {
+            if self.should_stop() {
+                break;
+            }
             match entry_result {
                 Ok(entry) => {
-                    // Update progress
-                    {
-                        let mut progress = self.progress.lock().unwrap();
-                        progress.current_path = Some(entry.path().to_path_buf());
-                        
-                        if entry.file_type().is_dir() {
-                            progress.directories_scanned += 1;
-                        } else {
-                            progress.files_scanned += 1;
-                        }
-                    }
-
-                    // Extract file entry
-                    if let Some(file_entry) = self.extract_file_entry(&entry) {
+                    self.record_visit(&entry);
+                    if let Some(file_entry) = self.extract_file_entry(&entry, max_age_cutoff) {
                         entries.push(file_entry);
                     }
                 }
+                Err(err) if is_vanished(&err) => self.record_changed_during_scan(),
+                // A link back to an ancestor of the same walk, caught before the visited set sees it
+                Err(err) if err.loop_ancestor().is_some() => self.record_already_scanned(),
                 Err(err) => {
                     // Handle permission errors and other issues gracefully
-                    eprintln!("Warning: Failed to access path: {}", err);
-                    let mut progress = self.progress.lock().unwrap();
-                    progress.errors_encountered += 1;
+                    if self.record_error(err.path(), err.to_string()) {
+                        eprintln!("Warning: Failed to access path: {}", err);
+                    }
                 }
             }
         }
 
+        let outside_links = outside_links.into_inner().unwrap();
+        self.progress.lock().unwrap().files_scanned += outside_links.len();
+        entries.extend(outside_links);
         entries
     }
 
+    /// Count an entry reached by a walk in the progress
+    fn record_visit(&self, entry: &DirEntry) {
+        let mut progress = self.progress.lock().unwrap();
+        progress.current_path = Some(entry.path().to_path_buf());
+        if entry.file_type().is_dir() {
+            progress.directories_scanned += 1;
+        } else {
+            progress.files_scanned += 1;
+        }
+    }
+
+    /// Get the canonical include paths symlinks may lead into, if following is restricted
+    fn symlink_target_roots(&self) -> Option<Vec<PathBuf>> {
+        let indexing = &self.config.indexing;
+        if !indexing.follow_symlinks || !indexing.follow_symlinks_within_roots {
+            return None;
+        }
+        Some(
+            self.config
+                .expand_paths()
+                .iter()
+                .filter_map(|root| std::fs::canonicalize(root).ok())
+                .collect(),
+        )
+    }
+
+    /// Index a symlink itself rather than what it points to
+    fn symlink_entry(&self, path: &Path) -> Option<FileEntry> {
+        let metadata = std::fs::symlink_metadata(path).ok()?;
+        Some(FileEntry::new(
+            models::entry_name(path),
+            path.to_path_buf(),
+            metadata.len(),
+            metadata.modified().unwrap_or_else(|_| SystemTime::now()),
+            FileType::Symlink,
+        ))
+    }
+
     /// Check if an entry should be included based on exclude patterns
-    fn should_include_entry(&self, entry: &DirEntry, exclude_patterns: &[Pattern], root_path: &Path) -> bool {
+    fn should_include_entry(&self, entry: &DirEntry, exclude_patterns: &ExcludePatterns, root_path: &Path) -> bool {
         let path = entry.path();
         
+        // The daemon's own database and config are never indexed, even under an include root
+        if self.config.indexing.is_internal_path(path) {
+            return false;
+        }
+        
         // Always include the root directory itself
         if path == root_path {
             return true;
         }
+
+        // Over-long paths are skipped; pruning a directory skips everything below it
+        if self.exceeds_max_path_length(path) {
+            return false;
+        }
         
         // Get the file/directory name
         let name = match path.file_name() {
@@ -255,38 +827,111 @@ impl Scanner {
             None => return true,
         };
 
+        // Skip hidden (dot-prefixed) entries unless explicitly enabled
+        if !self.config.indexing.index_hidden_files && name.starts_with('.') {
+            return false;
+        }
+
+        // Whitelisted file extensions take precedence over exclude patterns;
+        // directories are still pruned as usual
+        if entry.file_type().is_file() && self.config.indexing.always_includes(path) {
+            return true;
+        }
+
         // Check against exclude patterns
-        for pattern in exclude_patterns {
+        for pattern in &exclude_patterns.names {
             // Check if the name matches the pattern
             if pattern.matches(&name) {
                 return false;
             }
         }
 
+        // Directories above the entry were checked when the walk entered them
+        if !exclude_patterns.paths.is_empty() {
+            if let Some(relative) = config::relative_to_root(path, &exclude_patterns.include_roots) {
+                if exclude_patterns.paths.iter().any(|pattern| pattern.matches_path(relative)) {
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Check a path against `max_path_length`, counting the ones that exceed it
+    ///
+    /// Never true under `LongPathPolicy::Store`.
+    fn exceeds_max_path_length(&self, path: &Path) -> bool {
+        let limit = self.config.indexing.path_length_limit();
+        if limit.is_none_or(|limit| path.as_os_str().len() <= limit) {
+            return false;
+        }
+
+        self.progress.lock().unwrap().paths_too_long += 1;
         true
     }
 
+    /// Record a directory as scanned, returning `false` if this scan already entered it
+    fn first_visit(&self, entry: &DirEntry) -> bool {
+        // Unreadable directories are left to the walk, which reports the error
+        let Ok(metadata) = entry.metadata() else {
+            return true;
+        };
+        
+        let first = self.visited_dirs.lock().unwrap().insert((metadata.dev(), metadata.ino()));
+        if !first {
+            self.record_already_scanned();
+        }
+        first
+    }
+
+    /// Count a directory skipped because this scan already walked it
+    fn record_already_scanned(&self) {
+        self.progress.lock().unwrap().already_scanned += 1;
+    }
+
     /// Extract file entry from a directory entry
-    fn extract_file_entry(&self, entry: &DirEntry) -> Option<FileEntry> {
+    ///
+    /// Entries other than directories last modified before `cutoff` are skipped.
+    fn extract_file_entry(&self, entry: &DirEntry, cutoff: Option<SystemTime>) -> Option<FileEntry> {
         let path = entry.path();
         
         // Get filename
-        let filename = path.file_name()?.to_string_lossy().to_string();
+        let filename = models::entry_name(path);
         
-        // Get metadata
+        // Get metadata; every field below comes from this single lstat
         let metadata = match entry.metadata() {
             Ok(m) => m,
+            Err(err) if is_vanished(&err) => {
+                self.record_changed_during_scan();
+                return None;
+            }
             Err(err) => {
-                eprintln!("Warning: Failed to get metadata for {}: {}", path.display(), err);
+                if self.record_error(Some(path), err.to_string()) {
+                    eprintln!("Warning: Failed to get metadata for {}: {}", path.display(), err);
+                }
                 return None;
             }
         };
 
+        // A different type than the directory listing reported means the path was
+        // replaced mid-walk; the watcher will index whatever is there now
+        if metadata.file_type() != entry.file_type() {
+            self.record_changed_during_scan();
+            return None;
+        }
+
         // Get file size
         let size = metadata.len();
 
         // Get modification time
         let modified_time = metadata.modified().unwrap_or_else(|_| SystemTime::now());
+        if !metadata.is_dir() && cutoff.is_some_and(|cutoff| modified_time < cutoff) {
+            return None;
+        }
+        if models::is_future_dated(modified_time) {
+            self.progress.lock().unwrap().future_dated += 1;
+        }
 
         // Determine file type
         let file_type = if metadata.is_dir() {
@@ -299,13 +944,30 @@ impl Scanner {
             FileType::Other
         };
 
-        Some(FileEntry::new(
+        let mut file_entry = FileEntry::new(
             filename,
             path.to_path_buf(),
             size,
             modified_time,
             file_type,
-        ))
+        );
+        file_entry.is_executable = models::is_executable(&metadata);
+        if self.config.indexing.index_xattrs {
+            file_entry.xattrs = Some(read_xattrs(path, &self.config.indexing.xattr_allowlist));
+        }
+        if let Some(max_size) = self.config.indexing.content_size_limit().filter(|_| metadata.is_file()) {
+            // Empty for binary or oversized files, replacing any text stored before
+            file_entry.content = Some(models::read_text_content(path, max_size).unwrap_or_default());
+        }
+        if self.config.indexing.detect_git_repos {
+            file_entry.repo_root = self.repo_roots.lock().unwrap().repo_root(path, metadata.is_dir());
+        }
+        if self.config.indexing.index_ownership {
+            let (uid, gid) = models::ownership(&metadata);
+            (file_entry.uid, file_entry.gid) = (Some(uid), Some(gid));
+        }
+
+        Some(file_entry)
     }
 }
 
@@ -337,14 +999,14 @@ mod tests {
         create_test_directory_structure(temp_dir.path());
 
         let mut config = Config::default();
-        config.indexing.include_paths = vec![temp_dir.path().to_string_lossy().to_string()];
+        config.indexing.include_paths = vec![temp_dir.path().to_string_lossy().to_string().into()];
         config.indexing.exclude_patterns = vec![];
 
         let scanner = Scanner::new(config);
         let entries = scanner.scan();
 
         // Should find all files and directories
-        assert!(entries.len() > 0);
+        assert!(!entries.is_empty());
         
         // Check that we found some specific files
         let filenames: Vec<String> = entries.iter().map(|e| e.filename.clone()).collect();
@@ -353,13 +1015,181 @@ mod tests {
         assert!(filenames.contains(&"main.rs".to_string()));
     }
 
+    #[test]
+    fn test_scan_parallel_multiple_roots() {
+        let roots: Vec<TempDir> = (0..3).map(|_| TempDir::new().unwrap()).collect();
+        for (i, root) in roots.iter().enumerate() {
+            create_test_directory_structure(root.path());
+            fs::write(root.path().join(format!("root{}.txt", i)), "x").unwrap();
+        }
+
+        let mut config = Config::default();
+        config.indexing.include_paths = roots
+            .iter()
+            .map(|root| root.path().to_string_lossy().to_string().into())
+            .collect();
+        config.indexing.exclude_patterns = vec![];
+        config.indexing.index_applications = false;
+        config.performance.scan_parallelism = 1;
+        let sequential = Scanner::new(config.clone()).scan();
+        config.performance.scan_parallelism = 2;
+        let parallel = Scanner::new(config).scan_parallel();
+
+        let paths = |entries: &[FileEntry]| entries.iter().map(|e| e.path.clone()).collect::<Vec<_>>();
+        assert_eq!(paths(&parallel), paths(&sequential));
+        for (i, root) in roots.iter().enumerate() {
+            assert!(parallel.iter().any(|e| e.path == root.path().join(format!("root{}.txt", i))));
+        }
+    }
+
+    #[test]
+    fn test_scan_resumable_after_interruption() {
+        let first = TempDir::new().unwrap();
+        let second = TempDir::new().unwrap();
+        fs::write(first.path().join("first.txt"), "1").unwrap();
+        fs::write(second.path().join("second.txt"), "2").unwrap();
+        let db_dir = TempDir::new().unwrap();
+        let db = Database::open(db_dir.path().join("index.db")).unwrap();
+
+        let mut config = Config::default();
+        config.indexing.include_paths = vec![
+            first.path().to_string_lossy().to_string().into(),
+            second.path().to_string_lossy().to_string().into(),
+        ];
+        config.indexing.index_applications = false;
+        let scanner = Scanner::new(config);
+
+        let index = |entries: &[FileEntry]| {
+            for entry in entries {
+                db.insert_file(entry)?;
+            }
+            Ok(true)
+        };
+
+        // Interrupted after the first root
+        let mut roots_indexed = 0;
+        let completed = scanner.scan_resumable(&db, |entries| {
+            if roots_indexed == 1 {
+                return Ok(false);
+            }
+            roots_indexed += 1;
+            index(entries)
+        }).unwrap();
+        assert!(!completed);
+        assert_eq!(
+            load_scan_state(&db).unwrap().unwrap().completed_roots,
+            vec![first.path().to_path_buf()]
+        );
+        assert_eq!(db.query_files("first", 10).unwrap().len(), 1);
+        assert!(db.query_files("second", 10).unwrap().is_empty());
+
+        // Changes made to the finished root while the daemon was down
+        fs::remove_file(first.path().join("first.txt")).unwrap();
+        fs::write(first.path().join("added.txt"), "3").unwrap();
+
+        // Resuming only indexes the unfinished root and catches up on the other
+        let mut scanned = Vec::new();
+        let completed = scanner.scan_resumable(&db, |entries| {
+            scanned.extend(entries.iter().map(|e| e.path.clone()));
+            index(entries)
+        }).unwrap();
+        assert!(completed);
+        assert!(scanned.contains(&second.path().join("second.txt")));
+        assert!(!scanned.contains(&first.path().join("added.txt")));
+        assert_eq!(db.query_files("second", 10).unwrap().len(), 1);
+        assert!(db.query_files("first", 10).unwrap().is_empty());
+        assert_eq!(db.query_files("added", 10).unwrap().len(), 1);
+        assert_eq!(load_scan_state(&db).unwrap(), None);
+    }
+
+    #[test]
+    fn test_scan_resumable_cancelled_mid_scan() {
+        let first = TempDir::new().unwrap();
+        let second = TempDir::new().unwrap();
+        fs::write(first.path().join("first.txt"), "1").unwrap();
+        for i in 0..20 {
+            let dir = second.path().join(format!("dir{}", i));
+            fs::create_dir(&dir).unwrap();
+            for j in 0..50 {
+                fs::write(dir.join(format!("second{}.txt", j)), "2").unwrap();
+            }
+        }
+        let db_dir = TempDir::new().unwrap();
+        let db = Database::open(db_dir.path().join("index.db")).unwrap();
+
+        let mut config = Config::default();
+        config.indexing.include_paths = vec![
+            first.path().to_string_lossy().to_string().into(),
+            second.path().to_string_lossy().to_string().into(),
+        ];
+        config.indexing.index_applications = false;
+        let cancel = Arc::new(AtomicBool::new(false));
+        let mut scanner = Scanner::new(config);
+        scanner.set_cancel_token(Arc::clone(&cancel));
+
+        // The shutdown signal arrives while the first root is being stored
+        let started = Instant::now();
+        let mut calls = 0;
+        let completed = scanner.scan_resumable(&db, |entries| {
+            calls += 1;
+            for entry in entries {
+                db.insert_file(entry)?;
+            }
+            cancel.store(true, Ordering::Relaxed);
+            Ok(true)
+        }).unwrap();
+
+        assert!(!completed);
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        // The second root is not walked at all
+        assert_eq!(calls, 1);
+        assert!(db.query_files("second", 10).unwrap().is_empty());
+        // What was indexed before the signal is kept, and the root is walked again next time
+        assert_eq!(db.query_files("first", 10).unwrap().len(), 1);
+        assert!(load_scan_state(&db).unwrap().unwrap().completed_roots.is_empty());
+    }
+
+    #[test]
+    fn test_scan_resumable_stops_at_deadline() {
+        let temp_dir = TempDir::new().unwrap();
+        create_test_directory_structure(temp_dir.path());
+        let db_dir = TempDir::new().unwrap();
+        let db = Database::open(db_dir.path().join("index.db")).unwrap();
+
+        let mut config = Config::default();
+        config.indexing.include_paths = vec![temp_dir.path().to_string_lossy().to_string().into()];
+        config.indexing.index_applications = false;
+        let mut scanner = Scanner::new(config.clone());
+        scanner.set_deadline(Some(Instant::now()));
+
+        let index = |entries: &[FileEntry]| {
+            for entry in entries {
+                db.insert_file(entry)?;
+            }
+            Ok(true)
+        };
+
+        // The walk stops right away and the root stays unfinished
+        let completed = scanner.scan_resumable(&db, index).unwrap();
+        assert!(!completed);
+        assert!(scanner.timed_out());
+        assert!(load_scan_state(&db).unwrap().unwrap().completed_roots.is_empty());
+        assert!(db.query_files("readme", 10).unwrap().is_empty());
+
+        // A scanner without a deadline finishes the job
+        let completed = Scanner::new(config).scan_resumable(&db, index).unwrap();
+        assert!(completed);
+        assert_eq!(db.query_files("readme", 10).unwrap().len(), 1);
+        assert_eq!(load_scan_state(&db).unwrap(), None);
+    }
+
     #[test]
     fn test_scanner_with_exclusions() {
         let temp_dir = TempDir::new().unwrap();
         create_test_directory_structure(temp_dir.path());
 
         let mut config = Config::default();
-        config.indexing.include_paths = vec![temp_dir.path().to_string_lossy().to_string()];
+        config.indexing.include_paths = vec![temp_dir.path().to_string_lossy().to_string().into()];
         config.indexing.exclude_patterns = vec![".*".to_string(), "node_modules".to_string()];
 
         let scanner = Scanner::new(config);
@@ -376,6 +1206,243 @@ mod tests {
         assert!(filenames.contains(&"file1.txt".to_string()));
     }
 
+    #[test]
+    fn test_scanner_skips_hidden_files_by_default() {
+        let temp_dir = TempDir::new().unwrap();
+        create_test_directory_structure(temp_dir.path());
+        fs::write(temp_dir.path().join(".bashrc"), "export A=1").unwrap();
+
+        let mut config = Config::default();
+        config.indexing.include_paths = vec![temp_dir.path().to_string_lossy().to_string().into()];
+        config.indexing.exclude_patterns = vec![];
+
+        let scanner = Scanner::new(config);
+        let entries = scanner.scan();
+
+        let filenames: Vec<String> = entries.iter().map(|e| e.filename.clone()).collect();
+        assert!(!filenames.contains(&".hidden".to_string()));
+        assert!(!filenames.contains(&"secret.txt".to_string()));
+        assert!(!filenames.contains(&".bashrc".to_string()));
+        assert!(filenames.contains(&"readme.txt".to_string()));
+    }
+
+    #[test]
+    fn test_scanner_indexes_hidden_files_when_enabled() {
+        let temp_dir = TempDir::new().unwrap();
+        create_test_directory_structure(temp_dir.path());
+        fs::write(temp_dir.path().join(".bashrc"), "export A=1").unwrap();
+
+        let mut config = Config::default();
+        config.indexing.include_paths = vec![temp_dir.path().to_string_lossy().to_string().into()];
+        config.indexing.exclude_patterns = vec![];
+        config.indexing.index_hidden_files = true;
+
+        let scanner = Scanner::new(config);
+        let entries = scanner.scan();
+
+        let filenames: Vec<String> = entries.iter().map(|e| e.filename.clone()).collect();
+        assert!(filenames.contains(&".hidden".to_string()));
+        assert!(filenames.contains(&"secret.txt".to_string()));
+        assert!(filenames.contains(&".bashrc".to_string()));
+    }
+
+    #[test]
+    fn test_scanner_always_include_extensions() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("app.log"), "started").unwrap();
+        fs::write(temp_dir.path().join("notes.txt"), "notes").unwrap();
+        fs::create_dir(temp_dir.path().join("cache")).unwrap();
+        fs::write(temp_dir.path().join("cache/old.log"), "stale").unwrap();
+
+        let mut config = Config::default();
+        config.indexing.include_paths = vec![temp_dir.path().to_string_lossy().to_string().into()];
+        config.indexing.exclude_patterns = vec!["*.log".to_string(), "cache".to_string()];
+
+        let filenames = |config: &Config| -> Vec<String> {
+            Scanner::new(config.clone()).scan().into_iter().map(|e| e.filename).collect()
+        };
+
+        assert!(!filenames(&config).contains(&"app.log".to_string()));
+
+        config.indexing.always_include_extensions = vec!["log".to_string()];
+        let names = filenames(&config);
+        assert!(names.contains(&"app.log".to_string()));
+        assert!(names.contains(&"notes.txt".to_string()));
+        // Excluded directories are still pruned
+        assert!(!names.contains(&"old.log".to_string()));
+    }
+
+    #[test]
+    fn test_scan_indexes_text_content() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("notes.txt"), "Remember the quarterly zephyr budget").unwrap();
+        fs::write(temp_dir.path().join("image.bin"), b"zephyr\0\x89PNG").unwrap();
+        fs::write(temp_dir.path().join("large.log"), "zephyr ".repeat(200)).unwrap();
+
+        let mut config = Config::default();
+        config.indexing.include_paths = vec![temp_dir.path().to_string_lossy().to_string().into()];
+        config.indexing.index_applications = false;
+        config.indexing.index_content = true;
+        config.indexing.max_file_size = 1000;
+        let entries = Scanner::new(config.clone()).scan();
+
+        let content = |name: &str| entries.iter().find(|e| e.filename == name).unwrap().content.clone();
+        assert_eq!(content("notes.txt").as_deref(), Some("Remember the quarterly zephyr budget"));
+        // Binary and oversized files are stored without text
+        assert_eq!(content("image.bin").as_deref(), Some(""));
+        assert_eq!(content("large.log").as_deref(), Some(""));
+
+        let db = Database::open_in_memory().unwrap();
+        let operations: Vec<_> = entries.into_iter().map(models::IndexOperation::Add).collect();
+        db.execute_batch(&operations).unwrap();
+        let results = db.search_content("Quarterly", 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].filename, "notes.txt");
+        assert_eq!(db.search_content("zephyr", 10).unwrap().len(), 1);
+
+        // Off by default
+        config.indexing.index_content = false;
+        assert!(Scanner::new(config).scan().iter().all(|e| e.content.is_none()));
+    }
+
+    #[test]
+    fn test_scanner_relative_path_exclude_patterns() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for dir in ["build/debug", "src/build", "docs/_generated", "src/docs/_generated"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in [
+            "build/app.o",
+            "build/debug/app.o",
+            "src/build/keep.rs",
+            "docs/_generated/index.html",
+            "docs/guide.md",
+            "src/docs/_generated/kept.html",
+            "notes.tmp",
+        ] {
+            fs::write(root.join(file), "x").unwrap();
+        }
+
+        let mut config = Config::default();
+        config.indexing.include_paths = vec![root.to_string_lossy().to_string().into()];
+        config.indexing.exclude_patterns = vec![
+            "build/*".to_string(),
+            "docs/_generated".to_string(),
+            "*.tmp".to_string(),
+        ];
+
+        let mut paths: Vec<PathBuf> = Scanner::new(config)
+            .scan()
+            .into_iter()
+            .filter_map(|e| e.path.strip_prefix(root).ok().map(Path::to_path_buf))
+            .filter(|path| !path.as_os_str().is_empty())
+            .collect();
+        paths.sort();
+
+        // Path patterns are anchored at the include root; name patterns match anywhere
+        let expected: Vec<PathBuf> = [
+            "build",
+            "docs",
+            "docs/guide.md",
+            "src",
+            "src/build",
+            "src/build/keep.rs",
+            "src/docs",
+            "src/docs/_generated",
+            "src/docs/_generated/kept.html",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+        assert_eq!(paths, expected);
+    }
+
+    #[test]
+    fn test_scanner_deep_tree_and_max_path_length() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut deepest = temp_dir.path().to_path_buf();
+        for _ in 0..500 {
+            deepest.push("d");
+        }
+        fs::create_dir_all(&deepest).unwrap();
+        fs::write(deepest.join("bottom.txt"), "deep").unwrap();
+        fs::write(temp_dir.path().join("top.txt"), "shallow").unwrap();
+
+        let mut config = Config::default();
+        config.indexing.include_paths = vec![temp_dir.path().to_string_lossy().to_string().into()];
+        config.indexing.exclude_patterns = vec![];
+        config.indexing.index_applications = false;
+
+        // A 500-level tree is walked without overflowing the stack
+        let entries = Scanner::new(config.clone()).scan();
+        assert!(entries.iter().any(|e| e.filename == "bottom.txt"));
+        // Root, 500 directories and both files
+        assert_eq!(entries.len(), 503);
+
+        let max_path_length = temp_dir.path().as_os_str().len() + 100;
+        config.indexing.max_path_length = max_path_length;
+        let scanner = Scanner::new(config.clone());
+        let entries = scanner.scan();
+
+        assert!(entries.iter().any(|e| e.filename == "top.txt"));
+        assert!(!entries.iter().any(|e| e.filename == "bottom.txt"));
+        assert!(entries.iter().all(|e| e.path.as_os_str().len() <= max_path_length));
+        // Root, top.txt and the 50 directories within the limit; the 51st prunes the rest
+        assert_eq!(entries.len(), 52);
+        assert_eq!(scanner.get_progress().paths_too_long, 1);
+
+        config.indexing.long_path_policy = crate::config::LongPathPolicy::Store;
+        assert_eq!(Scanner::new(config).scan().len(), 503);
+    }
+
+    #[test]
+    fn test_scanner_indexes_xattrs() {
+        let temp_dir = TempDir::new().unwrap();
+        let tagged = temp_dir.path().join("tagged.txt");
+        fs::write(&tagged, "content").unwrap();
+
+        // Not every filesystem supports user xattrs; nothing to check then
+        if xattr::set(&tagged, "user.tags", b"work").is_err() {
+            return;
+        }
+        // Oversized values are skipped (some filesystems refuse to store them at all)
+        let _ = xattr::set(&tagged, "user.big", &vec![b'x'; MAX_XATTR_VALUE_BYTES + 1]);
+
+        let mut config = Config::default();
+        config.indexing.include_paths = vec![temp_dir.path().to_string_lossy().to_string().into()];
+        config.indexing.index_applications = false;
+        config.indexing.index_xattrs = true;
+
+        let entries = Scanner::new(config).scan();
+        let entry = entries.iter().find(|e| e.filename == "tagged.txt").unwrap();
+        assert_eq!(entry.xattrs, Some(vec![("user.tags".to_string(), "work".to_string())]));
+
+        let temp_file = tempfile::NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+        for entry in &entries {
+            db.update_file(entry).unwrap();
+        }
+        let results = db.query_by_xattr("user.tags", "work").unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].path, tagged);
+    }
+
+    #[test]
+    fn test_read_xattrs_allowlist() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("file.txt");
+        fs::write(&file, "content").unwrap();
+        if xattr::set(&file, "user.tags", b"home").is_err() {
+            return;
+        }
+        xattr::set(&file, "user.comment", b"note").unwrap();
+
+        let allowlist = vec!["user.tags".to_string()];
+        assert_eq!(read_xattrs(&file, &allowlist), vec![("user.tags".to_string(), "home".to_string())]);
+        assert!(read_xattrs(&file, &[]).is_empty());
+    }
+
     #[test]
     fn test_scanner_file_types() {
         let temp_dir = TempDir::new().unwrap();
@@ -383,7 +1450,7 @@ mod tests {
         fs::create_dir(temp_dir.path().join("directory")).unwrap();
 
         let mut config = Config::default();
-        config.indexing.include_paths = vec![temp_dir.path().to_string_lossy().to_string()];
+        config.indexing.include_paths = vec![temp_dir.path().to_string_lossy().to_string().into()];
         config.indexing.exclude_patterns = vec![];
 
         let scanner = Scanner::new(config);
@@ -408,7 +1475,7 @@ mod tests {
         fs::write(&file_path, content).unwrap();
 
         let mut config = Config::default();
-        config.indexing.include_paths = vec![temp_dir.path().to_string_lossy().to_string()];
+        config.indexing.include_paths = vec![temp_dir.path().to_string_lossy().to_string().into()];
         config.indexing.exclude_patterns = vec![];
 
         let scanner = Scanner::new(config);
@@ -429,7 +1496,7 @@ mod tests {
         create_test_directory_structure(temp_dir.path());
 
         let mut config = Config::default();
-        config.indexing.include_paths = vec![temp_dir.path().to_string_lossy().to_string()];
+        config.indexing.include_paths = vec![temp_dir.path().to_string_lossy().to_string().into()];
         config.indexing.exclude_patterns = vec![];
 
         let scanner = Scanner::new(config);
@@ -440,11 +1507,343 @@ mod tests {
         assert!(progress.directories_scanned > 0);
     }
 
+    #[test]
+    fn test_scanner_records_inaccessible_paths() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let locked = temp_dir.path().join("locked");
+        fs::create_dir(&locked).unwrap();
+        fs::write(locked.join("inside.txt"), "content").unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+
+        // Privileged users can read the directory anyway; nothing to record then
+        let readable = fs::read_dir(&locked).is_ok();
+
+        let mut config = Config::default();
+        config.indexing.include_paths = vec![temp_dir.path().to_string_lossy().to_string().into()];
+        config.indexing.exclude_patterns = vec![];
+
+        let scanner = Scanner::new(config);
+        let _entries = scanner.scan();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+
+        if !readable {
+            let errors = scanner.recent_errors();
+            assert!(errors.iter().any(|e| e.path.as_deref() == Some(locked.as_path())));
+        }
+    }
+
+    #[test]
+    fn test_entries_changed_during_walk_are_skipped_quietly() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("vanishing.txt"), "content").unwrap();
+        fs::write(temp_dir.path().join("replaced"), "content").unwrap();
+
+        let scanner = Scanner::new(Config::default());
+        let listed: Vec<DirEntry> = WalkDir::new(temp_dir.path())
+            .min_depth(1)
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+
+        // Change both files after they were listed but before they are stat'ed
+        fs::remove_file(temp_dir.path().join("vanishing.txt")).unwrap();
+        fs::remove_file(temp_dir.path().join("replaced")).unwrap();
+        fs::create_dir(temp_dir.path().join("replaced")).unwrap();
+
+        for entry in &listed {
+            assert!(scanner.extract_file_entry(entry, None).is_none());
+        }
+
+        let progress = scanner.get_progress();
+        assert_eq!(progress.changed_during_scan, 2);
+        assert_eq!(progress.errors_encountered, 0);
+        assert!(scanner.recent_errors().is_empty());
+    }
+
+    #[test]
+    fn test_store_and_load_scan_errors() {
+        let temp_file = tempfile::NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+        assert!(load_scan_errors(&db).unwrap().is_empty());
+
+        let errors = vec![ScanError {
+            path: Some(PathBuf::from("/root/private")),
+            message: "Permission denied".to_string(),
+        }];
+        store_scan_errors(&db, &errors).unwrap();
+        assert_eq!(load_scan_errors(&db).unwrap(), errors);
+    }
+
+    #[test]
+    fn test_recent_errors_ring_buffer() {
+        let scanner = Scanner::new(Config::default());
+
+        for i in 0..(MAX_RECENT_SCAN_ERRORS + 5) {
+            let path = PathBuf::from(format!("/denied/{}", i));
+            scanner.record_error(Some(&path), "Permission denied".to_string());
+        }
+
+        let errors = scanner.recent_errors();
+        assert_eq!(errors.len(), MAX_RECENT_SCAN_ERRORS);
+        assert_eq!(errors[0].path, Some(PathBuf::from("/denied/5")));
+        assert_eq!(scanner.get_progress().errors_encountered, MAX_RECENT_SCAN_ERRORS + 5);
+    }
+
+    #[test]
+    fn test_scan_error_logging_capped() {
+        let mut config = Config::default();
+        config.performance.max_logged_scan_errors = 2;
+        let scanner = Scanner::new(config);
+
+        let logged: Vec<bool> = (0..5)
+            .map(|i| scanner.record_error(Some(&PathBuf::from(format!("/denied/{}", i))), "Permission denied".to_string()))
+            .collect();
+        assert_eq!(logged, vec![true, true, false, false, false]);
+
+        let progress = scanner.get_progress();
+        assert_eq!(progress.errors_encountered, 5);
+        assert_eq!(progress.errors_suppressed, 3);
+        assert_eq!(scanner.recent_errors().len(), 5);
+
+        // The cap applies per scan
+        scanner.begin_scan();
+        assert!(scanner.record_error(None, "Permission denied".to_string()));
+        assert_eq!(scanner.get_progress().errors_suppressed, 0);
+    }
+
+    #[test]
+    fn test_system_application_watching_can_be_disabled() {
+        let mut config = Config::default();
+        let system_dirs = system_application_directories();
+
+        let scanner = Scanner::new(config.clone());
+        let watched = scanner.application_watch_directories();
+        assert!(system_dirs.iter().all(|dir| watched.contains(dir)));
+
+        config.indexing.watch_system_applications = false;
+        let scanner = Scanner::new(config.clone());
+        let watched = scanner.application_watch_directories();
+        assert!(system_dirs.iter().all(|dir| !watched.contains(dir)));
+        assert_eq!(watched, user_application_directories());
+        // System directories are still scanned
+        assert!(system_dirs.iter().all(|dir| scanner.get_application_directories().contains(dir)));
+
+        config.indexing.index_applications = false;
+        let scanner = Scanner::new(config);
+        assert!(scanner.application_watch_directories().is_empty());
+        assert!(scanner.get_application_directories().is_empty());
+    }
+
+    #[test]
+    fn test_no_application_entries_when_disabled() {
+        let temp_dir = TempDir::new().unwrap();
+        create_test_directory_structure(temp_dir.path());
+
+        let mut config = Config::default();
+        config.indexing.include_paths = vec![temp_dir.path().to_string_lossy().to_string().into()];
+        config.indexing.index_applications = false;
+        let scanner = Scanner::new(config);
+
+        let app_dirs: Vec<PathBuf> = system_application_directories()
+            .into_iter()
+            .chain(user_application_directories())
+            .collect();
+        let in_app_dir = |entry: &FileEntry| app_dirs.iter().any(|dir| entry.path.starts_with(dir));
+
+        let entries = scanner.scan();
+        assert!(!entries.is_empty());
+        assert!(!entries.iter().any(in_app_dir));
+        assert!(!entries.iter().any(|e| e.path.starts_with("/usr/share/applications")));
+        assert!(!scanner.scan_parallel().iter().any(in_app_dir));
+        assert_eq!(scanner.scan_roots(), vec![temp_dir.path().to_path_buf()]);
+    }
+
+    #[test]
+    fn test_database_files_never_scanned() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_dir = temp_dir.path().join("novasearch");
+        fs::create_dir(&db_dir).unwrap();
+        for name in ["index.db", "index.db-wal", "index.db-shm"] {
+            fs::write(db_dir.join(name), "db").unwrap();
+        }
+        fs::write(temp_dir.path().join("notes.txt"), "notes").unwrap();
+
+        let mut config = Config::default();
+        config.indexing.include_paths = vec![temp_dir.path().to_string_lossy().to_string().into()];
+        config.indexing.index_applications = false;
+        config.indexing.internal_dirs = vec![db_dir.clone()];
+        let scanner = Scanner::new(config);
+
+        let entries = scanner.scan();
+        assert!(entries.iter().any(|e| e.path == temp_dir.path().join("notes.txt")));
+        assert!(!entries.iter().any(|e| e.path.starts_with(&db_dir)));
+    }
+
+    #[test]
+    fn test_detect_git_repos() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = temp_dir.path().join("project");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::create_dir_all(repo.join("src")).unwrap();
+        fs::write(repo.join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(repo.join("main.txt"), "notes").unwrap();
+        fs::write(temp_dir.path().join("main.md"), "loose").unwrap();
+
+        let mut config = Config::default();
+        config.indexing.include_paths = vec![temp_dir.path().to_string_lossy().to_string().into()];
+        config.indexing.index_applications = false;
+        config.indexing.detect_git_repos = true;
+        let scanner = Scanner::new(config);
+        let entries = scanner.scan();
+
+        let repo_of = |path: PathBuf| entries.iter().find(|e| e.path == path).unwrap().repo_root.clone();
+        assert_eq!(repo_of(repo.join("src/main.rs")), Some(repo.clone()));
+        assert_eq!(repo_of(repo.join("src")), Some(repo.clone()));
+        assert_eq!(repo_of(temp_dir.path().join("main.md")), None);
+
+        let db_file = tempfile::NamedTempFile::new().unwrap();
+        let db = Database::open(db_file.path()).unwrap();
+        let operations: Vec<_> = entries.into_iter().map(models::IndexOperation::Add).collect();
+        db.execute_batch(&operations).unwrap();
+
+        let results = db.query_in_repo(&repo, "main", 10).unwrap();
+        let paths: Vec<_> = results.iter().map(|e| e.path.clone()).collect();
+        assert_eq!(paths, vec![repo.join("src/main.rs"), repo.join("main.txt")]);
+        assert!(results.iter().all(|e| e.repo_root.as_ref() == Some(&repo)));
+    }
+
+    #[test]
+    fn test_index_ownership() {
+        use crate::database::PathStorage;
+        use std::os::unix::fs::MetadataExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("mine.txt"), "notes").unwrap();
+        fs::write(temp_dir.path().join("other.txt"), "notes").unwrap();
+        let metadata = fs::metadata(temp_dir.path().join("mine.txt")).unwrap();
+        let (uid, gid) = (metadata.uid(), metadata.gid());
+
+        let mut config = Config::default();
+        config.indexing.include_paths = vec![temp_dir.path().to_string_lossy().to_string().into()];
+        config.indexing.index_applications = false;
+        assert!(Scanner::new(config.clone()).scan().iter().all(|e| e.uid.is_none()));
+
+        config.indexing.index_ownership = true;
+        let mut entries = Scanner::new(config).scan();
+        for entry in &mut entries {
+            assert_eq!((entry.uid, entry.gid), (Some(uid), Some(gid)));
+            if entry.filename == "other.txt" {
+                entry.uid = Some(uid.wrapping_add(1));
+            }
+        }
+
+        for storage in [PathStorage::Full, PathStorage::Interned] {
+            let mut db = Database::open_in_memory().unwrap();
+            db.set_path_storage(storage).unwrap();
+            let operations: Vec<_> = entries.iter().cloned().map(models::IndexOperation::Add).collect();
+            db.execute_batch(&operations).unwrap();
+
+            let results = db.query_by_owner(uid, ".txt", 10).unwrap();
+            let paths: Vec<_> = results.iter().map(|e| e.path.clone()).collect();
+            assert_eq!(paths, vec![temp_dir.path().join("mine.txt")], "{:?}", storage);
+            assert_eq!((results[0].uid, results[0].gid), (Some(uid), Some(gid)));
+            assert_eq!(db.query_by_owner(uid.wrapping_add(1), "", 10).unwrap().len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_follow_symlinks_breaks_cross_root_cycles() {
+        use std::os::unix::fs::symlink;
+
+        let root_a = TempDir::new().unwrap();
+        let root_b = TempDir::new().unwrap();
+        fs::create_dir(root_a.path().join("sub")).unwrap();
+        fs::write(root_a.path().join("sub/a.txt"), "a").unwrap();
+        fs::write(root_b.path().join("b.txt"), "b").unwrap();
+        // B links back into A, and A links to itself
+        symlink(root_a.path(), root_b.path().join("to_a")).unwrap();
+        symlink(root_a.path(), root_a.path().join("sub/loop")).unwrap();
+
+        let mut config = Config::default();
+        config.indexing.include_paths = vec![
+            root_a.path().to_string_lossy().to_string().into(),
+            root_b.path().to_string_lossy().to_string().into(),
+        ];
+        config.indexing.index_applications = false;
+        config.indexing.follow_symlinks = true;
+        let scanner = Scanner::new(config);
+
+        for entries in [scanner.scan(), scanner.scan()] {
+            let a_files: Vec<_> = entries.iter().filter(|e| e.filename == "a.txt").collect();
+            assert_eq!(a_files.len(), 1);
+            assert_eq!(a_files[0].path, root_a.path().join("sub/a.txt"));
+            assert!(entries.iter().any(|e| e.path == root_b.path().join("b.txt")));
+            assert!(!entries.iter().any(|e| e.path.starts_with(root_b.path().join("to_a"))));
+            assert!(!entries.iter().any(|e| e.path.starts_with(root_a.path().join("sub/loop"))));
+            assert_eq!(scanner.get_progress().already_scanned, 2);
+        }
+        assert!(scanner.recent_errors().is_empty());
+    }
+
+    #[test]
+    fn test_follow_symlinks_within_roots() {
+        use std::os::unix::fs::symlink;
+
+        let root = TempDir::new().unwrap();
+        let outside = TempDir::new().unwrap();
+        fs::create_dir(root.path().join("real")).unwrap();
+        fs::write(root.path().join("real/inside.txt"), "in").unwrap();
+        fs::write(outside.path().join("elsewhere.txt"), "out").unwrap();
+        symlink(root.path().join("real"), root.path().join("in_link")).unwrap();
+        symlink(outside.path(), root.path().join("out_link")).unwrap();
+
+        let mut config = Config::default();
+        config.indexing.include_paths = vec![root.path().to_string_lossy().to_string().into()];
+        config.indexing.index_applications = false;
+        config.indexing.follow_symlinks = true;
+
+        // The in-root target is walked once, through whichever path is listed first
+        let entries = Scanner::new(config.clone()).scan();
+        assert_eq!(entries.iter().filter(|e| e.filename == "inside.txt").count(), 1);
+        let out_link = entries.iter().find(|e| e.path == root.path().join("out_link")).unwrap();
+        assert_eq!(out_link.file_type, FileType::Symlink);
+        assert!(!entries.iter().any(|e| e.filename == "elsewhere.txt"));
+
+        // Without the restriction the outside target is walked too
+        config.indexing.follow_symlinks_within_roots = false;
+        let entries = Scanner::new(config).scan();
+        assert!(entries.iter().any(|e| e.path == root.path().join("out_link/elsewhere.txt")));
+    }
+
+    #[test]
+    fn test_non_recursive_root_scans_top_level_only() {
+        let temp_dir = TempDir::new().unwrap();
+        create_test_directory_structure(temp_dir.path());
+
+        let mut config = Config::default();
+        config.indexing.include_paths = vec![crate::config::IncludePath::Detailed {
+            path: temp_dir.path().to_string_lossy().to_string(),
+            recursive: false,
+        }];
+        config.indexing.exclude_patterns = vec![];
+        config.indexing.index_applications = false;
+        let scanner = Scanner::new(config);
+
+        let entries = scanner.scan_parallel();
+        assert!(entries.iter().any(|e| e.path == temp_dir.path().join("readme.txt")));
+        assert!(entries.iter().any(|e| e.path == temp_dir.path().join("documents")));
+        assert!(!entries.iter().any(|e| e.path == temp_dir.path().join("documents/file1.txt")));
+        assert!(!entries.iter().any(|e| e.path == temp_dir.path().join("projects/rust")));
+    }
+
     #[test]
     fn test_scanner_nonexistent_path() {
         let mut config = Config::default();
-        config.indexing.include_paths = vec!["/nonexistent/path/that/does/not/exist".to_string()];
+        config.indexing.include_paths = vec!["/nonexistent/path/that/does/not/exist".into()];
         config.indexing.exclude_patterns = vec![];
+        config.indexing.index_applications = false;
 
         let scanner = Scanner::new(config);
         let entries = scanner.scan();
@@ -458,14 +1857,14 @@ mod tests {
         let temp_dir = TempDir::new().unwrap();
 
         let mut config = Config::default();
-        config.indexing.include_paths = vec![temp_dir.path().to_string_lossy().to_string()];
+        config.indexing.include_paths = vec![temp_dir.path().to_string_lossy().to_string().into()];
         config.indexing.exclude_patterns = vec![];
 
         let scanner = Scanner::new(config);
         let entries = scanner.scan();
 
         // Should find at least the root directory itself
-        assert!(entries.len() >= 1);
+        assert!(!entries.is_empty());
     }
 
     #[test]
@@ -476,7 +1875,7 @@ mod tests {
         fs::write(temp_dir.path().join("file.tmp"), "temp content").unwrap();
 
         let mut config = Config::default();
-        config.indexing.include_paths = vec![temp_dir.path().to_string_lossy().to_string()];
+        config.indexing.include_paths = vec![temp_dir.path().to_string_lossy().to_string().into()];
         config.indexing.exclude_patterns = vec!["*.log".to_string(), "*.tmp".to_string()];
 
         let scanner = Scanner::new(config);
@@ -487,4 +1886,81 @@ mod tests {
         assert!(!filenames.contains(&"file.log".to_string()));
         assert!(!filenames.contains(&"file.tmp".to_string()));
     }
+
+    #[test]
+    fn test_configured_paths_expand_tilde() {
+        let home = PathBuf::from(std::env::var("HOME").unwrap());
+        let mut config = Config::default();
+        config.indexing.include_paths = vec!["~/Documents".into(), "~".into()];
+        config.indexing.index_applications = false;
+
+        let paths = Scanner::new(config).configured_paths();
+        let resolved: Vec<&Path> = paths.iter().map(|configured| configured.path.as_path()).collect();
+        assert_eq!(resolved, vec![home.join("Documents").as_path(), home.as_path()]);
+        assert!(paths.iter().all(|configured| configured.path.is_absolute()));
+        assert!(paths.iter().all(|configured| configured.kind == ConfiguredPathKind::Include && configured.watched));
+    }
+
+    #[test]
+    fn test_covered_application_directory_not_scanned_twice() {
+        let mut config = Config::default();
+        config.indexing.include_paths = vec!["/usr/share".into()];
+        config.indexing.exclude_patterns = vec![];
+
+        let scanner = Scanner::new(config.clone());
+        let app_dirs = scanner.application_scan_directories();
+        assert!(!app_dirs.contains(&PathBuf::from("/usr/share/applications")));
+        assert!(app_dirs.contains(&PathBuf::from("/usr/local/share/applications")));
+        let roots = scanner.scan_roots();
+        assert_eq!(roots.iter().filter(|root| root.starts_with("/usr/share")).count(), 1);
+
+        // An exclude pattern keeps the include walk out, so the directory is scanned on its own
+        config.indexing.exclude_patterns = vec!["applications".to_string()];
+        assert!(Scanner::new(config.clone()).application_scan_directories().contains(&PathBuf::from("/usr/share/applications")));
+
+        config.indexing.exclude_patterns = vec![];
+        config.indexing.skip_covered_applications = false;
+        assert!(Scanner::new(config).application_scan_directories().contains(&PathBuf::from("/usr/share/applications")));
+    }
+
+    #[test]
+    fn test_max_age_skips_old_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let year_ago = SystemTime::now() - std::time::Duration::from_secs(365 * 24 * 60 * 60);
+        fs::create_dir(temp_dir.path().join("archive")).unwrap();
+        for name in ["old.txt", "archive/recent.txt", "archive/old-report.pdf"] {
+            fs::write(temp_dir.path().join(name), "content").unwrap();
+        }
+        for name in ["old.txt", "archive/old-report.pdf", "archive"] {
+            fs::File::open(temp_dir.path().join(name)).unwrap().set_modified(year_ago).unwrap();
+        }
+
+        let mut config = Config::default();
+        config.indexing.include_paths = vec![temp_dir.path().to_string_lossy().to_string().into()];
+        config.indexing.index_applications = false;
+        config.indexing.max_age_days = Some(30);
+
+        let entries = Scanner::new(config.clone()).scan();
+        let mut names: Vec<&str> = entries.iter().map(|e| e.filename.as_str()).collect();
+        names.sort();
+        let root_name = models::entry_name(temp_dir.path());
+        let mut expected = vec!["archive", "recent.txt", root_name.as_str()];
+        expected.sort();
+        assert_eq!(names, expected);
+
+        // Old files indexed before the limit was set survive a rescan
+        let db = Database::open_in_memory().unwrap();
+        let old = temp_dir.path().join("old.txt");
+        let metadata = fs::metadata(&old).unwrap();
+        db.insert_file(&FileEntry::new("old.txt".to_string(), old, 7, metadata.modified().unwrap(), FileType::Regular))
+            .unwrap();
+        let roots = [temp_dir.path().to_path_buf()];
+        let stats = db.reconcile_since(&entries, &roots, config.indexing.aged_out_cutoff()).unwrap();
+        assert_eq!(stats.deleted, 0);
+        assert_eq!(db.query_files("old.txt", 10).unwrap().len(), 1);
+
+        config.indexing.prune_aged_out = true;
+        let stats = db.reconcile_since(&entries, &roots, config.indexing.aged_out_cutoff()).unwrap();
+        assert_eq!(stats.deleted, 1);
+    }
 }
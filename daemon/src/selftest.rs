@@ -0,0 +1,157 @@
+use crate::config::Config;
+use crate::database::Database;
+use crate::models::{FileEntry, IndexOperation};
+use crate::scanner::Scanner;
+use crate::watcher::{EventProcessor, FilesystemWatcher};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Files written into the scratch directory before scanning
+const SAMPLE_FILES: [&str; 3] = ["selftest-alpha.txt", "selftest-beta.md", "docs/selftest-gamma.txt"];
+
+/// How long to wait for the watcher to report the file created after the scan
+const WATCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Outcome of one stage of the self-test
+#[derive(Debug, Clone)]
+pub struct StageResult {
+    pub name: &'static str,
+    /// `Ok` with a short summary, or `Err` with what went wrong
+    pub outcome: Result<String, String>,
+}
+
+impl StageResult {
+    pub fn passed(&self) -> bool {
+        self.outcome.is_ok()
+    }
+}
+
+/// Run the indexing pipeline end to end in a scratch directory
+///
+/// Stages run in order (write files, scan, index into an in-memory database,
+/// query, pick up a change through the watcher) and stop at the first failure,
+/// so the last result names the stage that broke. The scratch directory is
+/// removed afterwards.
+pub fn run_self_test() -> Vec<StageResult> {
+    let dir = std::env::temp_dir().join(format!(
+        "novasearch-check-{}-{}",
+        std::process::id(),
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos()
+    ));
+
+    let mut results = Vec::new();
+    run_stages(&dir, &mut results);
+    let _ = std::fs::remove_dir_all(&dir);
+    results
+}
+
+fn run_stages(dir: &Path, results: &mut Vec<StageResult>) {
+    let mut config = Config::default();
+    config.indexing.include_paths = vec![dir.to_string_lossy().to_string().into()];
+    config.indexing.index_applications = false;
+
+    let outcome = write_sample_files(dir)
+        .map(|()| format!("{} files in {}", SAMPLE_FILES.len(), dir.display()))
+        .map_err(|e| e.to_string());
+    if !stage(results, "write files", outcome) {
+        return;
+    }
+
+    let entries = Scanner::new(config.clone()).scan();
+    let found = SAMPLE_FILES.iter().filter(|name| entries.iter().any(|e| e.path == dir.join(name))).count();
+    let outcome = if found == SAMPLE_FILES.len() {
+        Ok(format!("{} entries", entries.len()))
+    } else {
+        Err(format!("found {} of {} sample files", found, SAMPLE_FILES.len()))
+    };
+    if !stage(results, "scan", outcome) {
+        return;
+    }
+
+    let db = match Database::open_in_memory() {
+        Ok(db) => db,
+        Err(e) => {
+            stage(results, "index", Err(e.to_string()));
+            return;
+        }
+    };
+    let operations: Vec<_> = entries.into_iter().map(IndexOperation::Add).collect();
+    let outcome = db.execute_batch(&operations)
+        .and_then(|_| db.count_files())
+        .map(|count| format!("{} rows", count))
+        .map_err(|e| e.to_string());
+    if !stage(results, "index", outcome) {
+        return;
+    }
+
+    let outcome = match db.query_files("selftest", 10) {
+        Ok(matches) if matches.len() == SAMPLE_FILES.len() => Ok(format!("{} matches", matches.len())),
+        Ok(matches) => Err(format!("expected {} matches, got {}", SAMPLE_FILES.len(), matches.len())),
+        Err(e) => Err(e.to_string()),
+    };
+    if !stage(results, "query", outcome) {
+        return;
+    }
+
+    stage(results, "watch", watch_new_file(&config, &db, dir));
+}
+
+/// Record a stage's outcome and report whether it passed
+fn stage(results: &mut Vec<StageResult>, name: &'static str, outcome: Result<String, String>) -> bool {
+    let passed = outcome.is_ok();
+    results.push(StageResult { name, outcome });
+    passed
+}
+
+fn write_sample_files(dir: &Path) -> std::io::Result<()> {
+    for name in SAMPLE_FILES {
+        let path = dir.join(name);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, name)?;
+    }
+    Ok(())
+}
+
+/// Create a file under a watched `dir` and wait until it can be found in `db`
+fn watch_new_file(config: &Config, db: &Database, dir: &Path) -> Result<String, String> {
+    let mut watcher = FilesystemWatcher::new(config).map_err(|e| e.to_string())?;
+    watcher.watch_path(dir).map_err(|e| e.to_string())?;
+
+    let new_file: PathBuf = dir.join("selftest-delta.txt");
+    std::fs::write(&new_file, "delta").map_err(|e| e.to_string())?;
+
+    let mut processor = EventProcessor::new(Duration::ZERO, 100);
+    let started = Instant::now();
+    while started.elapsed() < WATCH_TIMEOUT {
+        while let Some(event) = watcher.try_recv_event() {
+            processor.add_event(event);
+        }
+        let operations = processor.process_pending();
+        db.execute_batch(&operations).map_err(|e| e.to_string())?;
+
+        let matches: Vec<FileEntry> = db.query_files("selftest-delta", 10).map_err(|e| e.to_string())?;
+        if matches.iter().any(|e| e.path == new_file) {
+            return Ok(format!("event received after {} ms", started.elapsed().as_millis()));
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+
+    Err(format!("no event for {} within {} s", new_file.display(), WATCH_TIMEOUT.as_secs()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_self_test_passes() {
+        let results = run_self_test();
+        let names: Vec<_> = results.iter().map(|r| r.name).collect();
+        assert_eq!(names, vec!["write files", "scan", "index", "query", "watch"]);
+        for result in &results {
+            assert!(result.passed(), "{}: {:?}", result.name, result.outcome);
+        }
+    }
+}
@@ -0,0 +1,401 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::io::{BufRead, Write};
+use crate::database::Database;
+
+/// A single indexed entry as written to a snapshot file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotRecord {
+    pub path: String,
+    pub size: u64,
+    pub modified_time: i64,
+    /// Sub-second part of the modification time (absent in older snapshots)
+    #[serde(default)]
+    pub modified_nsec: i64,
+    pub file_type: String,
+}
+
+/// Differences between two snapshots
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SnapshotDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub modified: Vec<String>,
+}
+
+impl SnapshotDiff {
+    /// Check whether the snapshots were identical
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+/// Export the index as a snapshot: one JSON record per line, sorted by path
+///
+/// The export reads from a snapshot, so it is consistent even while the daemon
+/// keeps indexing.
+pub fn export_snapshot<W: Write>(db: &Database, mut writer: W) -> Result<usize, SnapshotError> {
+    let snapshot = db.read_snapshot().map_err(|e| SnapshotError::DatabaseError(e.to_string()))?;
+    let mut stmt = snapshot.connection()
+        .prepare("SELECT path, size, modified_time, file_type, modified_nsec FROM files ORDER BY path")
+        .map_err(|e| SnapshotError::DatabaseError(e.to_string()))?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(SnapshotRecord {
+                path: row.get(0)?,
+                size: row.get::<_, i64>(1)? as u64,
+                modified_time: row.get(2)?,
+                modified_nsec: row.get(4)?,
+                file_type: row.get(3)?,
+            })
+        })
+        .map_err(|e| SnapshotError::DatabaseError(e.to_string()))?;
+
+    let mut count = 0;
+    for record in rows {
+        let record = record.map_err(|e| SnapshotError::DatabaseError(e.to_string()))?;
+        let line = serde_json::to_string(&record)
+            .map_err(|e| SnapshotError::ParseError(e.to_string()))?;
+        writeln!(writer, "{}", line).map_err(|e| SnapshotError::IoError(e.to_string()))?;
+        count += 1;
+    }
+
+    writer.flush().map_err(|e| SnapshotError::IoError(e.to_string()))?;
+    Ok(count)
+}
+
+/// Diff two snapshots by walking both sorted streams in lockstep
+///
+/// Only one record from each side is held in memory at a time; an entry is
+/// reported as modified when its size or modification time differs.
+pub fn diff_snapshots<A: BufRead, B: BufRead>(old: A, new: B) -> Result<SnapshotDiff, SnapshotError> {
+    let mut old = SnapshotReader::new(old);
+    let mut new = SnapshotReader::new(new);
+    let mut diff = SnapshotDiff::default();
+
+    let mut old_record = old.next_record()?;
+    let mut new_record = new.next_record()?;
+
+    loop {
+        match (old_record.take(), new_record.take()) {
+            (None, None) => break,
+            (Some(o), None) => {
+                diff.removed.push(o.path);
+                old_record = old.next_record()?;
+            }
+            (None, Some(n)) => {
+                diff.added.push(n.path);
+                new_record = new.next_record()?;
+            }
+            (Some(o), Some(n)) => match o.path.cmp(&n.path) {
+                Ordering::Less => {
+                    diff.removed.push(o.path);
+                    old_record = old.next_record()?;
+                    new_record = Some(n);
+                }
+                Ordering::Greater => {
+                    diff.added.push(n.path);
+                    old_record = Some(o);
+                    new_record = new.next_record()?;
+                }
+                Ordering::Equal => {
+                    if o.size != n.size
+                        || o.modified_time != n.modified_time
+                        || o.modified_nsec != n.modified_nsec
+                    {
+                        diff.modified.push(n.path);
+                    }
+                    old_record = old.next_record()?;
+                    new_record = new.next_record()?;
+                }
+            },
+        }
+    }
+
+    Ok(diff)
+}
+
+/// Usage statistics for one path, keyed by path so they can be re-linked on import
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageRecord {
+    pub path: String,
+    pub launch_count: i32,
+    pub last_launched: Option<i64>,
+}
+
+/// Result of importing usage statistics
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct UsageImportStats {
+    /// Records applied to an indexed path
+    pub imported: usize,
+    /// Records whose path is not in the index
+    pub skipped: usize,
+}
+
+/// Export usage statistics: one JSON record per line, sorted by path
+pub fn export_usage_stats<W: Write>(db: &Database, mut writer: W) -> Result<usize, SnapshotError> {
+    let mut stmt = db.connection()
+        .prepare(
+            "SELECT f.path, u.launch_count, u.last_launched
+             FROM usage_stats u
+             JOIN files f ON f.id = u.file_id
+             ORDER BY f.path",
+        )
+        .map_err(|e| SnapshotError::DatabaseError(e.to_string()))?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(UsageRecord {
+                path: row.get(0)?,
+                launch_count: row.get(1)?,
+                last_launched: row.get(2)?,
+            })
+        })
+        .map_err(|e| SnapshotError::DatabaseError(e.to_string()))?;
+
+    let mut count = 0;
+    for record in rows {
+        let record = record.map_err(|e| SnapshotError::DatabaseError(e.to_string()))?;
+        let line = serde_json::to_string(&record)
+            .map_err(|e| SnapshotError::ParseError(e.to_string()))?;
+        writeln!(writer, "{}", line).map_err(|e| SnapshotError::IoError(e.to_string()))?;
+        count += 1;
+    }
+
+    writer.flush().map_err(|e| SnapshotError::IoError(e.to_string()))?;
+    Ok(count)
+}
+
+/// Import usage statistics, matching records to indexed files by path
+///
+/// Records for paths that are not indexed are skipped and counted.
+pub fn import_usage_stats<R: BufRead>(db: &Database, reader: R) -> Result<UsageImportStats, SnapshotError> {
+    let mut stats = UsageImportStats::default();
+
+    for line in reader.lines() {
+        let line = line.map_err(|e| SnapshotError::IoError(e.to_string()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let record: UsageRecord = serde_json::from_str(&line)
+            .map_err(|e| SnapshotError::ParseError(e.to_string()))?;
+
+        let matched = db
+            .merge_file_usage(&record.path, record.launch_count, record.last_launched)
+            .map_err(|e| SnapshotError::DatabaseError(e.to_string()))?;
+        if matched {
+            stats.imported += 1;
+        } else {
+            stats.skipped += 1;
+        }
+    }
+
+    Ok(stats)
+}
+
+/// Line-by-line snapshot reader that enforces sorted order
+struct SnapshotReader<R: BufRead> {
+    lines: std::io::Lines<R>,
+    last_path: Option<String>,
+}
+
+impl<R: BufRead> SnapshotReader<R> {
+    fn new(reader: R) -> Self {
+        SnapshotReader {
+            lines: reader.lines(),
+            last_path: None,
+        }
+    }
+
+    fn next_record(&mut self) -> Result<Option<SnapshotRecord>, SnapshotError> {
+        for line in self.lines.by_ref() {
+            let line = line.map_err(|e| SnapshotError::IoError(e.to_string()))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let record: SnapshotRecord = serde_json::from_str(&line)
+                .map_err(|e| SnapshotError::ParseError(e.to_string()))?;
+
+            if let Some(last) = &self.last_path {
+                if record.path <= *last {
+                    return Err(SnapshotError::UnsortedError(record.path));
+                }
+            }
+            self.last_path = Some(record.path.clone());
+
+            return Ok(Some(record));
+        }
+
+        Ok(None)
+    }
+}
+
+/// Snapshot error types
+#[derive(Debug)]
+pub enum SnapshotError {
+    IoError(String),
+    DatabaseError(String),
+    ParseError(String),
+    UnsortedError(String),
+}
+
+impl std::fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SnapshotError::IoError(msg) => write!(f, "IO error: {}", msg),
+            SnapshotError::DatabaseError(msg) => write!(f, "Database error: {}", msg),
+            SnapshotError::ParseError(msg) => write!(f, "Parse error: {}", msg),
+            SnapshotError::UnsortedError(path) => write!(f, "Snapshot is not sorted by path at {}", path),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{FileEntry, FileType};
+    use std::path::PathBuf;
+    use std::time::{Duration, UNIX_EPOCH};
+    use tempfile::NamedTempFile;
+
+    fn record(path: &str, size: u64, modified_time: i64) -> String {
+        serde_json::to_string(&SnapshotRecord {
+            path: path.to_string(),
+            size,
+            modified_time,
+            modified_nsec: 0,
+            file_type: "regular".to_string(),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_diff_snapshots() {
+        let old = [
+            record("/home/user/a.txt", 10, 100),
+            record("/home/user/b.txt", 20, 100),
+            record("/home/user/c.txt", 30, 100),
+            record("/home/user/d.txt", 40, 100),
+        ]
+        .join("\n");
+        let new = [
+            record("/home/user/a.txt", 10, 100),
+            record("/home/user/b.txt", 25, 100),
+            record("/home/user/d.txt", 40, 200),
+            record("/home/user/e.txt", 50, 100),
+        ]
+        .join("\n");
+
+        let diff = diff_snapshots(old.as_bytes(), new.as_bytes()).unwrap();
+        assert_eq!(diff.added, vec!["/home/user/e.txt"]);
+        assert_eq!(diff.removed, vec!["/home/user/c.txt"]);
+        assert_eq!(diff.modified, vec!["/home/user/b.txt", "/home/user/d.txt"]);
+    }
+
+    #[test]
+    fn test_diff_identical_snapshots() {
+        let snapshot = [record("/a", 1, 1), record("/b", 2, 2)].join("\n");
+        let diff = diff_snapshots(snapshot.as_bytes(), snapshot.as_bytes()).unwrap();
+        assert!(diff.is_empty());
+    }
+
+    #[test]
+    fn test_diff_rejects_unsorted_snapshot() {
+        let unsorted = [record("/b", 1, 1), record("/a", 2, 2)].join("\n");
+        let result = diff_snapshots(unsorted.as_bytes(), "".as_bytes());
+        assert!(matches!(result, Err(SnapshotError::UnsortedError(_))));
+    }
+
+    #[test]
+    fn test_export_then_diff() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::open(temp_file.path()).unwrap();
+        let mtime = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        for name in ["b.txt", "a.txt"] {
+            db.insert_file(&FileEntry::new(
+                name.to_string(),
+                PathBuf::from("/home/user").join(name),
+                1024,
+                mtime,
+                FileType::Regular,
+            ))
+            .unwrap();
+        }
+
+        let mut before = Vec::new();
+        assert_eq!(export_snapshot(&db, &mut before).unwrap(), 2);
+
+        db.delete_file("/home/user/a.txt").unwrap();
+        db.insert_file(&FileEntry::new(
+            "c.txt".to_string(),
+            PathBuf::from("/home/user/c.txt"),
+            1024,
+            mtime,
+            FileType::Regular,
+        ))
+        .unwrap();
+
+        let mut after = Vec::new();
+        export_snapshot(&db, &mut after).unwrap();
+
+        let diff = diff_snapshots(before.as_slice(), after.as_slice()).unwrap();
+        assert_eq!(diff.added, vec!["/home/user/c.txt"]);
+        assert_eq!(diff.removed, vec!["/home/user/a.txt"]);
+        assert!(diff.modified.is_empty());
+    }
+
+    #[test]
+    fn test_usage_stats_round_trip() {
+        let mtime = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let insert = |db: &Database, name: &str| {
+            db.insert_file(&FileEntry::new(
+                name.to_string(),
+                PathBuf::from("/home/user").join(name),
+                1024,
+                mtime,
+                FileType::Regular,
+            ))
+            .unwrap();
+        };
+
+        let old_file = NamedTempFile::new().unwrap();
+        let old_db = Database::open(old_file.path()).unwrap();
+        for name in ["editor.desktop", "notes.txt", "gone.txt"] {
+            insert(&old_db, name);
+        }
+        for _ in 0..3 {
+            old_db.record_file_launch("/home/user/editor.desktop").unwrap();
+        }
+        old_db.record_file_launch("/home/user/notes.txt").unwrap();
+        old_db.record_file_launch("/home/user/gone.txt").unwrap();
+
+        let mut exported = Vec::new();
+        assert_eq!(export_usage_stats(&old_db, &mut exported).unwrap(), 3);
+
+        // The new machine has a different file set
+        let new_file = NamedTempFile::new().unwrap();
+        let new_db = Database::open(new_file.path()).unwrap();
+        for name in ["editor.desktop", "notes.txt", "new.txt"] {
+            insert(&new_db, name);
+        }
+
+        let stats = import_usage_stats(&new_db, exported.as_slice()).unwrap();
+        assert_eq!(stats, UsageImportStats { imported: 2, skipped: 1 });
+
+        let (launch_count, last_launched) = new_db.get_file_usage("/home/user/editor.desktop").unwrap().unwrap();
+        assert_eq!(launch_count, 3);
+        assert!(last_launched > 0);
+        assert_eq!(new_db.get_file_usage("/home/user/notes.txt").unwrap().unwrap().0, 1);
+        assert!(new_db.get_file_usage("/home/user/new.txt").unwrap().is_none());
+
+        // Importing again does not inflate the counts
+        import_usage_stats(&new_db, exported.as_slice()).unwrap();
+        assert_eq!(new_db.get_file_usage("/home/user/editor.desktop").unwrap().unwrap().0, 3);
+    }
+}
@@ -0,0 +1,61 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::{interval_at, Instant, MissedTickBehavior};
+
+/// Run `task` on the blocking thread pool every `period` while `running` is set
+///
+/// The first run happens one full period after spawning. Runs never overlap:
+/// ticks missed while a slow run is in progress are skipped rather than queued,
+/// so the async event loop is never blocked by the work itself.
+pub fn spawn_periodic_blocking<F>(period: Duration, running: Arc<AtomicBool>, task: F) -> JoinHandle<()>
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    let task = Arc::new(task);
+
+    tokio::spawn(async move {
+        let mut timer = interval_at(Instant::now() + period, period);
+        timer.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        loop {
+            timer.tick().await;
+            if !running.load(Ordering::Relaxed) {
+                break;
+            }
+
+            let task = Arc::clone(&task);
+            if let Err(e) = tokio::task::spawn_blocking(move || task()).await {
+                eprintln!("Background task failed: {}", e);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    #[tokio::test]
+    async fn test_periodic_task_runs_on_interval() {
+        let running = Arc::new(AtomicBool::new(true));
+        let runs = Arc::new(AtomicUsize::new(0));
+
+        let runs_clone = Arc::clone(&runs);
+        let handle = spawn_periodic_blocking(Duration::from_millis(50), Arc::clone(&running), move || {
+            runs_clone.fetch_add(1, Ordering::SeqCst);
+        });
+
+        // Nothing runs before the first period elapses
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(runs.load(Ordering::SeqCst) >= 2);
+
+        running.store(false, Ordering::Relaxed);
+        tokio::time::timeout(Duration::from_secs(1), handle).await.unwrap().unwrap();
+    }
+}
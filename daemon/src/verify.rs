@@ -0,0 +1,205 @@
+use crate::config::Config;
+use crate::database::Database;
+use crate::models::{self, FileEntry, FileType, IndexOperation};
+use crate::repo::RepoRootCache;
+use crate::scanner::read_xattrs;
+use rusqlite::Result as SqliteResult;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often the daemon runs a verification pass
+pub const VERIFY_PERIOD: Duration = Duration::from_secs(1);
+
+/// Entries indexed or verified more recently than this are not checked again
+pub const VERIFY_MIN_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// What one verification pass found
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct VerifyStats {
+    pub checked: usize,
+    pub updated: usize,
+    pub deleted: usize,
+}
+
+/// Re-stat the longest-unverified entries and repair the ones that drifted
+///
+/// Checks up to `performance.verify_rate_per_sec` entries older than
+/// `min_age`, stopping early once the pass has used `performance.max_cpu_percent`
+/// of `VERIFY_PERIOD`. Entries gone from disk are deleted, changed ones are
+/// re-indexed, and unchanged ones are marked verified so the next pass moves on.
+/// Entries under an include root that is missing or not mounted, or under an
+/// offline mount, are not fetched at all until it is back.
+pub fn verify_stale_entries(db: &Database, config: &Config, min_age: Duration) -> SqliteResult<VerifyStats> {
+    let mut stats = VerifyStats::default();
+    let limit = config.performance.verify_rate_per_sec;
+    if limit == 0 {
+        return Ok(stats);
+    }
+
+    let budget = VERIFY_PERIOD * u32::from(config.performance.max_cpu_percent) / 100;
+    let started = Instant::now();
+    let mut repo_roots = RepoRootCache::new();
+    let mut operations = Vec::new();
+    let mut unchanged = Vec::new();
+    let roots = config.indexing.include_roots();
+    let absent_roots: Vec<&Path> = roots
+        .iter()
+        .map(PathBuf::as_path)
+        .filter(|root| !models::root_present(root))
+        .collect();
+
+    for entry in db.stale_entries_outside(min_age, &absent_roots, limit)? {
+        if started.elapsed() >= budget {
+            break;
+        }
+        stats.checked += 1;
+
+        let metadata = match std::fs::symlink_metadata(&entry.path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                stats.deleted += 1;
+                operations.push(IndexOperation::Delete(entry.path));
+                continue;
+            }
+            // Unreadable for now; verify it again on a later pass
+            Err(_) => {
+                unchanged.push(entry.path);
+                continue;
+            }
+        };
+
+        let file_type = if metadata.is_dir() {
+            FileType::Directory
+        } else if metadata.is_symlink() {
+            FileType::Symlink
+        } else if metadata.is_file() {
+            FileType::Regular
+        } else {
+            FileType::Other
+        };
+        let mut current = FileEntry::new(
+            entry.filename.clone(),
+            entry.path.clone(),
+            metadata.len(),
+            metadata.modified().unwrap_or_else(|_| SystemTime::now()),
+            file_type,
+        );
+        current.is_executable = models::is_executable(&metadata);
+
+        if current == entry && current.file_type == entry.file_type && current.is_executable == entry.is_executable {
+            unchanged.push(entry.path);
+            continue;
+        }
+
+        if config.indexing.index_xattrs {
+            current.xattrs = Some(read_xattrs(&current.path, &config.indexing.xattr_allowlist));
+        }
+        if let Some(max_size) = config.indexing.content_size_limit().filter(|_| metadata.is_file()) {
+            current.content = Some(models::read_text_content(&current.path, max_size).unwrap_or_default());
+        }
+        if config.indexing.detect_git_repos {
+            current.repo_root = repo_roots.repo_root(&current.path, metadata.is_dir());
+        }
+        if config.indexing.index_ownership {
+            let (uid, gid) = models::ownership(&metadata);
+            (current.uid, current.gid) = (Some(uid), Some(gid));
+        }
+        stats.updated += 1;
+        operations.push(IndexOperation::Update(current));
+    }
+
+    db.execute_batch(&operations)?;
+    db.mark_verified(&unchanged)?;
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_deleted_file_is_pruned() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = Database::open_in_memory().unwrap();
+        let day = Duration::from_secs(24 * 60 * 60);
+
+        let kept = temp_dir.path().join("kept.txt");
+        let removed = temp_dir.path().join("removed.txt");
+        for path in [&kept, &removed] {
+            std::fs::write(path, "content").unwrap();
+            let metadata = std::fs::metadata(path).unwrap();
+            let mut entry = FileEntry::new(
+                models::entry_name(path),
+                path.clone(),
+                metadata.len(),
+                metadata.modified().unwrap(),
+                FileType::Regular,
+            );
+            entry.indexed_time = SystemTime::now() - day;
+            db.insert_file(&entry).unwrap();
+        }
+        std::fs::remove_file(&removed).unwrap();
+
+        let stats = verify_stale_entries(&db, &Config::default(), VERIFY_MIN_AGE).unwrap();
+        assert_eq!(stats, VerifyStats { checked: 2, updated: 0, deleted: 1 });
+
+        assert!(db.query_files("removed", 10).unwrap().is_empty());
+        assert_eq!(db.query_files("kept", 10).unwrap().len(), 1);
+
+        // The unchanged file counts as verified and is skipped next time
+        let stats = verify_stale_entries(&db, &Config::default(), VERIFY_MIN_AGE).unwrap();
+        assert_eq!(stats.checked, 0);
+    }
+
+    #[test]
+    fn test_entries_of_unmounted_root_are_kept() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = Database::open_in_memory().unwrap();
+        let path = temp_dir.path().join("photo.jpg");
+        let mut entry = FileEntry::new(models::entry_name(&path), path, 7, SystemTime::now(), FileType::Regular);
+        entry.indexed_time = SystemTime::now() - Duration::from_secs(24 * 60 * 60);
+        db.insert_file(&entry).unwrap();
+
+        // The root is an empty mount point while its filesystem is away
+        let mut config = Config::default();
+        config.indexing.include_paths = vec![temp_dir.path().to_string_lossy().to_string().into()];
+        let stats = verify_stale_entries(&db, &config, VERIFY_MIN_AGE).unwrap();
+        assert_eq!(stats, VerifyStats::default());
+        assert_eq!(db.count_files().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_entries_of_unmounted_root_do_not_block_others() {
+        let unmounted = TempDir::new().unwrap();
+        let home = TempDir::new().unwrap();
+        let db = Database::open_in_memory().unwrap();
+        let week_ago = SystemTime::now() - Duration::from_secs(7 * 24 * 60 * 60);
+
+        let mut config = Config::default();
+        config.indexing.include_paths = vec![
+            unmounted.path().to_string_lossy().to_string().into(),
+            home.path().to_string_lossy().to_string().into(),
+        ];
+        config.performance.verify_rate_per_sec = 5;
+
+        // The unmounted root's entries are the longest unverified, more than one pass checks
+        for i in 0..10 {
+            let path = unmounted.path().join(format!("photo{}.jpg", i));
+            let mut entry = FileEntry::new(models::entry_name(&path), path, 7, SystemTime::now(), FileType::Regular);
+            entry.indexed_time = week_ago;
+            db.insert_file(&entry).unwrap();
+        }
+        let removed = home.path().join("removed.txt");
+        let mut entry = FileEntry::new(models::entry_name(&removed), removed, 7, SystemTime::now(), FileType::Regular);
+        entry.indexed_time = SystemTime::now() - Duration::from_secs(24 * 60 * 60);
+        db.insert_file(&entry).unwrap();
+        std::fs::write(home.path().join("kept.txt"), "content").unwrap();
+
+        let stats = verify_stale_entries(&db, &config, VERIFY_MIN_AGE).unwrap();
+        assert_eq!(stats, VerifyStats { checked: 1, updated: 0, deleted: 1 });
+        assert!(db.query_files("removed", 10).unwrap().is_empty());
+        assert_eq!(db.count_files().unwrap(), 10);
+    }
+}
@@ -1,17 +1,42 @@
-use crate::config::Config;
-use crate::models::{FileEntry, FileType, IndexOperation};
-use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
-use std::collections::{HashMap, VecDeque};
-use std::path::{Path, PathBuf};
+use crate::config::{Config, IndexingConfig, PausePolicy};
+use crate::models::{self, FileEntry, FileType, IndexOperation};
+use crate::repo::RepoRootCache;
+use crate::scanner;
+use notify::event::{MetadataKind, ModifyKind, RenameMode};
+use notify::{Event, EventKind, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Component, Path, PathBuf};
 use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant, SystemTime};
+use walkdir::WalkDir;
 use glob::Pattern;
 
+/// Poll interval for paths that exceed the native watch budget
+const POLL_FALLBACK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Longest wait between attempts to watch a degraded path again
+const MAX_WATCH_RETRY_BACKOFF: Duration = Duration::from_secs(60 * 60);
+
+/// Debounce window for file events (see `EventProcessor::from_config`)
+const DEBOUNCE_MS: u64 = 200;
+
+/// Most operations kept queued between calls to `process_pending`
+const MAX_QUEUE_SIZE: usize = 10000;
+
 /// Filesystem watcher that monitors directories for changes
 pub struct FilesystemWatcher {
     watcher: RecommendedWatcher,
+    poll_watcher: Option<PollWatcher>,
+    event_sender: Sender<FilesystemEvent>,
     event_receiver: Receiver<FilesystemEvent>,
     watched_paths: Vec<PathBuf>,
+    polled_paths: Vec<PathBuf>,
+    indexing: IndexingConfig,
+    max_watches: Option<usize>,
+    /// Directories covered by the native watches, counted against `max_watches`
+    watched_dirs: usize,
+    errors: Arc<Mutex<WatchErrorTracker>>,
 }
 
 /// Filesystem event types
@@ -21,86 +46,218 @@ pub enum FilesystemEvent {
     Modified(PathBuf),
     Deleted(PathBuf),
     Moved { from: PathBuf, to: PathBuf },
+    /// The old side of a rename, paired with a `RenamedTo` by `tracker`
+    RenamedFrom { path: PathBuf, tracker: usize },
+    /// The new side of a rename, paired with a `RenamedFrom` by `tracker`
+    RenamedTo { path: PathBuf, tracker: usize },
+    /// Mode bits changed without a content change (e.g. `chmod +x`)
+    PermissionsChanged(PathBuf),
+    /// Events were lost, e.g. because the inotify queue overflowed; the paths
+    /// (every watched root when empty) have to be rescanned
+    Rescan(Vec<PathBuf>),
+}
+
+/// Outcome of registering a set of paths with the watcher
+#[derive(Debug, Default)]
+pub struct WatchReport {
+    /// Paths registered with a native watch
+    pub watched: Vec<PathBuf>,
+    /// Paths beyond `max_watches`, handed to the poll fallback instead
+    pub skipped: Vec<PathBuf>,
+    /// Paths that could not be watched at all
+    pub errors: Vec<WatcherError>,
+}
+
+impl WatchReport {
+    /// Add the outcome of registering another batch of paths
+    pub fn extend(&mut self, other: WatchReport) {
+        self.watched.extend(other.watched);
+        self.skipped.extend(other.skipped);
+        self.errors.extend(other.errors);
+    }
 }
 
 impl FilesystemWatcher {
     /// Create a new filesystem watcher
     pub fn new(config: &Config) -> Result<Self, WatcherError> {
         let (event_sender, event_receiver) = channel();
+        let errors = Arc::new(Mutex::new(WatchErrorTracker::new(
+            config.performance.watch_error_threshold,
+            config.watch_error_retry(),
+        )));
         
         // Create the notify watcher with event handler
-        let watcher = Self::create_watcher(event_sender, config)?;
+        let watcher = notify::recommended_watcher(
+            Self::event_handler(event_sender.clone(), config.indexing.clone(), Arc::clone(&errors)),
+        )
+        .map_err(|e| WatcherError::InitializationError(e.to_string()))?;
         
         Ok(FilesystemWatcher {
             watcher,
+            poll_watcher: None,
+            event_sender,
             event_receiver,
             watched_paths: Vec::new(),
+            polled_paths: Vec::new(),
+            indexing: config.indexing.clone(),
+            max_watches: config.performance.max_watches,
+            watched_dirs: 0,
+            errors,
         })
     }
     
-    /// Create the underlying notify watcher
-    fn create_watcher(
+    /// Build the notify event handler that forwards filtered events to the channel
+    fn event_handler(
         event_sender: Sender<FilesystemEvent>,
-        config: &Config,
-    ) -> Result<RecommendedWatcher, WatcherError> {
-        let exclude_patterns = config.indexing.exclude_patterns.clone();
-        
-        let watcher = notify::recommended_watcher(move |res: Result<Event, notify::Error>| {
+        indexing: IndexingConfig,
+        errors: Arc<Mutex<WatchErrorTracker>>,
+    ) -> impl FnMut(Result<Event, notify::Error>) + Send + 'static {
+        move |res: Result<Event, notify::Error>| {
             match res {
                 Ok(event) => {
+                    errors.lock().unwrap().record_success(&event.paths);
                     // Convert notify events to our FilesystemEvent type
-                    if let Some(fs_event) = Self::convert_event(event, &exclude_patterns) {
+                    if let Some(fs_event) = Self::convert_event(event, &indexing) {
                         let _ = event_sender.send(fs_event);
                     }
                 }
-                Err(e) => {
+                Err(e) if e.paths.is_empty() => {
                     eprintln!("Filesystem watch error: {:?}", e);
                 }
+                Err(e) => {
+                    let mut errors = errors.lock().unwrap();
+                    for path in &e.paths {
+                        match errors.record_error(path, Instant::now()) {
+                            WatchErrorAction::Log => eprintln!("Filesystem watch error: {:?}", e),
+                            WatchErrorAction::Degrade => eprintln!(
+                                "Warning: {:?} keeps failing to watch ({:?}); ignoring its errors until it recovers",
+                                path, e.kind
+                            ),
+                            WatchErrorAction::Suppress => {}
+                        }
+                    }
+                }
             }
-        })
-        .map_err(|e| WatcherError::InitializationError(e.to_string()))?;
-        
-        Ok(watcher)
+        }
     }
     
     /// Convert notify Event to FilesystemEvent, applying filters
-    fn convert_event(event: Event, exclude_patterns: &[String]) -> Option<FilesystemEvent> {
+    fn convert_event(event: Event, indexing: &IndexingConfig) -> Option<FilesystemEvent> {
         // Filter out events for excluded paths
         for path in &event.paths {
-            if Self::should_exclude(path, exclude_patterns) {
+            if Self::is_excluded(path, indexing) {
+                return None;
+            }
+        }
+        
+        if event.need_rescan() {
+            if !indexing.rescan_on_overflow {
+                eprintln!(
+                    "Warning: Filesystem events were lost and rescan_on_overflow is off; \
+                     run `novasearch-daemon reindex` to pick up the missed changes"
+                );
                 return None;
             }
+            return Some(FilesystemEvent::Rescan(event.paths));
         }
         
         match event.kind {
             EventKind::Create(_) => {
-                if let Some(path) = event.paths.first() {
-                    Some(FilesystemEvent::Created(path.clone()))
-                } else {
-                    None
-                }
+                event.paths.first().map(|path| FilesystemEvent::Created(path.clone()))
+            }
+            // inotify reports every IN_ATTRIB (touch, chown, xattrs) as an unspecified
+            // metadata change, which falls through to a full refresh below
+            EventKind::Modify(ModifyKind::Metadata(MetadataKind::Permissions)) => {
+                event.paths.first().map(|path| FilesystemEvent::PermissionsChanged(path.clone()))
+            }
+            // Renames are reported per side and, when both are watched, as a pair.
+            // The old path must not be refreshed: on case-insensitive filesystems
+            // it still resolves after a case-only rename.
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if event.paths.len() == 2 => {
+                Some(FilesystemEvent::Moved {
+                    from: event.paths[0].clone(),
+                    to: event.paths[1].clone(),
+                })
+            }
+            // A lone side is matched with the other by its tracker (the inotify
+            // cookie); without one it can only be a delete or create
+            EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+                let path = event.paths.first()?.clone();
+                Some(match event.tracker() {
+                    Some(tracker) => FilesystemEvent::RenamedFrom { path, tracker },
+                    None => FilesystemEvent::Deleted(path),
+                })
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+                let path = event.paths.first()?.clone();
+                Some(match event.tracker() {
+                    Some(tracker) => FilesystemEvent::RenamedTo { path, tracker },
+                    None => FilesystemEvent::Created(path),
+                })
             }
             EventKind::Modify(_) => {
-                if let Some(path) = event.paths.first() {
-                    Some(FilesystemEvent::Modified(path.clone()))
-                } else {
-                    None
-                }
+                event.paths.first().map(|path| FilesystemEvent::Modified(path.clone()))
             }
             EventKind::Remove(_) => {
-                if let Some(path) = event.paths.first() {
-                    Some(FilesystemEvent::Deleted(path.clone()))
-                } else {
-                    None
-                }
+                event.paths.first().map(|path| FilesystemEvent::Deleted(path.clone()))
             }
             EventKind::Access(_) => None, // Ignore access events
             EventKind::Any | EventKind::Other => None,
         }
     }
     
-    /// Check if a path should be excluded based on patterns
-    fn should_exclude(path: &Path, exclude_patterns: &[String]) -> bool {
+    /// Check if a path should be excluded under the indexing configuration
+    ///
+    /// Files with an always-include extension bypass the exclude patterns for
+    /// their own name, but are still dropped inside excluded directories.
+    pub(crate) fn is_excluded(path: &Path, indexing: &IndexingConfig) -> bool {
+        let index_hidden = indexing.index_hidden_files;
+        let exclude_patterns = indexing.effective_exclude_patterns();
+        
+        // Database writes would otherwise trigger events that cause more writes
+        if indexing.is_internal_path(path) {
+            return true;
+        }
+        
+        if indexing.path_length_limit().is_some_and(|limit| path.as_os_str().len() > limit) {
+            return true;
+        }
+        
+        // Hidden components above the include root were chosen explicitly
+        let roots = indexing.include_roots();
+        let root = models::containing_root(path, &roots).unwrap_or(Path::new(""));
+        
+        if indexing.always_includes(path) && !path.is_dir() {
+            let hidden = path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+            if hidden && !index_hidden {
+                return true;
+            }
+            return path.parent().is_some_and(|parent| {
+                Self::should_exclude(parent, root, &exclude_patterns, index_hidden)
+                    || indexing.excludes_relative_path(parent)
+            });
+        }
+        
+        Self::should_exclude(path, root, &exclude_patterns, index_hidden) || indexing.excludes_relative_path(path)
+    }
+    
+    /// Check if a path should be excluded based on patterns and hidden-file handling
+    ///
+    /// Only the components below `root` count as hidden, as in the scanner's walk.
+    fn should_exclude(path: &Path, root: &Path, exclude_patterns: &[String], index_hidden: bool) -> bool {
+        // Skip hidden (dot-prefixed) components unless explicitly enabled
+        if !index_hidden {
+            for component in path.strip_prefix(root).unwrap_or(path).components() {
+                if let Component::Normal(name) = component {
+                    if name.to_string_lossy().starts_with('.') {
+                        return true;
+                    }
+                }
+            }
+        }
+        
         for pattern_str in exclude_patterns {
             // Check if any component of the path matches the pattern
             for component in path.components() {
@@ -112,42 +269,185 @@ impl FilesystemWatcher {
                         return true;
                     }
                 }
-                
-                // Also do simple string matching for patterns like ".*" (hidden files)
-                if pattern_str.starts_with(".*") && component_str.starts_with('.') {
-                    return true;
-                }
             }
         }
         
         false
     }
     
-    /// Watch a directory recursively
+    /// Watch a directory, recursively unless it is a non-recursive include root
     pub fn watch_path<P: AsRef<Path>>(&mut self, path: P) -> Result<(), WatcherError> {
         let path = path.as_ref();
-        
+        let cost = self.watch_cost(path);
+        self.register_watch(path, cost)
+    }
+    
+    /// Register a native watch on `path` covering `cost` directories
+    fn register_watch(&mut self, path: &Path, cost: usize) -> Result<(), WatcherError> {
         self.watcher
-            .watch(path, RecursiveMode::Recursive)
+            .watch(path, self.recursive_mode(path))
             .map_err(|e| WatcherError::WatchError(format!("Failed to watch {:?}: {}", path, e)))?;
         
         self.watched_paths.push(path.to_path_buf());
+        self.watched_dirs += cost;
         
         Ok(())
     }
     
+    /// Count the directories a native watch on `path` registers
+    ///
+    /// A recursive watch adds one inotify watch per directory below it, excluded
+    /// or not, since the kernel knows nothing of the exclude patterns.
+    fn watch_cost(&self, path: &Path) -> usize {
+        match self.recursive_mode(path) {
+            RecursiveMode::NonRecursive => 1,
+            RecursiveMode::Recursive => WalkDir::new(path)
+                .follow_links(false)
+                .into_iter()
+                .filter_entry(|entry| entry.file_type().is_dir())
+                .filter(Result::is_ok)
+                .count()
+                .max(1),
+        }
+    }
+    
     /// Watch multiple directories
     pub fn watch_paths(&mut self, paths: &[PathBuf]) -> Vec<WatcherError> {
-        let mut errors = Vec::new();
+        self.watch_paths_prioritized(paths, &[]).errors
+    }
+    
+    /// Watch multiple directories in priority order, honoring `max_watches`
+    ///
+    /// Paths listed in `priority` are watched first, then the remaining paths
+    /// from shallowest to deepest. The budget counts directories, so a
+    /// recursive watch uses one per directory below it; a path that no longer
+    /// fits is polled instead and reported as skipped.
+    pub fn watch_paths_prioritized(&mut self, paths: &[PathBuf], priority: &[PathBuf]) -> WatchReport {
+        let mut report = WatchReport::default();
         
+        let mut ordered: Vec<&PathBuf> = paths.iter().collect();
+        ordered.sort_by_key(|path| (!priority.contains(path), path.components().count()));
+        
+        for path in ordered {
+            let cost = if self.max_watches.is_some() { self.watch_cost(path) } else { 0 };
+            let fits = self
+                .max_watches
+                .is_none_or(|max| self.watched_dirs + cost <= max);
+            
+            if !fits {
+                if let Err(e) = self.poll_path(path) {
+                    eprintln!("Warning: {}", e);
+                    report.errors.push(e);
+                }
+                report.skipped.push(path.clone());
+                continue;
+            }
+            
+            match self.register_watch(path, cost) {
+                Ok(()) => report.watched.push(path.clone()),
+                Err(e) => {
+                    eprintln!("Warning: {}", e);
+                    report.errors.push(e);
+                }
+            }
+        }
+        
+        report
+    }
+    
+    /// Order `paths` for registration, leaving out those a recursive watch covers
+    ///
+    /// Paths in `priority` come first, then the rest from shallowest to
+    /// deepest, as in `watch_paths_prioritized`. A path below another listed
+    /// or already watched path whose watch is recursive needs no watch of its
+    /// own, so it is dropped.
+    pub fn plan_watches(&self, paths: &[PathBuf], priority: &[PathBuf]) -> Vec<PathBuf> {
+        let recursive_roots: HashSet<&Path> = paths
+            .iter()
+            .chain(&self.watched_paths)
+            .filter(|root| matches!(self.recursive_mode(root), RecursiveMode::Recursive))
+            .map(PathBuf::as_path)
+            .collect();
+        let mut seen: HashSet<&Path> = self.watched_paths.iter().map(PathBuf::as_path).collect();
+
+        let mut planned: Vec<PathBuf> = Vec::new();
         for path in paths {
-            if let Err(e) = self.watch_path(path) {
-                eprintln!("Warning: {}", e);
-                errors.push(e);
+            let covered = path.ancestors().skip(1).any(|ancestor| recursive_roots.contains(ancestor));
+            if !covered && seen.insert(path) {
+                planned.push(path.clone());
             }
         }
+        planned.sort_by_key(|path| (!priority.contains(path), path.components().count()));
+        planned
+    }
+
+    /// Watch `paths` a batch at a time, pausing between batches
+    ///
+    /// Spreads the cost of setting up many watches instead of registering
+    /// them in one burst. The paths are ordered and trimmed by `plan_watches`
+    /// first; `progress` is called after each batch with the number of paths
+    /// handled so far and the total. A `batch_size` of 0 registers everything
+    /// in one batch.
+    pub fn watch_paths_batched<F>(
+        &mut self,
+        paths: &[PathBuf],
+        priority: &[PathBuf],
+        batch_size: usize,
+        pause: Duration,
+        mut progress: F,
+    ) -> WatchReport
+    where
+        F: FnMut(usize, usize),
+    {
+        let planned = self.plan_watches(paths, priority);
+        let batch_size = if batch_size == 0 { planned.len().max(1) } else { batch_size };
+
+        let mut report = WatchReport::default();
+        let mut done = 0;
+        for (index, batch) in planned.chunks(batch_size).enumerate() {
+            if index > 0 && !pause.is_zero() {
+                std::thread::sleep(pause);
+            }
+            report.extend(self.watch_paths_prioritized(batch, priority));
+            done += batch.len();
+            progress(done, planned.len());
+        }
+        report
+    }
+
+    /// Watch a directory with the poll fallback instead of a native watch
+    fn poll_path(&mut self, path: &Path) -> Result<(), WatcherError> {
+        if self.poll_watcher.is_none() {
+            let handler = Self::event_handler(
+                self.event_sender.clone(),
+                self.indexing.clone(),
+                Arc::clone(&self.errors),
+            );
+            let poll_config = notify::Config::default().with_poll_interval(POLL_FALLBACK_INTERVAL);
+            let poll_watcher = PollWatcher::new(handler, poll_config)
+                .map_err(|e| WatcherError::InitializationError(e.to_string()))?;
+            self.poll_watcher = Some(poll_watcher);
+        }
         
-        errors
+        let mode = self.recursive_mode(path);
+        if let Some(poll_watcher) = self.poll_watcher.as_mut() {
+            poll_watcher
+                .watch(path, mode)
+                .map_err(|e| WatcherError::WatchError(format!("Failed to poll {:?}: {}", path, e)))?;
+        }
+        
+        self.polled_paths.push(path.to_path_buf());
+        
+        Ok(())
+    }
+    
+    /// Get the watch mode configured for `path`
+    fn recursive_mode(&self, path: &Path) -> RecursiveMode {
+        if self.indexing.is_recursive_root(path) {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        }
     }
     
     /// Receive the next filesystem event (non-blocking)
@@ -164,14 +464,191 @@ impl FilesystemWatcher {
     pub fn watched_paths(&self) -> &[PathBuf] {
         &self.watched_paths
     }
+    
+    /// Get list of paths handled by the poll fallback
+    pub fn polled_paths(&self) -> &[PathBuf] {
+        &self.polled_paths
+    }
+
+    /// Get the tracker of persistent watch errors, shared with the event handlers
+    pub fn error_tracker(&self) -> Arc<Mutex<WatchErrorTracker>> {
+        Arc::clone(&self.errors)
+    }
+
+    /// Get the paths whose watch errors are being ignored
+    pub fn degraded_paths(&self) -> Vec<PathBuf> {
+        self.errors.lock().unwrap().degraded_paths()
+    }
+
+    /// Try again to watch degraded paths whose backoff has run out
+    ///
+    /// Each path under a native watch is watched anew, with the mode of its
+    /// root, which picks up a directory that became readable again; the rest
+    /// of the root's watches are left as they are. Paths below a
+    /// non-recursive root were never watched, and polled roots are walked
+    /// again on every poll anyway, so those only get their errors reported
+    /// again. Returns the paths that were retried.
+    pub fn retry_degraded(&mut self) -> Vec<PathBuf> {
+        let due = self.errors.lock().unwrap().take_due_retries(Instant::now());
+
+        for path in &due {
+            let mode = match models::containing_root(path, &self.watched_paths) {
+                Some(root) if root == path => self.recursive_mode(root),
+                Some(root) => match self.recursive_mode(root) {
+                    RecursiveMode::Recursive => RecursiveMode::Recursive,
+                    RecursiveMode::NonRecursive => continue,
+                },
+                None => continue,
+            };
+            if let Err(e) = self.watcher.watch(path, mode) {
+                eprintln!("Warning: Failed to watch {:?} again: {}", path, e);
+                // Counts as another failure, degrading the path again right away
+                self.errors.lock().unwrap().record_error(path, Instant::now());
+            }
+        }
+        due
+    }
+}
+
+/// What to do about a watch error reported for a path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchErrorAction {
+    /// Below the threshold; report the error
+    Log,
+    /// The error reached the threshold; report that the path is now degraded
+    Degrade,
+    /// The path is already degraded; stay quiet
+    Suppress,
+}
+
+/// Error count and retry schedule of one path
+#[derive(Debug, Clone)]
+struct PathErrors {
+    count: u32,
+    degraded: bool,
+    backoff: Duration,
+    retry_at: Option<Instant>,
+}
+
+/// Tracks paths whose watches keep failing, e.g. after losing read permission
+///
+/// Once a path has reported `threshold` errors it is degraded: its errors are
+/// no longer logged, and it is listed in the daemon's status. With a retry
+/// delay set the path is tried again after that delay, doubling on each
+/// failed retry up to an hour. A path that reports events again is taken off
+/// the list.
+#[derive(Debug)]
+pub struct WatchErrorTracker {
+    threshold: u32,
+    retry_after: Option<Duration>,
+    paths: HashMap<PathBuf, PathErrors>,
+}
+
+impl WatchErrorTracker {
+    /// Create a tracker degrading paths after `threshold` errors, retrying
+    /// them after `retry_after` (never when `None`)
+    pub fn new(threshold: u32, retry_after: Option<Duration>) -> Self {
+        WatchErrorTracker {
+            threshold: threshold.max(1),
+            retry_after,
+            paths: HashMap::new(),
+        }
+    }
+
+    /// Count an error for `path` and decide whether to report it
+    pub fn record_error(&mut self, path: &Path, now: Instant) -> WatchErrorAction {
+        let retry_after = self.retry_after;
+        let state = self.paths.entry(path.to_path_buf()).or_insert_with(|| PathErrors {
+            count: 0,
+            degraded: false,
+            backoff: retry_after.unwrap_or_default(),
+            retry_at: None,
+        });
+        if state.degraded {
+            return WatchErrorAction::Suppress;
+        }
+
+        state.count += 1;
+        if state.count < self.threshold {
+            return WatchErrorAction::Log;
+        }
+        state.degraded = true;
+        state.retry_at = retry_after.map(|_| now + state.backoff);
+        WatchErrorAction::Degrade
+    }
+
+    /// Forget the errors of paths that reported events again
+    pub fn record_success(&mut self, paths: &[PathBuf]) {
+        if self.paths.is_empty() {
+            return;
+        }
+        for path in paths {
+            self.paths.remove(path);
+        }
+    }
+
+    /// Check whether errors for `path` are being ignored
+    pub fn is_degraded(&self, path: &Path) -> bool {
+        self.paths.get(path).is_some_and(|state| state.degraded)
+    }
+
+    /// Get the degraded paths, sorted
+    pub fn degraded_paths(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = self
+            .paths
+            .iter()
+            .filter(|(_, state)| state.degraded)
+            .map(|(path, _)| path.clone())
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    /// Take the degraded paths due for another attempt, sorted
+    ///
+    /// Each one gets a single chance: the next error degrades it again, with
+    /// twice the previous wait before the following retry.
+    pub fn take_due_retries(&mut self, now: Instant) -> Vec<PathBuf> {
+        let threshold = self.threshold;
+        let mut due: Vec<PathBuf> = self
+            .paths
+            .iter_mut()
+            .filter(|(_, state)| state.degraded && state.retry_at.is_some_and(|at| at <= now))
+            .map(|(path, state)| {
+                state.degraded = false;
+                state.count = threshold - 1;
+                state.retry_at = None;
+                state.backoff = (state.backoff * 2).min(MAX_WATCH_RETRY_BACKOFF);
+                path.clone()
+            })
+            .collect();
+        due.sort();
+        due
+    }
 }
 
 /// Event processor that handles debouncing and converts events to IndexOperations
 pub struct EventProcessor {
+    /// Pending events with the instant they become ready
     pending_events: HashMap<PathBuf, (FilesystemEvent, Instant)>,
+    /// Source paths of the pending moves
+    pending_move_sources: HashSet<PathBuf>,
+    /// Rename sides waiting for their other side, by tracker, as the
+    /// delete or create they fall back to
+    pending_renames: HashMap<usize, FilesystemEvent>,
     debounce_duration: Duration,
+    directory_debounce: Duration,
     operation_queue: VecDeque<IndexOperation>,
     max_queue_size: usize,
+    paused: Option<PausePolicy>,
+    detect_git_repos: bool,
+    index_ownership: bool,
+    /// Paths with lost events awaiting a rescan; empty means everything
+    rescan_requested: Option<Vec<PathBuf>>,
+    /// Size limit for reading file text, when content indexing is on
+    content_size_limit: Option<u64>,
+    /// Attribute name prefixes to read, when xattr indexing is on
+    xattr_allowlist: Option<Vec<String>>,
 }
 
 impl EventProcessor {
@@ -179,42 +656,302 @@ impl EventProcessor {
     pub fn new(debounce_duration: Duration, max_queue_size: usize) -> Self {
         EventProcessor {
             pending_events: HashMap::new(),
+            pending_move_sources: HashSet::new(),
+            pending_renames: HashMap::new(),
             debounce_duration,
+            directory_debounce: debounce_duration,
             operation_queue: VecDeque::new(),
             max_queue_size,
+            paused: None,
+            detect_git_repos: false,
+            index_ownership: false,
+            rescan_requested: None,
+            content_size_limit: None,
+            xattr_allowlist: None,
+        }
+    }
+    
+    /// Create the event processor used by the daemon and the embedded engine
+    ///
+    /// Directory events are debounced for `performance.debounce_directory_ms`
+    /// and the settings of `apply_entry_settings` are taken from `config`.
+    pub fn from_config(config: &Config) -> Self {
+        let mut processor = EventProcessor::new(Duration::from_millis(DEBOUNCE_MS), MAX_QUEUE_SIZE);
+        processor.set_directory_debounce(Duration::from_millis(config.performance.debounce_directory_ms));
+        processor.apply_entry_settings(config);
+        processor
+    }
+    
+    /// Set what is recorded about added and updated entries from `config`
+    ///
+    /// The daemon applies these again when the config file is reloaded, since
+    /// they only affect changes seen from then on.
+    pub fn apply_entry_settings(&mut self, config: &Config) {
+        self.set_detect_git_repos(config.indexing.detect_git_repos);
+        self.set_index_ownership(config.indexing.index_ownership);
+        self.set_content_size_limit(config.indexing.content_size_limit());
+        self.set_xattr_allowlist(config.indexing.index_xattrs.then(|| config.indexing.xattr_allowlist.clone()));
+    }
+    
+    /// Use a different debounce window for events on directories
+    ///
+    /// Directory changes usually come in bursts (extracting an archive, a build
+    /// writing many files), so a longer window avoids refreshing them repeatedly.
+    pub fn set_directory_debounce(&mut self, duration: Duration) {
+        self.directory_debounce = duration;
+    }
+    
+    /// Record the git repository of added and updated entries
+    pub fn set_detect_git_repos(&mut self, enabled: bool) {
+        self.detect_git_repos = enabled;
+    }
+    
+    /// Record the owner and group of added and updated entries
+    pub fn set_index_ownership(&mut self, enabled: bool) {
+        self.index_ownership = enabled;
+    }
+    
+    /// Read the text of added and updated files up to `limit` bytes, or stop with `None`
+    pub fn set_content_size_limit(&mut self, limit: Option<u64>) {
+        self.content_size_limit = limit;
+    }
+    
+    /// Read the extended attributes in `allowlist` of added and updated entries, or stop with `None`
+    pub fn set_xattr_allowlist(&mut self, allowlist: Option<Vec<String>>) {
+        self.xattr_allowlist = allowlist;
+    }
+    
+    /// Get the debounce window for an event on `path`, based on what is there now
+    fn debounce_for(&self, path: &Path) -> Duration {
+        if path.is_dir() {
+            self.directory_debounce
+        } else {
+            self.debounce_duration
+        }
+    }
+    
+    /// Stop converting events and handing out batches until `resume` is called
+    ///
+    /// Under `PausePolicy::Drop`, events added while paused are discarded.
+    pub fn pause(&mut self, policy: PausePolicy) {
+        self.paused = Some(policy);
+    }
+    
+    /// Resume processing after `pause`
+    pub fn resume(&mut self) {
+        self.paused = None;
+    }
+    
+    /// Check whether processing is paused
+    pub fn is_paused(&self) -> bool {
+        self.paused.is_some()
+    }
+    
+    /// Take the watched roots that lost events and must be rescanned
+    ///
+    /// Each reported path maps to the outermost of `roots` containing it; a
+    /// report without paths covers all of `roots`. Returns nothing when no
+    /// events were lost since the last call, or while paused.
+    pub fn take_rescan_roots(&mut self, roots: &[PathBuf]) -> Vec<PathBuf> {
+        if self.is_paused() {
+            return Vec::new();
         }
+        let requested = match self.rescan_requested.take() {
+            Some(requested) => requested,
+            None => return Vec::new(),
+        };
+        
+        let mut affected: Vec<PathBuf> = roots
+            .iter()
+            .filter(|root| requested.is_empty() || requested.iter().any(|path| path.starts_with(root)))
+            .cloned()
+            .collect();
+        affected.sort();
+        affected.dedup();
+        let outermost = affected.clone();
+        affected.retain(|root| !outermost.iter().any(|other| other != root && root.starts_with(other)));
+        affected
     }
     
     /// Add a filesystem event for processing
+    ///
+    /// Events are keyed by path, so a change seen through several overlapping
+    /// watch roots (e.g. `~` and `~/Documents`) collapses into one pending event.
+    /// A root that only sees one side of a rename reports a create or delete
+    /// next to the other root's move; those never replace the pending move.
+    /// Rename sides reported separately become one move when the other side
+    /// arrives within the debounce window, and a delete or create otherwise.
     pub fn add_event(&mut self, event: FilesystemEvent) {
+        if self.paused == Some(PausePolicy::Drop) {
+            return;
+        }
+        
+        let event = match event {
+            FilesystemEvent::RenamedFrom { path, tracker } => {
+                match self.pending_renames.remove(&tracker) {
+                    Some(FilesystemEvent::Created(to)) => {
+                        self.pending_events.remove(&to);
+                        FilesystemEvent::Moved { from: path, to }
+                    }
+                    _ => {
+                        self.pending_renames.insert(tracker, FilesystemEvent::Deleted(path.clone()));
+                        FilesystemEvent::Deleted(path)
+                    }
+                }
+            }
+            FilesystemEvent::RenamedTo { path, tracker } => {
+                match self.pending_renames.remove(&tracker) {
+                    Some(FilesystemEvent::Deleted(from)) => FilesystemEvent::Moved { from, to: path },
+                    _ => {
+                        self.pending_renames.insert(tracker, FilesystemEvent::Created(path.clone()));
+                        FilesystemEvent::Created(path)
+                    }
+                }
+            }
+            event => event,
+        };
+        
         let path = match &event {
+            FilesystemEvent::Rescan(paths) => {
+                match &mut self.rescan_requested {
+                    Some(requested) if !requested.is_empty() && !paths.is_empty() => {
+                        requested.extend(paths.iter().cloned());
+                    }
+                    Some(requested) => requested.clear(),
+                    None => self.rescan_requested = Some(paths.clone()),
+                }
+                return;
+            }
             FilesystemEvent::Created(p) => p.clone(),
             FilesystemEvent::Modified(p) => p.clone(),
             FilesystemEvent::Deleted(p) => p.clone(),
             FilesystemEvent::Moved { to, .. } => to.clone(),
+            FilesystemEvent::PermissionsChanged(p) => p.clone(),
+            FilesystemEvent::RenamedFrom { path, .. } | FilesystemEvent::RenamedTo { path, .. } => path.clone(),
         };
         
-        // Store event with current timestamp for debouncing
-        self.pending_events.insert(path, (event, Instant::now()));
+        // A move supersedes the delete reported for its source path
+        if let FilesystemEvent::Moved { from, .. } = &event {
+            self.pending_events.remove(from);
+        }
+        if matches!(event, FilesystemEvent::Deleted(_)) && self.pending_move_sources.contains(&path) {
+            return;
+        }
+        
+        let ready_at = Instant::now() + self.debounce_for(&path);
+        
+        // The move refreshes its destination after moving, which covers a
+        // create or modify reported for it
+        if matches!(event, FilesystemEvent::Created(_) | FilesystemEvent::Modified(_)) {
+            if let Some((FilesystemEvent::Moved { .. }, pending_ready_at)) = self.pending_events.get_mut(&path) {
+                *pending_ready_at = ready_at;
+                return;
+            }
+        }
+        
+        // A full refresh already pending for the path covers the permission change
+        if matches!(event, FilesystemEvent::PermissionsChanged(_)) {
+            if let Some((pending, pending_ready_at)) = self.pending_events.get_mut(&path) {
+                if matches!(pending, FilesystemEvent::Created(_) | FilesystemEvent::Modified(_)) {
+                    *pending_ready_at = ready_at;
+                    return;
+                }
+            }
+        }
+        
+        // Store event with the time its debounce window ends; the same move
+        // reported again keeps its source
+        let move_source = match &event {
+            FilesystemEvent::Moved { from, .. } => Some(from.clone()),
+            _ => None,
+        };
+        if let Some((FilesystemEvent::Moved { from, .. }, _)) = self.pending_events.insert(path, (event, ready_at)) {
+            self.pending_move_sources.remove(&from);
+        }
+        self.pending_move_sources.extend(move_source);
     }
     
     /// Process pending events and convert to IndexOperations
+    ///
+    /// Nothing is processed while paused; events stay pending until resumed.
     pub fn process_pending(&mut self) -> Vec<IndexOperation> {
+        if self.is_paused() {
+            return Vec::new();
+        }
+        
         let now = Instant::now();
         let mut operations = Vec::new();
         
         // Find events that have been pending long enough
         let ready_paths: Vec<PathBuf> = self.pending_events
             .iter()
-            .filter(|(_, (_, timestamp))| now.duration_since(*timestamp) >= self.debounce_duration)
+            .filter(|(_, (_, ready_at))| now >= *ready_at)
             .map(|(path, _)| path.clone())
             .collect();
         
         // Convert ready events to operations
         for path in ready_paths {
             if let Some((event, _)) = self.pending_events.remove(&path) {
+                if let FilesystemEvent::Moved { from, .. } = &event {
+                    self.pending_move_sources.remove(from);
+                }
                 if let Some(operation) = self.event_to_operation(event) {
+                    // Refresh the destination too, in case the source was never indexed
+                    let moved_to = match &operation {
+                        IndexOperation::Move { to, .. } => Self::create_file_entry(to),
+                        _ => None,
+                    };
                     operations.push(operation);
+                    operations.extend(moved_to.map(IndexOperation::Update));
+                }
+            }
+        }
+        
+        // Rename sides whose window ended unmatched were handled as they are
+        let pending_events = &self.pending_events;
+        self.pending_renames.retain(|_, half| match half {
+            FilesystemEvent::Deleted(path) => matches!(pending_events.get(path), Some((FilesystemEvent::Deleted(_), _))),
+            FilesystemEvent::Created(path) => matches!(pending_events.get(path), Some((FilesystemEvent::Created(_), _))),
+            _ => false,
+        });
+        
+        // A fresh cache per batch, so repositories created or removed since
+        // the last batch are noticed
+        if self.detect_git_repos {
+            let mut repo_roots = RepoRootCache::new();
+            for operation in &mut operations {
+                if let IndexOperation::Add(entry) | IndexOperation::Update(entry) = operation {
+                    let is_dir = entry.file_type == FileType::Directory;
+                    entry.repo_root = repo_roots.repo_root(&entry.path, is_dir);
+                }
+            }
+        }
+        
+        if self.index_ownership {
+            for operation in &mut operations {
+                if let IndexOperation::Add(entry) | IndexOperation::Update(entry) = operation {
+                    if let Ok(metadata) = std::fs::symlink_metadata(&entry.path) {
+                        let (uid, gid) = models::ownership(&metadata);
+                        (entry.uid, entry.gid) = (Some(uid), Some(gid));
+                    }
+                }
+            }
+        }
+        
+        if let Some(max_size) = self.content_size_limit {
+            for operation in &mut operations {
+                if let IndexOperation::Add(entry) | IndexOperation::Update(entry) = operation {
+                    if entry.file_type == FileType::Regular {
+                        entry.content = Some(models::read_text_content(&entry.path, max_size).unwrap_or_default());
+                    }
+                }
+            }
+        }
+        
+        if let Some(allowlist) = &self.xattr_allowlist {
+            for operation in &mut operations {
+                if let IndexOperation::Add(entry) | IndexOperation::Update(entry) = operation {
+                    entry.xattrs = Some(scanner::read_xattrs(&entry.path, allowlist));
                 }
             }
         }
@@ -237,6 +974,17 @@ impl EventProcessor {
             FilesystemEvent::Moved { from, to } => {
                 Some(IndexOperation::Move { from, to })
             }
+            FilesystemEvent::PermissionsChanged(path) => {
+                let metadata = std::fs::metadata(&path).ok()?;
+                Some(IndexOperation::UpdatePermissions {
+                    is_executable: models::is_executable(&metadata),
+                    path,
+                })
+            }
+            // Handled in `add_event`
+            FilesystemEvent::Rescan(_)
+            | FilesystemEvent::RenamedFrom { .. }
+            | FilesystemEvent::RenamedTo { .. } => None,
         }
     }
     
@@ -251,7 +999,7 @@ impl EventProcessor {
         let metadata = std::fs::metadata(path).ok()?;
         
         // Extract filename
-        let filename = path.file_name()?.to_string_lossy().to_string();
+        let filename = models::entry_name(path);
         
         // Determine file type
         let file_type = if metadata.is_dir() {
@@ -267,13 +1015,16 @@ impl EventProcessor {
         // Get modification time
         let modified_time = metadata.modified().unwrap_or_else(|_| SystemTime::now());
         
-        Some(FileEntry::new(
+        let mut entry = FileEntry::new(
             filename,
             path.to_path_buf(),
             metadata.len(),
             modified_time,
             file_type,
-        ))
+        );
+        entry.is_executable = models::is_executable(&metadata);
+        
+        Some(entry)
     }
     
     /// Add an operation to the queue
@@ -291,11 +1042,86 @@ impl EventProcessor {
         self.operation_queue.pop_front()
     }
     
+    /// Dequeue up to `batch_size` operations for the next flush
+    ///
+    /// Returns nothing while paused, leaving queued operations in place.
+    pub fn take_batch(&mut self, batch_size: usize, coalesce: bool) -> Vec<IndexOperation> {
+        if self.is_paused() {
+            return Vec::new();
+        }
+        
+        let count = batch_size.min(self.operation_queue.len());
+        let operations: Vec<_> = self.operation_queue.drain(..count).collect();
+        if coalesce {
+            Self::coalesce_operations(operations)
+        } else {
+            operations
+        }
+    }
+    
+    /// Put the operations of a flush that failed back at the front of the queue
+    ///
+    /// They are retried ahead of anything queued since, and stay in the
+    /// journal until they commit. The queue limit does not apply, since they
+    /// were queued before.
+    pub fn requeue_front(&mut self, operations: Vec<IndexOperation>) {
+        for operation in operations.into_iter().rev() {
+            self.operation_queue.push_front(operation);
+        }
+    }
+    
+    /// Collapse operations that target the same path into the last one
+    ///
+    /// Later operations on a path supersede earlier ones (an update after an add
+    /// keeps only the update, a delete drops any prior updates). Moves touch two
+    /// paths, so they are kept as-is and act as a barrier for both of them.
+    /// Permission updates fold into a pending add/update for the same path and
+    /// are dropped after a delete.
+    pub fn coalesce_operations(operations: Vec<IndexOperation>) -> Vec<IndexOperation> {
+        let mut coalesced: Vec<Option<IndexOperation>> = Vec::with_capacity(operations.len());
+        let mut last_index: HashMap<PathBuf, usize> = HashMap::new();
+        
+        for operation in operations {
+            let path = match &operation {
+                IndexOperation::Add(entry) | IndexOperation::Update(entry) => entry.path.clone(),
+                IndexOperation::Delete(path) => path.clone(),
+                IndexOperation::UpdatePermissions { path, is_executable } => {
+                    match last_index.get(path).and_then(|&index| coalesced[index].as_mut()) {
+                        Some(IndexOperation::Add(entry) | IndexOperation::Update(entry)) => {
+                            entry.is_executable = *is_executable;
+                            continue;
+                        }
+                        Some(IndexOperation::Delete(_)) => continue,
+                        _ => path.clone(),
+                    }
+                }
+                IndexOperation::Move { from, to } => {
+                    last_index.remove(from);
+                    last_index.remove(to);
+                    coalesced.push(Some(operation));
+                    continue;
+                }
+            };
+            
+            if let Some(index) = last_index.insert(path, coalesced.len()) {
+                coalesced[index] = None;
+            }
+            coalesced.push(Some(operation));
+        }
+        
+        coalesced.into_iter().flatten().collect()
+    }
+    
     /// Get the number of pending events
     pub fn pending_event_count(&self) -> usize {
         self.pending_events.len()
     }
     
+    /// Get the queued operations, oldest first
+    pub fn queued_operations(&self) -> impl Iterator<Item = &IndexOperation> {
+        self.operation_queue.iter()
+    }
+
     /// Get the number of queued operations
     pub fn queued_operation_count(&self) -> usize {
         self.operation_queue.len()
@@ -304,7 +1130,10 @@ impl EventProcessor {
     /// Clear all pending events and queued operations
     pub fn clear(&mut self) {
         self.pending_events.clear();
+        self.pending_move_sources.clear();
+        self.pending_renames.clear();
         self.operation_queue.clear();
+        self.rescan_requested = None;
     }
 }
 
@@ -354,27 +1183,151 @@ mod tests {
         
         assert!(FilesystemWatcher::should_exclude(
             Path::new("/home/user/.hidden"),
-            &exclude_patterns
+            Path::new("/home/user"),
+            &exclude_patterns,
+            true
         ));
         
         assert!(!FilesystemWatcher::should_exclude(
             Path::new("/home/user/visible"),
-            &exclude_patterns
+            Path::new("/home/user"),
+            &exclude_patterns,
+            true
+        ));
+    }
+    
+    #[test]
+    fn test_should_exclude_hidden_files_by_default() {
+        let exclude_patterns: Vec<String> = vec![];
+        
+        assert!(FilesystemWatcher::should_exclude(
+            Path::new("/home/user/.config/app.conf"),
+            Path::new("/home/user"),
+            &exclude_patterns,
+            false
+        ));
+        
+        assert!(!FilesystemWatcher::should_exclude(
+            Path::new("/home/user/documents/notes.txt"),
+            Path::new("/home/user"),
+            &exclude_patterns,
+            false
+        ));
+    }
+    
+    #[test]
+    fn test_should_include_hidden_files_when_enabled() {
+        let exclude_patterns: Vec<String> = vec![];
+        
+        assert!(!FilesystemWatcher::should_exclude(
+            Path::new("/home/user/.config/app.conf"),
+            Path::new("/home/user"),
+            &exclude_patterns,
+            true
         ));
     }
     
+    #[test]
+    fn test_hidden_components_above_include_root_ignored() {
+        let indexing = IndexingConfig {
+            include_paths: vec!["/home/user/.config/notes".into()],
+            ..IndexingConfig::default()
+        };
+        
+        assert!(!FilesystemWatcher::is_excluded(Path::new("/home/user/.config/notes/todo.md"), &indexing));
+        assert!(FilesystemWatcher::is_excluded(Path::new("/home/user/.config/notes/.cache/todo.md"), &indexing));
+        assert!(FilesystemWatcher::is_excluded(Path::new("/home/user/.config/other/todo.md"), &indexing));
+    }
+    
+    #[test]
+    fn test_should_exclude_literal_dot_star_pattern() {
+        // Patterns starting with ".*" are plain globs, not a hidden-file shorthand
+        let exclude_patterns = vec![".*rc".to_string()];
+        
+        assert!(FilesystemWatcher::should_exclude(
+            Path::new("/home/user/.bashrc"),
+            Path::new("/home/user"),
+            &exclude_patterns,
+            true
+        ));
+        
+        assert!(!FilesystemWatcher::should_exclude(
+            Path::new("/home/user/.profile"),
+            Path::new("/home/user"),
+            &exclude_patterns,
+            true
+        ));
+    }
+    
+    #[test]
+    fn test_relative_path_exclude_patterns() {
+        let indexing = IndexingConfig {
+            include_paths: vec!["/home/user".into(), "/home/user/projects".into()],
+            exclude_patterns: vec!["build/*".to_string(), "docs/_generated".to_string()],
+            ..IndexingConfig::default()
+        };
+        
+        let excluded = |path: &str| FilesystemWatcher::is_excluded(Path::new(path), &indexing);
+        assert!(excluded("/home/user/build/app.o"));
+        assert!(excluded("/home/user/docs/_generated"));
+        assert!(excluded("/home/user/docs/_generated/api/index.html"));
+        assert!(!excluded("/home/user/build"));
+        assert!(!excluded("/home/user/src/build/app.o"));
+        assert!(!excluded("/home/user/docs/guide.md"));
+        // Matched against the innermost include root
+        assert!(excluded("/home/user/projects/build/app.o"));
+        assert!(!excluded("/srv/build/app.o"));
+    }
+    
+    #[test]
+    fn test_always_include_extension_bypasses_excludes() {
+        let mut indexing = IndexingConfig {
+            exclude_patterns: vec!["*.log".to_string(), "cache".to_string()],
+            ..IndexingConfig::default()
+        };
+        let log_file = Path::new("/home/user/logs/app.log");
+        
+        assert!(FilesystemWatcher::is_excluded(log_file, &indexing));
+        
+        indexing.always_include_extensions = vec!["log".to_string()];
+        assert!(!FilesystemWatcher::is_excluded(log_file, &indexing));
+        
+        // Excluded directories still win
+        assert!(FilesystemWatcher::is_excluded(Path::new("/home/user/cache/app.log"), &indexing));
+    }
+    
+    #[test]
+    fn test_internal_dirs_always_excluded() {
+        let indexing = IndexingConfig {
+            index_hidden_files: true,
+            exclude_patterns: vec![],
+            internal_dirs: vec![PathBuf::from("/home/user/.local/share/novasearch")],
+            ..IndexingConfig::default()
+        };
+        
+        for name in ["index.db", "index.db-wal", "index.db-shm"] {
+            let path = Path::new("/home/user/.local/share/novasearch").join(name);
+            assert!(FilesystemWatcher::is_excluded(&path, &indexing));
+        }
+        assert!(!FilesystemWatcher::is_excluded(Path::new("/home/user/.local/share/other.db"), &indexing));
+    }
+    
     #[test]
     fn test_should_exclude_node_modules() {
         let exclude_patterns = vec!["node_modules".to_string()];
         
         assert!(FilesystemWatcher::should_exclude(
             Path::new("/home/user/project/node_modules/package"),
-            &exclude_patterns
+            Path::new("/home/user"),
+            &exclude_patterns,
+            true
         ));
         
         assert!(!FilesystemWatcher::should_exclude(
             Path::new("/home/user/project/src"),
-            &exclude_patterns
+            Path::new("/home/user"),
+            &exclude_patterns,
+            true
         ));
     }
     
@@ -384,17 +1337,23 @@ mod tests {
         
         assert!(FilesystemWatcher::should_exclude(
             Path::new("/home/user/file.log"),
-            &exclude_patterns
+            Path::new("/home/user"),
+            &exclude_patterns,
+            true
         ));
         
         assert!(FilesystemWatcher::should_exclude(
             Path::new("/home/user/temp.tmp"),
-            &exclude_patterns
+            Path::new("/home/user"),
+            &exclude_patterns,
+            true
         ));
         
         assert!(!FilesystemWatcher::should_exclude(
             Path::new("/home/user/file.txt"),
-            &exclude_patterns
+            Path::new("/home/user"),
+            &exclude_patterns,
+            true
         ));
     }
     
@@ -423,6 +1382,31 @@ mod tests {
         assert_eq!(processor.pending_event_count(), 0);
     }
     
+    #[test]
+    fn test_directory_events_debounce_longer() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir_path = temp_dir.path().join("photos");
+        let file_path = temp_dir.path().join("notes.txt");
+        fs::create_dir(&dir_path).unwrap();
+        fs::write(&file_path, "content").unwrap();
+        
+        let mut processor = EventProcessor::new(Duration::from_millis(50), 100);
+        processor.set_directory_debounce(Duration::from_millis(300));
+        processor.add_event(FilesystemEvent::Modified(dir_path.clone()));
+        processor.add_event(FilesystemEvent::Modified(file_path.clone()));
+        
+        std::thread::sleep(Duration::from_millis(100));
+        let operations = processor.process_pending();
+        assert_eq!(operations.len(), 1);
+        assert!(matches!(&operations[0], IndexOperation::Update(e) if e.path == file_path));
+        assert_eq!(processor.pending_event_count(), 1);
+        
+        std::thread::sleep(Duration::from_millis(250));
+        let operations = processor.process_pending();
+        assert_eq!(operations.len(), 1);
+        assert!(matches!(&operations[0], IndexOperation::Update(e) if e.path == dir_path));
+    }
+    
     #[test]
     fn test_event_processor_queue() {
         let mut processor = EventProcessor::new(Duration::from_millis(50), 2);
@@ -444,6 +1428,31 @@ mod tests {
         assert_eq!(processor.queued_operation_count(), 1);
     }
     
+    #[test]
+    fn test_event_processor_reads_xattrs() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("tagged.txt");
+        fs::write(&file_path, "test").unwrap();
+        
+        // Not every filesystem supports user xattrs; nothing to check then
+        if xattr::set(&file_path, "user.tags", b"work").is_err() {
+            return;
+        }
+        
+        let mut processor = EventProcessor::new(Duration::from_millis(10), 100);
+        processor.add_event(FilesystemEvent::Created(file_path.clone()));
+        std::thread::sleep(Duration::from_millis(30));
+        let operations = processor.process_pending();
+        assert!(matches!(&operations[..], [IndexOperation::Add(e)] if e.xattrs.is_none()));
+        
+        processor.set_xattr_allowlist(Some(vec!["user.".to_string()]));
+        processor.add_event(FilesystemEvent::Modified(file_path.clone()));
+        std::thread::sleep(Duration::from_millis(30));
+        let operations = processor.process_pending();
+        let expected = vec![("user.tags".to_string(), "work".to_string())];
+        assert!(matches!(&operations[..], [IndexOperation::Update(e)] if e.xattrs.as_ref() == Some(&expected)));
+    }
+    
     #[test]
     fn test_create_file_entry() {
         let temp_dir = TempDir::new().unwrap();
@@ -474,6 +1483,18 @@ mod tests {
         assert_eq!(entry.file_type, FileType::Directory);
     }
     
+    #[test]
+    fn test_create_file_entry_without_file_name() {
+        let entry = EventProcessor::create_file_entry(Path::new("/")).unwrap();
+        assert_eq!(entry.filename, "/");
+        assert_eq!(entry.file_type, FileType::Directory);
+
+        let temp_dir = TempDir::new().unwrap();
+        let parent_ref = temp_dir.path().join("..");
+        let entry = EventProcessor::create_file_entry(&parent_ref).unwrap();
+        assert_eq!(entry.filename, parent_ref.to_string_lossy());
+    }
+    
     #[test]
     fn test_create_file_entry_nonexistent() {
         let entry = EventProcessor::create_file_entry(Path::new("/nonexistent/file.txt"));
@@ -562,6 +1583,549 @@ mod tests {
         assert_eq!(watcher.watched_paths().len(), 2);
     }
     
+    #[test]
+    fn test_watch_paths_batched_watches_every_path() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut paths = Vec::new();
+        for i in 0..200 {
+            let dir = temp_dir.path().join(format!("dir{}", i));
+            std::fs::create_dir(&dir).unwrap();
+            paths.push(dir);
+        }
+        // Covered by the recursive watch of dir0, so not watched separately
+        for i in 0..50 {
+            let nested = temp_dir.path().join("dir0").join(format!("nested{}", i));
+            std::fs::create_dir(&nested).unwrap();
+            paths.push(nested);
+        }
+
+        let mut watcher = FilesystemWatcher::new(&Config::default()).unwrap();
+        let mut batches = Vec::new();
+        let report = watcher.watch_paths_batched(&paths, &[], 64, Duration::from_millis(1), |done, total| {
+            batches.push((done, total));
+        });
+
+        assert!(report.errors.is_empty());
+        assert_eq!(report.watched.len(), 200);
+        assert_eq!(batches, vec![(64, 200), (128, 200), (192, 200), (200, 200)]);
+        let watched: HashSet<&PathBuf> = watcher.watched_paths().iter().collect();
+        assert_eq!(watched.len(), 200);
+        assert!(paths[..200].iter().all(|path| watched.contains(path)));
+
+        // Registering the same paths again adds nothing
+        let report = watcher.watch_paths_batched(&paths, &[], 64, Duration::ZERO, |_, _| {});
+        assert!(report.watched.is_empty());
+    }
+
+    #[test]
+    fn test_filesystem_watcher_max_watches_priority() {
+        let root = TempDir::new().unwrap();
+        let shallow = root.path().join("a");
+        let deeper = root.path().join("b/c");
+        let deepest = root.path().join("d/e/f");
+        for dir in [&shallow, &deeper, &deepest] {
+            fs::create_dir_all(dir).unwrap();
+        }
+        
+        let mut config = Config::default();
+        config.performance.max_watches = Some(2);
+        let mut watcher = FilesystemWatcher::new(&config).unwrap();
+        
+        let paths = vec![deeper.clone(), deepest.clone(), shallow.clone()];
+        let report = watcher.watch_paths_prioritized(&paths, std::slice::from_ref(&deepest));
+        
+        assert!(report.errors.is_empty());
+        assert_eq!(report.watched, vec![deepest.clone(), shallow.clone()]);
+        assert_eq!(report.skipped, vec![deeper.clone()]);
+        assert_eq!(watcher.watched_paths().len(), 2);
+        assert_eq!(watcher.polled_paths(), &[deeper]);
+    }
+    
+    #[test]
+    fn test_max_watches_counts_directories_below_recursive_roots() {
+        let root = TempDir::new().unwrap();
+        let large = root.path().join("large");
+        let small = root.path().join("small");
+        for dir in ["large/a", "large/b/c", "small"] {
+            fs::create_dir_all(root.path().join(dir)).unwrap();
+        }
+        fs::write(large.join("a/file.txt"), "content").unwrap();
+        
+        let mut config = Config::default();
+        config.performance.max_watches = Some(3);
+        let mut watcher = FilesystemWatcher::new(&config).unwrap();
+        
+        // "large" alone needs four watches, more than the whole budget
+        let report = watcher.watch_paths_prioritized(&[large.clone(), small.clone()], &[]);
+        assert!(report.errors.is_empty());
+        assert_eq!(report.watched, vec![small]);
+        assert_eq!(report.skipped, vec![large]);
+    }
+    
+    fn operation_entry(path: &str) -> FileEntry {
+        let path = PathBuf::from(path);
+        FileEntry::new(
+            path.file_name().unwrap().to_string_lossy().to_string(),
+            path,
+            100,
+            SystemTime::now(),
+            FileType::Regular,
+        )
+    }
+    
+    #[test]
+    fn test_coalesce_operations_same_path() {
+        let mut final_entry = operation_entry("/test/x.txt");
+        final_entry.size = 300;
+        
+        let operations = vec![
+            IndexOperation::Add(operation_entry("/test/x.txt")),
+            IndexOperation::Update(operation_entry("/test/x.txt")),
+            IndexOperation::Update(final_entry),
+        ];
+        
+        let coalesced = EventProcessor::coalesce_operations(operations);
+        assert_eq!(coalesced.len(), 1);
+        match &coalesced[0] {
+            IndexOperation::Update(entry) => assert_eq!(entry.size, 300),
+            other => panic!("Expected Update, got {:?}", other),
+        }
+    }
+    
+    #[test]
+    fn test_coalesce_operations_delete_wins_and_moves_kept() {
+        let operations = vec![
+            IndexOperation::Update(operation_entry("/test/a.txt")),
+            IndexOperation::Add(operation_entry("/test/b.txt")),
+            IndexOperation::Delete(PathBuf::from("/test/a.txt")),
+            IndexOperation::Move { from: PathBuf::from("/test/b.txt"), to: PathBuf::from("/test/c.txt") },
+            IndexOperation::Update(operation_entry("/test/c.txt")),
+        ];
+        
+        let coalesced = EventProcessor::coalesce_operations(operations);
+        assert_eq!(coalesced.len(), 4);
+        assert!(matches!(&coalesced[0], IndexOperation::Add(e) if e.path == Path::new("/test/b.txt")));
+        assert!(matches!(&coalesced[1], IndexOperation::Delete(p) if p == Path::new("/test/a.txt")));
+        assert!(matches!(&coalesced[2], IndexOperation::Move { .. }));
+        assert!(matches!(&coalesced[3], IndexOperation::Update(e) if e.path == Path::new("/test/c.txt")));
+    }
+    
+    #[test]
+    fn test_coalesce_operations_permission_updates() {
+        let operations = vec![
+            IndexOperation::Add(operation_entry("/test/a.sh")),
+            IndexOperation::UpdatePermissions { path: PathBuf::from("/test/a.sh"), is_executable: true },
+            IndexOperation::Delete(PathBuf::from("/test/b.sh")),
+            IndexOperation::UpdatePermissions { path: PathBuf::from("/test/b.sh"), is_executable: true },
+            IndexOperation::UpdatePermissions { path: PathBuf::from("/test/c.sh"), is_executable: true },
+            IndexOperation::UpdatePermissions { path: PathBuf::from("/test/c.sh"), is_executable: false },
+        ];
+        
+        let coalesced = EventProcessor::coalesce_operations(operations);
+        assert_eq!(coalesced.len(), 3);
+        assert!(matches!(&coalesced[0], IndexOperation::Add(e) if e.is_executable));
+        assert!(matches!(&coalesced[1], IndexOperation::Delete(_)));
+        assert!(matches!(
+            &coalesced[2],
+            IndexOperation::UpdatePermissions { is_executable: false, .. }
+        ));
+    }
+    
+    #[test]
+    fn test_convert_permission_event() {
+        let path = PathBuf::from("/test/run.sh");
+        let event = Event::new(EventKind::Modify(ModifyKind::Metadata(MetadataKind::Permissions)))
+            .add_path(path.clone());
+        
+        let converted = FilesystemWatcher::convert_event(event, &IndexingConfig::default());
+        assert!(matches!(converted, Some(FilesystemEvent::PermissionsChanged(p)) if p == path));
+    }
+    
+    #[test]
+    fn test_unspecified_attribute_change_reindexes() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("notes.txt");
+        fs::write(&file_path, "notes").unwrap();
+        
+        // What inotify reports for touch, chown or an xattr write
+        let event = Event::new(EventKind::Modify(ModifyKind::Metadata(MetadataKind::Any)))
+            .add_path(file_path.clone());
+        let indexing = IndexingConfig {
+            include_paths: vec![temp_dir.path().to_string_lossy().to_string().into()],
+            ..IndexingConfig::default()
+        };
+        let converted = FilesystemWatcher::convert_event(event, &indexing).unwrap();
+        assert!(matches!(&converted, FilesystemEvent::Modified(p) if *p == file_path));
+        
+        let mut processor = EventProcessor::new(Duration::from_millis(0), 100);
+        processor.add_event(converted);
+        let operations = processor.process_pending();
+        assert_eq!(operations.len(), 1);
+        let modified = fs::metadata(&file_path).unwrap().modified().unwrap();
+        assert!(matches!(
+            &operations[0],
+            IndexOperation::Update(entry) if entry.path == file_path && entry.modified_time == modified
+        ));
+    }
+    
+    #[test]
+    fn test_overflow_schedules_rescan() {
+        let home = PathBuf::from("/home/user");
+        let documents = PathBuf::from("/home/user/Documents");
+        let srv = PathBuf::from("/srv/shared");
+        let roots = vec![home.clone(), documents.clone(), srv.clone()];
+        let mut processor = EventProcessor::new(Duration::from_millis(50), 100);
+        assert!(processor.take_rescan_roots(&roots).is_empty());
+        
+        // inotify reports a queue overflow without a path
+        let overflow = Event::new(EventKind::Other).set_flag(notify::event::Flag::Rescan);
+        let converted = FilesystemWatcher::convert_event(overflow.clone(), &IndexingConfig::default()).unwrap();
+        processor.add_event(converted);
+        assert_eq!(processor.take_rescan_roots(&roots), vec![home.clone(), srv.clone()]);
+        assert!(processor.take_rescan_roots(&roots).is_empty());
+        
+        // A report naming a path rescans only the root it is under
+        processor.add_event(FilesystemEvent::Rescan(vec![srv.join("projects")]));
+        assert_eq!(processor.take_rescan_roots(&roots), vec![srv]);
+        
+        let indexing = IndexingConfig {
+            rescan_on_overflow: false,
+            ..IndexingConfig::default()
+        };
+        assert!(FilesystemWatcher::convert_event(overflow, &indexing).is_none());
+    }
+    
+    #[test]
+    fn test_persistent_watch_errors_degrade_path() {
+        let mut tracker = WatchErrorTracker::new(3, Some(Duration::from_secs(60)));
+        let path = Path::new("/home/user/locked");
+        let start = Instant::now();
+
+        assert_eq!(tracker.record_error(path, start), WatchErrorAction::Log);
+        assert_eq!(tracker.record_error(path, start), WatchErrorAction::Log);
+        assert!(!tracker.is_degraded(path));
+        assert_eq!(tracker.record_error(path, start), WatchErrorAction::Degrade);
+        assert!(tracker.is_degraded(path));
+        assert_eq!(tracker.degraded_paths(), vec![path.to_path_buf()]);
+
+        // Further errors stay quiet until the backoff runs out
+        for _ in 0..100 {
+            assert_eq!(tracker.record_error(path, start), WatchErrorAction::Suppress);
+        }
+        assert!(tracker.take_due_retries(start + Duration::from_secs(59)).is_empty());
+        assert_eq!(tracker.take_due_retries(start + Duration::from_secs(60)), vec![path.to_path_buf()]);
+        assert!(!tracker.is_degraded(path));
+
+        // A retry that fails again degrades at once and waits twice as long
+        let retried = start + Duration::from_secs(60);
+        assert_eq!(tracker.record_error(path, retried), WatchErrorAction::Degrade);
+        assert!(tracker.take_due_retries(retried + Duration::from_secs(119)).is_empty());
+        assert_eq!(tracker.take_due_retries(retried + Duration::from_secs(120)).len(), 1);
+
+        // Events from the path clear its record
+        tracker.record_success(&[path.to_path_buf()]);
+        assert_eq!(tracker.record_error(path, retried), WatchErrorAction::Log);
+
+        // Without a retry delay a degraded path stays degraded
+        let mut tracker = WatchErrorTracker::new(1, None);
+        assert_eq!(tracker.record_error(path, start), WatchErrorAction::Degrade);
+        assert!(tracker.take_due_retries(start + Duration::from_secs(24 * 60 * 60)).is_empty());
+    }
+
+    #[test]
+    fn test_retry_degraded_rewatches_only_the_path() {
+        let temp_dir = TempDir::new().unwrap();
+        let readable = temp_dir.path().join("readable");
+        let missing = temp_dir.path().join("missing");
+        fs::create_dir(&readable).unwrap();
+        
+        let mut config = Config::default();
+        config.indexing.include_paths = vec![temp_dir.path().to_string_lossy().to_string().into()];
+        config.performance.watch_error_threshold = 1;
+        config.performance.watch_error_retry_secs = 1;
+        let mut watcher = FilesystemWatcher::new(&config).unwrap();
+        watcher.watch_path(temp_dir.path()).unwrap();
+        
+        let tracker = watcher.error_tracker();
+        for path in [&readable, &missing] {
+            assert_eq!(tracker.lock().unwrap().record_error(path, Instant::now()), WatchErrorAction::Degrade);
+        }
+        std::thread::sleep(Duration::from_millis(1100));
+        
+        assert_eq!(watcher.retry_degraded(), vec![missing.clone(), readable.clone()]);
+        // The failure is recorded against the path itself, not its root
+        assert_eq!(watcher.degraded_paths(), vec![missing]);
+        assert_eq!(watcher.watched_paths(), [temp_dir.path().to_path_buf()]);
+    }
+    
+    #[test]
+    fn test_duplicate_events_from_overlapping_roots() {
+        let mut processor = EventProcessor::new(Duration::from_millis(0), 100);
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("report.txt");
+        fs::write(&file_path, "report").unwrap();
+        
+        // The same change reported by the `~` watch and the `~/Documents` watch
+        processor.add_event(FilesystemEvent::Created(file_path.clone()));
+        processor.add_event(FilesystemEvent::Created(file_path.clone()));
+        processor.add_event(FilesystemEvent::Modified(file_path.clone()));
+        processor.add_event(FilesystemEvent::Modified(file_path.clone()));
+        
+        let operations = processor.process_pending();
+        assert_eq!(operations.len(), 1);
+        assert!(matches!(&operations[0], IndexOperation::Update(e) if e.path == file_path));
+        
+        // A rename seen whole by one root and as a delete plus create by the other
+        let old_path = temp_dir.path().join("draft.txt");
+        processor.add_event(FilesystemEvent::Moved { from: old_path.clone(), to: file_path.clone() });
+        processor.add_event(FilesystemEvent::Deleted(old_path.clone()));
+        processor.add_event(FilesystemEvent::Created(file_path.clone()));
+        
+        let operations = processor.process_pending();
+        assert_eq!(operations.len(), 2);
+        assert!(matches!(&operations[0], IndexOperation::Move { from, to } if *from == old_path && *to == file_path));
+        assert!(matches!(&operations[1], IndexOperation::Update(e) if e.path == file_path));
+        
+        // Once the move is done, a later delete of its source is processed again
+        processor.add_event(FilesystemEvent::Deleted(old_path.clone()));
+        assert_eq!(processor.pending_event_count(), 1);
+    }
+    
+    #[test]
+    fn test_permission_event_does_not_replace_pending_modify() {
+        let mut processor = EventProcessor::new(Duration::from_millis(0), 100);
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("run.sh");
+        fs::write(&file_path, "#!/bin/sh\n").unwrap();
+        
+        processor.add_event(FilesystemEvent::Modified(file_path.clone()));
+        processor.add_event(FilesystemEvent::PermissionsChanged(file_path));
+        
+        let operations = processor.process_pending();
+        assert_eq!(operations.len(), 1);
+        assert!(matches!(&operations[0], IndexOperation::Update(_)));
+    }
+    
+    #[test]
+    fn test_chmod_updates_executable_flag() {
+        use crate::database::Database;
+        use std::os::unix::fs::PermissionsExt;
+        
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("run.sh");
+        fs::write(&file_path, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&file_path, fs::Permissions::from_mode(0o644)).unwrap();
+        
+        let db = Database::open(temp_dir.path().join("index.db")).unwrap();
+        let entry = EventProcessor::create_file_entry(&file_path).unwrap();
+        assert!(!entry.is_executable);
+        db.insert_file(&entry).unwrap();
+        
+        let mut config = Config::default();
+        config.indexing.include_paths = vec![temp_dir.path().to_string_lossy().to_string().into()];
+        let mut watcher = FilesystemWatcher::new(&config).unwrap();
+        watcher.watch_path(temp_dir.path()).unwrap();
+        
+        fs::set_permissions(&file_path, fs::Permissions::from_mode(0o755)).unwrap();
+        
+        let mut processor = EventProcessor::new(Duration::from_millis(0), 100);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut operations = Vec::new();
+        while Instant::now() < deadline {
+            while let Some(event) = watcher.try_recv_event() {
+                processor.add_event(event);
+            }
+            operations.extend(processor.process_pending());
+            // inotify reports the chmod as an unspecified attribute change,
+            // so it arrives as a full refresh rather than a permissions update
+            if operations.iter().any(|op| matches!(
+                op,
+                IndexOperation::UpdatePermissions { path, .. } | IndexOperation::Update(FileEntry { path, .. })
+                    if path == &file_path
+            )) {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        
+        db.execute_batch(&EventProcessor::coalesce_operations(operations)).unwrap();
+        let results = db.query_files("run", 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].is_executable);
+    }
+    
+    #[test]
+    fn test_event_processor_pause_holds_back_flush() {
+        let mut processor = EventProcessor::new(Duration::from_millis(0), 100);
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("paused.txt");
+        fs::write(&file_path, "content").unwrap();
+        processor.enqueue_operation(IndexOperation::Delete(PathBuf::from("/test/queued.txt"))).unwrap();
+        
+        processor.pause(PausePolicy::Buffer);
+        assert!(processor.is_paused());
+        processor.add_event(FilesystemEvent::Created(file_path.clone()));
+        assert!(processor.process_pending().is_empty());
+        assert!(processor.take_batch(10, true).is_empty());
+        assert_eq!(processor.pending_event_count(), 1);
+        assert_eq!(processor.queued_operation_count(), 1);
+        
+        processor.resume();
+        for operation in processor.process_pending() {
+            processor.enqueue_operation(operation).unwrap();
+        }
+        assert_eq!(processor.take_batch(10, true).len(), 2);
+        
+        // Dropped events are not indexed after resuming
+        processor.pause(PausePolicy::Drop);
+        processor.add_event(FilesystemEvent::Modified(file_path));
+        processor.resume();
+        assert_eq!(processor.pending_event_count(), 0);
+    }
+    
+    #[test]
+    fn test_convert_rename_events() {
+        let from = PathBuf::from("/test/File.txt");
+        let to = PathBuf::from("/test/file.txt");
+        let indexing = IndexingConfig::default();
+        let rename = |mode| Event::new(EventKind::Modify(ModifyKind::Name(mode)));
+        
+        let converted = FilesystemWatcher::convert_event(rename(RenameMode::From).add_path(from.clone()), &indexing);
+        assert!(matches!(converted, Some(FilesystemEvent::Deleted(p)) if p == from));
+        let converted = FilesystemWatcher::convert_event(rename(RenameMode::To).add_path(to.clone()), &indexing);
+        assert!(matches!(converted, Some(FilesystemEvent::Created(p)) if p == to));
+        let converted = FilesystemWatcher::convert_event(
+            rename(RenameMode::Both).add_path(from.clone()).add_path(to.clone()),
+            &indexing,
+        );
+        assert!(matches!(converted, Some(FilesystemEvent::Moved { from: f, to: t }) if f == from && t == to));
+        
+        let converted = FilesystemWatcher::convert_event(rename(RenameMode::From).add_path(from.clone()).set_tracker(7), &indexing);
+        assert!(matches!(converted, Some(FilesystemEvent::RenamedFrom { path, tracker: 7 }) if path == from));
+        let converted = FilesystemWatcher::convert_event(rename(RenameMode::To).add_path(to.clone()).set_tracker(7), &indexing);
+        assert!(matches!(converted, Some(FilesystemEvent::RenamedTo { path, tracker: 7 }) if path == to));
+    }
+    
+    #[test]
+    fn test_rename_sides_become_one_move() {
+        let mut processor = EventProcessor::new(Duration::from_millis(0), 100);
+        let temp_dir = TempDir::new().unwrap();
+        let old_path = temp_dir.path().join("draft.txt");
+        let new_path = temp_dir.path().join("final.txt");
+        fs::write(&new_path, "final").unwrap();
+        
+        // inotify reports both sides and then the pair
+        processor.add_event(FilesystemEvent::RenamedFrom { path: old_path.clone(), tracker: 7 });
+        processor.add_event(FilesystemEvent::RenamedTo { path: new_path.clone(), tracker: 7 });
+        processor.add_event(FilesystemEvent::Moved { from: old_path.clone(), to: new_path.clone() });
+        assert_eq!(processor.pending_event_count(), 1);
+        
+        let operations = processor.process_pending();
+        let moves: Vec<_> = operations.iter().filter(|op| matches!(op, IndexOperation::Move { .. })).collect();
+        assert_eq!(moves.len(), 1);
+        assert!(matches!(moves[0], IndexOperation::Move { from, to } if *from == old_path && *to == new_path));
+        assert!(!operations.iter().any(|op| matches!(op, IndexOperation::Delete(_) | IndexOperation::Add(_))));
+        
+        // Sides whose other side never arrives fall back to a delete and a create
+        let other_path = temp_dir.path().join("other.txt");
+        fs::write(&other_path, "other").unwrap();
+        processor.add_event(FilesystemEvent::RenamedFrom { path: old_path.clone(), tracker: 8 });
+        processor.add_event(FilesystemEvent::RenamedTo { path: other_path.clone(), tracker: 9 });
+        let operations = processor.process_pending();
+        assert_eq!(operations.len(), 2);
+        assert!(operations.iter().any(|op| matches!(op, IndexOperation::Delete(p) if *p == old_path)));
+        assert!(operations.iter().any(|op| matches!(op, IndexOperation::Add(e) if e.path == other_path)));
+        
+        // A side arriving after the window has ended is not paired
+        processor.add_event(FilesystemEvent::RenamedTo { path: new_path.clone(), tracker: 8 });
+        let operations = processor.process_pending();
+        assert_eq!(operations.len(), 1);
+        assert!(matches!(&operations[0], IndexOperation::Add(e) if e.path == new_path));
+    }
+    
+    #[test]
+    fn test_case_only_rename_leaves_one_row() {
+        use crate::database::Database;
+        
+        let temp_dir = TempDir::new().unwrap();
+        let old_path = temp_dir.path().join("File.txt");
+        let new_path = temp_dir.path().join("file.txt");
+        fs::write(&old_path, "content").unwrap();
+        
+        let db = Database::open(temp_dir.path().join("index.db")).unwrap();
+        db.insert_file(&EventProcessor::create_file_entry(&old_path).unwrap()).unwrap();
+        
+        let mut config = Config::default();
+        config.indexing.include_paths = vec![temp_dir.path().to_string_lossy().to_string().into()];
+        let mut watcher = FilesystemWatcher::new(&config).unwrap();
+        watcher.watch_path(temp_dir.path()).unwrap();
+        
+        fs::rename(&old_path, &new_path).unwrap();
+        
+        let mut processor = EventProcessor::new(Duration::from_millis(100), 100);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut operations = Vec::new();
+        while Instant::now() < deadline {
+            while let Some(event) = watcher.try_recv_event() {
+                processor.add_event(event);
+            }
+            operations.extend(processor.process_pending());
+            if operations.iter().any(|op| matches!(op, IndexOperation::Move { to, .. } if to == &new_path)) {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        
+        db.execute_batch(&EventProcessor::coalesce_operations(operations)).unwrap();
+        let results = db.query_files("file", 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].filename, "file.txt");
+        assert_eq!(results[0].path, new_path);
+    }
+    
+    #[test]
+    fn test_non_recursive_root_ignores_subdirectories() {
+        use crate::config::IncludePath;
+        
+        let temp_dir = TempDir::new().unwrap();
+        let sub_dir = temp_dir.path().join("sub");
+        fs::create_dir(&sub_dir).unwrap();
+        
+        let mut config = Config::default();
+        config.indexing.include_paths = vec![IncludePath::Detailed {
+            path: temp_dir.path().to_string_lossy().to_string(),
+            recursive: false,
+        }];
+        let mut watcher = FilesystemWatcher::new(&config).unwrap();
+        watcher.watch_path(temp_dir.path()).unwrap();
+        
+        let nested_path = sub_dir.join("nested.txt");
+        let top_path = temp_dir.path().join("top.txt");
+        fs::write(&nested_path, "nested").unwrap();
+        fs::write(&top_path, "top").unwrap();
+        
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut paths = Vec::new();
+        while Instant::now() < deadline && !paths.contains(&top_path) {
+            while let Some(event) = watcher.try_recv_event() {
+                match event {
+                    FilesystemEvent::Created(path)
+                    | FilesystemEvent::Modified(path)
+                    | FilesystemEvent::Deleted(path)
+                    | FilesystemEvent::PermissionsChanged(path)
+                    | FilesystemEvent::RenamedFrom { path, .. }
+                    | FilesystemEvent::RenamedTo { path, .. } => paths.push(path),
+                    FilesystemEvent::Moved { from, to } => paths.extend([from, to]),
+                    FilesystemEvent::Rescan(rescanned) => paths.extend(rescanned),
+                }
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        
+        assert!(paths.contains(&top_path));
+        assert!(!paths.iter().any(|path| path.starts_with(&sub_dir) && path != &sub_dir));
+    }
+    
     #[test]
     fn test_event_processor_clear() {
         let mut processor = EventProcessor::new(Duration::from_millis(50), 100);
@@ -0,0 +1,53 @@
+use novasearch_daemon::config::Config;
+use novasearch_daemon::database::{ReplaceOutcome, SearchOptions};
+use novasearch_daemon::NovaSearch;
+use std::time::{Duration, Instant};
+
+fn config_for(dir: &std::path::Path) -> Config {
+    let mut config = Config::default();
+    config.indexing.include_paths = vec![dir.to_string_lossy().to_string().into()];
+    config.indexing.index_applications = false;
+    config
+}
+
+#[test]
+fn test_scan_search_and_watch() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("embedded-notes.txt"), "notes").unwrap();
+    std::fs::create_dir(dir.path().join("sub")).unwrap();
+    std::fs::write(dir.path().join("sub/embedded-plan.md"), "plan").unwrap();
+
+    let mut engine = NovaSearch::open_in_memory(config_for(dir.path())).unwrap();
+    match engine.scan().unwrap() {
+        ReplaceOutcome::Replaced { previous, indexed } => {
+            assert_eq!(previous, 0);
+            assert!(indexed >= 3);
+        }
+        outcome => panic!("scan refused: {:?}", outcome),
+    }
+
+    let results = engine.search("embedded", &SearchOptions::default()).unwrap();
+    let mut names: Vec<_> = results.iter().map(|e| e.filename.clone()).collect();
+    names.sort();
+    assert_eq!(names, vec!["embedded-notes.txt", "embedded-plan.md"]);
+
+    let report = engine.watch().unwrap();
+    assert!(report.errors.is_empty());
+
+    let new_file = dir.path().join("embedded-later.txt");
+    std::fs::write(&new_file, "later").unwrap();
+
+    let started = Instant::now();
+    let found = loop {
+        engine.process_events().unwrap();
+        let results = engine.search("embedded-later", &SearchOptions::default()).unwrap();
+        if results.iter().any(|e| e.path == new_file) {
+            break true;
+        }
+        if started.elapsed() > Duration::from_secs(5) {
+            break false;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    };
+    assert!(found, "watcher did not index {}", new_file.display());
+}
@@ -6,6 +6,9 @@
 #include <string.h>
 #include <unistd.h>
 #include <time.h>
+#include <sys/socket.h>
+#include <sys/time.h>
+#include <sys/un.h>
 
 /* Retry configuration */
 #define MAX_RETRY_ATTEMPTS 5
@@ -220,12 +223,108 @@ SearchResult* nova_search_db_query(NovaSearchDB *db, const char *query, int max_
     return head;
 }
 
-/* Record file launch for usage tracking */
+/* Control socket of the daemon, kept next to the index database */
+#define DAEMON_SOCKET_NAME "daemon.sock"
+
+/* How long to wait for the daemon to accept a launch before writing it directly */
+#define DAEMON_TIMEOUT_MS 200
+
+/* Append a JSON string literal for value to buffer; returns false if it does not fit */
+static bool append_json_string(char *buffer, size_t size, size_t *length, const char *value) {
+    static const char hex[] = "0123456789abcdef";
+    char escaped[7];
+
+    if (*length + 1 >= size) {
+        return false;
+    }
+    buffer[(*length)++] = '"';
+
+    for (const unsigned char *p = (const unsigned char *)value; *p; p++) {
+        size_t count = 0;
+        if (*p == '"' || *p == '\\') {
+            escaped[count++] = '\\';
+            escaped[count++] = (char)*p;
+        } else if (*p < 0x20) {
+            memcpy(escaped, "\\u00", 4);
+            escaped[4] = hex[*p >> 4];
+            escaped[5] = hex[*p & 0x0f];
+            count = 6;
+        } else {
+            escaped[count++] = (char)*p;
+        }
+        if (*length + count + 1 >= size) {
+            return false;
+        }
+        memcpy(buffer + *length, escaped, count);
+        *length += count;
+    }
+
+    buffer[(*length)++] = '"';
+    buffer[*length] = '\0';
+    return true;
+}
+
+/* Hand a launch to the running daemon, which writes launches in batches */
+static bool send_launch_to_daemon(const char *db_path, const char *file_path) {
+    struct sockaddr_un address;
+    memset(&address, 0, sizeof(address));
+    address.sun_family = AF_UNIX;
+
+    const char *slash = strrchr(db_path, '/');
+    size_t dir_length = slash ? (size_t)(slash - db_path) + 1 : 0;
+    if (dir_length + strlen(DAEMON_SOCKET_NAME) >= sizeof(address.sun_path)) {
+        return false;
+    }
+    memcpy(address.sun_path, db_path, dir_length);
+    strcpy(address.sun_path + dir_length, DAEMON_SOCKET_NAME);
+
+    char request[8192];
+    size_t length = (size_t)snprintf(request, sizeof(request), "{\"command\":\"launch\",\"path\":");
+    if (!append_json_string(request, sizeof(request), &length, file_path) || length + 2 >= sizeof(request)) {
+        return false;
+    }
+    request[length++] = '}';
+    request[length++] = '\n';
+
+    int fd = socket(AF_UNIX, SOCK_STREAM, 0);
+    if (fd < 0) {
+        return false;
+    }
+
+    struct timeval timeout = { 0, DAEMON_TIMEOUT_MS * 1000 };
+    setsockopt(fd, SOL_SOCKET, SO_RCVTIMEO, &timeout, sizeof(timeout));
+    setsockopt(fd, SOL_SOCKET, SO_SNDTIMEO, &timeout, sizeof(timeout));
+
+    if (connect(fd, (struct sockaddr *)&address, sizeof(address)) != 0
+        || write(fd, request, length) != (ssize_t)length) {
+        close(fd);
+        return false;
+    }
+
+    char reply[256];
+    ssize_t received = read(fd, reply, sizeof(reply) - 1);
+    close(fd);
+    if (received <= 0) {
+        return false;
+    }
+    reply[received] = '\0';
+
+    return strstr(reply, "\"queued\"") != NULL;
+}
+
+/* Record file launch for usage tracking
+ *
+ * The launch goes to the running daemon so opening a file never waits on a
+ * database write; without a daemon it is written to the index directly. */
 bool nova_search_db_record_launch(NovaSearchDB *db, const char *file_path) {
     if (!db || !file_path) {
         return false;
     }
 
+    if (send_launch_to_daemon(db->db_path, file_path)) {
+        return true;
+    }
+
     /* We need to open the database in read-write mode for this operation */
     sqlite3 *rw_db = NULL;
     int rc = sqlite3_open(db->db_path, &rw_db);